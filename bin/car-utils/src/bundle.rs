@@ -0,0 +1,156 @@
+use std::{cell::RefCell, fs::File, path::Path};
+
+use blockless_car::{
+    error::CarError,
+    reader::{self as car_reader, CarReader},
+    utils::{self, BundleManifest, MANIFEST_NAME},
+    Cid,
+};
+
+use crate::{error::UtilError, usage::count_reader};
+
+#[derive(Debug, clap::Parser)]
+pub struct BundleInfoCommand {
+    #[clap(help = "the bundle car file.")]
+    car: String,
+}
+
+/// the entry of the car, `path` is relative to the root.
+pub(crate) struct Entry {
+    pub(crate) cid: Cid,
+    pub(crate) path: String,
+}
+
+/// list the entries of the car, the paths are relative to the root.
+pub(crate) fn list_entries(reader: &mut impl CarReader) -> Result<Vec<Entry>, UtilError> {
+    let entries = RefCell::new(Vec::new());
+    utils::list_call(reader, |cid, path| {
+        // strip the root cid, the link of file named by the cid has no parent.
+        if let Some((_, path)) = path.split_once('/') {
+            entries.borrow_mut().push(Entry {
+                cid: *cid,
+                path: path.to_string(),
+            });
+        }
+    })?;
+    Ok(entries.into_inner())
+}
+
+/// the size of the file content, the file node records the size so the content is not read.
+/// the directory has no size.
+pub(crate) fn file_size(reader: &mut impl CarReader, cid: &Cid) -> Result<Option<u64>, UtilError> {
+    match utils::file_size(reader, cid) {
+        Ok(size) => Ok(Some(size)),
+        Err(CarError::NotAFile(_)) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+impl BundleInfoCommand {
+    /// locate the manifest, list the wasm modules and check the referenced files exist,
+    /// the manifest referencing the files out of the bundle is rejected.
+    pub(crate) fn execute(&self) -> Result<(), UtilError> {
+        let path: &Path = self.car.as_ref();
+        if !path.exists() {
+            return Err(UtilError::new(format!(
+                "the car file [{}] does not exist.",
+                self.car
+            )));
+        }
+        let mut reader = car_reader::new_v1(File::open(path)?)?;
+        let entries = list_entries(&mut reader)?;
+        let name_of = |e: &Entry| e.path.rsplit('/').next().unwrap_or_default().to_string();
+        let manifest = entries
+            .iter()
+            .filter(|e| name_of(e) == MANIFEST_NAME)
+            .min_by_key(|e| e.path.matches('/').count())
+            .ok_or_else(|| UtilError::new(format!("the {MANIFEST_NAME} is not found.")))?;
+        let mut content = Vec::new();
+        utils::ipld_write(&mut reader, manifest.cid, &mut content)?;
+        let manifest_json = BundleManifest::parse(&content)?;
+        println!("manifest: {} {}", manifest.cid, manifest.path);
+        if let Some(name) = manifest_json.name.as_ref() {
+            println!("name: {name}");
+        }
+
+        println!("wasm modules:");
+        for e in entries.iter().filter(|e| e.path.ends_with(".wasm")) {
+            let size = file_size(&mut reader, &e.cid)?.unwrap_or_default();
+            println!("  {} {size} {}", e.cid, e.path);
+        }
+        count_reader(&reader);
+
+        let dir = manifest.path.rsplit_once('/').map_or("", |(d, _)| d);
+        let missing: Vec<&str> = manifest_json
+            .resolve_refs(dir)?
+            .into_iter()
+            .filter(|(_, path)| !entries.iter().any(|e| &e.path == path))
+            .map(|(r, _)| r)
+            .collect();
+        if missing.is_empty() {
+            return Ok(());
+        }
+        println!("missing assets:");
+        for r in missing.iter() {
+            println!("  {r}");
+        }
+        Err(UtilError::new(format!(
+            "{} assets referenced by the manifest are missing",
+            missing.len()
+        )))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::usage::Usage;
+    use blockless_car::utils::{pack_files_with, PackOptions};
+    use std::fs;
+    use tempdir::TempDir;
+
+    /// pack the bundle directory `name` with the manifest and the files, return the car path.
+    fn pack_bundle(temp_dir: &TempDir, name: &str, manifest: &str, files: &[&str]) -> String {
+        let bundle = temp_dir.path().join(name);
+        fs::create_dir_all(bundle.join("lib")).unwrap();
+        fs::write(bundle.join(MANIFEST_NAME), manifest).unwrap();
+        for file in files {
+            fs::write(bundle.join(file), b"\0asm").unwrap();
+        }
+        let car = temp_dir.path().join(format!("{name}.car"));
+        pack_files_with(&bundle, File::create(&car).unwrap(), &PackOptions::new()).unwrap();
+        car.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_bundle_info() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let manifest = r#"{"name": "hello", "entry": "release.wasm", "modules": [
+            {"file": "release.wasm", "type": "entry"},
+            {"file": "./lib/../lib/util.wasm", "type": "module"}
+        ]}"#;
+        let car = pack_bundle(
+            &temp_dir,
+            "ok",
+            manifest,
+            &["release.wasm", "lib/util.wasm"],
+        );
+        // the blocks read by the command are counted for the `--stats`.
+        let usage = Usage::start();
+        assert!(BundleInfoCommand { car }.execute().is_ok());
+        assert!(usage.blocks().0 > 0);
+        assert_eq!(usage.blocks().1, 0);
+
+        let car = pack_bundle(&temp_dir, "missing", manifest, &["release.wasm"]);
+        let err = BundleInfoCommand { car }.execute().unwrap_err();
+        assert_eq!(err.err, "1 assets referenced by the manifest are missing");
+
+        // the module out of the directory of the manifest is rejected, even if the path
+        // exists in the car.
+        let manifest =
+            r#"{"entry": "release.wasm", "modules": [{"file": "../escape/release.wasm"}]}"#;
+        let car = pack_bundle(&temp_dir, "escape", manifest, &["release.wasm"]);
+        let err = BundleInfoCommand { car }.execute().unwrap_err();
+        assert!(err.err.contains("unsafe path"), "{}", err.err);
+    }
+}
@@ -0,0 +1,208 @@
+//! the car paths of the read-only commands, the directories and the glob patterns are
+//! expanded to the car files, which are processed in parallel.
+use std::{
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    thread,
+};
+
+use blockless_car::utils::Pattern;
+
+use crate::{error::UtilError, stdio::is_stdio};
+
+/// whether the path has the glob chars, the path existing is taken as is.
+fn is_glob(path: &str) -> bool {
+    path.contains(['*', '?', '['])
+}
+
+/// collect the files under the `base` whose paths relative to the `base` match the `pattern`,
+/// the directories at the `depth` aren't walked, none walks all.
+fn walk_glob(
+    base: &Path,
+    rel: &Path,
+    pattern: &Pattern,
+    depth: Option<usize>,
+    files: &mut Vec<PathBuf>,
+) -> Result<(), UtilError> {
+    let dir = base.join(rel);
+    let dir = match dir.as_os_str().is_empty() {
+        true => Path::new("."),
+        false => dir.as_path(),
+    };
+    for entry in dir.read_dir()? {
+        let rel = rel.join(entry?.file_name());
+        let path = base.join(&rel);
+        if path.is_dir() {
+            if depth.is_none_or(|d| d > 1) {
+                walk_glob(base, &rel, pattern, depth.map(|d| d - 1), files)?;
+            }
+        } else if path.is_file() && pattern.matches_path(&rel) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// expand the glob to the files sorted by the name, the components before the first one
+/// with the glob chars are the directory walked, `**` walks all the directories under it.
+fn expand_glob(glob: &str) -> Result<Vec<PathBuf>, UtilError> {
+    let mut base = PathBuf::new();
+    let mut rest = Vec::new();
+    for c in Path::new(glob).components() {
+        let name = c.as_os_str().to_string_lossy();
+        if rest.is_empty() && !is_glob(&name) {
+            base.push(c);
+        } else {
+            rest.push(name.into_owned());
+        }
+    }
+    let depth = match rest.iter().any(|c| c.contains("**")) {
+        true => None,
+        false => Some(rest.len()),
+    };
+    // the leading `/` anchors the pattern to the path relative to the base.
+    let pattern = Pattern::new(&format!("/{}", rest.join("/")))?;
+    let mut files = Vec::new();
+    walk_glob(&base, Path::new(""), &pattern, depth, &mut files)?;
+    files.sort();
+    Ok(files)
+}
+
+/// expand the directories to the `.car` files in them and the glob patterns to the files
+/// matching them, sorted by the name, like `/data/cars` or `'/data/**/*.car'`.
+pub(crate) fn expand_cars(paths: &[String]) -> Result<Vec<PathBuf>, UtilError> {
+    let mut cars = Vec::new();
+    for p in paths.iter() {
+        let path: &Path = p.as_ref();
+        if is_stdio(path) {
+            cars.push(path.to_path_buf());
+        } else if path.is_dir() {
+            let mut entries = Vec::new();
+            for entry in path.read_dir()? {
+                let entry = entry?.path();
+                if entry.is_file() && entry.extension().is_some_and(|e| e == "car") {
+                    entries.push(entry);
+                }
+            }
+            entries.sort();
+            cars.extend(entries);
+        } else if path.exists() {
+            cars.push(path.to_path_buf());
+        } else if is_glob(p) {
+            let files = expand_glob(p)?;
+            if files.is_empty() {
+                return Err(UtilError::new(format!("no car file matches [{p}].")));
+            }
+            cars.extend(files);
+        } else {
+            return Err(UtilError::new(format!(
+                "the car file [{p}] does not exist."
+            )));
+        }
+    }
+    Ok(cars)
+}
+
+/// run the `f` on the cars by the `jobs` workers, 0 means the count of the CPUs,
+/// the results are in the order of the cars.
+pub(crate) fn map_cars<T, F>(cars: &[PathBuf], jobs: usize, f: F) -> Vec<Result<T, UtilError>>
+where
+    T: Send,
+    F: Fn(&Path) -> Result<T, UtilError> + Sync,
+{
+    let jobs = match jobs {
+        0 => thread::available_parallelism().map_or(1, NonZeroUsize::get),
+        n => n,
+    };
+    let next = Mutex::new(0usize);
+    let results: Mutex<Vec<Option<Result<T, UtilError>>>> =
+        Mutex::new((0..cars.len()).map(|_| None).collect());
+    thread::scope(|scope| {
+        for _ in 0..jobs.min(cars.len()) {
+            scope.spawn(|| loop {
+                let idx = {
+                    let mut next = next.lock().unwrap();
+                    *next += 1;
+                    *next - 1
+                };
+                let Some(path) = cars.get(idx) else {
+                    break;
+                };
+                let rs = f(path);
+                results.lock().unwrap()[idx] = Some(rs);
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(Option::unwrap)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_expand_cars() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("a/b")).unwrap();
+        for name in [
+            "2.car",
+            "1.car",
+            "x.txt",
+            "a/3.car",
+            "a/b/4.car",
+            "a/b/5.txt",
+        ] {
+            fs::write(root.join(name), name).unwrap();
+        }
+        let path = |p: &str| root.join(p).to_str().unwrap().to_string();
+        let names = |cars: Vec<PathBuf>| {
+            cars.iter()
+                .map(|c| c.strip_prefix(root).unwrap().to_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        // the directory is expanded to the car files in it, not the sub-directories.
+        let cars = expand_cars(&[path(""), "-".into(), path("x.txt")]).unwrap();
+        assert_eq!(cars[2], PathBuf::from("-"));
+        let cars = cars.into_iter().filter(|c| !is_stdio(c)).collect();
+        assert_eq!(names(cars), ["1.car", "2.car", "x.txt"]);
+
+        // the `*` doesn't cross the directories, the `**` does.
+        let cars = expand_cars(&[path("*.car")]).unwrap();
+        assert_eq!(names(cars), ["1.car", "2.car"]);
+        let cars = expand_cars(&[path("a/*/?.car"), path("**/*.car")]).unwrap();
+        assert_eq!(
+            names(cars),
+            ["a/b/4.car", "1.car", "2.car", "a/3.car", "a/b/4.car"]
+        );
+        let cars = expand_cars(&[path("[a-z]/**/*.[ct][ax]?")]).unwrap();
+        assert_eq!(names(cars), ["a/3.car", "a/b/4.car", "a/b/5.txt"]);
+
+        assert!(expand_cars(&[path("*.zst")]).is_err());
+        assert!(expand_cars(&[path("none.car")]).is_err());
+    }
+
+    #[test]
+    fn test_map_cars() {
+        let cars: Vec<PathBuf> = (0..10).map(|i| PathBuf::from(i.to_string())).collect();
+        let results = map_cars(&cars, 3, |path| match path.to_str().unwrap() {
+            "5" => Err(UtilError::new("failed".into())),
+            p => Ok(p.parse::<usize>().unwrap()),
+        });
+        for (i, rs) in results.into_iter().enumerate() {
+            match i {
+                5 => assert!(rs.is_err()),
+                i => assert_eq!(rs.unwrap(), i),
+            }
+        }
+    }
+}
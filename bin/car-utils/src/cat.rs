@@ -1,28 +1,126 @@
-use crate::error::UtilError;
-use blockless_car::reader as car_reader;
-use std::{fs::File, path::Path};
+use crate::{
+    error::UtilError,
+    stdio::read_car,
+    usage::{count_blocks, count_reader},
+};
+use blockless_car::{
+    error::CarError,
+    reader::{self as car_reader, CarReader},
+    store::BlockStore,
+    utils::{
+        cat_ipld, cat_ipld_path, cat_ipld_range, resolve_path_from, CarIndex, IndexedBlockStore,
+        ResolveOptions,
+    },
+    Cid,
+};
+use std::{
+    fs::File,
+    io::{BufReader, Seek},
+    str::FromStr,
+};
 
 #[derive(Debug, clap::Parser)]
 pub struct CatCommand {
-    #[clap(help = "the car file to cat.")]
+    #[clap(help = "the car file to cat, `-` reads the car from the stdin.")]
     car: String,
 
-    #[clap(short, help = "the cid of content to cat.")]
-    cid: String,
+    #[clap(
+        short,
+        help = "the cid of content to cat.",
+        required_unless_present = "path"
+    )]
+    cid: Option<String>,
+
+    #[clap(
+        long = "path",
+        help = "the unixfs path of content to cat, relative to the first root or `/ipfs/<cid>/...`.",
+        conflicts_with = "cid"
+    )]
+    path: Option<String>,
+
+    #[clap(
+        long = "offset",
+        help = "the offset in bytes of the content to cat.",
+        default_value_t = 0
+    )]
+    offset: u64,
+
+    #[clap(long = "length", help = "the length in bytes of the content to cat.")]
+    length: Option<u64>,
+
+    #[clap(
+        long = "index",
+        help = "the sidecar index of the car like `file.car.idx` to locate the blocks without scanning the car, the index embedded in the CARv2 is used without it."
+    )]
+    index: Option<String>,
+
+    #[clap(
+        long = "mmap",
+        help = "map the car without the index to the memory and write the leaves without the copies, the car must not change while the cat runs. the buffered reader is used if the map fails."
+    )]
+    mmap: bool,
 }
 
 impl CatCommand {
     pub(crate) fn execute(&self) -> Result<(), UtilError> {
-        let path: &Path = self.car.as_ref();
-        if !path.exists() {
-            return Err(UtilError::new(format!(
-                "the car file [{}] does not exist.",
-                self.car
-            )));
+        let mut input = read_car(&self.car)?;
+        // the indexed car is read by the index without scanning the sections.
+        let mut store = IndexedBlockStore::new(&mut input)?;
+        if let Some(index) = self.index.as_ref() {
+            let index = CarIndex::decode(&mut BufReader::new(File::open(index)?))?;
+            store = store.with_index(index);
+        }
+        if store.is_indexed() {
+            let cid = match self.path.as_ref() {
+                Some(path) => {
+                    let root = store.header().roots().first().copied();
+                    resolve_path_from(&mut store, root, path, &ResolveOptions::default())?
+                }
+                None => self.cid()?,
+            };
+            let result = self.cat(&mut store, cid);
+            count_blocks(store.blocks_read(), 0);
+            return result;
+        }
+        drop(store);
+        input.rewind()?;
+        if self.mmap {
+            // the car file is not expected to change while the cat runs, the map is not
+            // supported off unix, and the car of the stdin is in the memory already.
+            #[cfg(unix)]
+            if let Some(file) = input.file() {
+                match unsafe { car_reader::new_mmap_v1(file) } {
+                    Ok(mut reader) => return self.cat_reader(&mut reader),
+                    Err(CarError::IO(_)) => input.rewind()?,
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        }
+        let mut reader = car_reader::new_v1(BufReader::new(input))?;
+        self.cat_reader(&mut reader)
+    }
+
+    fn cat_reader(&self, reader: &mut impl CarReader) -> Result<(), UtilError> {
+        // the errors of the walk of the path include the path.
+        let result = match self.path.as_ref() {
+            Some(path) => Ok(cat_ipld_path(reader, path, self.offset, self.length)?),
+            None => self.cid().and_then(|cid| self.cat(reader, cid)),
+        };
+        count_reader(reader);
+        result
+    }
+
+    fn cid(&self) -> Result<Cid, UtilError> {
+        let cid = self.cid.as_ref().expect("the cid or the path is required");
+        Ok(Cid::from_str(cid).map_err(|e| CarError::Parsing(e.to_string()))?)
+    }
+
+    fn cat(&self, store: &mut impl BlockStore, cid: Cid) -> Result<(), UtilError> {
+        if self.offset == 0 && self.length.is_none() {
+            cat_ipld(store, cid)?;
+        } else {
+            cat_ipld_range(store, cid, self.offset, self.length)?;
         }
-        let file = File::open(path)?;
-        let mut reader = car_reader::new_v1(file)?;
-        blockless_car::utils::cat_ipld_str(&mut reader, &self.cid)?;
         Ok(())
     }
 }
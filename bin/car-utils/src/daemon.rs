@@ -0,0 +1,538 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, Write},
+    path::Path,
+    str::FromStr,
+    sync::{mpsc, Arc},
+    thread,
+};
+
+use blockless_car::{
+    error::CarError,
+    reader::{self as car_reader, CarReader},
+    utils::{
+        self, extract_ipld_with, pack_files_multi_root, pack_files_with, ExtractOptions,
+        PackOptions, Progress, ProgressHook, PruneDefaults, TopNode,
+    },
+    CidVersion,
+};
+use clap::ValueEnum;
+use serde_json::{json, Value};
+
+use crate::{error::UtilError, pack::HasherCodec};
+
+// the JSON-RPC error codes.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const EXECUTION_ERROR: i64 = -32000;
+
+#[derive(Debug, clap::Parser)]
+pub struct DaemonCommand {
+    #[cfg(unix)]
+    #[clap(long, help = "Listen on the unix socket instead of stdio.")]
+    socket: Option<String>,
+}
+
+impl DaemonCommand {
+    /// serve the JSON-RPC requests, one request per line.
+    /// the methods are `pack`, `unpack`, `ls`, `verify` and `shutdown`.
+    pub(crate) fn execute(&self) -> Result<(), UtilError> {
+        #[cfg(unix)]
+        if let Some(socket) = self.socket.as_ref() {
+            return serve_socket(socket);
+        }
+        serve(io::stdin().lock(), io::stdout())?;
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn serve_socket(path: &str) -> Result<(), UtilError> {
+    use std::{io::BufReader, os::unix::net::UnixListener};
+
+    let listener = UnixListener::bind(path)?;
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let input = BufReader::new(stream.try_clone()?);
+        match serve(input, stream) {
+            Ok(true) => break,
+            Ok(false) => {}
+            Err(e) => eprintln!("Error: {e}"),
+        }
+    }
+    std::fs::remove_file(path)?;
+    Ok(())
+}
+
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<UtilError> for RpcError {
+    fn from(value: UtilError) -> Self {
+        Self::new(EXECUTION_ERROR, value.err)
+    }
+}
+
+impl From<blockless_car::error::CarError> for RpcError {
+    fn from(value: blockless_car::error::CarError) -> Self {
+        Self::new(EXECUTION_ERROR, value.to_string())
+    }
+}
+
+impl From<io::Error> for RpcError {
+    fn from(value: io::Error) -> Self {
+        Self::new(EXECUTION_ERROR, value.to_string())
+    }
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    serde_json::to_writer(&mut *output, message)?;
+    output.write_all(b"\n")?;
+    output.flush()
+}
+
+/// serve the requests from `input` until the input end or the `shutdown` request.
+/// return true if the `shutdown` is requested. the malformed line is answered with the
+/// error and the next line is served.
+fn serve(input: impl BufRead, mut output: impl Write) -> Result<bool, UtilError> {
+    for line in input.split(b'\n') {
+        let line = line?;
+        if line.trim_ascii().is_empty() {
+            continue;
+        }
+        let request: Value = match serde_json::from_slice(&line) {
+            Ok(request) => request,
+            Err(e) => {
+                let err = RpcError::new(PARSE_ERROR, e.to_string());
+                write_message(&mut output, &error_response(Value::Null, err))?;
+                continue;
+            }
+        };
+        let id = request.get("id").cloned();
+        // the invalid request is answered even without the id.
+        let Some(method) = request.get("method").and_then(Value::as_str) else {
+            let err = RpcError::new(INVALID_REQUEST, "method is required");
+            write_message(&mut output, &error_response(id.unwrap_or(Value::Null), err))?;
+            continue;
+        };
+        let params = request.get("params").cloned().unwrap_or(Value::Null);
+        let result = match method {
+            "shutdown" => Ok(Value::Null),
+            method => {
+                let progress_id = id.clone().unwrap_or(Value::Null);
+                let mut notify = |params: Value| {
+                    let message = json!({
+                        "jsonrpc": "2.0",
+                        "method": "progress",
+                        "params": {"id": progress_id, "progress": params},
+                    });
+                    // the progress is best effort, the response reports the failure.
+                    let _ = write_message(&mut output, &message);
+                };
+                dispatch(method, &params, &mut notify)
+            }
+        };
+        // the request without id is a notification, don't response it.
+        if let Some(id) = id {
+            let response = match result {
+                Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+                Err(err) => error_response(id, err),
+            };
+            write_message(&mut output, &response)?;
+        }
+        if method == "shutdown" {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn error_response(id: Value, err: RpcError) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": {"code": err.code, "message": err.message},
+    })
+}
+
+fn dispatch(
+    method: &str,
+    params: &Value,
+    notify: &mut impl FnMut(Value),
+) -> Result<Value, RpcError> {
+    match method {
+        "pack" => pack(params, notify),
+        "unpack" => unpack(params, notify),
+        "ls" => ls(params),
+        "verify" => verify(params, notify),
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("method {method} not found"),
+        )),
+    }
+}
+
+fn str_param<'a>(params: &'a Value, name: &str) -> Result<&'a str, RpcError> {
+    params
+        .get(name)
+        .and_then(Value::as_str)
+        .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("the param {name} is required")))
+}
+
+fn bool_param(params: &Value, name: &str) -> bool {
+    params.get(name).and_then(Value::as_bool).unwrap_or(false)
+}
+
+/// the array param of the strings parsed, empty if absent.
+fn parsed_params<T>(params: &Value, name: &str) -> Result<Vec<T>, RpcError>
+where
+    T: FromStr<Err = CarError>,
+{
+    let Some(values) = params.get(name).and_then(Value::as_array) else {
+        return Ok(Vec::new());
+    };
+    values
+        .iter()
+        .map(|v| {
+            v.as_str()
+                .ok_or_else(|| {
+                    RpcError::new(INVALID_PARAMS, format!("the {name} are not strings"))
+                })?
+                .parse()
+                .map_err(|e: CarError| RpcError::new(INVALID_PARAMS, e.to_string()))
+        })
+        .collect()
+}
+
+/// the progress notification of the stage, the counters of `Progress`.
+fn progress_params(stage: &str, progress: &Progress) -> Value {
+    json!({
+        "stage": stage,
+        "files": progress.files,
+        "bytes_hashed": progress.bytes_hashed,
+        "bytes_written": progress.bytes_written,
+    })
+}
+
+/// run the `op` on a worker thread with the progress hook, the progress of every chunk is
+/// notified from the current thread.
+fn with_progress<T, F>(notify: &mut impl FnMut(Value), stage: &str, op: F) -> Result<T, RpcError>
+where
+    T: Send,
+    F: FnOnce(Arc<dyn ProgressHook>) -> Result<T, RpcError> + Send,
+{
+    let (tx, rx) = mpsc::channel();
+    thread::scope(|scope| {
+        let hook: Arc<dyn ProgressHook> = Arc::new(move |p: &Progress| {
+            let _ = tx.send(*p);
+        });
+        let worker = scope.spawn(move || op(hook));
+        // the channel is closed when the op drops the hook.
+        for progress in rx {
+            notify(progress_params(stage, &progress));
+        }
+        worker
+            .join()
+            .unwrap_or_else(|e| std::panic::resume_unwind(e))
+    })
+}
+
+fn open_car(params: &Value) -> Result<impl CarReader, RpcError> {
+    let car = str_param(params, "car")?;
+    let file = File::open(car)?;
+    Ok(car_reader::new_v1(file)?)
+}
+
+fn pack(params: &Value, notify: &mut impl FnMut(Value)) -> Result<Value, RpcError> {
+    let output = str_param(params, "output")?;
+    let hasher_codec = match params.get("hasher").and_then(Value::as_str) {
+        Some(hasher) => HasherCodec::from_str(hasher, true)
+            .map_err(|e| RpcError::new(INVALID_PARAMS, e))?
+            .codec(),
+        None => multicodec::Codec::Sha2_256,
+    };
+    let mut options = PackOptions::new()
+        .with_hasher_codec(hasher_codec)
+        .with_no_wrap_file(bool_param(params, "no_wrap"));
+    if bool_param(params, "no_top_node") {
+        options = options.with_top_node(TopNode::Omit);
+    } else if let Some(name) = params.get("top_node").and_then(Value::as_str) {
+        options = options.with_top_node(TopNode::Named(name.to_string()));
+    }
+    if let Some(chunk_size) = params.get("chunk_size").and_then(Value::as_u64) {
+        options = options.with_chunk_size(chunk_size as usize);
+    }
+    if let Some(raw_leaves) = params.get("raw_leaves").and_then(Value::as_bool) {
+        options = options.with_raw_leaves(raw_leaves);
+    }
+    if bool_param(params, "preserve_metadata") {
+        options = options.with_preserve_metadata(true);
+    }
+    if bool_param(params, "prune_defaults") {
+        options = options.with_walk_filter(Arc::new(PruneDefaults));
+    }
+    if bool_param(params, "gitignore") {
+        options = options.with_gitignore(true);
+    }
+    for pattern in parsed_params(params, "exclude")? {
+        options = options.with_exclude(pattern);
+    }
+    for pattern in parsed_params(params, "include")? {
+        options = options.with_include(pattern);
+    }
+    for rule in parsed_params(params, "chunk_rules")? {
+        options = options.with_chunk_rule(rule);
+    }
+    if let Some(version) = params.get("cid_version").and_then(Value::as_u64) {
+        let version = CidVersion::try_from(version)
+            .map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))?;
+        options = options.with_cid_version(version);
+    }
+    if let Some(threshold) = params.get("hamt_threshold").and_then(Value::as_u64) {
+        options = options.with_hamt_threshold(Some(threshold as usize).filter(|t| *t > 0));
+    }
+    // the `sources` packs every source as a root of the CAR.
+    if let Some(sources) = params.get("sources").and_then(Value::as_array) {
+        let sources = sources
+            .iter()
+            .map(|s| {
+                s.as_str()
+                    .ok_or_else(|| RpcError::new(INVALID_PARAMS, "the sources are not strings"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        notify(json!({"stage": "packing", "sources": sources}));
+        let file = File::create(output)?;
+        let (roots, _) = with_progress(notify, "packing", |hook| {
+            Ok(pack_files_multi_root(
+                &sources,
+                file,
+                &options.with_progress(hook),
+            )?)
+        })?;
+        let roots: Vec<String> = roots.iter().map(|r| r.to_string()).collect();
+        return Ok(json!({ "roots": roots }));
+    }
+    let source = str_param(params, "source")?;
+    notify(json!({"stage": "packing", "source": source}));
+    let file = File::create(output)?;
+    let root = with_progress(notify, "packing", |hook| {
+        let source: &Path = source.as_ref();
+        Ok(pack_files_with(source, file, &options.with_progress(hook))?)
+    })?;
+    Ok(json!({"root": root.to_string()}))
+}
+
+fn unpack(params: &Value, notify: &mut impl FnMut(Value)) -> Result<Value, RpcError> {
+    let mut reader = open_car(params)?;
+    let output = params.get("output").and_then(Value::as_str);
+    let roots: Vec<_> = reader
+        .header()
+        .roots()
+        .into_iter()
+        .filter(|r| !utils::is_listing_root(r))
+        .collect();
+    for root in roots.iter() {
+        notify(json!({"stage": "extracting", "root": root.to_string()}));
+        with_progress(notify, "extracting", |hook| {
+            let options = ExtractOptions::default().with_progress(hook);
+            Ok(extract_ipld_with(&mut reader, *root, output, &options)?)
+        })?;
+    }
+    let roots: Vec<String> = roots.iter().map(|r| r.to_string()).collect();
+    Ok(json!({ "roots": roots }))
+}
+
+fn ls(params: &Value) -> Result<Value, RpcError> {
+    let mut reader = open_car(params)?;
+    let entries = std::cell::RefCell::new(Vec::new());
+    utils::list_call(&mut reader, |cid, path| {
+        entries
+            .borrow_mut()
+            .push(json!({"cid": cid.to_string(), "path": path}));
+    })?;
+    Ok(json!({ "entries": entries.into_inner() }))
+}
+
+fn verify(params: &Value, notify: &mut impl FnMut(Value)) -> Result<Value, RpcError> {
+    let mut reader = open_car(params)?;
+    notify(json!({"stage": "verifying", "blocks": reader.section_count()?}));
+    let report = utils::verify_blocks(&mut reader)?;
+    let failures: Vec<String> = report.failures.iter().map(|c| c.to_string()).collect();
+    Ok(json!({
+        "blocks": report.blocks,
+        "ok": report.is_ok(),
+        "failures": failures,
+        "failure_offsets": report.failure_offsets,
+        "truncated": report.truncated,
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{fs, io::Cursor};
+    use tempdir::TempDir;
+
+    /// serve the input, return whether shutdown is requested and the messages written.
+    fn serve_input(input: Vec<u8>) -> (bool, Vec<Value>) {
+        let mut output = Vec::new();
+        let shutdown = serve(Cursor::new(input), &mut output).unwrap();
+        let messages = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        (shutdown, messages)
+    }
+
+    fn response(messages: &[Value], id: i64) -> &Value {
+        messages
+            .iter()
+            .find(|m| m["id"] == id && m.get("method").is_none())
+            .unwrap()
+    }
+
+    /// the progress notifications of the request `id` in the `stage`.
+    fn progress<'a>(messages: &'a [Value], id: i64, stage: &str) -> Vec<&'a Value> {
+        messages
+            .iter()
+            .filter(|m| m["method"] == "progress" && m["params"]["id"] == id)
+            .map(|m| &m["params"]["progress"])
+            .filter(|p| p["stage"] == stage && p.get("bytes_hashed").is_some())
+            .collect()
+    }
+
+    #[test]
+    fn test_daemon_round_trip() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let source = temp_dir.path().join("source");
+        fs::create_dir_all(&source).unwrap();
+        let content: Vec<u8> = (0..10000u32).map(|i| (i % 251) as u8).collect();
+        fs::write(source.join("a.bin"), &content).unwrap();
+        fs::write(source.join("b.txt"), b"hello").unwrap();
+        let car = temp_dir.path().join("source.car");
+        let output = temp_dir.path().join("output");
+        let path = |p: &Path| p.to_str().unwrap().to_string();
+        let lines = [
+            json!({"jsonrpc": "2.0", "id": 1, "method": "pack", "params": {
+                "source": path(&source), "output": path(&car), "chunk_size": 1024,
+            }}),
+            json!({"jsonrpc": "2.0", "id": 2, "method": "ls", "params": {"car": path(&car)}}),
+            // the notification is not answered.
+            json!({"jsonrpc": "2.0", "method": "verify", "params": {"car": path(&car)}}),
+            json!({"jsonrpc": "2.0", "id": 3, "method": "verify", "params": {"car": path(&car)}}),
+            json!({"jsonrpc": "2.0", "id": 4, "method": "unpack", "params": {
+                "car": path(&car), "output": path(&output),
+            }}),
+            json!({"jsonrpc": "2.0", "id": 5, "method": "shutdown"}),
+            // the requests after the shutdown are not served.
+            json!({"jsonrpc": "2.0", "id": 6, "method": "ls", "params": {"car": path(&car)}}),
+        ];
+        let lines: Vec<String> = lines.iter().map(Value::to_string).collect();
+        let (shutdown, messages) = serve_input(lines.join("\n").into_bytes());
+        assert!(shutdown);
+        let responses: Vec<&Value> = messages.iter().filter(|m| m.get("id").is_some()).collect();
+        let ids: Vec<&Value> = responses.iter().map(|m| &m["id"]).collect();
+        assert_eq!(ids, [1, 2, 3, 4, 5]);
+
+        let root = response(&messages, 1)["result"]["root"].as_str().unwrap();
+        let entries = &response(&messages, 2)["result"]["entries"];
+        let paths: Vec<&str> = entries
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["path"].as_str().unwrap())
+            .collect();
+        let expected =
+            ["", "/source", "/source/a.bin", "/source/b.txt"].map(|p| format!("{root}{p}"));
+        assert_eq!(paths, expected);
+        assert_eq!(entries[0]["cid"], root);
+        let verify = &response(&messages, 3)["result"];
+        assert_eq!(verify["ok"], true);
+        assert_eq!(verify["truncated"], false);
+        assert_eq!(response(&messages, 4)["result"]["roots"], json!([root]));
+        assert_eq!(fs::read(output.join("source/a.bin")).unwrap(), content);
+        assert_eq!(response(&messages, 5)["result"], Value::Null);
+
+        // the progress is notified per chunk, before the response.
+        let packing = progress(&messages, 1, "packing");
+        assert!(packing.len() > 1);
+        let last = packing.last().unwrap();
+        assert_eq!(last["files"], 2);
+        assert_eq!(last["bytes_hashed"], content.len() as u64 + 5);
+        let extracting = progress(&messages, 4, "extracting");
+        assert!(extracting.len() > 1);
+        assert_eq!(extracting.last().unwrap()["files"], 2);
+        let position = |m: &Value| messages.iter().position(|x| x == m).unwrap();
+        let first_packing = messages
+            .iter()
+            .position(|m| m["method"] == "progress" && m["params"]["id"] == 1)
+            .unwrap();
+        assert!(first_packing < position(response(&messages, 1)));
+    }
+
+    #[test]
+    fn test_daemon_malformed_input() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let missing = temp_dir.path().join("missing.car");
+        let missing = missing.to_str().unwrap();
+        let mut lines: Vec<String> = [
+            "{not json",
+            "",
+            "42",
+            r#"{"jsonrpc": "2.0", "id": 1}"#,
+            r#"{"jsonrpc": "2.0", "id": 2, "method": "nope"}"#,
+            r#"{"jsonrpc": "2.0", "id": 3, "method": "ls", "params": {}}"#,
+            r#"{"jsonrpc": "2.0", "id": 4, "method": "ls", "params": {"car": 1}}"#,
+            r#"{"jsonrpc": "2.0", "id": 6, "method": "pack", "params": {"source": ".", "output": "x", "hasher": "nope"}}"#,
+            r#"{"jsonrpc": "2.0", "id": 7, "method": "pack", "params": {"sources": [1], "output": "x"}}"#,
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+        lines.push(
+            json!({"jsonrpc": "2.0", "id": 5, "method": "ls", "params": {"car": missing}})
+                .to_string(),
+        );
+        let mut input = lines.join("\n").into_bytes();
+        // the line of the invalid utf-8 is a parse error, not the end of the daemon.
+        input.extend_from_slice(b"\n\xff\xfe\n");
+        input.extend_from_slice(br#"{"jsonrpc": "2.0", "id": 8, "method": "shutdown"}"#);
+        let (shutdown, messages) = serve_input(input);
+        assert!(shutdown);
+        let errors: Vec<(Value, i64)> = messages
+            .iter()
+            .filter_map(|m| Some((m["id"].clone(), m["error"]["code"].as_i64()?)))
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                (Value::Null, PARSE_ERROR),
+                (Value::Null, INVALID_REQUEST),
+                (json!(1), INVALID_REQUEST),
+                (json!(2), METHOD_NOT_FOUND),
+                (json!(3), INVALID_PARAMS),
+                (json!(4), INVALID_PARAMS),
+                (json!(6), INVALID_PARAMS),
+                (json!(7), INVALID_PARAMS),
+                (json!(5), EXECUTION_ERROR),
+                (Value::Null, PARSE_ERROR),
+            ]
+        );
+        assert_eq!(response(&messages, 8)["result"], Value::Null);
+        assert!(!Path::new("x").exists());
+    }
+}
@@ -0,0 +1,64 @@
+use blockless_car::{reader as car_reader, utils, Cid};
+use std::{io::BufWriter, path::Path};
+
+use crate::{
+    error::UtilError,
+    stdio::{create_output, open_car, read_input},
+    usage::{count_blocks, count_reader},
+};
+
+#[derive(Debug, clap::Parser)]
+pub struct FilterCommand {
+    #[clap(help = "The car file to filter, `-` reads the car from the stdin.")]
+    car: String,
+
+    #[clap(
+        long,
+        help = "The file of the CIDs to copy, one per line, `-` reads the CIDs from stdin."
+    )]
+    cids: String,
+
+    #[clap(long, help = "Copy the blocks linked from the listed CIDs too.")]
+    closed: bool,
+
+    #[clap(
+        short,
+        help = "The car file to output, `-` writes the car to the stdout."
+    )]
+    output: String,
+
+    #[clap(
+        long = "tmpdir",
+        help = "The directory of the car of the stdin spooled for the random access, instead of the OS temp directory."
+    )]
+    tmpdir: Option<String>,
+}
+
+/// parse the cids one per line, the blank lines are skipped.
+fn parse_cids(list: &str) -> Result<Vec<Cid>, UtilError> {
+    list.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(|l| Cid::try_from(l).map_err(|e| UtilError::new(format!("invalid cid {l}: {e}"))))
+        .collect()
+}
+
+impl FilterCommand {
+    /// copy the listed blocks of the car, with `--closed` the DAGs under them.
+    pub(crate) fn execute(&self) -> Result<(), UtilError> {
+        let tmpdir = self.tmpdir.as_deref().map(Path::new);
+        let mut reader = car_reader::new_v1(open_car(&self.car, tmpdir)?)?;
+        let list = String::from_utf8(read_input(&self.cids)?)
+            .map_err(|e| UtilError::new(format!("invalid cids {}: {e}", self.cids)))?;
+        let cids = parse_cids(&list)?;
+        let output = create_output(&self.output)?;
+        let report = utils::filter_car(&mut reader, &cids, self.closed, BufWriter::new(output))?;
+        for cid in report.missing.iter() {
+            eprintln!("warning: {cid} is not in the car.");
+        }
+        eprintln!("{} blocks written.", report.blocks);
+        count_reader(&reader);
+        count_blocks(0, report.blocks as u64);
+        Ok(())
+    }
+}
@@ -0,0 +1,146 @@
+use blockless_car::{
+    reader::{self as car_reader, CarReader},
+    unixfs::FileType,
+    utils::{self, DagGraph, GraphNode, GraphNodeKind},
+    Cid,
+};
+use std::{collections::HashMap, fs::File, path::Path};
+
+use crate::{error::UtilError, stats::codec_name, usage::count_reader};
+
+#[derive(clap::ValueEnum, Clone, Debug)]
+pub(crate) enum GraphFormat {
+    Dot,
+    Mermaid,
+}
+
+#[derive(Debug, clap::Parser)]
+pub struct GraphCommand {
+    #[clap(help = "The car file to graph.")]
+    car: String,
+
+    #[clap(long, help = "The root of the graph, the roots of the car by default.")]
+    cid: Option<String>,
+
+    #[clap(
+        long,
+        value_enum,
+        default_value = "dot",
+        help = "The format of the graph."
+    )]
+    format: GraphFormat,
+
+    #[clap(
+        long,
+        help = "Don't follow the links of the blocks at the depth, the roots are the depth 0."
+    )]
+    depth: Option<usize>,
+}
+
+/// the lines of the node label, the cid prefix, the type and the size.
+fn label(node: &GraphNode) -> [String; 3] {
+    let cid = node.cid.to_string();
+    let cid = match cid.char_indices().nth(12) {
+        Some((i, _)) => format!("{}…", &cid[..i]),
+        None => cid,
+    };
+    let kind = match node.kind {
+        GraphNodeKind::Unixfs(FileType::Directory) => "directory".into(),
+        GraphNodeKind::Unixfs(FileType::HAMTShard) => "hamt-shard".into(),
+        GraphNodeKind::Unixfs(file_type) => file_type.to_string(),
+        GraphNodeKind::Raw => "raw".into(),
+        GraphNodeKind::Codec(code) => codec_name(code),
+        GraphNodeKind::Missing => "missing".into(),
+    };
+    let size = match (node.kind, node.truncated) {
+        (GraphNodeKind::Missing, _) => "not in the car".into(),
+        (_, true) => format!("{} B, links not shown", node.size),
+        (_, false) => format!("{} B", node.size),
+    };
+    [cid, kind, size]
+}
+
+fn dot(graph: &DagGraph) -> String {
+    let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+    let mut out = String::from("digraph car {\n    node [shape=box];\n");
+    for node in graph.nodes.iter() {
+        let style = match node.kind {
+            GraphNodeKind::Missing => ", style=dashed",
+            _ => "",
+        };
+        out.push_str(&format!(
+            "    \"{}\" [label=\"{}\"{style}];\n",
+            node.cid,
+            escape(&label(node).join("\n")).replace('\n', "\\n")
+        ));
+    }
+    for edge in graph.edges.iter() {
+        match edge.name.as_deref() {
+            Some(name) if !name.is_empty() => out.push_str(&format!(
+                "    \"{}\" -> \"{}\" [label=\"{}\"];\n",
+                edge.from,
+                edge.to,
+                escape(name)
+            )),
+            _ => out.push_str(&format!("    \"{}\" -> \"{}\";\n", edge.from, edge.to)),
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn mermaid(graph: &DagGraph) -> String {
+    let escape = |s: &str| s.replace('"', "#quot;").replace('|', "#124;");
+    let ids: HashMap<Cid, usize> = graph
+        .nodes
+        .iter()
+        .enumerate()
+        .map(|(i, n)| (n.cid, i))
+        .collect();
+    let mut out = String::from("graph TD\n");
+    for (i, node) in graph.nodes.iter().enumerate() {
+        let label = label(node).map(|l| escape(&l)).join("<br/>");
+        out.push_str(&format!("    n{i}[\"{label}\"]\n"));
+    }
+    for edge in graph.edges.iter() {
+        let (from, to) = (ids[&edge.from], ids[&edge.to]);
+        match edge.name.as_deref() {
+            Some(name) if !name.is_empty() => {
+                out.push_str(&format!("    n{from} -->|\"{}\"| n{to}\n", escape(name)))
+            }
+            _ => out.push_str(&format!("    n{from} --> n{to}\n")),
+        }
+    }
+    out
+}
+
+impl GraphCommand {
+    /// print the block graph of the DAGs of the car for Graphviz or Mermaid.
+    pub(crate) fn execute(&self) -> Result<(), UtilError> {
+        let path: &Path = self.car.as_ref();
+        if !path.exists() {
+            return Err(UtilError::new(format!(
+                "the car file [{}] does not exist.",
+                self.car
+            )));
+        }
+        let mut reader = car_reader::new_v1(File::open(path)?)?;
+        let roots = match &self.cid {
+            Some(cid) => vec![Cid::try_from(cid.as_str())
+                .map_err(|e| UtilError::new(format!("invalid cid {cid}: {e}")))?],
+            None => reader
+                .header()
+                .roots()
+                .into_iter()
+                .filter(|r| !utils::is_listing_root(r))
+                .collect(),
+        };
+        let graph = utils::dag_graph(&mut reader, &roots, self.depth)?;
+        count_reader(&reader);
+        match self.format {
+            GraphFormat::Dot => print!("{}", dot(&graph)),
+            GraphFormat::Mermaid => print!("{}", mermaid(&graph)),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+use blockless_car::{
+    utils::{self, CarIndex, IndexSource},
+    Cid,
+};
+use serde_json::json;
+
+use crate::error::UtilError;
+
+#[derive(Debug, clap::Parser)]
+pub struct IndexGetCommand {
+    #[clap(help = "The car file, or the sidecar index of the car like `file.car.idx`.")]
+    file: String,
+
+    #[clap(help = "The CID of the block to locate.", value_parser = parse_cid)]
+    cid: Cid,
+
+    #[clap(
+        long,
+        help = "The car file of the sidecar index, the index file without the `.idx` by default."
+    )]
+    car: Option<String>,
+}
+
+fn parse_cid(s: &str) -> Result<Cid, String> {
+    Cid::try_from(s).map_err(|e| format!("invalid cid {s}: {e}"))
+}
+
+fn source_name(source: IndexSource) -> &'static str {
+    match source {
+        IndexSource::Embedded => "embedded",
+        IndexSource::Sidecar => "sidecar",
+        IndexSource::Scan => "scan",
+    }
+}
+
+impl IndexGetCommand {
+    /// print the offset and the section length of the block in the car, located by the
+    /// sidecar or the embedded index, or by scanning the sections without the index.
+    pub(crate) fn execute(&self, is_json: bool) -> Result<(), UtilError> {
+        let path: &Path = self.file.as_ref();
+        if !path.exists() {
+            return Err(UtilError::new(format!(
+                "the file [{}] does not exist.",
+                self.file
+            )));
+        }
+        let is_car = utils::probe(&mut BufReader::new(File::open(path)?))?.is_car();
+        let (car, sidecar) = match is_car {
+            true => (path.to_path_buf(), None),
+            false => {
+                let index = CarIndex::decode(&mut BufReader::new(File::open(path)?))?;
+                let car = match &self.car {
+                    Some(car) => PathBuf::from(car),
+                    None if path.extension().is_some_and(|e| e == "idx") => path.with_extension(""),
+                    None => {
+                        return Err(UtilError::new(
+                            "the --car is required for the index not named `*.idx`.".into(),
+                        ))
+                    }
+                };
+                (car, Some(index))
+            }
+        };
+        if !car.exists() {
+            return Err(UtilError::new(format!(
+                "the car file [{}] does not exist.",
+                car.display()
+            )));
+        }
+        let mut reader = BufReader::new(File::open(&car)?);
+        let location = utils::locate_block(&mut reader, sidecar.as_ref(), &self.cid)?
+            .ok_or_else(|| UtilError::new(format!("the block {} is not in the car.", self.cid)))?;
+        let source = source_name(location.source);
+        match is_json {
+            true => println!(
+                "{}",
+                json!({
+                    "cid": self.cid.to_string(),
+                    "offset": location.offset,
+                    "length": location.length,
+                    "source": source,
+                })
+            ),
+            false => println!(
+                "{}\t{}\t{}\t{source}",
+                self.cid, location.offset, location.length
+            ),
+        }
+        Ok(())
+    }
+}
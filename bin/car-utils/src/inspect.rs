@@ -0,0 +1,136 @@
+use std::{io::BufReader, path::Path};
+
+use blockless_car::utils::{inspect, Inspect};
+use serde_json::{json, Value};
+
+use crate::{
+    cars::{expand_cars, map_cars},
+    error::UtilError,
+    ls::root_type,
+    probe::v2_json,
+    stdio::open_car,
+};
+
+#[derive(Debug, clap::Parser)]
+pub struct InspectCommand {
+    #[clap(
+        required = true,
+        help = "the car files to inspect, the directory is expanded to the car files in it and the glob to the files matching it, `-` reads a car from the stdin."
+    )]
+    cars: Vec<String>,
+
+    #[clap(
+        short,
+        long,
+        default_value_t = 0,
+        help = "The count of the cars inspected at once for multiple cars, 0 means the count of the CPUs."
+    )]
+    jobs: usize,
+
+    #[clap(
+        long = "tmpdir",
+        help = "the directory of the car of the stdin spooled for the random access, instead of the OS temp directory."
+    )]
+    tmpdir: Option<String>,
+}
+
+/// print the inspection, every line starts with the `prefix`.
+fn print_inspect(rs: &Inspect, prefix: &str) {
+    println!("{prefix}version: {}", rs.version);
+    if let Some(v2) = rs.v2.as_ref() {
+        println!("{prefix}characteristics: {:#034x}", v2.characteristics);
+        println!("{prefix}fully indexed: {}", v2.is_fully_indexed());
+        println!("{prefix}data offset: {}", v2.data_offset);
+        println!("{prefix}data size: {}", v2.data_size);
+        println!("{prefix}index offset: {}", v2.index_offset);
+    }
+    println!("{prefix}header size: {}", rs.header_size);
+    println!("{prefix}roots:");
+    for (root, kind) in rs.roots.iter().zip(rs.root_kinds.iter()) {
+        println!("{prefix}  {root} {}", root_type(*kind));
+    }
+    println!("{prefix}sections: {}", rs.sections);
+    for m in rs.malformed.iter() {
+        println!("{prefix}malformed: at offset {}: {}", m.offset, m.reason);
+    }
+}
+
+fn inspect_json(rs: &Inspect) -> Value {
+    let roots: Vec<String> = rs.roots.iter().map(|r| r.to_string()).collect();
+    let root_types: Vec<String> = rs.root_kinds.iter().map(|k| root_type(*k)).collect();
+    let malformed: Vec<Value> = rs
+        .malformed
+        .iter()
+        .map(|m| json!({"offset": m.offset, "reason": m.reason}))
+        .collect();
+    json!({
+        "version": rs.version,
+        "v2": rs.v2.as_ref().map(v2_json),
+        "header_size": rs.header_size,
+        "roots": roots,
+        "root_types": root_types,
+        "sections": rs.sections,
+        "malformed": malformed,
+    })
+}
+
+impl InspectCommand {
+    /// dump the header and check the framing of the sections, the command fails
+    /// if any section is malformed. multiple cars are inspected in parallel, as one
+    /// combined JSON object if `is_json`.
+    pub(crate) fn execute(&self, is_json: bool) -> Result<(), UtilError> {
+        let tmpdir = self.tmpdir.as_deref().map(Path::new);
+        let cars = expand_cars(&self.cars)?;
+        let inspect_car = |path: &Path| Ok(inspect(&mut BufReader::new(open_car(path, tmpdir)?))?);
+        if let [path] = cars.as_slice() {
+            let rs = inspect_car(path)?;
+            match is_json {
+                true => println!("{}", inspect_json(&rs)),
+                false => print_inspect(&rs, ""),
+            }
+            return match rs.malformed.len() {
+                0 => Ok(()),
+                n => Err(UtilError::new(format!("{n} malformed sections"))),
+            };
+        }
+        let results = map_cars(&cars, self.jobs, inspect_car);
+        let failed = results
+            .iter()
+            .filter(|rs| !matches!(rs, Ok(r) if r.malformed.is_empty()))
+            .count();
+        if is_json {
+            let reports: Vec<Value> = cars
+                .iter()
+                .zip(results.iter())
+                .map(|(path, rs)| {
+                    let path = path.display().to_string();
+                    match rs {
+                        Ok(rs) => {
+                            let mut value = inspect_json(rs);
+                            value["path"] = path.into();
+                            value["ok"] = rs.malformed.is_empty().into();
+                            value
+                        }
+                        Err(e) => json!({"path": path, "ok": false, "error": e.err}),
+                    }
+                })
+                .collect();
+            println!("{}", json!({"ok": failed == 0, "cars": reports}));
+        } else {
+            for (path, rs) in cars.iter().zip(results.iter()) {
+                let prefix = format!("{}: ", path.display());
+                match rs {
+                    Ok(rs) => print_inspect(rs, &prefix),
+                    Err(e) => println!("{prefix}error: {}", e.err),
+                }
+            }
+        }
+        match failed {
+            0 => Ok(()),
+            failed => Err(UtilError::new(format!(
+                "{failed} of {} cars failed the inspection",
+                cars.len()
+            ))),
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use blockless_car::utils::CancelToken;
+
+use crate::error::UtilError;
+
+/// cancel the running command on SIGINT/SIGTERM or when the timeout elapsed.
+pub(crate) struct Interrupt {
+    token: CancelToken,
+    timed_out: Arc<AtomicBool>,
+}
+
+impl Interrupt {
+    /// install the signal handler, and start the timer if the `timeout` in seconds is set.
+    pub(crate) fn install(timeout: Option<u64>) -> Result<Self, UtilError> {
+        let token = CancelToken::new();
+        let timed_out = Arc::new(AtomicBool::new(false));
+        let handler_token = token.clone();
+        ctrlc::set_handler(move || handler_token.cancel())
+            .map_err(|e| UtilError::new(format!("install the signal handler fail: {e}")))?;
+        if let Some(timeout) = timeout {
+            let timer_token = token.clone();
+            let timer_timed_out = timed_out.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_secs(timeout));
+                timer_timed_out.store(true, Ordering::SeqCst);
+                timer_token.cancel();
+            });
+        }
+        Ok(Self { token, timed_out })
+    }
+
+    #[inline]
+    pub(crate) fn token(&self) -> CancelToken {
+        self.token.clone()
+    }
+
+    /// the error of the cancelled command with the partial summary.
+    pub(crate) fn cancelled(&self, command: &str, summary: String) -> UtilError {
+        let (reason, code) = if self.timed_out.load(Ordering::SeqCst) {
+            ("timed out", 124)
+        } else {
+            ("interrupted", 130)
+        };
+        UtilError {
+            err: format!("{command} {reason}, {summary}"),
+            code,
+        }
+    }
+}
@@ -1,35 +1,232 @@
-use blockless_car::reader as car_reader;
-use blockless_car::utils;
-use std::fs::File;
-use std::path::Path;
+use blockless_car::reader::{self as car_reader, CarReader};
+use blockless_car::{
+    unixfs::FileType,
+    utils::{self, GraphNodeKind},
+};
+use blockless_car::{Cid, CidVersion as Version};
+use serde_json::json;
 
-use crate::error::UtilError;
+use crate::{
+    error::UtilError,
+    stats::{codec_name, hash_name},
+    stdio::read_car,
+    usage::count_reader,
+};
 
 #[derive(Debug, clap::Parser)]
 pub struct LsCommand {
-    #[clap(help = "the car file for list.")]
+    #[clap(help = "the car file for list, `-` reads the car from the stdin.")]
     car: String,
+
+    #[clap(
+        short = '0',
+        long = "null",
+        help = "Terminate the entries with NUL instead of newline."
+    )]
+    null: bool,
+
+    #[clap(
+        long = "relative-to",
+        help = "Print the paths relative to the root path, the entries out of the root are skipped."
+    )]
+    relative_to: Option<String>,
+
+    #[clap(
+        long = "fast",
+        help = "Print the listing embedded by `pack --embed-listing` instead of walking the DAG."
+    )]
+    fast: bool,
+
+    #[clap(
+        short = 'v',
+        long = "long",
+        help = "List the blocks in the file order with the cid, the codec, the byte offset and the length of each section, or with `roots` the version, the codec, the multihash, the presence and the type of each root.",
+        conflicts_with_all = ["relative_to", "fast"]
+    )]
+    long: bool,
+
+    #[clap(
+        long,
+        help = "Render the hierarchy as a tree with the branch characters.",
+        conflicts_with_all = ["null", "relative_to", "fast", "long"]
+    )]
+    tree: bool,
+
+    #[clap(
+        long,
+        help = "The max depth of the directories expanded in the tree, the roots are at depth 0.",
+        requires = "tree"
+    )]
+    depth: Option<usize>,
+}
+
+/// the path relative to the `root`, none if the path is not under the `root`.
+fn relative_path<'a>(path: &'a str, root: &str) -> Option<&'a str> {
+    path.strip_prefix(root)
+        .and_then(|p| p.strip_prefix('/'))
+        .filter(|p| !p.is_empty())
+}
+
+/// the bytes of the varint of the `n`.
+fn varint_len(n: u64) -> u64 {
+    (u64::from(64 - n.leading_zeros()).max(1)).div_ceil(7)
+}
+
+/// print the sections in the file order, like `go-car ls -v`. the offset is the start of
+/// the length varint in the file and the length is the varint, the cid and the data.
+fn print_sections(reader: &mut impl CarReader, term: char, is_json: bool) -> Result<(), UtilError> {
+    for section in reader.sections()? {
+        let cid = section.cid();
+        let len = (cid.encoded_len() + section.len()) as u64;
+        let offset = section.pos() - cid.encoded_len() as u64 - varint_len(len);
+        let codec = codec_name(cid.codec());
+        let length = varint_len(len) + len;
+        match is_json {
+            true => println!(
+                "{}",
+                json!({"cid": cid.to_string(), "codec": codec, "offset": offset, "length": length})
+            ),
+            false => print!("{cid}\t{codec}\t{offset}\t{length}{term}"),
+        }
+    }
+    Ok(())
+}
+
+/// the type of the root block, the unixfs type, the raw leaf or the codec of the IPLD.
+pub(crate) fn root_type(kind: GraphNodeKind) -> String {
+    match kind {
+        GraphNodeKind::Unixfs(FileType::HAMTShard) => "unixfs-hamt-shard".into(),
+        GraphNodeKind::Unixfs(file_type) => format!("unixfs-{file_type}"),
+        GraphNodeKind::Raw => "raw-leaf".into(),
+        GraphNodeKind::Codec(code) => format!("ipld-{}", codec_name(code)),
+        GraphNodeKind::Missing => "missing".into(),
+    }
+}
+
+/// print the cid version, the codec, the multihash and the digest size of each root,
+/// whether the root block is in the car and the type of the root block.
+fn print_roots(reader: &mut impl CarReader, term: char, is_json: bool) -> Result<(), UtilError> {
+    for root in reader.header().roots() {
+        let version = match root.version() {
+            Version::V0 => 0,
+            Version::V1 => 1,
+        };
+        let codec = codec_name(root.codec());
+        let hash = hash_name(root.hash().code());
+        let digest_size = root.hash().size();
+        let present = reader.block_len(&root)?.is_some();
+        let root_type = root_type(utils::block_kind(reader, &root)?);
+        match is_json {
+            true => {
+                let root = json!({
+                    "cid": root.to_string(),
+                    "version": version,
+                    "codec": codec,
+                    "hash": hash,
+                    "digest_size": digest_size,
+                    "present": present,
+                    "type": root_type,
+                });
+                println!("{root}");
+            }
+            false => {
+                let present = if present { "present" } else { "missing" };
+                print!("{root}\tv{version}\t{codec}\t{hash}\t{digest_size}\t{present}\t{root_type}{term}");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// print the tree of the roots, the cids instead of the names if `is_cid`.
+fn print_tree(
+    reader: &mut impl CarReader,
+    depth: Option<usize>,
+    is_cid: bool,
+    is_json: bool,
+) -> Result<(), UtilError> {
+    // whether the ancestor at each depth is the last entry of its directory.
+    let mut lasts: Vec<bool> = Vec::new();
+    utils::list_tree(reader, depth, |e| {
+        if is_json {
+            let entry = json!({
+                "cid": e.cid.to_string(),
+                "name": e.name,
+                "depth": e.depth,
+                "last": e.last,
+            });
+            println!("{entry}");
+            return;
+        }
+        lasts.truncate(e.depth);
+        lasts.push(e.last);
+        let mut line = String::new();
+        if e.depth > 0 {
+            for last in lasts[1..e.depth].iter() {
+                line.push_str(if *last { "    " } else { "│   " });
+            }
+            line.push_str(if e.last { "└── " } else { "├── " });
+        }
+        match is_cid {
+            true => println!("{line}{}", e.cid),
+            false => println!("{line}{}", e.name),
+        }
+    })?;
+    Ok(())
 }
 
 impl LsCommand {
     /// list files from car file.
     /// `path` is the car file path.
-    pub(crate) fn execute(&self, is_cid: bool) -> Result<(), UtilError> {
-        // Ok(list_car_file(&self.car, is_cid)?)
-        let path: &Path = self.car.as_ref();
-        if !path.exists() {
-            return Err(UtilError::new(format!(
-                "car file [{}] is not exist.",
-                path.to_str().unwrap()
-            )));
-        }
-        let file = File::open(path)?;
-        let mut reader = car_reader::new_v1(file)?;
-        if is_cid {
-            utils::list_cid(&mut reader)?;
-        } else {
-            utils::list(&mut reader)?;
+    /// the entries are printed as the JSON objects one per line if `is_json`.
+    pub(crate) fn execute(&self, is_cid: bool, is_json: bool) -> Result<(), UtilError> {
+        let mut reader = car_reader::new_v1(read_car(&self.car)?)?;
+        let result = self.list(&mut reader, is_cid, is_json);
+        count_reader(&reader);
+        result
+    }
+
+    /// list the entries of the car read by the `reader`.
+    fn list(
+        &self,
+        reader: &mut impl CarReader,
+        is_cid: bool,
+        is_json: bool,
+    ) -> Result<(), UtilError> {
+        let term = if self.null { '\0' } else { '\n' };
+        if self.long && is_cid {
+            return print_roots(reader, term, is_json);
+        }
+        if self.long {
+            return print_sections(reader, term, is_json);
+        }
+        if self.tree {
+            return print_tree(reader, self.depth, is_cid, is_json);
+        }
+        let root = self.relative_to.as_deref().map(|r| r.trim_end_matches('/'));
+        let print = |cid: &Cid, file_n: &str| {
+            let path = match root {
+                Some(root) if !is_cid => match relative_path(file_n, root) {
+                    Some(p) => p,
+                    None => return,
+                },
+                _ => file_n,
+            };
+            match (is_json, is_cid) {
+                (true, true) => println!("{}", json!({"cid": cid.to_string()})),
+                (true, false) => println!("{}", json!({"cid": cid.to_string(), "path": path})),
+                (false, true) => print!("{cid}{term}"),
+                (false, false) => print!("{path}{term}"),
+            }
+        };
+        // the CAR without the listing is walked.
+        if self.fast {
+            if let Some(entries) = utils::read_listing(reader)? {
+                entries.iter().for_each(|e| print(&e.cid, &e.path));
+                return Ok(());
+            }
         }
+        utils::list_call(reader, print)?;
         Ok(())
     }
 }
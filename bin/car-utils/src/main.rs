@@ -1,8 +1,31 @@
+mod bundle;
+mod cars;
 mod cat;
+mod daemon;
 mod error;
+mod filter;
+mod graph;
+mod index_get;
+mod inspect;
+mod interrupt;
 mod ls;
+mod merge;
 mod pack;
+mod probe;
+mod progress;
+mod put_blocks;
+mod repack;
+mod salvage;
+mod selftest;
+mod serve;
+mod set_roots;
+mod split;
+mod stats;
+mod stdio;
 mod unpack;
+mod usage;
+mod validate;
+mod verify;
 use clap::{Parser, Subcommand};
 
 /// The short version information for car-utils.
@@ -22,6 +45,14 @@ struct Cli {
     /// The command to run
     #[clap(subcommand)]
     command: Commands,
+
+    /// Print the output as JSON, the entries of ls and roots one object per line.
+    #[clap(long, global = true)]
+    json: bool,
+
+    /// Print the time, the memory, the bytes and the blocks used by the command to the stderr.
+    #[clap(long, global = true)]
+    stats: bool,
 }
 
 /// Commands to be executed
@@ -46,17 +77,133 @@ pub enum Commands {
     /// View cid content from a car file.
     #[command(name = "cat")]
     Cat(cat::CatCommand),
+
+    /// Assemble a CAR from the raw blocks and the root CIDs.
+    #[command(name = "put-blocks")]
+    PutBlocks(put_blocks::PutBlocksCommand),
+
+    /// Merge CARs into one with the union of their roots and each block once.
+    #[command(name = "merge")]
+    Merge(merge::MergeCommand),
+
+    /// Copy the listed blocks of a CAR, optionally with the DAGs under them.
+    #[command(name = "filter")]
+    Filter(filter::FilterCommand),
+
+    /// Split a CAR into the CARs of a max size, each with the roots.
+    #[command(name = "split")]
+    Split(split::SplitCommand),
+
+    /// Copy a CAR with the block payloads aligned for mmap serving.
+    #[command(name = "repack")]
+    Repack(repack::RepackCommand),
+
+    /// Replace the roots in the header of a CARv1 file.
+    #[command(name = "set-roots")]
+    SetRoots(set_roots::SetRootsCommand),
+
+    /// Recover the valid blocks of a truncated or corrupt CAR.
+    #[command(name = "salvage")]
+    Salvage(salvage::SalvageCommand),
+
+    /// Verify the blocks of a CAR match their CIDs.
+    #[command(name = "verify")]
+    Verify(verify::VerifyCommand),
+
+    /// Check the paths of a CAR resolve to the CIDs and sizes of a JSON manifest.
+    #[command(name = "validate-manifest")]
+    ValidateManifest(validate::ValidateManifestCommand),
+
+    /// Show the manifest and wasm modules of a Blockless bundle CAR.
+    #[command(name = "bundle-info")]
+    BundleInfo(bundle::BundleInfoCommand),
+
+    /// Report whether a file is a CAR and its format.
+    #[command(name = "probe")]
+    Probe(probe::ProbeCommand),
+
+    /// Dump the header of a CAR and check the framing of its sections.
+    #[command(name = "inspect")]
+    Inspect(inspect::InspectCommand),
+
+    /// Print the block graph of a CAR for Graphviz or Mermaid.
+    #[command(name = "graph")]
+    Graph(graph::GraphCommand),
+
+    /// Print the offset and the length of a block by the index of a CAR.
+    #[command(name = "index-get")]
+    IndexGet(index_get::IndexGetCommand),
+
+    /// Serve JSON-RPC requests over stdio or a unix socket.
+    #[command(name = "daemon")]
+    Daemon(daemon::DaemonCommand),
+
+    /// Serve the presence and integrity of the blocks of a CAR over HTTP.
+    #[command(name = "serve")]
+    Serve(serve::ServeCommand),
+
+    /// Report the header, the blocks and the bytes of a CAR.
+    #[command(name = "stats")]
+    Stats(stats::StatsCommand),
+
+    /// Pack, verify and extract a generated tree and report whether it comes back the same.
+    #[command(name = "selftest")]
+    Selftest(selftest::SelftestCommand),
+}
+
+impl Commands {
+    /// whether the command prints the JSON output with `--json`.
+    fn has_json(&self) -> bool {
+        matches!(
+            self,
+            Commands::Ls(_)
+                | Commands::Roots(_)
+                | Commands::Verify(_)
+                | Commands::Probe(_)
+                | Commands::Inspect(_)
+                | Commands::IndexGet(_)
+                | Commands::Stats(_)
+        )
+    }
 }
 
 fn main() {
     let opt = Cli::parse();
-    if let Err(err) = match opt.command {
+    let json = opt.json;
+    if json && !opt.command.has_json() {
+        eprintln!("Error: the command has no JSON output.");
+        std::process::exit(2);
+    }
+    let usage = opt.stats.then(usage::Usage::start);
+    let result = match opt.command {
         Commands::Pack(command) => command.execute(),
         Commands::Unpack(command) => command.execute(),
-        Commands::Ls(command) => command.execute(false),
-        Commands::Roots(command) => command.execute(true),
+        Commands::Ls(command) => command.execute(false, json),
+        Commands::Roots(command) => command.execute(true, json),
         Commands::Cat(command) => command.execute(),
-    } {
+        Commands::PutBlocks(command) => command.execute(),
+        Commands::Merge(command) => command.execute(),
+        Commands::Filter(command) => command.execute(),
+        Commands::Split(command) => command.execute(),
+        Commands::Repack(command) => command.execute(),
+        Commands::SetRoots(command) => command.execute(),
+        Commands::Salvage(command) => command.execute(),
+        Commands::Verify(command) => command.execute(json),
+        Commands::ValidateManifest(command) => command.execute(),
+        Commands::BundleInfo(command) => command.execute(),
+        Commands::Probe(command) => command.execute(json),
+        Commands::Inspect(command) => command.execute(json),
+        Commands::Graph(command) => command.execute(),
+        Commands::IndexGet(command) => command.execute(json),
+        Commands::Daemon(command) => command.execute(),
+        Commands::Serve(command) => command.execute(),
+        Commands::Stats(command) => command.execute(json),
+        Commands::Selftest(command) => command.execute(),
+    };
+    if let Some(usage) = usage {
+        usage.report(json);
+    }
+    if let Err(err) = result {
         eprintln!("Error: {err:?}");
         std::process::exit(1);
     }
@@ -0,0 +1,57 @@
+use blockless_car::{reader as car_reader, utils};
+use std::{fs::File, io::BufWriter, path::Path};
+
+use crate::{
+    error::UtilError,
+    stdio::create_output,
+    usage::{count_blocks, count_reader},
+};
+
+#[derive(Debug, clap::Parser)]
+pub struct MergeCommand {
+    #[clap(
+        required = true,
+        help = "The car files to merge, in the order of their blocks."
+    )]
+    cars: Vec<String>,
+
+    #[clap(
+        short,
+        help = "The car file to output, `-` writes the car to the stdout."
+    )]
+    output: String,
+}
+
+impl MergeCommand {
+    /// merge the car files into one with the union of the roots, the repeated blocks are
+    /// written once.
+    pub(crate) fn execute(&self) -> Result<(), UtilError> {
+        let output = Path::new(&self.output);
+        let mut readers = Vec::with_capacity(self.cars.len());
+        for car in self.cars.iter() {
+            let path: &Path = car.as_ref();
+            if !path.exists() {
+                return Err(UtilError::new(format!(
+                    "the car file [{car}] does not exist."
+                )));
+            }
+            // the output is truncated before the inputs are read.
+            if output.exists() && path.canonicalize()? == output.canonicalize()? {
+                return Err(UtilError::new(format!(
+                    "the output [{}] is one of the car files.",
+                    self.output
+                )));
+            }
+            readers.push(car_reader::new_v1(File::open(path)?)?);
+        }
+        let output = create_output(&self.output)?;
+        let stats = utils::merge_cars(&mut readers, BufWriter::new(output))?;
+        readers.iter().for_each(count_reader);
+        count_blocks(0, stats.blocks as u64);
+        eprintln!(
+            "{} roots, {} blocks written, {} duplicates skipped.",
+            stats.roots, stats.blocks, stats.duplicates
+        );
+        Ok(())
+    }
+}
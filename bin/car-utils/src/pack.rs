@@ -1,18 +1,48 @@
-use crate::error::UtilError;
-use blockless_car::utils::pack_files;
-use std::path::Path;
+use crate::{
+    error::UtilError,
+    interrupt::Interrupt,
+    progress::{source_bytes, ProgressBar},
+    stdio::read_input,
+    usage::count_blocks,
+};
+use blockless_car::{
+    error::CarError,
+    utils::{
+        embed_listing, pack_file_list, pack_files_multi_root, pack_files_to_stream,
+        pack_files_with_report, ChunkRule, Determinism, PackCache, PackOptions, PathLimits,
+        Pattern, PruneDefaults, SpecialFiles, TopNode, DEFAULT_CHUNK_SIZE, DEFAULT_HAMT_THRESHOLD,
+        DEFAULT_MAX_NAME_LEN, DEFAULT_MAX_PATH_DEPTH,
+    },
+    writer::BlockDedup,
+    CidVersion,
+};
+use std::{
+    io::{self, Write},
+    path::Path,
+    sync::Arc,
+};
 
 #[allow(non_camel_case_types)]
 #[derive(clap::ValueEnum, Clone, Debug)]
-enum HasherCodec {
+pub(crate) enum HasherCodec {
     Sha2_256,
     Blake2b_256,
 }
 
+impl HasherCodec {
+    pub(crate) fn codec(&self) -> multicodec::Codec {
+        match self {
+            HasherCodec::Sha2_256 => multicodec::Codec::Sha2_256,
+            HasherCodec::Blake2b_256 => multicodec::Codec::Blake2b_256,
+        }
+    }
+}
+
 #[derive(Debug, clap::Parser)]
 pub struct PackCommand {
-    /// The source file or directory to be packed.
-    source: String,
+    /// The source file or directory to be packed, or the base directory of --files-from.
+    #[clap(required_unless_present_any = ["files_from", "multi_root"])]
+    source: Option<String>,
 
     #[clap(
         value_enum,
@@ -28,8 +58,243 @@ pub struct PackCommand {
     )]
     no_wrap_file: bool,
 
-    #[clap(short, help = "The car file to output.")]
+    #[clap(
+        long = "top-node",
+        help = "The name of the top node linking the source, the basename of the source by default."
+    )]
+    top_node: Option<String>,
+
+    #[clap(
+        long = "no-top-node",
+        help = "Don't add the top node, the root is the source itself.",
+        conflicts_with = "top_node"
+    )]
+    no_top_node: bool,
+
+    #[clap(
+        short,
+        help = "The car file to output, `-` writes the car to the stdout."
+    )]
     output: String,
+
+    #[clap(
+        long = "chunk-size",
+        help = "The size in bytes of the file chunks, `size-<n>` like the kubo chunker is accepted.",
+        default_value_t = DEFAULT_CHUNK_SIZE,
+        value_parser = parse_chunk_size
+    )]
+    chunk_size: usize,
+
+    #[clap(
+        long = "raw-leaves",
+        help = "Write the file chunks as raw blocks, `--raw-leaves=false` wraps them into dag-pb nodes like `ipfs add`.",
+        default_value_t = true,
+        action = clap::ArgAction::Set
+    )]
+    raw_leaves: bool,
+
+    #[clap(
+        short,
+        long,
+        default_value_t = 0,
+        help = "The count of the workers hashing the chunks of the large files, 0 means the count of the CPUs."
+    )]
+    jobs: usize,
+
+    #[clap(
+        long,
+        help = "Write the chunks in the order the workers finish them, the bytes of the car may differ between the runs."
+    )]
+    unordered: bool,
+
+    #[clap(
+        long = "chunk-rule",
+        help = "Override the chunk size or the leaf layout (`raw` or `pb`) of the matched files, e.g. `*.mp4=1MiB`.",
+        value_name = "GLOB=SPEC",
+        value_parser = parse_chunk_rule
+    )]
+    chunk_rules: Vec<ChunkRule>,
+
+    #[clap(
+        long = "cid-version",
+        help = "The cid version of the dag-pb nodes, 0 requires the sha2-256 hasher.",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u64).range(0..=1)
+    )]
+    cid_version: u64,
+
+    #[clap(
+        long = "hamt-threshold",
+        help = "Shard the directories whose estimated node size exceeds the bytes, 0 disables the sharding.",
+        default_value_t = DEFAULT_HAMT_THRESHOLD
+    )]
+    hamt_threshold: usize,
+
+    #[clap(
+        long = "max-depth",
+        help = "The maximum depth of nested paths.",
+        default_value_t = DEFAULT_MAX_PATH_DEPTH
+    )]
+    max_depth: usize,
+
+    #[clap(
+        long = "max-name-len",
+        help = "The maximum length in bytes of a file name.",
+        default_value_t = DEFAULT_MAX_NAME_LEN
+    )]
+    max_name_len: usize,
+
+    #[clap(
+        long = "prune-defaults",
+        help = "Don't descend into the `.git`, `target` and `node_modules` directories."
+    )]
+    prune_defaults: bool,
+
+    #[clap(
+        long = "exclude",
+        help = "Don't pack the entries matching the glob, e.g. `node_modules` or `src/**/*.tmp`.",
+        value_name = "GLOB",
+        value_parser = parse_pattern
+    )]
+    exclude: Vec<Pattern>,
+
+    #[clap(
+        long = "include",
+        help = "Only pack the files matching any of the globs, the directories are still walked.",
+        value_name = "GLOB",
+        value_parser = parse_pattern
+    )]
+    include: Vec<Pattern>,
+
+    #[clap(
+        long = "gitignore",
+        help = "Don't pack the entries ignored by the `.gitignore` files."
+    )]
+    gitignore: bool,
+
+    #[clap(
+        long = "dedup-files",
+        help = "Reuse the DAG of identical files instead of hashing them again."
+    )]
+    dedup_files: bool,
+
+    #[clap(
+        long = "block-dedup",
+        default_value = "all",
+        help = "The blocks remembered to write the repeated blocks once, `all`, `off` for the inputs known unique, or the count of the recent blocks.",
+        value_parser = parse_block_dedup
+    )]
+    block_dedup: BlockDedup,
+
+    #[clap(
+        long = "chunk-cache",
+        help = "The capacity of the chunk cache in chunks, identical chunks across files are hashed and written once."
+    )]
+    chunk_cache: Option<usize>,
+
+    #[clap(
+        long = "cache",
+        help = "The CAR packed before from the same source, the unchanged files are copied from it.",
+        value_name = "CAR"
+    )]
+    cache: Option<String>,
+
+    #[clap(
+        long = "preserve-owner",
+        help = "Store the uid and gid of files and directories in the metadata."
+    )]
+    preserve_owner: bool,
+
+    #[clap(
+        long = "preserve-metadata",
+        help = "Store the mode and mtime of files and directories in the metadata."
+    )]
+    preserve_metadata: bool,
+
+    #[clap(
+        short = 'T',
+        long = "files-from",
+        help = "Pack exactly the paths listed in the file, `-` reads the list from stdin."
+    )]
+    files_from: Option<String>,
+
+    #[clap(
+        long = "null",
+        help = "The paths of --files-from are separated by NUL instead of newline."
+    )]
+    null: bool,
+
+    #[clap(
+        long = "multi-root",
+        help = "Pack every source as an independent DAG, the header lists all the roots.",
+        num_args = 1..,
+        value_name = "SOURCE",
+        conflicts_with_all = ["source", "files_from"]
+    )]
+    multi_root: Vec<String>,
+
+    #[clap(
+        long = "embed-listing",
+        help = "Embed the listing of the entries for `ls --fast`, linked from an extra root."
+    )]
+    embed_listing: bool,
+
+    #[cfg(feature = "xattr")]
+    #[clap(
+        long = "preserve-xattrs",
+        help = "Store the extended attributes of files and directories in the metadata."
+    )]
+    preserve_xattrs: bool,
+
+    #[clap(
+        long = "timeout",
+        help = "Cancel the pack after the seconds, the partial output is removed."
+    )]
+    timeout: Option<u64>,
+
+    #[clap(
+        long = "tmpdir",
+        help = "The directory of the temp files, like the car spilled by `-o -`, instead of the OS temp directory."
+    )]
+    tmpdir: Option<String>,
+
+    #[clap(
+        long = "special-files",
+        help = "Pack the special source, like `/dev/stdin` or a named pipe, as a file read until the end."
+    )]
+    special_files: bool,
+
+    #[clap(
+        long = "special-size",
+        help = "The declared size in bytes of the special source, the shorter stream fails.",
+        requires = "special_files"
+    )]
+    special_size: Option<u64>,
+
+    #[clap(
+        long = "progress",
+        help = "Show a progress bar with the files, the bytes hashed and written and the ETA on the stderr."
+    )]
+    progress: bool,
+}
+
+/// parse the chunk size, the kubo chunker format `size-<n>` is accepted.
+fn parse_chunk_size(s: &str) -> Result<usize, String> {
+    let n = s.strip_prefix("size-").unwrap_or(s);
+    n.parse()
+        .map_err(|e| format!("invalid chunk size {s}: {e}"))
+}
+
+fn parse_chunk_rule(s: &str) -> Result<ChunkRule, String> {
+    s.parse().map_err(|e: CarError| e.to_string())
+}
+
+fn parse_block_dedup(s: &str) -> Result<BlockDedup, String> {
+    s.parse().map_err(|e: CarError| e.to_string())
+}
+
+fn parse_pattern(s: &str) -> Result<Pattern, String> {
+    Pattern::new(s).map_err(|e| e.to_string())
 }
 
 impl PackCommand {
@@ -37,17 +302,207 @@ impl PackCommand {
     /// `target` is the car file
     /// `source` is the directory where the archive is prepared.
     pub(crate) fn execute(&self) -> Result<(), UtilError> {
-        let file = std::fs::File::create(self.output.as_ref() as &Path).unwrap(); // todo handle error
-        let hasher_codec = match self.hasher_codec {
-            HasherCodec::Sha2_256 => multicodec::Codec::Sha2_256,
-            HasherCodec::Blake2b_256 => multicodec::Codec::Blake2b_256,
+        // read the list before the signal handler installed, so the blocked read can be interrupted.
+        let paths = match self.files_from.as_ref() {
+            Some(list) => Some(self.read_file_list(list)?),
+            None => None,
+        };
+        let interrupt = Interrupt::install(self.timeout)?;
+        let hasher_codec = self.hasher_codec.codec();
+        let cid_version =
+            CidVersion::try_from(self.cid_version).map_err(|e| UtilError::new(e.to_string()))?;
+        let top_node = match (self.no_top_node, self.top_node.as_ref()) {
+            (true, _) => TopNode::Omit,
+            (false, Some(name)) => TopNode::Named(name.clone()),
+            (false, None) => TopNode::Basename,
+        };
+        let options = PackOptions::new()
+            .with_hasher_codec(hasher_codec)
+            .with_no_wrap_file(self.no_wrap_file)
+            .with_top_node(top_node)
+            .with_chunk_size(self.chunk_size)
+            .with_raw_leaves(self.raw_leaves)
+            .with_cid_version(cid_version)
+            .with_hamt_threshold(Some(self.hamt_threshold).filter(|t| *t > 0))
+            .with_limits(PathLimits::new(self.max_depth, self.max_name_len))
+            .with_dedup_files(self.dedup_files)
+            .with_jobs(self.jobs)
+            .with_determinism(match self.unordered {
+                true => Determinism::Relaxed,
+                false => Determinism::Strict,
+            })
+            .with_block_dedup(self.block_dedup)
+            .with_chunk_cache(self.chunk_cache)
+            .with_preserve_owner(self.preserve_owner)
+            .with_preserve_metadata(self.preserve_metadata)
+            .with_gitignore(self.gitignore)
+            .with_cancel(interrupt.token());
+        let options = match self.prune_defaults {
+            true => options.with_walk_filter(Arc::new(PruneDefaults)),
+            false => options,
+        };
+        let options = self
+            .chunk_rules
+            .iter()
+            .cloned()
+            .fold(options, PackOptions::with_chunk_rule);
+        let options = self
+            .exclude
+            .iter()
+            .cloned()
+            .fold(options, PackOptions::with_exclude);
+        let options = self
+            .include
+            .iter()
+            .cloned()
+            .fold(options, PackOptions::with_include);
+        #[cfg(feature = "xattr")]
+        let options = options.with_preserve_xattrs(self.preserve_xattrs);
+        let options = match (self.special_files, self.special_size) {
+            (false, _) => options,
+            (true, None) => options.with_special_files(SpecialFiles::UntilEof),
+            (true, Some(len)) => options.with_special_files(SpecialFiles::Size(len)),
+        };
+        let to_stdout = self.output == "-";
+        if to_stdout && (paths.is_some() || !self.multi_root.is_empty() || self.embed_listing) {
+            return Err(UtilError::new(
+                "`-o -` only packs a single source without --embed-listing.".into(),
+            ));
+        }
+        // the stdout is not seekable, the car is spilled to the temp directory and copied.
+        let spill_dir = match self.tmpdir.as_ref() {
+            Some(tmpdir) => tmpdir.into(),
+            None => std::env::temp_dir(),
         };
-        pack_files(
-            self.source.as_ref() as &Path,
-            file,
-            hasher_codec,
-            self.no_wrap_file,
-        )?;
+        let options = options.with_spill_dir(spill_dir);
+        let options = match self.cache.as_ref() {
+            Some(cache) => {
+                let same = |a: &str, b: &str| {
+                    std::fs::canonicalize(a)
+                        .ok()
+                        .zip(std::fs::canonicalize(b).ok())
+                        .is_some_and(|(a, b)| a == b)
+                };
+                if same(cache, &self.output) {
+                    return Err(UtilError::new(
+                        "the --cache car can't be the output, it's truncated before read.".into(),
+                    ));
+                }
+                options.with_cache(Arc::new(PackCache::open(cache)?))
+            }
+            None => options,
+        };
+        let options = match self.progress {
+            true => {
+                let total = match paths.as_ref() {
+                    Some(paths) => {
+                        let base: &Path = self.source.as_deref().unwrap_or(".").as_ref();
+                        paths.iter().map(|p| source_bytes(base.join(p))).sum()
+                    }
+                    None if !self.multi_root.is_empty() => {
+                        self.multi_root.iter().map(source_bytes).sum()
+                    }
+                    None => source_bytes(self.source.as_deref().unwrap_or_default()),
+                };
+                options.with_progress(ProgressBar::new("packing", Some(total)).hook())
+            }
+            false => options,
+        };
+        let create = || std::fs::File::create(self.output.as_ref() as &Path);
+        let result = match paths {
+            Some(paths) => {
+                let base = self.source.as_deref().unwrap_or(".");
+                pack_file_list(base, paths, create()?, &options)
+            }
+            None if !self.multi_root.is_empty() => {
+                pack_files_multi_root(&self.multi_root, create()?, &options).map(
+                    |(roots, report)| {
+                        for (source, root) in self.multi_root.iter().zip(roots.iter()) {
+                            println!("{root} {source}");
+                        }
+                        (roots[0], report)
+                    },
+                )
+            }
+            None => {
+                let source: &Path = self.source.as_deref().unwrap_or_default().as_ref();
+                match to_stdout {
+                    true => pack_files_to_stream(source, io::stdout().lock(), &options),
+                    false => pack_files_with_report(source, create()?, &options),
+                }
+            }
+        };
+        let report = match result {
+            Ok((_, report)) => report,
+            Err(CarError::Cancelled { entries }) if to_stdout => {
+                let summary = format!("{entries} entries packed to the stdout");
+                return Err(interrupt.cancelled("pack", summary));
+            }
+            Err(CarError::Cancelled { entries }) => {
+                std::fs::remove_file(&self.output)?;
+                let summary = format!(
+                    "{entries} entries packed, the partial output {} is removed",
+                    self.output
+                );
+                return Err(interrupt.cancelled("pack", summary));
+            }
+            Err(e) => return Err(e.into()),
+        };
+        count_blocks(0, report.blocks);
+        if self.embed_listing {
+            embed_listing(&self.output)?;
+        }
+        // the summaries go to the stderr when the car is written to the stdout.
+        let mut log: Box<dyn Write> = match to_stdout {
+            true => Box::new(io::stderr()),
+            false => Box::new(io::stdout()),
+        };
+        if self.dedup_files {
+            writeln!(
+                log,
+                "deduplicated {} files, {} bytes saved.",
+                report.dedup.files_deduped(),
+                report.dedup.bytes_saved()
+            )?;
+        }
+        if self.cache.is_some() {
+            writeln!(
+                log,
+                "copied {} files from the cache, {} bytes not read.",
+                report.cache.files, report.cache.bytes
+            )?;
+        }
+        if self.chunk_cache.is_some() {
+            let chunks = &report.chunks;
+            writeln!(
+                log,
+                "deduplicated {}/{} chunks, {} bytes saved, dedup ratio {:.2}%.",
+                chunks.chunks_deduped,
+                chunks.chunks,
+                chunks.bytes_deduped,
+                chunks.ratio() * 100.
+            )?;
+        }
         Ok(())
     }
+
+    /// read the paths separated by newline or NUL from the file or stdin.
+    fn read_file_list(&self, list: &str) -> Result<Vec<String>, UtilError> {
+        let buf = read_input(list)?;
+        let sep = if self.null { b'\0' } else { b'\n' };
+        buf.split(|b| *b == sep)
+            .map(|p| {
+                if self.null {
+                    p
+                } else {
+                    p.strip_suffix(b"\r").unwrap_or(p)
+                }
+            })
+            .filter(|p| !p.is_empty())
+            .map(|p| {
+                String::from_utf8(p.to_vec())
+                    .map_err(|_| UtilError::new("the path in the file list is not utf8".into()))
+            })
+            .collect()
+    }
 }
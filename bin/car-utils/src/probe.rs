@@ -0,0 +1,101 @@
+use std::{fs::File, io::BufReader};
+
+use blockless_car::utils::{probe, CarV2Header, Probe, ProbeFormat};
+use serde_json::{json, Value};
+
+use crate::error::UtilError;
+
+#[derive(Debug, clap::Parser)]
+pub struct ProbeCommand {
+    #[clap(help = "the file to probe.")]
+    file: String,
+}
+
+fn format_name(format: ProbeFormat) -> &'static str {
+    match format {
+        ProbeFormat::CarV1 => "CARv1",
+        ProbeFormat::CarV2 => "CARv2",
+        ProbeFormat::Gzip => "gzip",
+        ProbeFormat::Zstd => "zstd",
+        ProbeFormat::Unknown => "not a CAR",
+    }
+}
+
+impl ProbeCommand {
+    /// classify the file, the compressed file is decompressed and probed again.
+    pub(crate) fn execute(&self, is_json: bool) -> Result<(), UtilError> {
+        let mut file = BufReader::new(File::open(&self.file)?);
+        let rs = probe(&mut file)?;
+        let (compression, rs) = match rs.format {
+            ProbeFormat::Gzip => {
+                let file = BufReader::new(File::open(&self.file)?);
+                let mut decoder = flate2::bufread::MultiGzDecoder::new(file);
+                (Some(rs.format), probe(&mut decoder)?)
+            }
+            ProbeFormat::Zstd => {
+                let file = BufReader::new(File::open(&self.file)?);
+                let mut decoder = ruzstd::decoding::StreamingDecoder::new(file)
+                    .map_err(|e| UtilError::new(format!("zstd decode fail: {e}")))?;
+                (Some(rs.format), probe(&mut decoder)?)
+            }
+            _ => (None, rs),
+        };
+        match is_json {
+            true => println!("{}", probe_json(compression, &rs)),
+            false => print_probe(compression, &rs),
+        }
+        Ok(())
+    }
+}
+
+/// the CARv2 header as JSON, the characteristics bitfield in hex.
+pub(crate) fn v2_json(v2: &CarV2Header) -> Value {
+    json!({
+        "characteristics": format!("{:#034x}", v2.characteristics),
+        "fully_indexed": v2.is_fully_indexed(),
+        "data_offset": v2.data_offset,
+        "data_size": v2.data_size,
+        "index_offset": v2.index_offset,
+    })
+}
+
+fn probe_json(compression: Option<ProbeFormat>, rs: &Probe) -> Value {
+    let roots: Vec<String> = rs.roots.iter().map(|r| r.to_string()).collect();
+    json!({
+        "format": format_name(rs.format),
+        "compression": compression.map(format_name),
+        "car": rs.is_car(),
+        "reason": rs.error,
+        "v2": rs.v2.as_ref().map(v2_json),
+        "roots": roots,
+    })
+}
+
+fn print_probe(compression: Option<ProbeFormat>, rs: &Probe) {
+    match compression {
+        Some(c) if rs.is_car() => {
+            println!(
+                "format: {}-compressed {}",
+                format_name(c),
+                format_name(rs.format)
+            )
+        }
+        Some(c) => println!("format: {}-compressed, not a CAR", format_name(c)),
+        None => println!("format: {}", format_name(rs.format)),
+    }
+    if let Some(error) = rs.error.as_ref() {
+        println!("reason: {error}");
+    }
+    if let Some(v2) = rs.v2.as_ref() {
+        println!("data offset: {}", v2.data_offset);
+        println!("data size: {}", v2.data_size);
+        println!("index offset: {}", v2.index_offset);
+        println!("fully indexed: {}", v2.is_fully_indexed());
+    }
+    if rs.is_car() {
+        println!("roots:");
+        for root in rs.roots.iter() {
+            println!("  {root}");
+        }
+    }
+}
@@ -0,0 +1,161 @@
+//! the progress bar of `--progress` on the stderr, redrawn in place at most every
+//! `REDRAW_INTERVAL`, the line is ended when the bar is dropped.
+use std::{
+    io::{self, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use blockless_car::utils::{Progress, ProgressHook};
+
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+const BAR_WIDTH: usize = 30;
+
+struct BarState {
+    // the counters of the runs finished, the library counts every run from 0.
+    base: Progress,
+    current: Progress,
+    drawn: Option<Instant>,
+}
+
+pub(crate) struct ProgressBar {
+    label: &'static str,
+    // the bytes to hash, none if unknown, e.g. the stdin.
+    total: Option<u64>,
+    start: Instant,
+    state: Mutex<BarState>,
+}
+
+/// the bytes with the binary unit, e.g. `1.5 GiB`.
+fn human_bytes(n: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = n as f64;
+    let mut unit = 0;
+    while value >= 1024. && unit < UNITS.len() - 1 {
+        value /= 1024.;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{n} B"),
+        _ => format!("{value:.1} {}", UNITS[unit]),
+    }
+}
+
+fn human_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    match secs / 3600 {
+        0 => format!("{:02}:{:02}", secs / 60, secs % 60),
+        hours => format!("{hours}:{:02}:{:02}", secs / 60 % 60, secs % 60),
+    }
+}
+
+/// the bytes of the regular files under the `path`, the symlinks are not followed.
+pub(crate) fn source_bytes(path: impl AsRef<Path>) -> u64 {
+    let Ok(metadata) = std::fs::symlink_metadata(path.as_ref()) else {
+        return 0;
+    };
+    if metadata.is_file() {
+        return metadata.len();
+    }
+    if !metadata.is_dir() {
+        return 0;
+    }
+    std::fs::read_dir(path.as_ref())
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|e| source_bytes(e.path()))
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+impl ProgressBar {
+    pub(crate) fn new(label: &'static str, total: Option<u64>) -> Arc<Self> {
+        Arc::new(Self {
+            label,
+            total: total.filter(|t| *t > 0),
+            start: Instant::now(),
+            state: Mutex::new(BarState {
+                base: Progress::default(),
+                current: Progress::default(),
+                drawn: None,
+            }),
+        })
+    }
+
+    /// the hook of the pack or extract options drawing the bar.
+    pub(crate) fn hook(self: &Arc<Self>) -> Arc<dyn ProgressHook> {
+        let bar = self.clone();
+        Arc::new(move |p: &Progress| bar.update(p))
+    }
+
+    /// the next pack or extract counts after the ones finished, e.g. the next root.
+    pub(crate) fn next_run(&self) {
+        let mut state = self.state.lock().unwrap();
+        let current = std::mem::take(&mut state.current);
+        state.base.files += current.files;
+        state.base.bytes_hashed += current.bytes_hashed;
+        state.base.bytes_written += current.bytes_written;
+    }
+
+    fn update(&self, progress: &Progress) {
+        let mut state = self.state.lock().unwrap();
+        state.current = *progress;
+        if state.drawn.is_some_and(|t| t.elapsed() < REDRAW_INTERVAL) {
+            return;
+        }
+        self.draw(&mut state);
+    }
+
+    fn draw(&self, state: &mut BarState) {
+        state.drawn = Some(Instant::now());
+        let files = state.base.files + state.current.files;
+        let hashed = state.base.bytes_hashed + state.current.bytes_hashed;
+        let written = state.base.bytes_written + state.current.bytes_written;
+        let elapsed = self.start.elapsed();
+        let rate = hashed as f64 / elapsed.as_secs_f64().max(1e-3);
+        let mut line = format!("{} ", self.label);
+        if let Some(total) = self.total {
+            let ratio = (hashed as f64 / total as f64).min(1.);
+            let filled = (ratio * BAR_WIDTH as f64) as usize;
+            let eta = match hashed {
+                0 => "--:--".to_string(),
+                _ => human_duration(
+                    elapsed.mul_f64((total.saturating_sub(hashed)) as f64 / hashed as f64),
+                ),
+            };
+            line.push_str(&format!(
+                "[{}{}] {:>3}% {}/{} ETA {eta}, ",
+                "#".repeat(filled),
+                "-".repeat(BAR_WIDTH - filled),
+                (ratio * 100.) as u32,
+                human_bytes(hashed),
+                human_bytes(total),
+            ));
+        } else {
+            line.push_str(&format!("{}, ", human_bytes(hashed)));
+        }
+        line.push_str(&format!(
+            "{}/s, {files} files, {} written",
+            human_bytes(rate as u64),
+            human_bytes(written)
+        ));
+        // the progress is best effort, the stderr errors are ignored.
+        let mut stderr = io::stderr().lock();
+        let _ = write!(stderr, "\r{line}\x1b[K");
+        let _ = stderr.flush();
+    }
+}
+
+impl Drop for ProgressBar {
+    fn drop(&mut self) {
+        let mut state = self.state.lock().unwrap();
+        if state.drawn.is_some() {
+            self.draw(&mut state);
+            eprintln!();
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use blockless_car::{utils, Cid};
+use std::{
+    io::{self, BufWriter, Read},
+    path::Path,
+};
+
+use crate::{error::UtilError, stdio::create_output, usage::count_blocks};
+
+#[derive(Debug, clap::Parser)]
+pub struct PutBlocksCommand {
+    #[clap(
+        help = "The directory of the block files named by their CIDs, or the list of `<cid> <file>` lines, `-` reads the list from stdin."
+    )]
+    blocks: String,
+
+    #[clap(
+        long = "root",
+        required = true,
+        help = "The root CID of the car, repeat it for multiple roots.",
+        value_parser = parse_cid
+    )]
+    roots: Vec<Cid>,
+
+    #[clap(
+        short,
+        help = "The car file to output, `-` writes the car to the stdout."
+    )]
+    output: String,
+}
+
+fn parse_cid(s: &str) -> Result<Cid, String> {
+    Cid::try_from(s).map_err(|e| format!("invalid cid {s}: {e}"))
+}
+
+impl PutBlocksCommand {
+    /// assemble the car from the block files given by the directory or the list, the
+    /// relative files of the list are resolved against the directory of the list.
+    pub(crate) fn execute(&self) -> Result<(), UtilError> {
+        let path: &Path = self.blocks.as_ref();
+        let blocks = if path.is_dir() {
+            utils::block_dir(path)?
+        } else if self.blocks == "-" {
+            let mut list = String::new();
+            io::stdin().lock().read_to_string(&mut list)?;
+            utils::parse_block_list(&list, Path::new("."))?
+        } else if path.exists() {
+            let list = std::fs::read_to_string(path)?;
+            let base = path.parent().unwrap_or(Path::new("."));
+            utils::parse_block_list(&list, base)?
+        } else {
+            return Err(UtilError::new(format!(
+                "the blocks [{}] does not exist.",
+                self.blocks
+            )));
+        };
+        let output = create_output(&self.output)?;
+        let count = utils::put_blocks(self.roots.clone(), &blocks, BufWriter::new(output))?;
+        eprintln!("{count} blocks written.");
+        count_blocks(0, count as u64);
+        Ok(())
+    }
+}
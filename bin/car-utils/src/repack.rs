@@ -0,0 +1,60 @@
+use blockless_car::reader as car_reader;
+use blockless_car::utils;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use crate::{
+    error::UtilError,
+    usage::{count_blocks, count_reader},
+};
+
+#[derive(Debug, clap::Parser)]
+pub struct RepackCommand {
+    #[clap(help = "the car file to repack.")]
+    car: String,
+
+    #[clap(short, help = "The car file to output.")]
+    output: String,
+
+    #[clap(
+        long = "align",
+        default_value_t = 4096,
+        help = "Pad the sections so the block payloads start at the offsets aligned to the bytes."
+    )]
+    align: usize,
+
+    #[clap(
+        long = "index",
+        help = "Write the `<cid> <offset> <length>` line of every block payload to the file."
+    )]
+    index: Option<String>,
+}
+
+impl RepackCommand {
+    /// copy the car file with the aligned block payloads.
+    pub(crate) fn execute(&self) -> Result<(), UtilError> {
+        let path: &Path = self.car.as_ref();
+        if !path.exists() {
+            return Err(UtilError::new(format!(
+                "the car file [{}] does not exist.",
+                self.car
+            )));
+        }
+        let mut reader = car_reader::new_v1(File::open(path)?)?;
+        let output = BufWriter::new(File::create(&self.output)?);
+        let sections = utils::repack_aligned(&mut reader, output, self.align)?;
+        count_reader(&reader);
+        count_blocks(0, sections.len() as u64);
+        if let Some(index) = self.index.as_ref() {
+            let mut index = BufWriter::new(File::create(index)?);
+            for s in sections.iter() {
+                writeln!(index, "{} {} {}", s.cid(), s.pos(), s.len())?;
+            }
+            index.flush()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,52 @@
+use blockless_car::utils;
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+
+use crate::{error::UtilError, stdio::create_output, usage::count_blocks};
+
+#[derive(Debug, clap::Parser)]
+pub struct SalvageCommand {
+    #[clap(help = "The truncated or corrupt car file to salvage.")]
+    car: String,
+
+    #[clap(
+        short,
+        help = "The car file to output, `-` writes the car to the stdout."
+    )]
+    output: String,
+}
+
+impl SalvageCommand {
+    /// copy the valid sections of the car file, the damaged bytes are skipped.
+    pub(crate) fn execute(&self) -> Result<(), UtilError> {
+        let path: &Path = self.car.as_ref();
+        if !path.exists() {
+            return Err(UtilError::new(format!(
+                "the car file [{}] does not exist.",
+                self.car
+            )));
+        }
+        let output = Path::new(&self.output);
+        if output.exists() && path.canonicalize()? == output.canonicalize()? {
+            return Err(UtilError::new(format!(
+                "the output [{}] is the car file.",
+                self.output
+            )));
+        }
+        let mut input = BufReader::new(File::open(path)?);
+        let output = create_output(&self.output)?;
+        let report = utils::salvage_car(&mut input, BufWriter::new(output))?;
+        for range in report.skipped.iter() {
+            eprintln!(
+                "skipped {} bytes at offset {}: {}",
+                range.len, range.offset, range.reason
+            );
+        }
+        eprintln!("{} blocks recovered.", report.blocks);
+        count_blocks(report.blocks as u64, report.blocks as u64);
+        Ok(())
+    }
+}
@@ -0,0 +1,214 @@
+use blockless_car::{
+    reader as car_reader,
+    utils::{self, PackOptions, RoundtripDiff},
+};
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    error::UtilError,
+    usage::{count_blocks, count_reader},
+};
+
+#[derive(Debug, clap::Parser)]
+pub struct SelftestCommand {
+    #[clap(
+        long,
+        help = "The seed of the generated file contents.",
+        default_value_t = 1
+    )]
+    seed: u64,
+
+    #[clap(
+        long,
+        help = "The directory of the generated tree and the cars, instead of the OS temp directory."
+    )]
+    tmpdir: Option<String>,
+}
+
+/// the working directory of the self test, removed on drop.
+struct WorkDir(PathBuf);
+
+impl WorkDir {
+    fn new(parent: Option<&str>) -> Result<Self, UtilError> {
+        let parent = parent.map_or_else(std::env::temp_dir, PathBuf::from);
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos());
+        let path = parent.join(format!("car-utils-selftest-{}-{nanos}", std::process::id()));
+        fs::create_dir(&path)?;
+        Ok(Self(path))
+    }
+}
+
+impl Drop for WorkDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// the xorshift bytes of the `len`, the same on all the platforms for the `seed`.
+fn content(seed: u64, len: usize) -> Vec<u8> {
+    let mut x = seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1;
+    (0..len)
+        .map(|_| {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            x as u8
+        })
+        .collect()
+}
+
+/// generate the tree of the files around the chunk boundaries, the deep nesting, the
+/// unicode names, the wide directory, the files with the same content, the empty directory
+/// and the symlink on unix.
+fn generate_tree(root: &Path, seed: u64) -> Result<(), UtilError> {
+    let sizes = [0, 1, 1023, 1024, 1025, 262_143, 262_144, 262_145, 600_000];
+    fs::create_dir_all(root.join("sizes"))?;
+    for (i, size) in sizes.iter().enumerate() {
+        fs::write(
+            root.join("sizes").join(format!("{size}.bin")),
+            content(seed + i as u64, *size),
+        )?;
+    }
+    let mut deep = root.join("deep");
+    for depth in 0..24 {
+        deep.push(format!("d{depth}"));
+    }
+    fs::create_dir_all(&deep)?;
+    fs::write(deep.join("leaf.txt"), b"the deepest file")?;
+    let names = [
+        "héllo.txt",
+        "日本語.md",
+        "crab-🦀.rs",
+        "Ελληνικά",
+        "space name.txt",
+        "combining-e\u{301}.txt",
+    ];
+    fs::create_dir_all(root.join("unicode"))?;
+    for (i, name) in names.iter().enumerate() {
+        fs::write(
+            root.join("unicode").join(name),
+            content(seed + 100 + i as u64, 100),
+        )?;
+    }
+    fs::create_dir_all(root.join("wide"))?;
+    for i in 0..300 {
+        fs::write(
+            root.join("wide").join(format!("entry-{i:03}")),
+            i.to_string(),
+        )?;
+    }
+    // the same blocks at many paths.
+    fs::create_dir_all(root.join("duplicates"))?;
+    for name in ["a.bin", "b.bin"] {
+        fs::write(
+            root.join("duplicates").join(name),
+            content(seed + 200, 300_000),
+        )?;
+    }
+    fs::create_dir_all(root.join("empty"))?;
+    #[cfg(unix)]
+    std::os::unix::fs::symlink("sizes/1.bin", root.join("link"))?;
+    Ok(())
+}
+
+fn diff_line(diff: &RoundtripDiff) -> String {
+    match diff {
+        RoundtripDiff::Missing(p) => format!("{} is missing", p.display()),
+        RoundtripDiff::Extra(p) => format!("{} is extra", p.display()),
+        RoundtripDiff::Kind(p) => format!("{} is extracted as another type", p.display()),
+        RoundtripDiff::Content(p) => format!("{} has the different content", p.display()),
+        RoundtripDiff::Metadata(p) => format!("{} has the different metadata", p.display()),
+    }
+}
+
+/// pack the tree to a car and verify it, then check the round trip, return the failures.
+fn check(
+    source: &Path,
+    work: &Path,
+    name: &str,
+    options: &PackOptions,
+) -> Result<Vec<String>, UtilError> {
+    let car = work.join(format!("{name}.car"));
+    let (_, pack) = utils::pack_files_with_report(source, File::create(&car)?, options)?;
+    count_blocks(0, pack.blocks);
+    let mut reader = car_reader::new_v1(File::open(&car)?)?;
+    let mut failures = Vec::new();
+    let report = utils::verify_blocks(&mut reader)?;
+    failures.extend(
+        report
+            .failures
+            .iter()
+            .map(|c| format!("{c} mismatches its cid")),
+    );
+    if report.truncated {
+        failures.push("the car is truncated".into());
+    }
+    let completeness = utils::check_complete(&mut reader)?;
+    failures.extend(
+        completeness
+            .missing
+            .iter()
+            .map(|m| format!("{} is missing in the car", m.cid)),
+    );
+    count_reader(&reader);
+    let roundtrip = utils::verify_roundtrip(source, options)?;
+    failures.extend(roundtrip.diffs.iter().map(diff_line));
+    fs::remove_file(&car)?;
+    Ok(failures)
+}
+
+impl SelftestCommand {
+    /// generate a synthetic tree and check it packs, verifies and extracts back the same
+    /// with the pack layouts, a diagnostic of the platform issues.
+    pub(crate) fn execute(&self) -> Result<(), UtilError> {
+        let work = WorkDir::new(self.tmpdir.as_deref())?;
+        let source = work.0.join("tree");
+        generate_tree(&source, self.seed)?;
+        println!(
+            "car-utils {} on {}-{}, seed {}",
+            crate::SHORT_VERSION,
+            std::env::consts::OS,
+            std::env::consts::ARCH,
+            self.seed
+        );
+        let base = PackOptions::new().with_spill_dir(&work.0);
+        let checks = [
+            ("default", base.clone()),
+            ("dag-pb leaves", base.clone().with_raw_leaves(false)),
+            ("small chunks", base.clone().with_chunk_size(1024)),
+            ("hamt", base.clone().with_hamt_threshold(Some(1024))),
+            ("metadata", base.clone().with_preserve_metadata(true)),
+        ];
+        let mut failed = 0;
+        for (i, (name, options)) in checks.iter().enumerate() {
+            let failures = match check(&source, &work.0, &format!("check-{i}"), options) {
+                Ok(failures) => failures,
+                Err(e) => vec![e.err],
+            };
+            match failures.is_empty() {
+                true => println!("pass: {name}"),
+                false => {
+                    failed += 1;
+                    println!("fail: {name}");
+                    failures.iter().for_each(|f| println!("  {f}"));
+                }
+            }
+        }
+        match failed {
+            0 => {
+                println!("all {} checks passed.", checks.len());
+                Ok(())
+            }
+            n => Err(UtilError::new(format!(
+                "{n} of {} checks failed",
+                checks.len()
+            ))),
+        }
+    }
+}
@@ -0,0 +1,527 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    num::NonZeroUsize,
+    path::Path,
+    str::FromStr,
+    sync::{mpsc, Arc, Mutex, PoisonError},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use blockless_car::{
+    error::CarError,
+    reader::{self as car_reader, CarReader},
+    section::Section,
+    store::BlockStore,
+    utils::verify_block,
+    Cid,
+};
+use serde_json::{json, Value};
+
+use crate::error::UtilError;
+
+#[derive(Debug, clap::Parser)]
+pub struct ServeCommand {
+    #[clap(help = "The car file to serve.")]
+    car: String,
+
+    #[clap(
+        long = "listen",
+        help = "The address to listen on.",
+        default_value = "127.0.0.1:8080"
+    )]
+    listen: String,
+
+    #[clap(
+        long = "verify-only",
+        help = "Serve the presence and integrity of the blocks only, `/has/<cid>` and `/verify/<cid>`."
+    )]
+    verify_only: bool,
+
+    #[clap(
+        long = "access-log",
+        help = "Append an NDJSON line of every request to the file, `-` writes the lines to the stdout."
+    )]
+    access_log: Option<String>,
+
+    #[clap(
+        long = "allow-cid",
+        help = "Allow the requests of the cid only, can be repeated, the other cids respond 403."
+    )]
+    allow_cids: Vec<String>,
+
+    #[clap(
+        long = "allow-root-only",
+        help = "Allow the requests of the roots of the car only, with --allow-cid the cids are allowed too."
+    )]
+    allow_root_only: bool,
+
+    #[clap(
+        short,
+        long,
+        default_value_t = 0,
+        help = "The count of the workers handling the connections, 0 means the count of the CPUs."
+    )]
+    jobs: usize,
+
+    #[clap(
+        long = "io-timeout",
+        default_value_t = 30,
+        help = "The read and write timeout of the connections in seconds, 0 disables it."
+    )]
+    io_timeout: u64,
+}
+
+/// the max bytes of the request line and the headers of a request.
+const MAX_HEAD_BYTES: u64 = 8192;
+
+/// the wait after a failed accept, e.g. out of the file descriptors, before the next one.
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// the state shared by the workers handling the connections.
+struct Server {
+    allowed: Option<HashSet<Cid>>,
+    access_log: Option<Mutex<Box<dyn Write + Send>>>,
+    timeout: Option<Duration>,
+}
+
+/// the blocks of the car read by a worker through its own file, the sections are indexed
+/// once and shared by the workers, so the workers read the blocks in parallel.
+struct WorkerBlocks {
+    file: File,
+    sections: Arc<HashMap<Cid, Section>>,
+}
+
+impl BlockStore for WorkerBlocks {
+    fn get(&mut self, cid: &Cid) -> Result<Option<Vec<u8>>, CarError> {
+        let Some(section) = self.sections.get(cid) else {
+            return Ok(None);
+        };
+        section.read_data(&mut self.file).map(Some)
+    }
+
+    fn put(&mut self, cid: Cid, _data: Vec<u8>) -> Result<(), CarError> {
+        Err(CarError::NotImplemented(format!(
+            "put the block {cid} to the served car"
+        )))
+    }
+
+    #[inline]
+    fn get_len(&mut self, cid: &Cid) -> Result<Option<usize>, CarError> {
+        Ok(self.sections.get(cid).map(Section::len))
+    }
+}
+
+/// the request handled, for the access log, the fields are filled as the request is handled.
+#[derive(Default)]
+struct Access {
+    method: String,
+    target: String,
+    status: Option<u16>,
+    bytes: usize,
+    error: Option<String>,
+}
+
+impl ServeCommand {
+    /// serve the HTTP GET requests over the indexed car file, the connections are handled
+    /// by the `--jobs` workers and closed after the response.
+    pub(crate) fn execute(&self) -> Result<(), UtilError> {
+        if !self.verify_only {
+            return Err(UtilError::new(
+                "only the --verify-only mode is supported, the block data is not served.".into(),
+            ));
+        }
+        let path: &Path = self.car.as_ref();
+        if !path.exists() {
+            return Err(UtilError::new(format!(
+                "the car file [{}] does not exist.",
+                self.car
+            )));
+        }
+        let mut reader = car_reader::new_v1(File::open(path)?)?;
+        let allowed = self.allowed(&reader)?;
+        // the first section of the repeated cid wins, like the reader.
+        let mut sections = HashMap::new();
+        for section in reader.sections()? {
+            sections.entry(section.cid()).or_insert(section);
+        }
+        drop(reader);
+        let sections = Arc::new(sections);
+        let access_log: Option<Box<dyn Write + Send>> = match self.access_log.as_deref() {
+            Some("-") => Some(Box::new(io::stdout())),
+            Some(log) => Some(Box::new(
+                OpenOptions::new().create(true).append(true).open(log)?,
+            )),
+            None => None,
+        };
+        let server = Server {
+            allowed,
+            access_log: access_log.map(Mutex::new),
+            timeout: (self.io_timeout > 0).then(|| Duration::from_secs(self.io_timeout)),
+        };
+        let jobs = match self.jobs {
+            0 => thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            n => n,
+        };
+        let stores = (0..jobs)
+            .map(|_| {
+                Ok(WorkerBlocks {
+                    file: File::open(path)?,
+                    sections: sections.clone(),
+                })
+            })
+            .collect::<Result<Vec<_>, UtilError>>()?;
+        let listener = TcpListener::bind(&self.listen)?;
+        eprintln!("serving {} on http://{}", self.car, listener.local_addr()?);
+        server.run(listener, stores)
+    }
+
+    /// the cids allowed by the `--allow-cid` and the `--allow-root-only`, none allows all.
+    fn allowed(&self, reader: &impl CarReader) -> Result<Option<HashSet<Cid>>, UtilError> {
+        if self.allow_cids.is_empty() && !self.allow_root_only {
+            return Ok(None);
+        }
+        let mut allowed = HashSet::new();
+        for cid in self.allow_cids.iter() {
+            let cid = Cid::from_str(cid)
+                .map_err(|e| UtilError::new(format!("invalid cid {cid}: {e}")))?;
+            allowed.insert(cid);
+        }
+        if self.allow_root_only {
+            allowed.extend(reader.header().roots());
+        }
+        Ok(Some(allowed))
+    }
+}
+
+impl Server {
+    /// accept the connections of the `listener` and handle them by a worker per store, the
+    /// accepted connections wait for a free worker in the bounded channel.
+    fn run<S>(&self, listener: TcpListener, stores: Vec<S>) -> Result<(), UtilError>
+    where
+        S: BlockStore + Send,
+    {
+        let (sender, receiver) = mpsc::sync_channel::<TcpStream>(stores.len());
+        let receiver = Mutex::new(receiver);
+        thread::scope(|scope| {
+            for mut store in stores {
+                let receiver = &receiver;
+                scope.spawn(move || loop {
+                    let Ok(stream) = receiver.lock().unwrap().recv() else {
+                        break;
+                    };
+                    if let Err(e) = self.serve(&mut store, stream) {
+                        eprintln!("Error: {e}");
+                    }
+                });
+            }
+            for stream in listener.incoming() {
+                // the transient accept errors, e.g. EMFILE or ECONNABORTED, don't stop the server.
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        eprintln!("Error: accept the connection: {e}");
+                        thread::sleep(ACCEPT_RETRY_DELAY);
+                        continue;
+                    }
+                };
+                if sender.send(stream).is_err() {
+                    break;
+                }
+            }
+            drop(sender);
+            Ok(())
+        })
+    }
+
+    /// handle the connection and append the request to the access log, the failed requests
+    /// are logged with the error too.
+    fn serve(&self, store: &mut impl BlockStore, stream: TcpStream) -> Result<(), UtilError> {
+        let remote = stream.peer_addr().map(|a| a.to_string()).ok();
+        let start = Instant::now();
+        let mut access = Access::default();
+        let result = self.handle(store, &stream, &mut access);
+        if let Err(e) = result.as_ref() {
+            access.error = Some(e.to_string());
+        }
+        if let Some(log) = self.access_log.as_ref() {
+            let time = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0., |d| d.as_secs_f64());
+            let line = json!({
+                "time": time,
+                "remote": remote,
+                "method": access.method,
+                "target": access.target,
+                "status": access.status,
+                "bytes": access.bytes,
+                "error": access.error,
+                "duration_ms": start.elapsed().as_secs_f64() * 1000.,
+            });
+            let mut log = log.lock().unwrap_or_else(PoisonError::into_inner);
+            writeln!(log, "{line}")?;
+            log.flush()?;
+        }
+        result
+    }
+
+    /// read the request line of the connection and write the JSON response, the request
+    /// line and the headers are read up to the `MAX_HEAD_BYTES`, the request not read in
+    /// the timeout responds 408.
+    fn handle(
+        &self,
+        store: &mut impl BlockStore,
+        stream: &TcpStream,
+        access: &mut Access,
+    ) -> Result<(), UtilError> {
+        stream.set_read_timeout(self.timeout)?;
+        stream.set_write_timeout(self.timeout)?;
+        let mut input = BufReader::new(stream).take(MAX_HEAD_BYTES);
+        let mut request_line = String::new();
+        let mut header = String::new();
+        let read = read_head(&mut input, &mut request_line, &mut header);
+        let mut parts = request_line.split_whitespace();
+        let (method, target) = (parts.next(), parts.next());
+        access.method = method.unwrap_or_default().into();
+        access.target = target.unwrap_or_default().into();
+        let (status, body) = match (method, target) {
+            _ if read.as_ref().is_err_and(is_timeout) => {
+                (408, json!({"error": "the request is not read in time"}))
+            }
+            _ if read.is_err() => return read.map_err(UtilError::from),
+            _ if input.limit() == 0 && !header.ends_with('\n') => (
+                431,
+                json!({"error": "the request line or headers are too large"}),
+            ),
+            (Some("GET"), Some(target)) => route(store, target, self.allowed.as_ref()),
+            (Some(_), Some(_)) => (405, json!({"error": "only GET is allowed"})),
+            _ => (400, json!({"error": "invalid request line"})),
+        };
+        access.status = Some(status);
+        access.bytes = write_response(stream, status, &body)?;
+        Ok(())
+    }
+}
+
+/// read the request line and the headers, the headers are ignored, the requests have no
+/// body. the `header` is the last line read, the empty line ending the headers.
+fn read_head(
+    input: &mut impl BufRead,
+    request_line: &mut String,
+    header: &mut String,
+) -> io::Result<()> {
+    input.read_line(request_line)?;
+    while input.read_line(header)? > 2 {
+        header.clear();
+    }
+    Ok(())
+}
+
+/// the read or the write is timed out, `WouldBlock` on unix and `TimedOut` on windows.
+fn is_timeout(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+/// the status and the body of the `target`, the failures are reported in the body.
+fn route(
+    reader: &mut impl BlockStore,
+    target: &str,
+    allowed: Option<&HashSet<Cid>>,
+) -> (u16, Value) {
+    let target = target.split('?').next().unwrap_or(target);
+    let (endpoint, cid) = match target.trim_start_matches('/').split_once('/') {
+        Some((endpoint @ ("has" | "verify"), cid)) => (endpoint, cid),
+        _ => return (404, json!({"error": format!("no endpoint {target}")})),
+    };
+    let cid = match Cid::from_str(cid) {
+        Ok(cid) => cid,
+        Err(e) => return (400, json!({"error": format!("invalid cid {cid}: {e}")})),
+    };
+    if allowed.is_some_and(|a| !a.contains(&cid)) {
+        return (
+            403,
+            json!({"cid": cid.to_string(), "error": "the cid is not allowed"}),
+        );
+    }
+    let result = match endpoint {
+        "has" => reader.get_len(&cid).map(|len| match len {
+            Some(len) => (
+                200,
+                json!({"cid": cid.to_string(), "has": true, "size": len}),
+            ),
+            None => (404, json!({"cid": cid.to_string(), "has": false})),
+        }),
+        _ => reader.get(&cid).and_then(|data| match data {
+            Some(data) => {
+                let valid = verify_block(&cid, &data)?;
+                Ok((200, json!({"cid": cid.to_string(), "valid": valid})))
+            }
+            None => Ok((404, json!({"cid": cid.to_string(), "has": false}))),
+        }),
+    };
+    result.unwrap_or_else(|e| (500, json!({"cid": cid.to_string(), "error": e.to_string()})))
+}
+
+/// write the response, return the bytes of the body.
+fn write_response(mut stream: &TcpStream, status: u16, body: &Value) -> Result<usize, UtilError> {
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        431 => "Request Header Fields Too Large",
+        _ => "Internal Server Error",
+    };
+    let body = body.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {status} {reason}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()?;
+    Ok(body.len())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use blockless_car::{
+        header::CarHeader,
+        utils::raw_cid,
+        writer::{self as car_writer, CarWriter},
+    };
+    use std::{io::Cursor, net::SocketAddr};
+
+    /// the car of the `hello` block and the `world` block stored under the cid of `hello!`.
+    fn test_car() -> (impl CarReader, Cid, Cid) {
+        let hello = raw_cid(b"hello", multicodec::Codec::Sha2_256);
+        let corrupt = raw_cid(b"hello!", multicodec::Codec::Sha2_256);
+        let mut car = Cursor::new(vec![]);
+        let mut writer = car_writer::new_v1(&mut car, CarHeader::new_v1(vec![hello])).unwrap();
+        writer.write_block(hello, b"hello").unwrap();
+        writer.write_block(corrupt, b"world").unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+        let reader = car_reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+        (reader, hello, corrupt)
+    }
+
+    #[test]
+    fn test_route() {
+        let (mut reader, hello, corrupt) = test_car();
+        let missing = raw_cid(b"missing", multicodec::Codec::Sha2_256);
+        let mut get =
+            |target: String, allowed: Option<&HashSet<Cid>>| route(&mut reader, &target, allowed);
+
+        let (status, body) = get(format!("/has/{hello}"), None);
+        assert_eq!(
+            (status, &body["has"], &body["size"]),
+            (200, &json!(true), &json!(5))
+        );
+        let (status, body) = get(format!("/verify/{hello}?fresh=1"), None);
+        assert_eq!((status, &body["valid"]), (200, &json!(true)));
+        let (status, body) = get(format!("/verify/{corrupt}"), None);
+        assert_eq!((status, &body["valid"]), (200, &json!(false)));
+
+        // the missing blocks, the unknown endpoints and the invalid cids.
+        assert_eq!(get(format!("/has/{missing}"), None).0, 404);
+        assert_eq!(get(format!("/verify/{missing}"), None).0, 404);
+        assert_eq!(get(format!("/ipfs/{hello}"), None).0, 404);
+        assert_eq!(get("/".into(), None).0, 404);
+        assert_eq!(get("/has/not-a-cid".into(), None).0, 400);
+
+        // the cids out of the allow-list respond 403 even if missing.
+        let allowed = HashSet::from([hello]);
+        assert_eq!(get(format!("/has/{hello}"), Some(&allowed)).0, 200);
+        assert_eq!(get(format!("/verify/{corrupt}"), Some(&allowed)).0, 403);
+        assert_eq!(get(format!("/has/{missing}"), Some(&allowed)).0, 403);
+    }
+
+    /// the access log lines written to the memory.
+    #[derive(Clone, Default)]
+    struct MemoryLog(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for MemoryLog {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// send the request and read the response until the server closes the connection.
+    fn request(addr: SocketAddr, request: &[u8]) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(request).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_serve() {
+        let (first, hello, _) = test_car();
+        let (second, ..) = test_car();
+        let log = MemoryLog::default();
+        let server = Server {
+            allowed: None,
+            access_log: Some(Mutex::new(Box::new(log.clone()))),
+            timeout: Some(Duration::from_secs(1)),
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || server.run(listener, vec![first, second]));
+
+        // the idle connection holds a worker, the other worker serves the requests.
+        let mut idle = TcpStream::connect(addr).unwrap();
+        thread::sleep(Duration::from_millis(100));
+        let start = Instant::now();
+        let response = request(
+            addr,
+            format!("GET /has/{hello} HTTP/1.1\r\n\r\n").as_bytes(),
+        );
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+        assert!(start.elapsed() < Duration::from_millis(500));
+
+        // the request line reaching the cap without the end responds 431.
+        let mut long = b"GET /".to_vec();
+        long.resize(MAX_HEAD_BYTES as usize, b'a');
+        let response = request(addr, &long);
+        assert!(response.starts_with("HTTP/1.1 431 "), "{response}");
+
+        // the request not completed in the timeout responds 408.
+        idle.write_all(b"GET /has/").unwrap();
+        let mut response = String::new();
+        idle.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 408 "), "{response}");
+
+        let log = String::from_utf8(log.0.lock().unwrap().clone()).unwrap();
+        let mut lines: Vec<(u64, String)> = log
+            .lines()
+            .map(|line| {
+                let line: Value = serde_json::from_str(line).unwrap();
+                let status = line["status"].as_u64().unwrap();
+                (
+                    status,
+                    line["target"].as_str().unwrap().chars().take(9).collect(),
+                )
+            })
+            .collect();
+        lines.sort();
+        let expected = [(200, "/has/bafk"), (408, "/has/"), (431, "/aaaaaaaa")];
+        let expected: Vec<(u64, String)> =
+            expected.iter().map(|(s, t)| (*s, t.to_string())).collect();
+        assert_eq!(lines, expected);
+    }
+}
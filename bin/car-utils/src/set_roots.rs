@@ -0,0 +1,131 @@
+use blockless_car::{
+    reader::{self as car_reader, CarReader},
+    utils::{self, ProbeFormat},
+    Cid,
+};
+use std::{fs::File, io::BufReader, path::Path};
+
+use crate::error::UtilError;
+
+#[derive(Debug, clap::Parser)]
+pub struct SetRootsCommand {
+    #[clap(help = "The CARv1 file to set the roots of.")]
+    car: String,
+
+    #[clap(required = true, help = "The new roots of the car.")]
+    cids: Vec<String>,
+}
+
+impl SetRootsCommand {
+    /// replace the roots in the header of the car, in place if the new header fits,
+    /// otherwise the car is rewritten for the larger header.
+    pub(crate) fn execute(&self) -> Result<(), UtilError> {
+        let path: &Path = self.car.as_ref();
+        if !path.exists() {
+            return Err(UtilError::new(format!(
+                "the car file [{}] does not exist.",
+                self.car
+            )));
+        }
+        let roots = self
+            .cids
+            .iter()
+            .map(|c| {
+                Cid::try_from(c.as_str())
+                    .map_err(|e| UtilError::new(format!("invalid cid {c}: {e}")))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let format = utils::probe(&mut BufReader::new(File::open(path)?))?.format;
+        if format != ProbeFormat::CarV1 {
+            return Err(UtilError::new(format!(
+                "the roots of the CARv1 file only can be set, [{}] is not.",
+                self.car
+            )));
+        }
+        let mut reader = car_reader::new_v1(File::open(path)?)?;
+        for root in roots.iter() {
+            if reader.block_len(root)?.is_none() {
+                eprintln!("warning: {root} is not in the car.");
+            }
+        }
+        drop(reader);
+        if utils::replace_roots(path, roots.clone())? {
+            eprintln!("{} roots set in place.", roots.len());
+        } else {
+            eprintln!(
+                "{} roots set, the car rewritten for the larger header.",
+                roots.len()
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use blockless_car::{
+        header::CarHeader,
+        utils::raw_cid,
+        writer::{self as car_writer, CarWriter},
+    };
+    use std::fs;
+    use tempdir::TempDir;
+
+    fn write_car(path: &Path, roots: Vec<Cid>, blocks: &[(Cid, Vec<u8>)]) {
+        let file = File::create(path).unwrap();
+        let mut writer = car_writer::new_v1(file, CarHeader::new_v1(roots)).unwrap();
+        for (cid, data) in blocks.iter() {
+            writer.write_block(*cid, data).unwrap();
+        }
+        writer.flush().unwrap();
+    }
+
+    fn set_roots(car: &Path, roots: &[Cid]) {
+        SetRootsCommand {
+            car: car.to_str().unwrap().into(),
+            cids: roots.iter().map(|c| c.to_string()).collect(),
+        }
+        .execute()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_set_roots() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let blocks = (0..4u8)
+            .map(|i| {
+                let data = vec![i; 1000];
+                (raw_cid(&data, multicodec::Codec::Sha2_256), data)
+            })
+            .collect::<Vec<_>>();
+        let cids = blocks.iter().map(|(cid, _)| *cid).collect::<Vec<_>>();
+        let car = temp_dir.path().join("test.car");
+        let expected = temp_dir.path().join("expected.car");
+        write_car(&car, cids[..2].to_vec(), &blocks);
+
+        // the larger header rewrites the car.
+        set_roots(&car, &cids);
+        write_car(&expected, cids.clone(), &blocks);
+        assert_eq!(fs::read(&car).unwrap(), fs::read(&expected).unwrap());
+
+        // the smaller header is stuffed in place, the stuffing is dropped by the next rewrite.
+        set_roots(&car, &cids[..1]);
+        assert_eq!(
+            fs::metadata(&car).unwrap().len(),
+            fs::metadata(&expected).unwrap().len()
+        );
+        let roots = cids.repeat(2);
+        set_roots(&car, &roots);
+        write_car(&expected, roots, &blocks);
+        assert_eq!(fs::read(&car).unwrap(), fs::read(&expected).unwrap());
+
+        // no temp file is left next to the car.
+        let mut names = fs::read_dir(temp_dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["expected.car", "test.car"]);
+    }
+}
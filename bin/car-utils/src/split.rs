@@ -0,0 +1,88 @@
+use blockless_car::{reader as car_reader, utils};
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::UtilError,
+    usage::{count_blocks, count_reader},
+};
+
+#[derive(Debug, clap::Parser)]
+pub struct SplitCommand {
+    #[clap(help = "The car file to split.")]
+    car: String,
+
+    #[clap(
+        long,
+        help = "The max bytes of each shard, with the suffix KiB, MiB, GiB or TiB, e.g. 32GiB.",
+        value_parser = parse_bytes
+    )]
+    max_size: u64,
+
+    #[clap(
+        short,
+        help = "The prefix of the shard files `<prefix>-<n>.car`, the car file without `.car` by default."
+    )]
+    output: Option<String>,
+}
+
+/// parse the bytes of `<n>` or with the binary suffix like `32GiB`.
+fn parse_bytes(s: &str) -> Result<u64, String> {
+    let (n, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => s.split_at(i),
+        None => (s, ""),
+    };
+    let shift = match unit.to_ascii_lowercase().as_str() {
+        "" | "b" => 0,
+        "k" | "kib" => 10,
+        "m" | "mib" => 20,
+        "g" | "gib" => 30,
+        "t" | "tib" => 40,
+        _ => return Err(format!("invalid size {s}")),
+    };
+    n.parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(1 << shift))
+        .ok_or_else(|| format!("invalid size {s}"))
+}
+
+impl SplitCommand {
+    /// split the car into the shards of at most `--max-size` bytes, each with the roots of
+    /// the car, and print the shard files.
+    pub(crate) fn execute(&self) -> Result<(), UtilError> {
+        let path: &Path = self.car.as_ref();
+        if !path.exists() {
+            return Err(UtilError::new(format!(
+                "the car file [{}] does not exist.",
+                self.car
+            )));
+        }
+        let prefix = match &self.output {
+            Some(output) => PathBuf::from(output),
+            None => path.with_extension(""),
+        };
+        let shard_path = |i: usize| {
+            let mut name = prefix.clone().into_os_string();
+            name.push(format!("-{i:03}.car"));
+            PathBuf::from(name)
+        };
+        let mut reader = car_reader::new_v1(File::open(path)?)?;
+        let shards = utils::split_car(&mut reader, self.max_size, |i| {
+            Ok(BufWriter::new(File::create(shard_path(i))?))
+        })?;
+        count_reader(&reader);
+        count_blocks(0, shards.iter().map(|s| s.blocks as u64).sum());
+        for (i, shard) in shards.iter().enumerate() {
+            println!(
+                "{}\t{} blocks\t{} bytes",
+                shard_path(i).display(),
+                shard.blocks,
+                shard.bytes
+            );
+        }
+        Ok(())
+    }
+}
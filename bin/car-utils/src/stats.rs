@@ -0,0 +1,340 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufReader, Read},
+    path::Path,
+};
+
+use blockless_car::reader::{self as car_reader, CarReader};
+use ruzstd::encoding::{compress_to_vec, CompressionLevel};
+use serde_json::{json, Value};
+
+use crate::{
+    cars::{expand_cars, map_cars},
+    error::UtilError,
+    stdio::is_stdio,
+    usage::count_blocks,
+};
+
+#[derive(Debug, clap::Parser)]
+pub struct StatsCommand {
+    #[clap(
+        required = true,
+        help = "The car files to report, the directory is expanded to the car files in it and the glob to the files matching it."
+    )]
+    cars: Vec<String>,
+
+    #[clap(
+        short,
+        long,
+        default_value_t = 0,
+        help = "The count of the cars reported at once for multiple cars, 0 means the count of the CPUs."
+    )]
+    jobs: usize,
+
+    #[clap(
+        long,
+        help = "Sample the blocks of each codec for the entropy and the zstd ratio."
+    )]
+    compression: bool,
+
+    #[clap(
+        long,
+        help = "The count of the blocks sampled per codec with --compression.",
+        default_value = "64"
+    )]
+    samples: usize,
+}
+
+/// the blocks of a codec.
+#[derive(Default)]
+struct CodecStats {
+    /// the section indexes of the blocks in the file order.
+    indexes: Vec<usize>,
+    bytes: u64,
+}
+
+/// the compressibility of the sampled blocks of a codec.
+struct Compression {
+    samples: usize,
+    /// the shannon entropy of the sampled bytes, in bits per byte.
+    entropy: f64,
+    /// the zstd size of the concatenated samples over their size.
+    ratio: f64,
+}
+
+/// the name of the multicodec of the common blocks, the hex code of the others.
+pub(crate) fn codec_name(codec: u64) -> String {
+    match codec {
+        0x55 => "raw".into(),
+        0x70 => "dag-pb".into(),
+        0x71 => "dag-cbor".into(),
+        0x0129 => "dag-json".into(),
+        _ => format!("0x{codec:x}"),
+    }
+}
+
+/// the name of the multihash of the common hashes, the hex code of the others.
+pub(crate) fn hash_name(code: u64) -> String {
+    match code {
+        0x00 => "identity".into(),
+        0x12 => "sha2-256".into(),
+        0x13 => "sha2-512".into(),
+        0x16 => "sha3-256".into(),
+        0x1b => "keccak-256".into(),
+        0x1e => "blake3".into(),
+        0xb220 => "blake2b-256".into(),
+        0xb260 => "blake2s-256".into(),
+        _ => format!("0x{code:x}"),
+    }
+}
+
+/// the bytes of the header in the file, the varint of the length and the dag-cbor header.
+fn header_size(path: &Path) -> io::Result<u64> {
+    let mut len = 0u64;
+    for (i, b) in BufReader::new(File::open(path)?)
+        .bytes()
+        .take(10)
+        .enumerate()
+    {
+        let b = b?;
+        len |= ((b & 0x7f) as u64) << (7 * i);
+        if b & 0x80 == 0 {
+            return Ok(len + i as u64 + 1);
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "invalid header varint",
+    ))
+}
+
+fn entropy(histogram: &[u64; 256], total: u64) -> f64 {
+    histogram
+        .iter()
+        .filter(|n| **n > 0)
+        .map(|n| {
+            let p = *n as f64 / total as f64;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// read the evenly spaced samples of the `indexes` and compress them as one stream,
+/// like the blocks in a `.car.zst`.
+fn sample_compression(
+    reader: &mut impl CarReader,
+    indexes: &[usize],
+    samples: usize,
+) -> Result<Compression, UtilError> {
+    let samples = samples.min(indexes.len());
+    let mut histogram = [0u64; 256];
+    let mut data = Vec::new();
+    for i in 0..samples {
+        let idx = indexes[i * indexes.len() / samples];
+        if let Some((_, block)) = reader.block_at(idx)? {
+            block.iter().for_each(|b| histogram[*b as usize] += 1);
+            data.extend_from_slice(&block);
+        }
+    }
+    if data.is_empty() {
+        return Ok(Compression {
+            samples,
+            entropy: 0.0,
+            ratio: 1.0,
+        });
+    }
+    let compressed = compress_to_vec(data.as_slice(), CompressionLevel::Fastest);
+    Ok(Compression {
+        samples,
+        entropy: entropy(&histogram, data.len() as u64),
+        ratio: compressed.len() as f64 / data.len() as f64,
+    })
+}
+
+/// the stats of a car.
+struct CarStats {
+    roots: usize,
+    header_size: u64,
+    blocks: usize,
+    duplicate: usize,
+    payload: u64,
+    min: Option<usize>,
+    avg: f64,
+    max: Option<usize>,
+    codecs: BTreeMap<u64, CodecStats>,
+    compressions: Vec<Compression>,
+    /// the blocks sampled by the reader of the car, added to the counts on the main thread.
+    blocks_read: u64,
+}
+
+impl CarStats {
+    fn to_json(&self) -> Value {
+        let codecs: Vec<Value> = self
+            .codecs
+            .iter()
+            .enumerate()
+            .map(|(i, (codec, stats))| {
+                let mut value = json!({
+                    "codec": codec_name(*codec),
+                    "blocks": stats.indexes.len(),
+                    "bytes": stats.bytes,
+                });
+                if let Some(c) = self.compressions.get(i) {
+                    value["samples"] = c.samples.into();
+                    value["entropy"] = c.entropy.into();
+                    value["ratio"] = c.ratio.into();
+                }
+                value
+            })
+            .collect();
+        json!({
+            "roots": self.roots,
+            "header_size": self.header_size,
+            "blocks": self.blocks,
+            "unique": self.blocks - self.duplicate,
+            "duplicate": self.duplicate,
+            "payload": self.payload,
+            "block_size": {"min": self.min, "avg": self.avg, "max": self.max},
+            "codecs": codecs,
+        })
+    }
+
+    /// print the stats, every line starts with the `prefix`.
+    fn print(&self, prefix: &str) {
+        println!("{prefix}roots: {}", self.roots);
+        println!("{prefix}header size: {} bytes", self.header_size);
+        println!(
+            "{prefix}blocks: {} ({} unique, {} duplicate)",
+            self.blocks,
+            self.blocks - self.duplicate,
+            self.duplicate
+        );
+        println!("{prefix}payload: {} bytes", self.payload);
+        if let (Some(min), Some(max)) = (self.min, self.max) {
+            println!(
+                "{prefix}block size: min {min}, avg {:.1}, max {max}",
+                self.avg
+            );
+        }
+        for (i, (codec, stats)) in self.codecs.iter().enumerate() {
+            println!(
+                "{prefix}{}: {} blocks, {} bytes",
+                codec_name(*codec),
+                stats.indexes.len(),
+                stats.bytes
+            );
+            if let Some(c) = self.compressions.get(i) {
+                println!(
+                    "{prefix}  sampled {} blocks: entropy {:.2} bits/byte, zstd ratio {:.2}",
+                    c.samples, c.entropy, c.ratio
+                );
+            }
+        }
+    }
+}
+
+/// collect the stats of the car, the blocks of each codec are sampled by the `samples`.
+fn car_stats(path: &Path, samples: Option<usize>) -> Result<CarStats, UtilError> {
+    if is_stdio(path) {
+        return Err(UtilError::new(
+            "the car of the stdin can't be reported by stats.".into(),
+        ));
+    }
+    let mut reader = car_reader::new_v1(File::open(path)?)?;
+    let sections = reader.sections()?;
+    let mut codecs: BTreeMap<u64, CodecStats> = BTreeMap::new();
+    for (idx, section) in sections.iter().enumerate() {
+        let stats = codecs.entry(section.cid().codec()).or_default();
+        stats.indexes.push(idx);
+        stats.bytes += section.len() as u64;
+    }
+    let payload: u64 = codecs.values().map(|c| c.bytes).sum();
+    let duplicate: usize = reader
+        .duplicates()?
+        .iter()
+        .map(|d| d.offsets.len() - 1)
+        .sum();
+    let sizes = || sections.iter().map(|s| s.len());
+    let mut compressions = Vec::new();
+    if let Some(samples) = samples {
+        for stats in codecs.values() {
+            compressions.push(sample_compression(&mut reader, &stats.indexes, samples)?);
+        }
+    }
+    Ok(CarStats {
+        roots: reader.header().roots().len(),
+        header_size: header_size(path)?,
+        blocks: sections.len(),
+        duplicate,
+        payload,
+        min: sizes().min(),
+        avg: payload as f64 / sections.len().max(1) as f64,
+        max: sizes().max(),
+        codecs,
+        compressions,
+        blocks_read: reader.blocks_read(),
+    })
+}
+
+impl StatsCommand {
+    /// report the header, the blocks and the bytes of the car and of each codec, with the
+    /// sampled compressibility to tell whether the `.car.zst` is worthwhile.
+    /// multiple cars are reported in parallel, as one combined JSON object if `is_json`.
+    pub(crate) fn execute(&self, is_json: bool) -> Result<(), UtilError> {
+        if self.compression && self.samples == 0 {
+            return Err(UtilError::new("the --samples must be positive.".into()));
+        }
+        let cars = expand_cars(&self.cars)?;
+        let samples = self.compression.then_some(self.samples);
+        if let [path] = cars.as_slice() {
+            let stats = car_stats(path, samples)?;
+            count_blocks(stats.blocks_read, 0);
+            match is_json {
+                true => println!("{}", stats.to_json()),
+                false => stats.print(""),
+            }
+            return Ok(());
+        }
+        let results = map_cars(&cars, self.jobs, |path| car_stats(path, samples));
+        results
+            .iter()
+            .flatten()
+            .for_each(|s| count_blocks(s.blocks_read, 0));
+        let failed = results.iter().filter(|rs| rs.is_err()).count();
+        if is_json {
+            let reports: Vec<Value> = cars
+                .iter()
+                .zip(results.iter())
+                .map(|(path, rs)| {
+                    let path = path.display().to_string();
+                    match rs {
+                        Ok(stats) => {
+                            let mut value = stats.to_json();
+                            value["path"] = path.into();
+                            value["ok"] = true.into();
+                            value
+                        }
+                        Err(e) => json!({"path": path, "ok": false, "error": e.err}),
+                    }
+                })
+                .collect();
+            println!("{}", json!({"ok": failed == 0, "cars": reports}));
+        } else {
+            for (path, rs) in cars.iter().zip(results.iter()) {
+                let prefix = format!("{}: ", path.display());
+                match rs {
+                    Ok(stats) => stats.print(&prefix),
+                    Err(e) => println!("{prefix}error: {}", e.err),
+                }
+            }
+        }
+        match failed {
+            0 => Ok(()),
+            failed => Err(UtilError::new(format!(
+                "{failed} of {} cars failed the stats",
+                cars.len()
+            ))),
+        }
+    }
+}
@@ -0,0 +1,200 @@
+//! the `-` of the car paths and the outputs, the stdin and the stdout of the commands.
+use std::{
+    fs::{self, File},
+    io::{self, Cursor, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use blockless_car::{error::CarError, reader::StreamCarReaderV1};
+
+use crate::error::UtilError;
+
+/// the path of the stdin or the stdout.
+pub(crate) const STDIO: &str = "-";
+
+/// the stdin is read once, the second `-` of the inputs is an error.
+static STDIN_TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// the temp file holding the car of the stdin, removed after the file is closed.
+struct Spool(PathBuf);
+
+impl Drop for Spool {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+enum Input {
+    File(File),
+    Memory(Cursor<Vec<u8>>),
+}
+
+/// the car input of a command, the file, the car of the stdin spooled to a temp file or
+/// the car of the stdin read to the memory, so the readers seeking the sections work in
+/// the pipelines.
+pub(crate) struct CarInput {
+    input: Input,
+    // dropped after the file.
+    _spool: Option<Spool>,
+}
+
+impl CarInput {
+    /// the file of the input, none for the car of the stdin read to the memory.
+    #[inline(always)]
+    pub(crate) fn file(&self) -> Option<&File> {
+        match &self.input {
+            Input::File(file) => Some(file),
+            Input::Memory(_) => None,
+        }
+    }
+}
+
+impl Read for CarInput {
+    #[inline(always)]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut self.input {
+            Input::File(file) => file.read(buf),
+            Input::Memory(cursor) => cursor.read(buf),
+        }
+    }
+}
+
+impl Seek for CarInput {
+    #[inline(always)]
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match &mut self.input {
+            Input::File(file) => file.seek(pos),
+            Input::Memory(cursor) => cursor.seek(pos),
+        }
+    }
+}
+
+/// the reader keeping the bytes read.
+struct Tee<R> {
+    inner: R,
+    buf: Vec<u8>,
+}
+
+impl<R: Read> Read for Tee<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.buf.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+}
+
+/// the path is the stdin or the stdout.
+#[inline(always)]
+pub(crate) fn is_stdio(path: impl AsRef<Path>) -> bool {
+    path.as_ref() == Path::new(STDIO)
+}
+
+/// open the car file, `-` spools the car of the stdin to a temp file in the `tmpdir`,
+/// the OS temp directory if none, for the commands seeking the malformed car too.
+pub(crate) fn open_car(
+    car: impl AsRef<Path>,
+    tmpdir: Option<&Path>,
+) -> Result<CarInput, UtilError> {
+    let path = car.as_ref();
+    if is_stdio(path) {
+        return spool_stdin(tmpdir);
+    }
+    open_file(path)
+}
+
+/// open the car file, `-` reads the car of the stdin to the memory through the stream
+/// reader, which checks the sections while they are read, without the temp file.
+/// the last section cut off by the end of the stdin is kept for the reader to report.
+pub(crate) fn read_car(car: impl AsRef<Path>) -> Result<CarInput, UtilError> {
+    let path = car.as_ref();
+    if !is_stdio(path) {
+        return open_file(path);
+    }
+    take_stdin()?;
+    let tee = Tee {
+        inner: io::stdin().lock(),
+        buf: Vec::new(),
+    };
+    let mut stream = StreamCarReaderV1::new(tee)?;
+    loop {
+        match stream.next_block() {
+            Ok(Some(_)) => {}
+            Ok(None) => break,
+            Err(CarError::IO(e)) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(CarInput {
+        input: Input::Memory(Cursor::new(stream.into_inner().buf)),
+        _spool: None,
+    })
+}
+
+fn open_file(path: &Path) -> Result<CarInput, UtilError> {
+    if !path.exists() {
+        return Err(UtilError::new(format!(
+            "the car file [{}] does not exist.",
+            path.display()
+        )));
+    }
+    Ok(CarInput {
+        input: Input::File(File::open(path)?),
+        _spool: None,
+    })
+}
+
+fn take_stdin() -> Result<(), UtilError> {
+    match STDIN_TAKEN.swap(true, Ordering::SeqCst) {
+        true => Err(UtilError::new("the stdin can be read only once.".into())),
+        false => Ok(()),
+    }
+}
+
+fn spool_stdin(tmpdir: Option<&Path>) -> Result<CarInput, UtilError> {
+    take_stdin()?;
+    let dir = tmpdir.map_or_else(std::env::temp_dir, Path::to_path_buf);
+    let mut n = 0u32;
+    let (mut file, spool) = loop {
+        let path = dir.join(format!("car-utils-stdin-{}-{n}.car", std::process::id()));
+        match fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(file) => break (file, Spool(path)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => n += 1,
+            Err(e) => return Err(e.into()),
+        }
+    };
+    io::copy(&mut io::stdin().lock(), &mut file)?;
+    file.rewind()?;
+    Ok(CarInput {
+        input: Input::File(file),
+        _spool: Some(spool),
+    })
+}
+
+/// read the whole file, `-` reads the stdin.
+pub(crate) fn read_input(path: &str) -> Result<Vec<u8>, UtilError> {
+    let mut buf = Vec::new();
+    match path {
+        STDIO => {
+            take_stdin()?;
+            io::stdin().lock().read_to_end(&mut buf)?;
+        }
+        path => {
+            File::open(path)?.read_to_end(&mut buf)?;
+        }
+    }
+    Ok(buf)
+}
+
+/// create the output file, `-` writes to the stdout.
+pub(crate) fn create_output(output: &str) -> Result<Box<dyn Write>, UtilError> {
+    Ok(match output {
+        STDIO => Box::new(io::stdout().lock()),
+        output => Box::new(File::create(output)?),
+    })
+}
@@ -1,8 +1,13 @@
 use std::{fs::File, path::Path};
 
-use crate::error::UtilError;
+use crate::{error::UtilError, interrupt::Interrupt, progress::ProgressBar, usage::count_reader};
+use blockless_car::error::CarError;
 use blockless_car::reader::{self as car_reader, CarReader};
-use blockless_car::utils::extract_ipld;
+use blockless_car::utils::{
+    extract_ipld_subtree_with, extract_ipld_with, is_listing_root, resolve_path, ExtractOptions,
+    Overwrite, PathLimits, SymlinkPrefix, DEFAULT_MAX_NAME_LEN, DEFAULT_MAX_PATH_DEPTH,
+};
+use blockless_car::Cid;
 
 #[derive(Debug, clap::Parser)]
 pub struct UnpackCommand {
@@ -11,9 +16,128 @@ pub struct UnpackCommand {
 
     #[clap(short, help = "Target directory to unpack car to.")]
     output: Option<String>,
+
+    #[clap(
+        long = "cid",
+        help = "Extract only the subtree of the cid to the target, the cid can be any block of the car."
+    )]
+    cid: Option<Cid>,
+
+    #[clap(
+        long = "path",
+        help = "Extract only the subtree of the unixfs path, relative to the first root or `/ipfs/<cid>/...`.",
+        conflicts_with = "cid"
+    )]
+    path: Option<String>,
+
+    #[clap(
+        long = "max-depth",
+        help = "The maximum depth of nested paths.",
+        default_value_t = DEFAULT_MAX_PATH_DEPTH
+    )]
+    max_depth: usize,
+
+    #[clap(
+        long = "max-name-len",
+        help = "The maximum length in bytes of a file name.",
+        default_value_t = DEFAULT_MAX_NAME_LEN
+    )]
+    max_name_len: usize,
+
+    #[clap(
+        long = "no-follow-target-symlinks",
+        help = "Don't follow symlinks in the target directory (default).",
+        overrides_with = "follow_target_symlinks"
+    )]
+    no_follow_target_symlinks: bool,
+
+    #[clap(
+        long = "follow-target-symlinks",
+        help = "Follow symlinks in the target directory.",
+        overrides_with = "no_follow_target_symlinks"
+    )]
+    follow_target_symlinks: bool,
+
+    #[clap(
+        long = "dereference",
+        help = "Copy the targets in place of the symlinks instead of recreating them."
+    )]
+    dereference: bool,
+
+    #[clap(
+        long = "rewrite-symlink-prefix",
+        value_name = "OLD=NEW",
+        help = "Rewrite the absolute symlink targets under OLD to NEW, can be repeated.",
+        value_parser = parse_symlink_prefix
+    )]
+    rewrite_symlink_prefix: Vec<SymlinkPrefix>,
+
+    #[clap(
+        long = "overwrite",
+        help = "Replace the files existing in the target, the existing directories are merged.",
+        conflicts_with_all = ["skip_existing", "error_if_exists"]
+    )]
+    overwrite: bool,
+
+    #[clap(
+        long = "skip-existing",
+        help = "Keep the files existing in the target, the extracted ones are skipped.",
+        conflicts_with = "error_if_exists"
+    )]
+    skip_existing: bool,
+
+    #[clap(
+        long = "error-if-exists",
+        help = "Fail on the first file existing in the target (default)."
+    )]
+    error_if_exists: bool,
+
+    #[clap(
+        long = "preserve-owner",
+        help = "Restore the stored uid and gid, skipped if not permitted."
+    )]
+    preserve_owner: bool,
+
+    #[clap(
+        long = "preserve-metadata",
+        help = "Restore the stored mode and mtime, skipped if not permitted."
+    )]
+    preserve_metadata: bool,
+
+    #[clap(
+        long = "timeout",
+        help = "Cancel the unpack after the seconds, the extracted entries are kept."
+    )]
+    timeout: Option<u64>,
+
+    #[clap(
+        long = "progress",
+        help = "Show a progress bar with the files, the bytes verified and written and the ETA on the stderr."
+    )]
+    progress: bool,
+
+    #[cfg(feature = "xattr")]
+    #[clap(
+        long = "preserve-xattrs",
+        help = "Restore the stored extended attributes, skipped if not permitted or supported."
+    )]
+    preserve_xattrs: bool,
+}
+
+fn parse_symlink_prefix(s: &str) -> Result<SymlinkPrefix, String> {
+    s.parse().map_err(|e: CarError| e.to_string())
 }
 
 impl UnpackCommand {
+    /// the existing files fail the unpack unless `--overwrite` or `--skip-existing`.
+    fn overwrite_policy(&self) -> Overwrite {
+        match (self.overwrite, self.skip_existing) {
+            (true, _) => Overwrite::Always,
+            (_, true) => Overwrite::Never,
+            _ => Overwrite::Error,
+        }
+    }
+
     /// extract car file to local file system.
     /// `car` the car file to extract.
     /// `target` target directory to extract.
@@ -27,11 +151,85 @@ impl UnpackCommand {
         }
         let file = File::open(path)?;
         let mut reader = car_reader::new_v1(file)?;
-        let roots = reader.header().roots();
-        for cid in roots {
+        // the listing root is not extracted, see `embed_listing`.
+        let roots: Vec<Cid> = reader
+            .header()
+            .roots()
+            .into_iter()
+            .filter(|r| !is_listing_root(r))
+            .collect();
+        let interrupt = Interrupt::install(self.timeout)?;
+        let options = ExtractOptions::new()
+            .with_limits(PathLimits::new(self.max_depth, self.max_name_len))
+            .with_follow_target_symlinks(self.follow_target_symlinks)
+            .with_dereference(self.dereference)
+            .with_overwrite(self.overwrite_policy())
+            .with_preserve_owner(self.preserve_owner)
+            .with_preserve_metadata(self.preserve_metadata)
+            .with_cancel(interrupt.token());
+        let options = self
+            .rewrite_symlink_prefix
+            .iter()
+            .fold(options, |o, p| o.with_symlink_prefix(p.clone()));
+        #[cfg(feature = "xattr")]
+        let options = options.with_preserve_xattrs(self.preserve_xattrs);
+        // the whole car is read to extract the roots, the size of the subtree is unknown.
+        let bar = match self.progress {
+            true => {
+                let total = match self.path.is_some() || self.cid.is_some() {
+                    true => None,
+                    false => Some(reader.sections()?.iter().map(|s| s.len() as u64).sum()),
+                };
+                Some(ProgressBar::new("unpacking", total))
+            }
+            false => None,
+        };
+        let options = match bar.as_ref() {
+            Some(bar) => options.with_progress(bar.hook()),
+            None => options,
+        };
+        let subtree = match self.path.as_ref() {
+            Some(path) => Some((resolve_path(&mut reader, path)?, path.as_str())),
+            None => self.cid.map(|cid| (cid, "")),
+        };
+        if let Some((cid, path)) = subtree {
+            // the subtree is named by the last segment of the path, or by the cid.
+            let name = path.rsplit('/').find(|n| !n.is_empty());
+            let target = match (self.output.as_ref(), name) {
+                (Some(output), _) => output.clone(),
+                (None, Some(name)) if name != cid.to_string() => name.to_string(),
+                (None, _) => cid.to_string(),
+            };
+            let result = extract_ipld_subtree_with(&mut reader, cid, target, &options);
+            count_reader(&reader);
+            return match result {
+                Err(CarError::Cancelled { entries }) => {
+                    let summary = format!("{entries} entries of the subtree {cid} extracted");
+                    Err(interrupt.cancelled("unpack", summary))
+                }
+                result => Ok(result?),
+            };
+        }
+        for (i, cid) in roots.iter().enumerate() {
             let target: Option<&Path> = self.output.as_ref().map(|s| s.as_ref());
-            extract_ipld(&mut reader, cid, target)?;
+            match extract_ipld_with(&mut reader, *cid, target, &options) {
+                Ok(()) => {
+                    if let Some(bar) = bar.as_ref() {
+                        bar.next_run();
+                    }
+                }
+                Err(CarError::Cancelled { entries }) => {
+                    count_reader(&reader);
+                    let summary = format!(
+                        "{i}/{} roots extracted, {entries} entries of the root {cid} extracted",
+                        roots.len()
+                    );
+                    return Err(interrupt.cancelled("unpack", summary));
+                }
+                Err(e) => return Err(e.into()),
+            }
         }
+        count_reader(&reader);
         Ok(())
     }
 }
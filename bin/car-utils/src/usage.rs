@@ -0,0 +1,163 @@
+use blockless_car::reader::CarReader;
+use serde_json::{json, Value};
+use std::{
+    cell::Cell,
+    time::{Duration, Instant},
+};
+
+thread_local! {
+    /// the blocks read and written by the command, added by the command on its thread from
+    /// the counts of its readers, writers and reports.
+    static BLOCKS: Cell<(u64, u64)> = const { Cell::new((0, 0)) };
+}
+
+/// add the blocks read and written to the counts of the command.
+pub(crate) fn count_blocks(read: u64, written: u64) {
+    BLOCKS.with(|b| {
+        let (r, w) = b.get();
+        b.set((r + read, w + written));
+    });
+}
+
+/// add the blocks read by the `reader` to the counts of the command.
+pub(crate) fn count_reader(reader: &impl CarReader) {
+    count_blocks(reader.blocks_read(), 0);
+}
+
+/// the resources used by the command, printed to the stderr with `--stats`.
+pub(crate) struct Usage {
+    start: Instant,
+    blocks: (u64, u64),
+}
+
+/// the cpu time in the user and the system mode and the peak rss bytes of the process.
+#[cfg(unix)]
+fn rusage() -> Option<(Duration, Duration, u64)> {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) } != 0 {
+        return None;
+    }
+    let time = |t: libc::timeval| Duration::new(t.tv_sec as u64, t.tv_usec as u32 * 1000);
+    // the max rss is in KiB on linux and in bytes on macos.
+    let rss = match cfg!(target_os = "macos") {
+        true => usage.ru_maxrss as u64,
+        false => usage.ru_maxrss as u64 * 1024,
+    };
+    Some((time(usage.ru_utime), time(usage.ru_stime), rss))
+}
+
+#[cfg(not(unix))]
+fn rusage() -> Option<(Duration, Duration, u64)> {
+    None
+}
+
+/// the bytes read and written by the process through the syscalls, including the pipes.
+fn io_bytes() -> Option<(u64, u64)> {
+    let io = std::fs::read_to_string("/proc/self/io").ok()?;
+    let field = |name: &str| {
+        io.lines()
+            .find_map(|l| l.strip_prefix(name))
+            .and_then(|v| v.trim().parse().ok())
+    };
+    Some((field("rchar:")?, field("wchar:")?))
+}
+
+impl Usage {
+    pub(crate) fn start() -> Self {
+        Self {
+            start: Instant::now(),
+            blocks: BLOCKS.with(Cell::get),
+        }
+    }
+
+    /// the blocks read and written since the start.
+    pub(crate) fn blocks(&self) -> (u64, u64) {
+        let (read, written) = BLOCKS.with(Cell::get);
+        (read - self.blocks.0, written - self.blocks.1)
+    }
+
+    /// the usage as the json object, the values not available on the platform are left out.
+    fn to_json(&self, wall: Duration) -> Value {
+        let blocks = self.blocks();
+        let mut stats = json!({
+            "wall_secs": wall.as_secs_f64(),
+            "blocks_read": blocks.0,
+            "blocks_written": blocks.1,
+        });
+        if let Some((user, system, rss)) = rusage() {
+            stats["user_secs"] = json!(user.as_secs_f64());
+            stats["system_secs"] = json!(system.as_secs_f64());
+            stats["peak_rss"] = json!(rss);
+        }
+        if let Some((read, written)) = io_bytes() {
+            stats["bytes_read"] = json!(read);
+            stats["bytes_written"] = json!(written);
+        }
+        json!({ "stats": stats })
+    }
+
+    /// the usage as the text lines, the values not available on the platform are left out.
+    fn to_lines(&self, wall: Duration) -> Vec<String> {
+        let blocks = self.blocks();
+        let mut lines = vec![format!("wall time: {:.3}s", wall.as_secs_f64())];
+        if let Some((user, system, rss)) = rusage() {
+            lines.push(format!(
+                "cpu time: {:.3}s user, {:.3}s system",
+                user.as_secs_f64(),
+                system.as_secs_f64()
+            ));
+            lines.push(format!(
+                "peak rss: {:.1} MiB",
+                rss as f64 / (1 << 20) as f64
+            ));
+        }
+        if let Some((read, written)) = io_bytes() {
+            lines.push(format!("bytes: {read} read, {written} written"));
+        }
+        lines.push(format!("blocks: {} read, {} written", blocks.0, blocks.1));
+        lines
+    }
+
+    /// print the usage since the start to the stderr.
+    pub(crate) fn report(&self, json: bool) {
+        let wall = self.start.elapsed();
+        match json {
+            true => eprintln!("{}", self.to_json(wall)),
+            false => self.to_lines(wall).iter().for_each(|l| eprintln!("{l}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_usage() {
+        // the blocks counted before the start are left out.
+        count_blocks(5, 5);
+        let usage = Usage::start();
+        count_blocks(3, 0);
+        count_blocks(1, 2);
+        let wall = Duration::from_millis(1500);
+
+        let stats = &usage.to_json(wall)["stats"];
+        assert_eq!(stats["wall_secs"], 1.5);
+        assert_eq!(stats["blocks_read"], 4);
+        assert_eq!(stats["blocks_written"], 2);
+        #[cfg(unix)]
+        assert!(stats["peak_rss"].as_u64().unwrap() > 0);
+        #[cfg(target_os = "linux")]
+        assert!(stats["bytes_read"].as_u64().is_some());
+
+        let lines = usage.to_lines(wall);
+        assert_eq!(lines[0], "wall time: 1.500s");
+        assert_eq!(lines.last().unwrap(), "blocks: 4 read, 2 written");
+        #[cfg(unix)]
+        assert!(lines[2].starts_with("peak rss: "));
+
+        // the counts of the other threads, like the other tests, are not added.
+        std::thread::spawn(|| count_blocks(10, 10)).join().unwrap();
+        assert_eq!(usage.blocks(), (4, 2));
+    }
+}
@@ -0,0 +1,212 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    path::Path,
+    str::FromStr,
+};
+
+use blockless_car::{reader as car_reader, Cid};
+use serde_json::Value;
+
+use crate::{
+    bundle::{file_size, list_entries},
+    error::UtilError,
+    usage::count_reader,
+};
+
+#[derive(Debug, clap::Parser)]
+pub struct ValidateManifestCommand {
+    #[clap(help = "the car file to validate.")]
+    car: String,
+
+    #[clap(
+        help = "the JSON manifest, the entries list the path relative to the root, the cid and optionally the size."
+    )]
+    manifest: String,
+}
+
+/// the entry expected by the manifest.
+struct Expected {
+    path: String,
+    cid: Cid,
+    size: Option<u64>,
+}
+
+/// parse the manifest, `{"entries": [{"path", "cid", "size"}]}` or the entries array.
+fn parse_manifest(manifest: &Value) -> Result<Vec<Expected>, UtilError> {
+    let entries = manifest
+        .get("entries")
+        .unwrap_or(manifest)
+        .as_array()
+        .ok_or_else(|| UtilError::new("the manifest has no entries.".into()))?;
+    entries
+        .iter()
+        .map(|e| {
+            let path = e.get("path").and_then(Value::as_str);
+            let cid = e.get("cid").and_then(Value::as_str);
+            let (path, cid) = path
+                .zip(cid)
+                .ok_or_else(|| UtilError::new(format!("the entry {e} has no path or cid.")))?;
+            let cid = Cid::from_str(cid)
+                .map_err(|err| UtilError::new(format!("invalid cid of the entry {path}: {err}")))?;
+            Ok(Expected {
+                path: path.trim_matches('/').to_string(),
+                cid,
+                size: e.get("size").and_then(Value::as_u64),
+            })
+        })
+        .collect()
+}
+
+/// the problems of the car against the manifest, one line per entry.
+struct Report {
+    entries: usize,
+    problems: Vec<String>,
+}
+
+impl ValidateManifestCommand {
+    /// check every entry of the manifest resolves to the listed cid and size,
+    /// the files of the car not listed in the manifest are reported as extra.
+    fn validate(&self) -> Result<Report, UtilError> {
+        let path: &Path = self.car.as_ref();
+        if !path.exists() {
+            return Err(UtilError::new(format!(
+                "the car file [{}] does not exist.",
+                self.car
+            )));
+        }
+        let manifest = std::fs::read(&self.manifest)?;
+        let manifest: Value = serde_json::from_slice(&manifest)
+            .map_err(|e| UtilError::new(format!("invalid manifest {}: {e}", self.manifest)))?;
+        let expected = parse_manifest(&manifest)?;
+        let mut reader = car_reader::new_v1(File::open(path)?)?;
+        let entries: HashMap<String, Cid> = list_entries(&mut reader)?
+            .into_iter()
+            .map(|e| (e.path, e.cid))
+            .collect();
+
+        let mut problems = Vec::new();
+        for e in expected.iter() {
+            let Some(cid) = entries.get(&e.path) else {
+                problems.push(format!("missing: {}", e.path));
+                continue;
+            };
+            let size = file_size(&mut reader, cid)?;
+            let size_differs = e.size.is_some_and(|s| size != Some(s));
+            if *cid != e.cid || size_differs {
+                let size = size.map_or("-".to_string(), |s| s.to_string());
+                let expected_size = e.size.map_or("-".to_string(), |s| s.to_string());
+                problems.push(format!(
+                    "differ: {} {cid} {size}, expected {} {expected_size}",
+                    e.path, e.cid
+                ));
+            }
+        }
+        let listed: HashSet<&str> = expected.iter().map(|e| e.path.as_str()).collect();
+        let mut extra: Vec<&String> = entries
+            .keys()
+            .filter(|p| !listed.contains(p.as_str()))
+            .collect();
+        extra.sort();
+        for path in extra {
+            // the directories are implied by the listed files.
+            if file_size(&mut reader, &entries[path])?.is_some() {
+                problems.push(format!("extra: {path}"));
+            }
+        }
+        count_reader(&reader);
+        Ok(Report {
+            entries: expected.len(),
+            problems,
+        })
+    }
+
+    /// print the problems of the car against the manifest, the command fails for the
+    /// release pipelines if any entry is missing, extra or differs.
+    pub(crate) fn execute(&self) -> Result<(), UtilError> {
+        let report = self.validate()?;
+        for problem in report.problems.iter() {
+            println!("{problem}");
+        }
+        if !report.problems.is_empty() {
+            return Err(UtilError::new(format!(
+                "{} entries don't match the manifest",
+                report.problems.len()
+            )));
+        }
+        println!("{} entries match the manifest", report.entries);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use blockless_car::utils::{pack_files_with, PackOptions};
+    use serde_json::json;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_validate_manifest() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let site = temp_dir.path().join("site");
+        fs::create_dir_all(site.join("sub")).unwrap();
+        fs::write(site.join("a.txt"), b"abc").unwrap();
+        fs::write(site.join("sub/b.txt"), b"hello").unwrap();
+        let car = temp_dir.path().join("site.car");
+        pack_files_with(&site, File::create(&car).unwrap(), &PackOptions::new()).unwrap();
+        let mut reader = car_reader::new_v1(File::open(&car).unwrap()).unwrap();
+        let cids: HashMap<String, Cid> = list_entries(&mut reader)
+            .unwrap()
+            .into_iter()
+            .map(|e| (e.path, e.cid))
+            .collect();
+        let (a, b) = (cids["site/a.txt"], cids["site/sub/b.txt"]);
+        let command = |manifest: Value| {
+            let path = temp_dir.path().join("manifest.json");
+            fs::write(&path, manifest.to_string()).unwrap();
+            ValidateManifestCommand {
+                car: car.to_str().unwrap().into(),
+                manifest: path.to_str().unwrap().into(),
+            }
+        };
+
+        // the directories are implied by the files, the size is optional.
+        let valid = command(json!({"entries": [
+            {"path": "site/a.txt", "cid": a.to_string(), "size": 3},
+            {"path": "/site/sub/b.txt", "cid": b.to_string()},
+        ]}));
+        assert_eq!(valid.validate().unwrap().problems, Vec::<String>::new());
+        assert!(valid.execute().is_ok());
+
+        // the missing, differing and extra entries fail the command, which exits with 1.
+        let invalid = command(json!([
+            {"path": "site/a.txt", "cid": a.to_string(), "size": 4},
+            {"path": "site/missing.wasm", "cid": a.to_string()},
+        ]));
+        assert_eq!(
+            invalid.validate().unwrap().problems,
+            [
+                format!("differ: site/a.txt {a} 3, expected {a} 4"),
+                "missing: site/missing.wasm".to_string(),
+                "extra: site/sub/b.txt".to_string(),
+            ]
+        );
+        let err = invalid.execute().unwrap_err();
+        assert_eq!(err.err, "3 entries don't match the manifest");
+
+        let differ = command(json!([
+            {"path": "site/a.txt", "cid": b.to_string()},
+            {"path": "site/sub/b.txt", "cid": b.to_string()},
+        ]));
+        assert_eq!(
+            differ.validate().unwrap().problems,
+            [format!("differ: site/a.txt {a} 3, expected {b} -")]
+        );
+        assert!(differ.execute().is_err());
+
+        // the manifest without the cid of an entry is an error, not a mismatch.
+        assert!(command(json!([{"path": "site/a.txt"}])).execute().is_err());
+    }
+}
@@ -0,0 +1,228 @@
+use blockless_car::reader::{self as car_reader, CarReader, Duplicate};
+use blockless_car::utils::{self, CompletenessReport, MissingKind, VerifyReport};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+use crate::{
+    cars::{expand_cars, map_cars},
+    error::UtilError,
+    stdio::read_car,
+    usage::count_blocks,
+};
+
+#[derive(Debug, clap::Parser)]
+pub struct VerifyCommand {
+    #[clap(
+        required = true,
+        help = "the car files to verify, the directory is expanded to the car files in it and the glob to the files matching it, `-` reads a car from the stdin."
+    )]
+    cars: Vec<String>,
+
+    #[clap(
+        short,
+        long,
+        default_value_t = 0,
+        help = "The count of the hashing workers, or the cars verified at once for multiple cars, 0 means the count of the CPUs."
+    )]
+    jobs: usize,
+
+    #[clap(
+        long,
+        help = "Report the CIDs stored in more than one section with their offsets."
+    )]
+    duplicates: bool,
+
+    #[clap(
+        long,
+        help = "Walk the DAGs from the roots and report the linked blocks missing in the car."
+    )]
+    complete: bool,
+}
+
+/// the verification of a car.
+struct CarResult {
+    report: VerifyReport,
+    duplicates: Vec<Duplicate>,
+    completeness: Option<CompletenessReport>,
+    /// the blocks read by the reader of the car, added to the counts on the main thread.
+    blocks_read: u64,
+}
+
+impl CarResult {
+    fn is_ok(&self) -> bool {
+        self.report.is_ok() && self.completeness.as_ref().is_none_or(|c| c.is_complete())
+    }
+}
+
+fn missing_kind(kind: MissingKind) -> &'static str {
+    match kind {
+        MissingKind::External => "external",
+        MissingKind::Truncated => "truncated",
+    }
+}
+
+fn verify_car(path: &Path, jobs: usize, complete: bool) -> Result<CarResult, UtilError> {
+    let mut reader = car_reader::new_v1(read_car(path)?)?;
+    let completeness = match complete {
+        true => Some(utils::check_complete(&mut reader)?),
+        false => None,
+    };
+    Ok(CarResult {
+        report: utils::verify_blocks_parallel(&mut reader, jobs)?,
+        duplicates: reader.duplicates()?,
+        completeness,
+        blocks_read: reader.blocks_read(),
+    })
+}
+
+fn report_json(path: &Path, rs: &Result<CarResult, UtilError>, with_duplicates: bool) -> Value {
+    match rs {
+        Ok(
+            result @ CarResult {
+                report, duplicates, ..
+            },
+        ) => {
+            let failures: Vec<String> = report.failures.iter().map(|c| c.to_string()).collect();
+            let mut value = json!({
+                "path": path.display().to_string(),
+                "ok": result.is_ok(),
+                "blocks": report.blocks,
+                "failures": failures,
+                "failure_offsets": report.failure_offsets,
+                "truncated": report.truncated,
+            });
+            if with_duplicates {
+                let duplicates: Vec<Value> = duplicates
+                    .iter()
+                    .map(|d| json!({"cid": d.cid.to_string(), "offsets": d.offsets}))
+                    .collect();
+                value["duplicates"] = Value::Array(duplicates);
+            }
+            if let Some(completeness) = result.completeness.as_ref() {
+                let missing: Vec<Value> = completeness
+                    .missing
+                    .iter()
+                    .map(|m| {
+                        json!({
+                            "cid": m.cid.to_string(),
+                            "parent": m.parent.map(|p| p.to_string()),
+                            "kind": missing_kind(m.kind),
+                        })
+                    })
+                    .collect();
+                value["complete"] = Value::Bool(completeness.is_complete());
+                value["missing"] = Value::Array(missing);
+            }
+            value
+        }
+        Err(e) => json!({
+            "path": path.display().to_string(),
+            "ok": false,
+            "error": e.err,
+        }),
+    }
+}
+
+impl VerifyCommand {
+    /// re-hash the blocks of the car files and check the digests match their cids,
+    /// the combined report of all cars is printed as one JSON object if `is_json`.
+    pub(crate) fn execute(&self, is_json: bool) -> Result<(), UtilError> {
+        let cars = expand_cars(&self.cars)?;
+        let results = if cars.len() == 1 {
+            vec![verify_car(&cars[0], self.jobs, self.complete)]
+        } else {
+            self.verify_batch(&cars)
+        };
+        results
+            .iter()
+            .flatten()
+            .for_each(|r| count_blocks(r.blocks_read, 0));
+        let failed = results
+            .iter()
+            .filter(|rs| !matches!(rs, Ok(r) if r.is_ok()))
+            .count();
+        if is_json {
+            let reports: Vec<Value> = cars
+                .iter()
+                .zip(results.iter())
+                .map(|(path, rs)| report_json(path, rs, self.duplicates))
+                .collect();
+            let out = json!({"ok": failed == 0, "cars": reports});
+            println!("{out}");
+        } else {
+            let prefix = |path: &Path| match cars.len() {
+                1 => String::new(),
+                _ => format!("{}: ", path.display()),
+            };
+            for (path, rs) in cars.iter().zip(results.iter()) {
+                let prefix = prefix(path);
+                match rs {
+                    Ok(
+                        result @ CarResult {
+                            report, duplicates, ..
+                        },
+                    ) => {
+                        let failures = report.failures.iter().zip(report.failure_offsets.iter());
+                        for (cid, offset) in failures {
+                            println!("{prefix}mismatch: {cid} at offset {offset}");
+                        }
+                        for d in duplicates.iter().filter(|_| self.duplicates) {
+                            let offsets: Vec<String> =
+                                d.offsets.iter().map(|o| o.to_string()).collect();
+                            println!("{prefix}duplicate: {} at {}", d.cid, offsets.join(", "));
+                        }
+                        if report.truncated {
+                            println!("{prefix}truncated: the last section is cut off");
+                        }
+                        let missing = result.completeness.iter().flat_map(|c| c.missing.iter());
+                        for m in missing {
+                            match m.parent {
+                                Some(parent) => println!(
+                                    "{prefix}missing: {} ({}) linked by {parent}",
+                                    m.cid,
+                                    missing_kind(m.kind)
+                                ),
+                                None => println!(
+                                    "{prefix}missing: {} ({}) root",
+                                    m.cid,
+                                    missing_kind(m.kind)
+                                ),
+                            }
+                        }
+                        if result.is_ok() {
+                            println!("{prefix}{} blocks verified", report.blocks);
+                        }
+                    }
+                    Err(e) => println!("{prefix}error: {}", e.err),
+                }
+            }
+        }
+        match (failed, results.len()) {
+            (0, _) => Ok(()),
+            (_, 1) => match results.into_iter().next().unwrap() {
+                Ok(CarResult { report, .. }) if report.truncated => Err(UtilError::new(format!(
+                    "the car is truncated after {} blocks",
+                    report.blocks
+                ))),
+                Ok(CarResult { report, .. }) if !report.is_ok() => Err(UtilError::new(format!(
+                    "{} of {} blocks don't match their cids",
+                    report.failures.len(),
+                    report.blocks
+                ))),
+                Ok(CarResult { completeness, .. }) => Err(UtilError::new(format!(
+                    "{} linked blocks are missing",
+                    completeness.map_or(0, |c| c.missing.len())
+                ))),
+                Err(e) => Err(e),
+            },
+            (failed, n) => Err(UtilError::new(format!(
+                "{failed} of {n} cars failed the verification"
+            ))),
+        }
+    }
+
+    /// verify the cars in parallel, every car is hashed by one worker.
+    fn verify_batch(&self, cars: &[PathBuf]) -> Vec<Result<CarResult, UtilError>> {
+        map_cars(cars, self.jobs, |path| verify_car(path, 1, self.complete))
+    }
+}
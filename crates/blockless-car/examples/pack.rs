@@ -1,4 +1,4 @@
-use blockless_car::utils::pack_files;
+use blockless_car::{pack_dir, utils::PackOptions};
 
 /// Cat the file in car file by file id
 /// e.g. ```cargo run -p blockless-car --example pack <target-car-file>```
@@ -8,5 +8,5 @@ fn main() {
         .nth(2)
         .expect("need the target file as argument");
     let file = std::fs::File::create(target).unwrap();
-    pack_files(file_name, file, multicodec::Codec::Sha2_256, false).unwrap();
+    pack_dir(file_name, file, &PackOptions::new()).unwrap();
 }
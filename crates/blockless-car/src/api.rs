@@ -0,0 +1,133 @@
+//! the entry points named by what they do, e.g. `pack_dir` and `unpack`. they take the
+//! options of the `utils` functions behind them, which stay for the finer control.
+#[cfg(feature = "unixfs-fs")]
+use std::{
+    io::{Read, Seek, Write},
+    path::Path,
+};
+
+#[cfg(feature = "cli-utils")]
+use std::cell::RefCell;
+
+use crate::{error::CarError, reader::CarReader, Cid};
+
+#[cfg(feature = "cli-utils")]
+use crate::utils::VerifyReport;
+#[cfg(feature = "unixfs-fs")]
+use crate::utils::{ExtractOptions, PackOptions};
+
+/// pack the directory to the CAR, return the root cid.
+#[cfg(feature = "unixfs-fs")]
+pub fn pack_dir<T>(path: impl AsRef<Path>, to: T, options: &PackOptions) -> Result<Cid, CarError>
+where
+    T: Write + Seek,
+{
+    let path = path.as_ref();
+    if !path.is_dir() {
+        return Err(CarError::InvalidFile(format!(
+            "{} is not a directory",
+            path.display()
+        )));
+    }
+    crate::utils::pack_files_with(path, to, options)
+}
+
+/// pack the file to the CAR, the file is wrapped in a directory unless the
+/// `no_wrap_file` of the options. return the root cid.
+#[cfg(feature = "unixfs-fs")]
+pub fn pack_file<T>(path: impl AsRef<Path>, to: T, options: &PackOptions) -> Result<Cid, CarError>
+where
+    T: Write + Seek,
+{
+    let path = path.as_ref();
+    if !path.is_file() {
+        return Err(CarError::NotAFile(path.display().to_string()));
+    }
+    crate::utils::pack_files_with(path, to, options)
+}
+
+/// pack the content of the stream, e.g. the stdin, to the CAR as a file without the name,
+/// the root is the file cid.
+#[cfg(feature = "unixfs-fs")]
+pub fn pack_stream<R, T>(r: &mut R, to: T, options: &PackOptions) -> Result<Cid, CarError>
+where
+    R: Read,
+    T: Write + Seek,
+{
+    crate::utils::pack_reader_with(r, to, options)
+}
+
+/// extract the DAG of the `cid` to the `target` directory.
+#[cfg(feature = "unixfs-fs")]
+pub fn unpack(
+    reader: &mut impl CarReader,
+    cid: Cid,
+    target: impl AsRef<Path>,
+    options: &ExtractOptions,
+) -> Result<(), CarError> {
+    crate::utils::extract_ipld_with(reader, cid, Some(target), options)
+}
+
+/// the cids and the paths of the entries of the roots, in the order of `utils::list_call`.
+#[cfg(feature = "cli-utils")]
+pub fn list(reader: &mut impl CarReader) -> Result<Vec<(Cid, String)>, CarError> {
+    let entries = RefCell::new(Vec::new());
+    crate::utils::list_call(reader, |cid, path| {
+        entries.borrow_mut().push((*cid, path.to_string()))
+    })?;
+    Ok(entries.into_inner())
+}
+
+/// re-hash the blocks of the CAR, see `utils::verify_blocks`.
+#[cfg(feature = "cli-utils")]
+pub fn verify(reader: &mut impl CarReader) -> Result<VerifyReport, CarError> {
+    crate::utils::verify_blocks(reader)
+}
+
+#[cfg(all(test, feature = "unixfs-fs", feature = "cli-utils"))]
+mod test {
+    use super::*;
+    use crate::reader;
+    use std::{fs, io::Cursor};
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_api() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        fs::create_dir_all(root_dir.join("sub")).unwrap();
+        fs::write(root_dir.join("sub/a.txt"), b"hello").unwrap();
+        let options = PackOptions::new().with_no_wrap_file(true);
+        let mut car = Cursor::new(vec![]);
+        let root = pack_dir(&root_dir, &mut car, &options).unwrap();
+        let mut reader = reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+        assert!(verify(&mut reader).unwrap().is_ok());
+        let paths: Vec<String> = list(&mut reader)
+            .unwrap()
+            .into_iter()
+            .map(|(_, p)| p)
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                root.to_string(),
+                format!("{root}/root"),
+                format!("{root}/root/sub"),
+                format!("{root}/root/sub/a.txt")
+            ]
+        );
+        let target = temp_dir.path().join("out");
+        unpack(&mut reader, root, &target, &ExtractOptions::default()).unwrap();
+        assert_eq!(fs::read(target.join("root/sub/a.txt")).unwrap(), b"hello");
+
+        // the file packed from the path and from the stream has the same cid.
+        let file = root_dir.join("sub/a.txt");
+        let mut car = Cursor::new(vec![]);
+        let file_root = pack_file(&file, &mut car, &options).unwrap();
+        let mut car = Cursor::new(vec![]);
+        let stream_root = pack_stream(&mut &b"hello"[..], &mut car, &options).unwrap();
+        assert_eq!(stream_root, file_root);
+        assert!(pack_dir(&file, Cursor::new(vec![]), &options).is_err());
+        assert!(pack_file(&root_dir, Cursor::new(vec![]), &options).is_err());
+    }
+}
@@ -1,4 +1,15 @@
-use crate::error::CarError;
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, OnceLock, RwLock},
+};
+
+use ipld::{
+    codec::{Decode, Encode},
+    IpldCodec,
+};
+
+use crate::{error::CarError, Ipld};
 
 pub trait Decoder<T> {
     fn decode(&self) -> Result<T, CarError>;
@@ -7,3 +18,189 @@ pub trait Decoder<T> {
 pub trait Encoder<T> {
     fn encode(&self) -> Result<T, CarError>;
 }
+
+type DecodeFn = dyn Fn(&[u8]) -> Result<Ipld, CarError> + Send + Sync;
+type EncodeFn = dyn Fn(&Ipld) -> Result<Vec<u8>, CarError> + Send + Sync;
+
+/// the decode and the optional encode functions of a codec not built in, e.g. the
+/// proprietary codec of the private-use multicodec.
+#[derive(Clone)]
+pub struct CustomCodec {
+    decode: Arc<DecodeFn>,
+    encode: Option<Arc<EncodeFn>>,
+}
+
+impl fmt::Debug for CustomCodec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CustomCodec")
+            .field("encode", &self.encode.is_some())
+            .finish()
+    }
+}
+
+impl CustomCodec {
+    pub fn new<D>(decode: D) -> Self
+    where
+        D: Fn(&[u8]) -> Result<Ipld, CarError> + Send + Sync + 'static,
+    {
+        Self {
+            decode: Arc::new(decode),
+            encode: None,
+        }
+    }
+
+    pub fn with_encode<E>(mut self, encode: E) -> Self
+    where
+        E: Fn(&Ipld) -> Result<Vec<u8>, CarError> + Send + Sync + 'static,
+    {
+        self.encode = Some(Arc::new(encode));
+        self
+    }
+
+    #[inline]
+    pub fn decode(&self, data: &[u8]) -> Result<Ipld, CarError> {
+        (self.decode)(data)
+    }
+
+    /// encode the ipld, the codec without the encode function returns `NotImplemented`.
+    pub fn encode(&self, ipld: &Ipld) -> Result<Vec<u8>, CarError> {
+        match &self.encode {
+            Some(encode) => encode(ipld),
+            None => Err(CarError::NotImplemented("the encode of the codec".into())),
+        }
+    }
+}
+
+fn registry() -> &'static RwLock<HashMap<u64, CustomCodec>> {
+    static REGISTRY: OnceLock<RwLock<HashMap<u64, CustomCodec>>> = OnceLock::new();
+    REGISTRY.get_or_init(Default::default)
+}
+
+/// register the codec of the multicodec `code` for the process, the blocks of the code are
+/// decoded by it wherever the blocks are read, e.g. the traversals and the cat. the built in
+/// codecs dag-pb, dag-cbor, dag-json and raw can't be replaced. return the codec replaced.
+pub fn register_codec(code: u64, codec: CustomCodec) -> Result<Option<CustomCodec>, CarError> {
+    if IpldCodec::try_from(code).is_ok() {
+        return Err(CarError::BuiltinCodec(code));
+    }
+    let mut registry = registry().write().unwrap_or_else(|e| e.into_inner());
+    Ok(registry.insert(code, codec))
+}
+
+/// remove the codec registered for the `code`.
+pub fn unregister_codec(code: u64) -> Option<CustomCodec> {
+    let mut registry = registry().write().unwrap_or_else(|e| e.into_inner());
+    registry.remove(&code)
+}
+
+/// the codec registered for the `code`.
+pub fn custom_codec(code: u64) -> Option<CustomCodec> {
+    let registry = registry().read().unwrap_or_else(|e| e.into_inner());
+    registry.get(&code).cloned()
+}
+
+/// the codec `code` is built in or registered.
+pub fn is_known_codec(code: u64) -> bool {
+    IpldCodec::try_from(code).is_ok() || custom_codec(code).is_some()
+}
+
+/// decode the block data of the built in or the registered codec `code`.
+pub fn decode_ipld(code: u64, data: &[u8]) -> Result<Ipld, CarError> {
+    match IpldCodec::try_from(code) {
+        Ok(codec) => <Ipld as Decode<IpldCodec>>::decode(codec, &mut std::io::Cursor::new(data))
+            .map_err(|e| CarError::Parsing(e.to_string())),
+        Err(_) => match custom_codec(code) {
+            Some(codec) => codec.decode(data),
+            None => Err(CarError::NotImplemented(format!("the codec {code:#x}"))),
+        },
+    }
+}
+
+/// encode the ipld by the built in or the registered codec `code`.
+pub fn encode_ipld(code: u64, ipld: &Ipld) -> Result<Vec<u8>, CarError> {
+    match IpldCodec::try_from(code) {
+        Ok(codec) => {
+            let mut data = Vec::new();
+            ipld.encode(codec, &mut data)
+                .map_err(|e| CarError::Parsing(e.to_string()))?;
+            Ok(data)
+        }
+        Err(_) => match custom_codec(code) {
+            Some(codec) => codec.encode(ipld),
+            None => Err(CarError::NotImplemented(format!("the codec {code:#x}"))),
+        },
+    }
+}
+
+#[cfg(all(test, feature = "cli-utils"))]
+mod test {
+    use super::*;
+    use crate::{
+        header::CarHeader,
+        reader::{self, CarReader},
+        utils::{dag_graph, raw_cid},
+        writer::{CarWriter, CarWriterV1},
+        Cid,
+    };
+    use cid::multihash::{Code, MultihashDigest};
+    use std::io::Cursor;
+
+    /// the test codec of the private-use range, the data is the cids of the links.
+    const LINKS_CODEC: u64 = 0x300001;
+
+    fn links_codec() -> CustomCodec {
+        CustomCodec::new(|mut data: &[u8]| {
+            let mut links = Vec::new();
+            while !data.is_empty() {
+                let cid =
+                    Cid::read_bytes(&mut data).map_err(|e| CarError::Parsing(e.to_string()))?;
+                links.push(Ipld::Link(cid));
+            }
+            Ok(Ipld::List(links))
+        })
+        .with_encode(|ipld| match ipld {
+            Ipld::List(links) => Ok(links
+                .iter()
+                .filter_map(|l| match l {
+                    Ipld::Link(cid) => Some(cid.to_bytes()),
+                    _ => None,
+                })
+                .flatten()
+                .collect()),
+            _ => Err(CarError::Parsing("not a list".into())),
+        })
+    }
+
+    #[test]
+    fn test_custom_codec() {
+        assert!(matches!(
+            register_codec(0x71, links_codec()),
+            Err(CarError::BuiltinCodec(0x71))
+        ));
+        let leaf = raw_cid(b"leaf", multicodec::Codec::Sha2_256);
+        let ipld = Ipld::List(vec![Ipld::Link(leaf)]);
+        assert!(encode_ipld(LINKS_CODEC, &ipld).is_err());
+        register_codec(LINKS_CODEC, links_codec()).unwrap();
+        let data = encode_ipld(LINKS_CODEC, &ipld).unwrap();
+        let node = Cid::new_v1(LINKS_CODEC, Code::Sha2_256.digest(&data));
+
+        let mut car = Cursor::new(vec![]);
+        let mut writer = CarWriterV1::new(&mut car, CarHeader::new_v1(vec![node]));
+        writer.write_block(node, &data).unwrap();
+        writer.write_block(leaf, b"leaf").unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+        let mut reader = reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+        assert_eq!(reader.ipld(&node).unwrap(), ipld);
+        // the traversal follows the links of the custom codec.
+        let graph = dag_graph(&mut reader, &[node], None).unwrap();
+        assert_eq!(graph.nodes.len(), 2);
+        assert_eq!(graph.edges[0].to, leaf);
+
+        unregister_codec(LINKS_CODEC).unwrap();
+        assert!(matches!(
+            reader.ipld(&node),
+            Err(CarError::NotImplemented(_))
+        ));
+    }
+}
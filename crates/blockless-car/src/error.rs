@@ -15,11 +15,116 @@ pub enum CarError {
     IO(#[from] std::io::Error),
 
     #[error("too large section error: {0}")]
-    TooLargeSection(usize),
+    TooLargeSection(u64),
 
     #[error("Not found {0}")]
     NotFound(String),
 
     #[error("Invalid not implemented: {0}")]
     NotImplemented(String),
+
+    #[error("path too deep: {path} (depth {depth}, limit {limit})")]
+    PathTooDeep {
+        path: String,
+        depth: usize,
+        limit: usize,
+    },
+
+    #[error("name too long: {path} ({len} bytes, limit {limit})")]
+    NameTooLong {
+        path: String,
+        len: usize,
+        limit: usize,
+    },
+
+    #[error("unsafe path: {0}")]
+    UnsafePath(String),
+
+    #[error("symlink in the extraction target: {0}")]
+    TargetSymlink(String),
+
+    #[error("the extraction target exists: {0}")]
+    TargetExists(String),
+
+    #[error("cancelled after {entries} entries")]
+    Cancelled { entries: usize },
+
+    #[error("invalid chunk size {0}, the size must be in 1..=1048576")]
+    InvalidChunkSize(usize),
+
+    #[error("extract budget exceeded: {0}")]
+    BudgetExceeded(String),
+
+    #[error("invalid cid version: {0}")]
+    InvalidCidVersion(String),
+
+    #[error("not a file: {0}")]
+    NotAFile(String),
+
+    #[error("{name} {value} exceeds the limit {limit}")]
+    LimitExceeded {
+        name: &'static str,
+        value: usize,
+        limit: usize,
+    },
+
+    #[error("invalid pattern {0}")]
+    InvalidPattern(String),
+
+    #[error("invalid chunk rule {0}")]
+    InvalidChunkRule(String),
+
+    #[error("rejected by the pack hook: {0}")]
+    Rejected(String),
+
+    #[error("invalid symlink prefix {0}")]
+    InvalidSymlinkPrefix(String),
+
+    #[error("the codec {0:#x} is built in")]
+    BuiltinCodec(u64),
+
+    #[error("too many symlinks resolving {path}, the limit is {limit}")]
+    SymlinkHops { path: String, limit: usize },
+
+    #[error("symlink cycle resolving {path} at the symlink {cid}")]
+    SymlinkCycle { path: String, cid: String },
+
+    #[error("{path}: the block {cid}{}: {source}", linked_by(.parent))]
+    Traversal {
+        /// the path being resolved, starts with the root cid or the path of the caller.
+        path: String,
+        cid: String,
+        /// the block linking the failed one, none for the root of the walk.
+        parent: Option<String>,
+        source: Box<CarError>,
+    },
+}
+
+fn linked_by(parent: &Option<String>) -> String {
+    parent
+        .as_ref()
+        .map(|p| format!(" linked by {p}"))
+        .unwrap_or_default()
+}
+
+#[cfg(any(feature = "unixfs-fs", feature = "cli-utils"))]
+impl CarError {
+    /// add the context of the walk to the error of reading or decoding the block `cid`,
+    /// the error of a nested walk keeps its context.
+    pub(crate) fn traversal(
+        self,
+        path: impl std::fmt::Display,
+        cid: &cid::Cid,
+        parent: Option<&cid::Cid>,
+    ) -> Self {
+        match self {
+            e @ CarError::Traversal { .. } => e,
+            e => CarError::Traversal {
+                path: path.to_string(),
+                cid: cid.to_string(),
+                parent: parent.map(|p| p.to_string()),
+                source: Box::new(e),
+            },
+        }
+    }
 }
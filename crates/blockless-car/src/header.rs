@@ -1,5 +1,4 @@
 mod header_v1;
-use std::io;
 
 pub(crate) use header_v1::CarHeaderV1;
 
@@ -7,7 +6,9 @@ use cid::Cid;
 use ipld::prelude::Codec;
 use ipld_cbor::DagCborCodec;
 
-use crate::{error::CarError, reader::read_block};
+use crate::error::CarError;
+#[cfg(feature = "reader")]
+use crate::reader::{read_block_checked, ReaderLimits};
 
 #[derive(Clone, Debug)]
 pub enum CarHeader {
@@ -27,16 +28,35 @@ impl CarHeader {
         }
     }
 
+    #[cfg(feature = "reader")]
     pub fn read_header<R>(r: R) -> Result<CarHeader, CarError>
     where
-        R: io::Read + io::Seek,
+        R: std::io::Read,
     {
-        let data = match read_block(r) {
+        Self::read_header_with_limits(r, &ReaderLimits::default())
+    }
+
+    /// read the header, the header size and the roots count are checked with the `limits`.
+    #[cfg(feature = "reader")]
+    pub fn read_header_with_limits<R>(r: R, limits: &ReaderLimits) -> Result<CarHeader, CarError>
+    where
+        R: std::io::Read,
+    {
+        let data = match read_block_checked(r, |l| limits.check_header_size(l)) {
             Ok(Some(d)) => d,
             Ok(None) => return Err(CarError::Parsing("Invalid Header".into())),
             Err(e) => return Err(e),
         };
-        let header = CarHeader::decode(&data[..])?;
+        let header = Self::decode_with_limits(&data[..], limits)?;
+        Ok(header)
+    }
+
+    /// decode the header, the size and the roots count are checked with the `limits`.
+    #[cfg(feature = "reader")]
+    pub fn decode_with_limits(buf: &[u8], limits: &ReaderLimits) -> Result<CarHeader, CarError> {
+        limits.check_header_size(buf.len())?;
+        let header = Self::decode(buf)?;
+        limits.check_roots(header.roots().len())?;
         Ok(header)
     }
 
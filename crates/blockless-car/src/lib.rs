@@ -1,18 +1,33 @@
+#[cfg(any(feature = "unixfs-fs", feature = "cli-utils"))]
+mod api;
 pub mod codec;
 pub mod error;
 pub mod header;
+#[cfg(feature = "unixfs")]
 mod pb;
+#[cfg(feature = "reader")]
 pub mod reader;
 pub mod section;
+#[cfg(feature = "reader")]
+pub mod store;
+#[cfg(feature = "unixfs")]
 pub mod unixfs;
+#[cfg(feature = "unixfs")]
 mod unixfs_codec;
 pub mod utils;
+#[cfg(feature = "writer")]
 pub mod writer;
 
+#[cfg(any(feature = "unixfs-fs", feature = "cli-utils"))]
+pub use api::*;
 pub use codec::Decoder;
 pub use header::CarHeader;
 
 pub type Ipld = ipld::Ipld;
 
 // re-export hasher codec types
+#[cfg(feature = "writer")]
 pub use multicodec::Codec;
+
+pub use cid::Cid;
+pub use cid::Version as CidVersion;
@@ -25,6 +25,9 @@ pub struct Data<'a> {
     pub fanout: Option<u64>,
     pub mode: Option<u32>,
     pub mtime: Option<UnixTime>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub xattrs: Vec<Xattr<'a>>,
 }
 
 impl<'a> MessageRead<'a> for Data<'a> {
@@ -40,6 +43,9 @@ impl<'a> MessageRead<'a> for Data<'a> {
                 Ok(48) => msg.fanout = Some(r.read_uint64(bytes)?),
                 Ok(56) => msg.mode = Some(r.read_uint32(bytes)?),
                 Ok(66) => msg.mtime = Some(r.read_message::<UnixTime>(bytes)?),
+                Ok(8008) => msg.uid = Some(r.read_uint32(bytes)?),
+                Ok(8016) => msg.gid = Some(r.read_uint32(bytes)?),
+                Ok(8026) => msg.xattrs.push(r.read_message::<Xattr>(bytes)?),
                 Ok(t) => { r.read_unknown(bytes, t)?; }
                 Err(e) => return Err(e),
             }
@@ -59,6 +65,9 @@ impl<'a> MessageWrite for Data<'a> {
         + self.fanout.as_ref().map_or(0, |m| 1 + sizeof_varint(*(m) as u64))
         + self.mode.as_ref().map_or(0, |m| 1 + sizeof_varint(*(m) as u64))
         + self.mtime.as_ref().map_or(0, |m| 1 + sizeof_len((m).get_size()))
+        + self.uid.as_ref().map_or(0, |m| 2 + sizeof_varint(*(m) as u64))
+        + self.gid.as_ref().map_or(0, |m| 2 + sizeof_varint(*(m) as u64))
+        + self.xattrs.iter().map(|s| 2 + sizeof_len((s).get_size())).sum::<usize>()
     }
 
     fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
@@ -70,6 +79,9 @@ impl<'a> MessageWrite for Data<'a> {
         if let Some(ref s) = self.fanout { w.write_with_tag(48, |w| w.write_uint64(*s))?; }
         if let Some(ref s) = self.mode { w.write_with_tag(56, |w| w.write_uint32(*s))?; }
         if let Some(ref s) = self.mtime { w.write_with_tag(66, |w| w.write_message(s))?; }
+        if let Some(ref s) = self.uid { w.write_with_tag(8008, |w| w.write_uint32(*s))?; }
+        if let Some(ref s) = self.gid { w.write_with_tag(8016, |w| w.write_uint32(*s))?; }
+        for s in &self.xattrs { w.write_with_tag(8026, |w| w.write_message(s))?; }
         Ok(())
     }
 }
@@ -191,3 +203,38 @@ impl<'a> MessageWrite for Metadata<'a> {
     }
 }
 
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Xattr<'a> {
+    pub name: Cow<'a, str>,
+    pub value: Cow<'a, [u8]>,
+}
+
+impl<'a> MessageRead<'a> for Xattr<'a> {
+    fn from_reader(r: &mut BytesReader, bytes: &'a [u8]) -> Result<Self> {
+        let mut msg = Self::default();
+        while !r.is_eof() {
+            match r.next_tag(bytes) {
+                Ok(10) => msg.name = r.read_string(bytes).map(Cow::Borrowed)?,
+                Ok(18) => msg.value = r.read_bytes(bytes).map(Cow::Borrowed)?,
+                Ok(t) => { r.read_unknown(bytes, t)?; }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(msg)
+    }
+}
+
+impl<'a> MessageWrite for Xattr<'a> {
+    fn get_size(&self) -> usize {
+        0
+        + 1 + sizeof_len((&self.name).len())
+        + 1 + sizeof_len((&self.value).len())
+    }
+
+    fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
+        w.write_with_tag(10, |w| w.write_string(&**&self.name))?;
+        w.write_with_tag(18, |w| w.write_bytes(&**&self.value))?;
+        Ok(())
+    }
+}
@@ -1,23 +1,57 @@
 use cid::Cid;
+#[cfg(feature = "unixfs")]
 use ipld::raw::RawCodec;
 
+#[cfg(feature = "tokio")]
+mod async_reader_v1;
+mod limits;
+#[cfg(all(feature = "mmap", unix))]
+mod mmap_reader_v1;
+mod mode;
 mod reader_v1;
-use crate::{error::CarError, header::CarHeader, section::Section, unixfs::UnixFs, Ipld};
-use integer_encoding::VarIntReader;
+mod stream_reader_v1;
+#[cfg(feature = "unixfs")]
+use crate::unixfs::UnixFs;
+use crate::{
+    error::CarError,
+    header::CarHeader,
+    section::{check_block, Section},
+    Ipld,
+};
+use integer_encoding::{VarInt, VarIntReader};
+#[cfg(feature = "unixfs")]
+use std::collections::VecDeque;
 use std::{
-    collections::VecDeque,
-    io::{self, Read, Seek},
+    collections::HashMap,
+    io::{self, Read, Seek, Write},
 };
 
+#[cfg(feature = "tokio")]
+pub use async_reader_v1::AsyncCarReaderV1;
+pub use limits::*;
+#[cfg(all(feature = "mmap", unix))]
+pub use mmap_reader_v1::{Mmap, MmapCarReaderV1};
+pub use mode::*;
 pub(crate) use reader_v1::CarReaderV1;
+pub use stream_reader_v1::StreamCarReaderV1;
 
-const MAX_ALLOWED_SECTION_SIZE: usize = 32 << 20;
+pub(crate) use crate::section::checked_section_len;
 
-pub fn read_block<R>(mut reader: R) -> Result<Option<Vec<u8>>, CarError>
+pub fn read_block<R>(reader: R) -> Result<Option<Vec<u8>>, CarError>
 where
     R: std::io::Read,
 {
-    let l: usize = match reader.read_varint() {
+    read_block_checked(reader, |l| checked_section_len(l as u64).map(|_| ()))
+}
+
+/// read the length prefixed block, the length is checked before the block is allocated.
+/// the length over the usize of the 32-bit targets is checked as `usize::MAX`.
+pub(crate) fn read_block_checked<R, F>(mut reader: R, check: F) -> Result<Option<Vec<u8>>, CarError>
+where
+    R: std::io::Read,
+    F: FnOnce(usize) -> Result<(), CarError>,
+{
+    let l = match reader.read_varint::<u64>() {
         Ok(i) => i,
         Err(e) => {
             if e.kind() == std::io::ErrorKind::UnexpectedEof {
@@ -26,53 +60,278 @@ where
             return Err(CarError::IO(e));
         }
     };
-    if l > MAX_ALLOWED_SECTION_SIZE {
-        return Err(CarError::TooLargeSection(l));
+    let l = usize::try_from(l).unwrap_or(usize::MAX);
+    check(l)?;
+    read_exact_len(reader, l).map(Some)
+}
+
+/// read the `len` bytes, the buffer grows with the bytes read instead of being allocated
+/// up front, so the length declared by the truncated input costs only the bytes present.
+pub(crate) fn read_exact_len<R>(reader: R, len: usize) -> Result<Vec<u8>, CarError>
+where
+    R: std::io::Read,
+{
+    let mut data = Vec::new();
+    reader.take(len as u64).read_to_end(&mut data)?;
+    if data.len() < len {
+        return Err(CarError::IO(io::ErrorKind::UnexpectedEof.into()));
+    }
+    Ok(data)
+}
+
+/// the max length of the section prefix, the length varint and the cid of the
+/// version, codec, hash code and digest size varints with the 64 bytes digest.
+const SECTION_PREFIX_LEN: usize = 10 + 4 * 10 + 64;
+
+/// fill the buffer until the end of the input, return the filled length.
+fn read_full<R: io::Read>(mut reader: R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
     }
-    let mut data = vec![0u8; l];
-    reader.read_exact(&mut data[..])?;
-    Ok(Some(data))
+    Ok(filled)
 }
 
-pub(crate) fn read_section<R>(mut reader: R) -> Result<Option<Section>, CarError>
+/// read the framing of the next section and seek to the next one, the section is
+/// checked with the `limits` and the `mode`.
+pub(crate) fn read_section<R>(
+    mut reader: R,
+    limits: &ReaderLimits,
+    mode: DecodeMode,
+) -> Result<Option<Section>, CarError>
 where
     R: io::Read + io::Seek,
 {
-    let len: usize = match reader.read_varint() {
-        Ok(i) => i,
-        Err(e) => {
-            if e.kind() == io::ErrorKind::UnexpectedEof {
-                return Ok(None);
-            }
-            return Err(CarError::IO(e));
-        }
-    };
+    // the length and the cid are decoded from one read of the stack buffer,
+    // the byte by byte reads of the varints cost a syscall each on the file.
     let start = reader.stream_position()?;
-    if len > MAX_ALLOWED_SECTION_SIZE {
-        return Err(CarError::TooLargeSection(len));
+    let mut buf = [0u8; SECTION_PREFIX_LEN];
+    let filled = read_full(&mut reader, &mut buf)?;
+    if filled == 0 {
+        return Ok(None);
     }
-    let cid = Cid::read_bytes(&mut reader).map_err(|e| CarError::Parsing(e.to_string()))?;
-    let pos = reader.stream_position()?;
-    let l = len - ((pos - start) as usize);
-    reader.seek(io::SeekFrom::Current(l as _))?;
+    let (len, varint_len) = u64::decode_var(&buf[..filled])
+        .ok_or_else(|| CarError::Parsing("invalid section length".into()))?;
+    mode.check_varint(len, varint_len)?;
+    let len = limits.check_section_size(len)?;
+    let prefix = &buf[varint_len..filled.min(varint_len + len)];
+    let mut cid_bytes = prefix;
+    let cid = Cid::read_bytes(&mut cid_bytes).map_err(|e| CarError::Parsing(e.to_string()))?;
+    let cid_len = prefix.len() - cid_bytes.len();
+    limits.check_cid_len(cid_len)?;
+    let pos = start + (varint_len + cid_len) as u64;
+    let l = len - cid_len;
+    reader.seek(io::SeekFrom::Start(pos + l as u64))?;
     Ok(Some(Section::new(cid, pos, l)))
 }
 
+/// the position of the read of the sections in the file order, see `CarReader::next_block`.
+#[derive(Debug, Clone, Default)]
+pub struct SectionCursor {
+    /// the offset of the next section, none before the first section.
+    offset: Option<u64>,
+    /// the end of the input the lenient mode cuts the sections off at.
+    end: Option<u64>,
+    /// the count of the sections read, the sections skipped by the mode are counted too.
+    read: usize,
+    done: bool,
+}
+
+impl SectionCursor {
+    /// the count of the sections read.
+    #[inline(always)]
+    pub fn read(&self) -> usize {
+        self.read
+    }
+}
+
+/// read the framing of the next section of the `cursor` kept by the decode `mode`, the first
+/// section is at the `start`. return none after the last section, the lenient mode ends
+/// the sections at the malformed one or the one cut off by the end of the input.
+pub(crate) fn next_section<R>(
+    mut reader: R,
+    cursor: &mut SectionCursor,
+    start: u64,
+    limits: &ReaderLimits,
+    mode: DecodeMode,
+) -> Result<Option<Section>, CarError>
+where
+    R: io::Read + io::Seek,
+{
+    if cursor.done {
+        return Ok(None);
+    }
+    let end = match (cursor.end, mode) {
+        (Some(end), _) => end,
+        (None, DecodeMode::Lenient) => *cursor.end.insert(reader.seek(io::SeekFrom::End(0))?),
+        (None, DecodeMode::Strict) => *cursor.end.insert(u64::MAX),
+    };
+    reader.seek(io::SeekFrom::Start(cursor.offset.unwrap_or(start)))?;
+    loop {
+        let section = match read_section(&mut reader, limits, mode) {
+            Ok(Some(section)) => section,
+            Ok(None) => break,
+            Err(e) if mode.is_end(&e) => break,
+            Err(e) => return Err(e),
+        };
+        let next = section.pos() + section.len() as u64;
+        if next > end {
+            break;
+        }
+        cursor.offset = Some(next);
+        cursor.read += 1;
+        limits.check_sections(cursor.read)?;
+        if mode.keep_codec(section.cid().codec()) {
+            return Ok(Some(section));
+        }
+    }
+    cursor.done = true;
+    Ok(None)
+}
+
+/// the iterator of the blocks in the file order, the sections are read from the input and
+/// decoded on demand, so the iteration doesn't index the sections. the iteration ends
+/// after the first error.
+pub struct Blocks<'a, R: ?Sized> {
+    reader: &'a mut R,
+    cursor: SectionCursor,
+    failed: bool,
+}
+
+impl<R> Iterator for Blocks<'_, R>
+where
+    R: CarReader + ?Sized,
+{
+    type Item = Result<(Cid, Vec<u8>), CarError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let block = self.reader.next_block(&mut self.cursor).transpose()?;
+        self.failed = block.is_err();
+        Some(block)
+    }
+}
+
+/// the cid stored in more than one section.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Duplicate {
+    pub cid: Cid,
+    /// the offsets of the block data of the sections in the file order.
+    pub offsets: Vec<u64>,
+}
+
 pub trait CarReader {
     fn header(&self) -> &CarHeader;
 
-    fn sections(&self) -> Vec<Section>;
+    /// the limits of the reader, the traversals bound their pending blocks with it.
+    #[inline(always)]
+    fn limits(&self) -> ReaderLimits {
+        ReaderLimits::default()
+    }
+
+    /// the decode mode the sections were read with.
+    #[inline(always)]
+    fn decode_mode(&self) -> DecodeMode {
+        DecodeMode::default()
+    }
+
+    /// clone all the sections in the file order, all the sections are read and indexed,
+    /// prefer `blocks` for the large car files.
+    fn sections(&mut self) -> Result<Vec<Section>, CarError>;
+
+    /// the count of the sections in the car file, all the sections are read and indexed.
+    fn section_count(&mut self) -> Result<usize, CarError>;
+
+    /// read the block of the `idx`th section in the file order, the sections are indexed
+    /// as far as the `idx`. return none if the index is out of the sections.
+    fn block_at(&mut self, idx: usize) -> Result<Option<(Cid, Vec<u8>)>, CarError>;
+
+    /// read the block of the next section of the `cursor` in the file order, return none
+    /// after the last section. the section is read from the input, it's not indexed.
+    fn next_block(
+        &mut self,
+        cursor: &mut SectionCursor,
+    ) -> Result<Option<(Cid, Vec<u8>)>, CarError>;
+
+    /// the count of the block data read by the reader, a block read twice is counted twice.
+    fn blocks_read(&self) -> u64;
+
+    /// iterate the blocks in the file order, see `Blocks`.
+    #[inline(always)]
+    fn blocks(&mut self) -> Blocks<'_, Self> {
+        Blocks {
+            reader: self,
+            cursor: SectionCursor::default(),
+            failed: false,
+        }
+    }
+
+    /// the cids stored in more than one section, ordered by their first section.
+    /// the random access by the cid reads the first section.
+    fn duplicates(&mut self) -> Result<Vec<Duplicate>, CarError> {
+        let mut offsets: HashMap<Cid, Vec<u64>> = HashMap::new();
+        let mut order = Vec::new();
+        for section in self.sections()? {
+            let entry = offsets.entry(section.cid()).or_default();
+            if entry.is_empty() {
+                order.push(section.cid());
+            }
+            entry.push(section.pos());
+        }
+        Ok(order
+            .into_iter()
+            .filter_map(|cid| {
+                let offsets = offsets.remove(&cid).filter(|o| o.len() > 1)?;
+                Some(Duplicate { cid, offsets })
+            })
+            .collect())
+    }
 
     fn read_section_data(&mut self, cid: &Cid) -> Result<Vec<u8>, CarError>;
 
+    /// random access the block data by the cid with the offset index of the sections,
+    /// return none if the block is not in the car file.
+    fn get_block(&mut self, cid: &Cid) -> Result<Option<Vec<u8>>, CarError>;
+
+    /// write the block data to the `output` after checking the hash, return the bytes
+    /// written or none if the block is not in the car file. the mapped reader writes the
+    /// block from the mapping without the copy.
+    fn copy_block_to(
+        &mut self,
+        cid: &Cid,
+        output: &mut dyn Write,
+    ) -> Result<Option<usize>, CarError> {
+        let Some(data) = self.get_block(cid)? else {
+            return Ok(None);
+        };
+        check_block(cid, &data)?;
+        output.write_all(&data)?;
+        Ok(Some(data.len()))
+    }
+
+    /// the length of the block data, return none if the block is not in the car file.
+    #[inline]
+    fn block_len(&mut self, cid: &Cid) -> Result<Option<usize>, CarError> {
+        Ok(self.get_block(cid)?.map(|b| b.len()))
+    }
+
     fn ipld(&mut self, cid: &Cid) -> Result<Ipld, CarError>;
 
+    #[cfg(feature = "unixfs")]
     #[inline(always)]
     fn unixfs(&mut self, cid: &Cid) -> Result<UnixFs, CarError> {
         let fs_ipld = self.ipld(cid)?;
         (*cid, fs_ipld).try_into()
     }
 
+    #[cfg(feature = "unixfs")]
     fn search_file_cid_inner(
         &mut self,
         searchq: &mut VecDeque<Cid>,
@@ -98,6 +357,7 @@ pub trait CarReader {
         Err(CarError::NotFound(format!("search {f} fail.")))
     }
 
+    #[cfg(feature = "unixfs")]
     #[inline]
     fn search_file_cid(&mut self, f: &str) -> Result<Cid, CarError> {
         let roots = self.header().roots();
@@ -121,3 +381,60 @@ where
 {
     CarReaderV1::new(inner)
 }
+
+/// create the reader with the `limits`, the header and the sections are checked while
+/// reading, so the untrusted input can't exhaust the memory. the sections are read on
+/// demand, the errors of the sections are returned by the reads rather than the open.
+#[inline(always)]
+pub fn new_v1_with_limits<R>(inner: R, limits: ReaderLimits) -> Result<impl CarReader, CarError>
+where
+    R: Read + Seek,
+{
+    CarReaderV1::new_with_limits(inner, limits)
+}
+
+/// create the reader with the `limits` and the decode `mode`, see `DecodeMode`.
+#[inline(always)]
+pub fn new_v1_with_mode<R>(
+    inner: R,
+    limits: ReaderLimits,
+    mode: DecodeMode,
+) -> Result<impl CarReader, CarError>
+where
+    R: Read + Seek,
+{
+    CarReaderV1::new_with_mode(inner, limits, mode)
+}
+
+/// map the car file to memory and create the reader of it, see `MmapCarReaderV1`.
+///
+/// # Safety
+///
+/// the file must not be truncated or modified while the reader is alive, the block
+/// slices are the pages of the file.
+#[cfg(all(feature = "mmap", unix))]
+#[inline(always)]
+pub unsafe fn new_mmap_v1(file: &std::fs::File) -> Result<MmapCarReaderV1, CarError> {
+    MmapCarReaderV1::new(file)
+}
+
+/// create the streaming reader over the non-seekable input, the blocks are yielded one by one.
+#[inline(always)]
+pub fn new_stream_v1<R>(inner: R) -> Result<StreamCarReaderV1<R>, CarError>
+where
+    R: Read,
+{
+    StreamCarReaderV1::new(inner)
+}
+
+/// create the streaming reader with the `limits` of the header.
+#[inline(always)]
+pub fn new_stream_v1_with_limits<R>(
+    inner: R,
+    limits: ReaderLimits,
+) -> Result<StreamCarReaderV1<R>, CarError>
+where
+    R: Read,
+{
+    StreamCarReaderV1::new_with_limits(inner, limits)
+}
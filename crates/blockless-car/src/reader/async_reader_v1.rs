@@ -0,0 +1,164 @@
+use std::io::Cursor;
+
+use cid::Cid;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::{error::CarError, header::CarHeader, section::decode_block, Ipld};
+
+use super::{DecodeMode, ReaderLimits};
+
+/// read the varint from the async reader, return the value and the count of the bytes
+/// encoding it, or none at the end of stream.
+async fn read_varint<R>(reader: &mut R) -> Result<Option<(u64, usize)>, CarError>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut value: u64 = 0;
+    for i in 0..10 {
+        let b = match reader.read_u8().await {
+            Ok(b) => b,
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof && i == 0 => return Ok(None),
+            Err(e) => return Err(CarError::IO(e)),
+        };
+        value |= ((b & 0x7f) as u64) << (i * 7);
+        if b & 0x80 == 0 {
+            return Ok(Some((value, i + 1)));
+        }
+    }
+    Err(CarError::Parsing("invalid varint".into()))
+}
+
+/// read the length prefixed block, the length and the count of the bytes of its varint
+/// are checked before the block is read.
+async fn read_block_checked<R, F>(reader: &mut R, check: F) -> Result<Option<Vec<u8>>, CarError>
+where
+    R: AsyncRead + Unpin,
+    F: FnOnce(u64, usize) -> Result<(), CarError>,
+{
+    let (len, encoded) = match read_varint(reader).await? {
+        Some(varint) => varint,
+        None => return Ok(None),
+    };
+    check(len, encoded)?;
+    let len = usize::try_from(len).unwrap_or(usize::MAX);
+    // the buffer grows with the bytes read, see `read_exact_len`.
+    let mut data = Vec::new();
+    reader.take(len as u64).read_to_end(&mut data).await?;
+    if data.len() < len {
+        return Err(CarError::IO(std::io::ErrorKind::UnexpectedEof.into()));
+    }
+    Ok(Some(data))
+}
+
+/// the CARv1 reader over the `tokio::io::AsyncRead`.
+/// the input is not required to be seekable, the blocks are read one by one in the stream order.
+pub struct AsyncCarReaderV1<R> {
+    inner: R,
+    header: CarHeader,
+    limits: ReaderLimits,
+    mode: DecodeMode,
+    /// the count of the sections read.
+    sections: usize,
+    /// the count of the block data read.
+    blocks_read: u64,
+    /// the lenient mode reached the bytes not framing a section.
+    ended: bool,
+}
+
+impl<R> AsyncCarReaderV1<R>
+where
+    R: AsyncRead + Unpin,
+{
+    /// read the header from the input.
+    pub async fn new(inner: R) -> Result<Self, CarError> {
+        Self::new_with_limits(inner, ReaderLimits::default()).await
+    }
+
+    /// read the header from the input, the header and the sections are checked with the `limits`.
+    pub async fn new_with_limits(inner: R, limits: ReaderLimits) -> Result<Self, CarError> {
+        Self::new_with_mode(inner, limits, DecodeMode::default()).await
+    }
+
+    /// read the header from the input, the sections are read with the decode `mode`.
+    pub async fn new_with_mode(
+        mut inner: R,
+        limits: ReaderLimits,
+        mode: DecodeMode,
+    ) -> Result<Self, CarError> {
+        let check =
+            |len: u64, _| limits.check_header_size(usize::try_from(len).unwrap_or(usize::MAX));
+        let data = read_block_checked(&mut inner, check).await?;
+        let header = match data {
+            Some(data) => CarHeader::decode_with_limits(&data, &limits)?,
+            None => return Err(CarError::Parsing("Invalid Header".into())),
+        };
+        Ok(Self {
+            inner,
+            header,
+            limits,
+            mode,
+            sections: 0,
+            blocks_read: 0,
+            ended: false,
+        })
+    }
+
+    #[inline(always)]
+    pub fn header(&self) -> &CarHeader {
+        &self.header
+    }
+
+    /// the count of the block data read, the blocks skipped by the lenient mode count too.
+    #[inline(always)]
+    pub fn blocks_read(&self) -> u64 {
+        self.blocks_read
+    }
+
+    /// read the next block, return none at the end of stream. the lenient mode skips the
+    /// blocks of the unknown codecs and ends the stream at the bytes not framing a section.
+    pub async fn next_block(&mut self) -> Result<Option<(Cid, Vec<u8>)>, CarError> {
+        while !self.ended {
+            match self.read_block().await {
+                Ok(Some((cid, _))) if !self.mode.keep_codec(cid.codec()) => continue,
+                Err(e) if self.mode.is_end(&e) => self.ended = true,
+                rs => return rs,
+            }
+        }
+        Ok(None)
+    }
+
+    async fn read_block(&mut self) -> Result<Option<(Cid, Vec<u8>)>, CarError> {
+        let (limits, mode) = (self.limits, self.mode);
+        let check = |len, encoded| {
+            mode.check_varint(len, encoded)?;
+            limits.check_section_size(len).map(|_| ())
+        };
+        let data = match read_block_checked(&mut self.inner, check).await? {
+            Some(data) => data,
+            None => return Ok(None),
+        };
+        let mut cursor = Cursor::new(data);
+        let cid = Cid::read_bytes(&mut cursor).map_err(|e| CarError::Parsing(e.to_string()))?;
+        let pos = cursor.position() as usize;
+        limits.check_cid_len(pos)?;
+        self.sections += 1;
+        limits.check_sections(self.sections)?;
+        let mut data = cursor.into_inner();
+        data.drain(0..pos);
+        self.blocks_read += 1;
+        Ok(Some((cid, data)))
+    }
+
+    /// read the next block and decode it to ipld, return none at the end of stream.
+    pub async fn next_ipld(&mut self) -> Result<Option<(Cid, Ipld)>, CarError> {
+        match self.next_block().await? {
+            Some((cid, data)) => Ok(Some((cid, decode_block(cid, data)?))),
+            None => Ok(None),
+        }
+    }
+
+    /// return the inner reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
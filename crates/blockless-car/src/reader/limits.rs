@@ -0,0 +1,154 @@
+use crate::error::CarError;
+
+/// the default maximum count of the blocks indexed or queued in memory.
+pub const DEFAULT_MAX_BLOCKS_IN_MEMORY: usize = 1 << 22;
+
+/// the default maximum length of the encoded header, same as go-car.
+pub const DEFAULT_MAX_HEADER_SIZE: usize = 32 << 20;
+
+/// the default maximum count of the roots in the header.
+pub const DEFAULT_MAX_ROOTS: usize = 1 << 16;
+
+/// the default maximum length of a section, the cid and the block data.
+pub const DEFAULT_MAX_SECTION_SIZE: usize = crate::section::MAX_ALLOWED_SECTION_SIZE;
+
+/// the default maximum length of the encoded cid of a section.
+pub const DEFAULT_MAX_CID_LEN: usize = 256;
+
+/// the default maximum count of the sections, not limited, the readers indexing the
+/// sections are bounded by the `max_blocks_in_memory`.
+pub const DEFAULT_MAX_SECTIONS: usize = usize::MAX;
+
+/// the default maximum depth of the links followed from the root of a file DAG.
+pub const DEFAULT_MAX_LINK_DEPTH: usize = 1 << 10;
+
+/// limits of the reader and the traversals over it, bound the memory used by
+/// the untrusted CAR files. the readers check the header and the section index,
+/// the walks of `ls`, `cat` and the extraction check their pending blocks and the
+/// walks of the file DAGs check the depth of the links.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReaderLimits {
+    /// the maximum count of the blocks indexed by the reader or pending in a traversal.
+    pub max_blocks_in_memory: usize,
+    /// the maximum length in bytes of the encoded header.
+    pub max_header_size: usize,
+    /// the maximum count of the roots in the header.
+    pub max_roots: usize,
+    /// the maximum length in bytes of a section, checked before the section is allocated.
+    pub max_section_size: usize,
+    /// the maximum length in bytes of the encoded cid of a section.
+    pub max_cid_len: usize,
+    /// the maximum count of the sections read.
+    pub max_sections: usize,
+    /// the maximum depth of the links followed from the root of a file DAG.
+    pub max_link_depth: usize,
+}
+
+impl Default for ReaderLimits {
+    fn default() -> Self {
+        Self {
+            max_blocks_in_memory: DEFAULT_MAX_BLOCKS_IN_MEMORY,
+            max_header_size: DEFAULT_MAX_HEADER_SIZE,
+            max_roots: DEFAULT_MAX_ROOTS,
+            max_section_size: DEFAULT_MAX_SECTION_SIZE,
+            max_cid_len: DEFAULT_MAX_CID_LEN,
+            max_sections: DEFAULT_MAX_SECTIONS,
+            max_link_depth: DEFAULT_MAX_LINK_DEPTH,
+        }
+    }
+}
+
+impl ReaderLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_blocks_in_memory(mut self, max_blocks_in_memory: usize) -> Self {
+        self.max_blocks_in_memory = max_blocks_in_memory;
+        self
+    }
+
+    pub fn with_max_header_size(mut self, max_header_size: usize) -> Self {
+        self.max_header_size = max_header_size;
+        self
+    }
+
+    pub fn with_max_roots(mut self, max_roots: usize) -> Self {
+        self.max_roots = max_roots;
+        self
+    }
+
+    pub fn with_max_section_size(mut self, max_section_size: usize) -> Self {
+        self.max_section_size = max_section_size;
+        self
+    }
+
+    pub fn with_max_cid_len(mut self, max_cid_len: usize) -> Self {
+        self.max_cid_len = max_cid_len;
+        self
+    }
+
+    pub fn with_max_sections(mut self, max_sections: usize) -> Self {
+        self.max_sections = max_sections;
+        self
+    }
+
+    pub fn with_max_link_depth(mut self, max_link_depth: usize) -> Self {
+        self.max_link_depth = max_link_depth;
+        self
+    }
+
+    #[inline]
+    fn check(name: &'static str, value: usize, limit: usize) -> Result<(), CarError> {
+        if value > limit {
+            return Err(CarError::LimitExceeded { name, value, limit });
+        }
+        Ok(())
+    }
+
+    /// check the count of the blocks held in memory.
+    #[inline]
+    pub fn check_blocks(&self, blocks: usize) -> Result<(), CarError> {
+        Self::check("blocks in memory", blocks, self.max_blocks_in_memory)
+    }
+
+    /// check the length of the encoded header.
+    #[inline]
+    pub fn check_header_size(&self, size: usize) -> Result<(), CarError> {
+        Self::check("header size", size, self.max_header_size)
+    }
+
+    /// check the count of the roots.
+    #[inline]
+    pub fn check_roots(&self, roots: usize) -> Result<(), CarError> {
+        Self::check("roots", roots, self.max_roots)
+    }
+
+    /// check the section length read from the varint, the lengths are read as u64
+    /// so the check is the same on the 32-bit targets.
+    #[inline]
+    pub fn check_section_size(&self, len: u64) -> Result<usize, CarError> {
+        if len > self.max_section_size as u64 {
+            return Err(CarError::TooLargeSection(len));
+        }
+        Ok(len as usize)
+    }
+
+    /// check the length of the encoded cid.
+    #[inline]
+    pub fn check_cid_len(&self, len: usize) -> Result<(), CarError> {
+        Self::check("cid length", len, self.max_cid_len)
+    }
+
+    /// check the count of the sections read.
+    #[inline]
+    pub fn check_sections(&self, sections: usize) -> Result<(), CarError> {
+        Self::check("sections", sections, self.max_sections)
+    }
+
+    /// check the depth of the link followed, the root is depth 0.
+    #[inline]
+    pub fn check_link_depth(&self, depth: usize) -> Result<(), CarError> {
+        Self::check("link depth", depth, self.max_link_depth)
+    }
+}
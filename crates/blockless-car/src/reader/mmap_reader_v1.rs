@@ -0,0 +1,275 @@
+use std::{
+    fs::File,
+    io::{self, Cursor, Write},
+    ops::Deref,
+    os::fd::AsRawFd,
+    ptr, slice,
+};
+
+use cid::Cid;
+
+use crate::{
+    error::CarError,
+    header::CarHeader,
+    reader::CarReader,
+    section::{check_block, decode_block, Section},
+    Ipld,
+};
+
+use super::{CarReaderV1, DecodeMode, ReaderLimits, SectionCursor};
+
+/// the read only shared mapping of the whole file.
+pub struct Mmap {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+// the mapping is read only, the pages can be read from any thread.
+unsafe impl Send for Mmap {}
+unsafe impl Sync for Mmap {}
+
+impl Mmap {
+    /// map the whole file, the empty file is the empty mapping.
+    ///
+    /// # Safety
+    ///
+    /// the file must not be truncated or modified while mapped.
+    pub unsafe fn map(file: &File) -> io::Result<Self> {
+        let len = usize::try_from(file.metadata()?.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "the file is too large"))?;
+        if len == 0 {
+            return Ok(Self {
+                ptr: ptr::null_mut(),
+                len,
+            });
+        }
+        let ptr = libc::mmap(
+            ptr::null_mut(),
+            len,
+            libc::PROT_READ,
+            libc::MAP_SHARED,
+            file.as_raw_fd(),
+            0,
+        );
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self { ptr, len })
+    }
+}
+
+impl Deref for Mmap {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        match self.len {
+            0 => &[],
+            len => unsafe { slice::from_raw_parts(self.ptr as *const u8, len) },
+        }
+    }
+}
+
+impl AsRef<[u8]> for Mmap {
+    #[inline(always)]
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            unsafe { libc::munmap(self.ptr, self.len) };
+        }
+    }
+}
+
+/// the CARv1 reader over the memory mapped file, the blocks are borrowed from the mapping
+/// by `block_slice` and `block_slice_at` instead of read to a new `Vec` per section.
+/// the `CarReader` reads copy the slices.
+pub struct MmapCarReaderV1 {
+    inner: CarReaderV1<Cursor<Mmap>>,
+}
+
+impl MmapCarReaderV1 {
+    /// map the file and index the sections.
+    ///
+    /// # Safety
+    ///
+    /// the file must not be truncated or modified while the reader is alive.
+    pub unsafe fn new(file: &File) -> Result<Self, CarError> {
+        Self::new_with_limits(file, ReaderLimits::default())
+    }
+
+    /// map the file and index the sections with the `limits`.
+    ///
+    /// # Safety
+    ///
+    /// the file must not be truncated or modified while the reader is alive.
+    pub unsafe fn new_with_limits(file: &File, limits: ReaderLimits) -> Result<Self, CarError> {
+        Self::new_with_mode(file, limits, DecodeMode::default())
+    }
+
+    /// map the file and index the sections with the `limits` and the decode `mode`.
+    ///
+    /// # Safety
+    ///
+    /// the file must not be truncated or modified while the reader is alive.
+    pub unsafe fn new_with_mode(
+        file: &File,
+        limits: ReaderLimits,
+        mode: DecodeMode,
+    ) -> Result<Self, CarError> {
+        let mmap = Mmap::map(file)?;
+        let mut inner = CarReaderV1::new_with_mode(Cursor::new(mmap), limits, mode)?;
+        // the slices are borrowed by the shared reference, the sections are indexed up front.
+        inner.index_all()?;
+        Ok(Self { inner })
+    }
+
+    /// the block data of the cid in the mapping, return none if the block is not in the
+    /// car file. the data is not verified.
+    #[inline(always)]
+    pub fn block_slice(&self, cid: &Cid) -> Result<Option<&[u8]>, CarError> {
+        self.inner.block_slice(cid)
+    }
+
+    /// the block of the `idx`th section in the file order in the mapping,
+    /// return none if the index is out of the sections.
+    #[inline(always)]
+    pub fn block_slice_at(&self, idx: usize) -> Result<Option<(Cid, &[u8])>, CarError> {
+        self.inner.block_slice_at(idx)
+    }
+}
+
+impl CarReader for MmapCarReaderV1 {
+    #[inline(always)]
+    fn header(&self) -> &CarHeader {
+        self.inner.header()
+    }
+
+    #[inline(always)]
+    fn limits(&self) -> ReaderLimits {
+        self.inner.limits()
+    }
+
+    #[inline(always)]
+    fn decode_mode(&self) -> DecodeMode {
+        self.inner.decode_mode()
+    }
+
+    #[inline(always)]
+    fn sections(&mut self) -> Result<Vec<Section>, CarError> {
+        self.inner.sections()
+    }
+
+    #[inline(always)]
+    fn section_count(&mut self) -> Result<usize, CarError> {
+        self.inner.section_count()
+    }
+
+    #[inline]
+    fn block_at(&mut self, idx: usize) -> Result<Option<(Cid, Vec<u8>)>, CarError> {
+        let block = self.block_slice_at(idx)?;
+        Ok(block.map(|(cid, data)| (cid, data.to_vec())))
+    }
+
+    #[inline(always)]
+    fn next_block(
+        &mut self,
+        cursor: &mut SectionCursor,
+    ) -> Result<Option<(Cid, Vec<u8>)>, CarError> {
+        self.inner.next_block(cursor)
+    }
+
+    #[inline(always)]
+    fn blocks_read(&self) -> u64 {
+        self.inner.blocks_read()
+    }
+
+    #[inline]
+    fn read_section_data(&mut self, cid: &Cid) -> Result<Vec<u8>, CarError> {
+        match self.block_slice(cid)? {
+            Some(data) => Ok(data.to_vec()),
+            None => Err(CarError::InvalidSection("cid not exist".into())),
+        }
+    }
+
+    #[inline]
+    fn get_block(&mut self, cid: &Cid) -> Result<Option<Vec<u8>>, CarError> {
+        Ok(self.block_slice(cid)?.map(<[u8]>::to_vec))
+    }
+
+    #[inline]
+    fn block_len(&mut self, cid: &Cid) -> Result<Option<usize>, CarError> {
+        self.inner.block_len(cid)
+    }
+
+    fn copy_block_to(
+        &mut self,
+        cid: &Cid,
+        output: &mut dyn Write,
+    ) -> Result<Option<usize>, CarError> {
+        let Some(data) = self.block_slice(cid)? else {
+            return Ok(None);
+        };
+        check_block(cid, data)?;
+        output.write_all(data)?;
+        Ok(Some(data.len()))
+    }
+
+    #[inline]
+    fn ipld(&mut self, cid: &Cid) -> Result<Ipld, CarError> {
+        match self.block_slice(cid)? {
+            Some(data) => decode_block(*cid, data.to_vec()),
+            None => Err(CarError::NotFound("cid not exist".into())),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "writer"))]
+mod test {
+    use super::*;
+    use crate::reader;
+
+    #[test]
+    fn test_mmap_reader() {
+        let path = std::path::Path::new("test").join("carv1-basic.car");
+        let file = File::open(&path).unwrap();
+        let mut mapped = unsafe { MmapCarReaderV1::new(&file).unwrap() };
+        let mut reader = reader::new_v1(File::open(&path).unwrap()).unwrap();
+        assert_eq!(mapped.header().roots(), reader.header().roots());
+        assert_eq!(
+            mapped.section_count().unwrap(),
+            reader.section_count().unwrap()
+        );
+        for (idx, s) in reader.sections().unwrap().iter().enumerate() {
+            let data = reader.get_block(&s.cid()).unwrap().unwrap();
+            assert_eq!(mapped.block_slice(&s.cid()).unwrap().unwrap(), &data[..]);
+            let (cid, slice) = mapped.block_slice_at(idx).unwrap().unwrap();
+            assert_eq!((cid, slice), (s.cid(), &data[..]));
+            assert_eq!(
+                mapped.ipld(&s.cid()).unwrap(),
+                reader.ipld(&s.cid()).unwrap()
+            );
+            let mut out = vec![];
+            assert_eq!(
+                mapped.copy_block_to(&s.cid(), &mut out).unwrap(),
+                Some(data.len())
+            );
+            assert_eq!(out, data);
+        }
+        assert!(mapped
+            .block_slice_at(reader.section_count().unwrap())
+            .unwrap()
+            .is_none());
+        let missing = crate::utils::raw_cid(b"missing", multicodec::Codec::Sha2_256);
+        assert!(mapped.block_slice(&missing).unwrap().is_none());
+
+        // the empty file is mapped but has no header.
+        let temp_dir = tempdir::TempDir::new("blockless-car-temp-dir").unwrap();
+        let empty = File::create(temp_dir.path().join("empty.car")).unwrap();
+        assert!(unsafe { MmapCarReaderV1::new(&empty) }.is_err());
+    }
+}
@@ -0,0 +1,54 @@
+use integer_encoding::VarInt;
+
+use crate::{codec, error::CarError};
+
+/// how the readers treat the input not following the CARv1 spec.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DecodeMode {
+    /// validate the input: the non-canonical section length varints and the bytes after
+    /// the last section are errors, the blocks of the unknown codecs fail to decode.
+    #[default]
+    Strict,
+    /// read the input best-effort: the sections of the codecs neither built in nor
+    /// registered are skipped, and the reading stops at the bytes not framing a section,
+    /// e.g. the trailing garbage or the truncated last section. the custom codecs must be
+    /// registered before the reader is created.
+    Lenient,
+}
+
+impl DecodeMode {
+    #[inline(always)]
+    pub fn is_lenient(&self) -> bool {
+        matches!(self, DecodeMode::Lenient)
+    }
+
+    /// the section of the codec `code` is read, the lenient mode skips the unknown codecs.
+    #[inline]
+    pub(crate) fn keep_codec(&self, code: u64) -> bool {
+        !self.is_lenient() || codec::is_known_codec(code)
+    }
+
+    /// check the section length `len` was encoded in the `encoded` bytes, the strict mode
+    /// rejects the varints longer than the minimal encoding.
+    #[inline]
+    pub(crate) fn check_varint(&self, len: u64, encoded: usize) -> Result<(), CarError> {
+        if !self.is_lenient() && len.required_space() != encoded {
+            return Err(CarError::Parsing(format!(
+                "non-canonical varint of the section length {len}"
+            )));
+        }
+        Ok(())
+    }
+
+    /// the error reading the section is the end of the input, the lenient mode stops at
+    /// the bytes not framing a section instead of failing.
+    #[inline]
+    pub(crate) fn is_end(&self, err: &CarError) -> bool {
+        self.is_lenient()
+            && match err {
+                CarError::Parsing(_) | CarError::TooLargeSection(_) => true,
+                CarError::IO(e) => e.kind() == std::io::ErrorKind::UnexpectedEof,
+                _ => false,
+            }
+    }
+}
@@ -1,36 +1,190 @@
 #![allow(unused)]
 use cid::Cid;
 
-use crate::{error::CarError, header::CarHeader, reader::CarReader, section::Section, Ipld};
+use crate::{
+    error::CarError,
+    header::CarHeader,
+    reader::CarReader,
+    section::{decode_block, write_section_header, Section},
+    Ipld,
+};
 use std::{
     collections::HashMap,
-    io::{Read, Seek},
+    io::{self, Cursor, Read, Seek, Write},
+    sync::atomic::{AtomicU64, Ordering},
 };
 
-use super::read_section;
+use super::{next_section, read_section, DecodeMode, ReaderLimits, SectionCursor};
 
 pub(crate) struct CarReaderV1<R> {
     inner: R,
-    sections: HashMap<Cid, Section>,
+    /// the sections in the file order indexed so far, the sections are read on demand as
+    /// far as the lookups need, the walks of the blocks in the file order don't index them.
+    sections: Vec<Section>,
+    /// the index of the sections by the cid, the first section of the duplicated cid wins.
+    index: HashMap<Cid, usize>,
+    /// the read of the sections indexed.
+    scan: SectionCursor,
+    /// the offset of the first section after the header.
+    start: u64,
+    /// the count of the block data read, the borrowed slices count too.
+    blocks_read: AtomicU64,
     header: CarHeader,
+    limits: ReaderLimits,
+    mode: DecodeMode,
 }
 
 impl<R> CarReaderV1<R>
 where
     R: Read + Seek,
 {
-    pub(crate) fn new(mut inner: R) -> Result<Self, CarError> {
-        let header = CarHeader::read_header(&mut inner)?;
-        let mut sections = HashMap::new();
-        while let Some(section) = read_section(&mut inner)? {
-            sections.insert(section.cid(), section);
-        }
+    pub(crate) fn new(inner: R) -> Result<Self, CarError> {
+        Self::new_with_limits(inner, ReaderLimits::default())
+    }
+
+    pub(crate) fn new_with_limits(inner: R, limits: ReaderLimits) -> Result<Self, CarError> {
+        Self::new_with_mode(inner, limits, DecodeMode::default())
+    }
+
+    /// read the header, the sections are read on demand.
+    pub(crate) fn new_with_mode(
+        mut inner: R,
+        limits: ReaderLimits,
+        mode: DecodeMode,
+    ) -> Result<Self, CarError> {
+        let header = CarHeader::read_header_with_limits(&mut inner, &limits)?;
+        let start = inner.stream_position()?;
         Ok(Self {
             inner,
             header,
-            sections,
+            sections: Vec::new(),
+            index: HashMap::new(),
+            scan: SectionCursor::default(),
+            blocks_read: AtomicU64::new(0),
+            start,
+            limits,
+            mode,
         })
     }
+
+    /// read and index the next section, return false after the last section.
+    fn index_next(&mut self) -> Result<bool, CarError> {
+        let section = next_section(
+            &mut self.inner,
+            &mut self.scan,
+            self.start,
+            &self.limits,
+            self.mode,
+        )?;
+        let Some(section) = section else {
+            return Ok(false);
+        };
+        self.limits.check_blocks(self.sections.len() + 1)?;
+        self.index
+            .entry(section.cid())
+            .or_insert(self.sections.len());
+        self.sections.push(section);
+        Ok(true)
+    }
+
+    /// read and index all the sections.
+    pub(crate) fn index_all(&mut self) -> Result<(), CarError> {
+        while self.index_next()? {}
+        Ok(())
+    }
+
+    /// the section of the cid, the sections are indexed until the cid is found.
+    fn section(&mut self, cid: &Cid) -> Result<Option<Section>, CarError> {
+        loop {
+            if let Some(i) = self.index.get(cid) {
+                return Ok(Some(self.sections[*i].clone()));
+            }
+            if !self.index_next()? {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// the `idx`th section in the file order, the sections are indexed as far as the `idx`.
+    fn section_at(&mut self, idx: usize) -> Result<Option<Section>, CarError> {
+        while self.sections.len() <= idx {
+            if !self.index_next()? {
+                return Ok(None);
+            }
+        }
+        Ok(Some(self.sections[idx].clone()))
+    }
+
+    /// read the block data of the section.
+    fn read_data(&mut self, section: &Section) -> Result<Vec<u8>, CarError> {
+        let data = section.read_data(&mut self.inner)?;
+        self.blocks_read.fetch_add(1, Ordering::Relaxed);
+        Ok(data)
+    }
+
+    #[inline]
+    pub(crate) fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R> CarReaderV1<R>
+where
+    R: Read + Write + Seek,
+{
+    /// append the section of the block to the end of the CAR and index it,
+    /// the block already in the CAR is not written again.
+    pub(crate) fn append_block(&mut self, cid: Cid, data: &[u8]) -> Result<(), CarError> {
+        self.index_all()?;
+        if self.index.contains_key(&cid) {
+            return Ok(());
+        }
+        self.limits.check_sections(self.sections.len() + 1)?;
+        self.limits.check_blocks(self.sections.len() + 1)?;
+        self.inner.seek(io::SeekFrom::End(0))?;
+        write_section_header(&mut self.inner, &cid, data.len())?;
+        let pos = self.inner.stream_position()?;
+        self.inner.write_all(data)?;
+        self.index.insert(cid, self.sections.len());
+        self.sections.push(Section::new(cid, pos, data.len()));
+        Ok(())
+    }
+}
+
+impl<T> CarReaderV1<Cursor<T>>
+where
+    T: AsRef<[u8]>,
+{
+    /// the block data of the section in the buffer, the section cut off by the end
+    /// of the buffer is the `UnexpectedEof` of the read.
+    fn slice(&self, section: &Section) -> Result<&[u8], CarError> {
+        let buf = self.inner.get_ref().as_ref();
+        let start = usize::try_from(section.pos()).unwrap_or(usize::MAX);
+        match buf.get(start..).and_then(|b| b.get(..section.len())) {
+            Some(data) => {
+                self.blocks_read.fetch_add(1, Ordering::Relaxed);
+                Ok(data)
+            }
+            None => Err(CarError::IO(io::ErrorKind::UnexpectedEof.into())),
+        }
+    }
+
+    /// the block data of the cid borrowed from the buffer, the sections not indexed yet
+    /// are not found, see `index_all`.
+    pub(crate) fn block_slice(&self, cid: &Cid) -> Result<Option<&[u8]>, CarError> {
+        match self.index.get(cid) {
+            Some(i) => self.slice(&self.sections[*i]).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// the block of the `idx`th section borrowed from the buffer.
+    pub(crate) fn block_slice_at(&self, idx: usize) -> Result<Option<(Cid, &[u8])>, CarError> {
+        match self.sections.get(idx) {
+            Some(s) => Ok(Some((s.cid(), self.slice(s)?))),
+            None => Ok(None),
+        }
+    }
 }
 
 impl<R> CarReader for CarReaderV1<R>
@@ -43,34 +197,86 @@ where
     }
 
     #[inline(always)]
-    fn sections(&self) -> Vec<Section> {
-        self.sections.values().map(Section::clone).collect()
+    fn limits(&self) -> ReaderLimits {
+        self.limits
+    }
+
+    #[inline(always)]
+    fn decode_mode(&self) -> DecodeMode {
+        self.mode
+    }
+
+    #[inline]
+    fn sections(&mut self) -> Result<Vec<Section>, CarError> {
+        self.index_all()?;
+        Ok(self.sections.clone())
+    }
+
+    #[inline]
+    fn section_count(&mut self) -> Result<usize, CarError> {
+        self.index_all()?;
+        Ok(self.sections.len())
+    }
+
+    #[inline]
+    fn block_at(&mut self, idx: usize) -> Result<Option<(Cid, Vec<u8>)>, CarError> {
+        match self.section_at(idx)? {
+            Some(s) => Ok(Some((s.cid(), self.read_data(&s)?))),
+            None => Ok(None),
+        }
+    }
+
+    fn next_block(
+        &mut self,
+        cursor: &mut SectionCursor,
+    ) -> Result<Option<(Cid, Vec<u8>)>, CarError> {
+        let section = next_section(&mut self.inner, cursor, self.start, &self.limits, self.mode)?;
+        match section {
+            Some(s) => Ok(Some((s.cid(), self.read_data(&s)?))),
+            None => Ok(None),
+        }
     }
 
     #[inline]
     fn read_section_data(&mut self, cid: &Cid) -> Result<Vec<u8>, CarError> {
         let s = self
-            .sections
-            .get(cid)
+            .section(cid)?
             .ok_or(CarError::InvalidSection("cid not exist".into()))?;
-        s.read_data(&mut self.inner)
+        self.read_data(&s)
+    }
+
+    #[inline]
+    fn get_block(&mut self, cid: &Cid) -> Result<Option<Vec<u8>>, CarError> {
+        match self.section(cid)? {
+            Some(s) => self.read_data(&s).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    #[inline]
+    fn block_len(&mut self, cid: &Cid) -> Result<Option<usize>, CarError> {
+        Ok(self.section(cid)?.map(|s| s.len()))
     }
 
     #[inline]
     fn ipld(&mut self, cid: &Cid) -> Result<Ipld, CarError> {
         let s = self
-            .sections
-            .get_mut(cid)
+            .section(cid)?
             .ok_or(CarError::NotFound("cid not exist".into()))?;
-        s.ipld(&mut self.inner)
+        decode_block(s.cid(), self.read_data(&s)?)
+    }
+
+    #[inline(always)]
+    fn blocks_read(&self) -> u64 {
+        self.blocks_read.load(Ordering::Relaxed)
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "unixfs", feature = "writer"))]
 mod test {
 
     use super::*;
-    use crate::unixfs::UnixFs;
+    use crate::{unixfs::UnixFs, writer::CarWriter};
 
     #[test]
     fn test_read() {
@@ -79,7 +285,7 @@ mod test {
         let file = std::fs::File::open(file).unwrap();
         let mut reader = CarReaderV1::new(file).unwrap();
         let roots = reader.header().roots();
-        assert_eq!(reader.sections().len(), 6);
+        assert_eq!(reader.sections().unwrap().len(), 6);
         for r in roots.iter() {
             let s_ipld = reader.ipld(r).unwrap();
             let unix_fs: Result<UnixFs, CarError> = s_ipld.try_into();
@@ -89,4 +295,282 @@ mod test {
         let rs = reader.search_file_cid("not-distributed.jpg");
         println!("{rs:?}");
     }
+
+    #[test]
+    fn test_get_block() {
+        let path = std::path::Path::new("test").join("carv1-basic.car");
+        let open = || CarReaderV1::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let sections = open().sections().unwrap();
+        // the open reads no section, the lookup reads the sections as far as the block.
+        let mut reader = open();
+        assert!(reader.sections.is_empty());
+        let data = reader.get_block(&sections[1].cid()).unwrap().unwrap();
+        assert_eq!((data.len(), reader.sections.len()), (sections[1].len(), 2));
+        // random access the blocks in the reverse order.
+        for s in sections.iter().rev() {
+            let data = reader.get_block(&s.cid()).unwrap().unwrap();
+            assert_eq!(data, reader.read_section_data(&s.cid()).unwrap());
+            assert_eq!(data.len(), s.len());
+        }
+        let missing = crate::utils::raw_cid(b"missing", multicodec::Codec::Sha2_256);
+        assert_eq!(reader.get_block(&missing).unwrap(), None);
+        // the blocks read are counted by the reader, not by the other readers.
+        assert_eq!(reader.blocks_read(), 1 + 2 * sections.len() as u64);
+        assert_eq!(open().blocks_read(), 0);
+    }
+
+    #[test]
+    fn test_blocks() {
+        let file = std::path::Path::new("test").join("carv1-basic.car");
+        let file = std::fs::File::open(file).unwrap();
+        let mut reader = CarReaderV1::new(file).unwrap();
+        // the iteration reads the sections on demand without indexing them.
+        let blocks: Vec<_> = reader.blocks().map(Result::unwrap).collect();
+        assert!(reader.sections.is_empty());
+        let sections = reader.sections().unwrap();
+        assert!(sections.windows(2).all(|w| w[0].pos() < w[1].pos()));
+        assert_eq!(blocks.len(), sections.len());
+        for (s, (cid, data)) in sections.iter().zip(blocks) {
+            assert_eq!(s.cid(), cid);
+            assert_eq!(data.len(), s.len());
+        }
+        assert!(reader.block_at(sections.len()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_read_section() {
+        let cid = crate::utils::raw_cid(b"hello", multicodec::Codec::Sha2_256);
+        let mut data = cid.to_bytes();
+        data.extend_from_slice(b"hello");
+        let mut buf = vec![data.len() as u8];
+        buf.extend_from_slice(&data);
+        let mut cursor = std::io::Cursor::new(buf);
+        let limits = ReaderLimits::default();
+        let section = read_section(&mut cursor, &limits, DecodeMode::Strict)
+            .unwrap()
+            .unwrap();
+        assert_eq!(section.cid(), cid);
+        assert_eq!(section.pos(), 1 + cid.encoded_len() as u64);
+        assert_eq!(section.len(), 5);
+        assert_eq!(section.read_data(&mut cursor).unwrap(), b"hello");
+        assert!(read_section(&mut cursor, &limits, DecodeMode::Strict)
+            .unwrap()
+            .is_none());
+
+        // the length is shorter than the cid.
+        let mut cursor = std::io::Cursor::new(vec![3, 1, 0x55, 0x12]);
+        assert!(read_section(&mut cursor, &limits, DecodeMode::Strict).is_err());
+    }
+
+    #[test]
+    fn test_decode_mode() {
+        let hello = crate::utils::raw_cid(b"hello", multicodec::Codec::Sha2_256);
+        // the private-use codec not built in nor registered.
+        let unknown = Cid::new_v1(0x300099, *hello.hash());
+        let header = CarHeader::new_v1(vec![hello]).encode().unwrap();
+        let mut car = vec![header.len() as u8];
+        car.extend_from_slice(&header);
+        for cid in [hello, unknown] {
+            let cid = cid.to_bytes();
+            car.push((cid.len() + 5) as u8);
+            car.extend_from_slice(&cid);
+            car.extend_from_slice(b"hello");
+        }
+        let open = |car: &[u8], mode| {
+            CarReaderV1::new_with_mode(Cursor::new(car.to_vec()), ReaderLimits::new(), mode)
+        };
+        let mut reader = open(&car, DecodeMode::Strict).unwrap();
+        assert_eq!(reader.section_count().unwrap(), 2);
+        assert!(matches!(
+            reader.ipld(&unknown),
+            Err(CarError::NotImplemented(_))
+        ));
+        let mut reader = open(&car, DecodeMode::Lenient).unwrap();
+        assert_eq!(reader.section_count().unwrap(), 1);
+        assert_eq!(reader.decode_mode(), DecodeMode::Lenient);
+        // the skipped sections count for the limit.
+        let limits = ReaderLimits::new().with_max_sections(1);
+        let mut reader =
+            CarReaderV1::new_with_mode(Cursor::new(car.clone()), limits, DecodeMode::Lenient)
+                .unwrap();
+        assert!(matches!(
+            reader.section_count(),
+            Err(CarError::LimitExceeded { .. })
+        ));
+
+        // the trailing garbage, the blocks before it are read and the iteration ends after
+        // the error.
+        let mut damaged = car.clone();
+        damaged.extend_from_slice(&[0, 0xff]);
+        let mut reader = open(&damaged, DecodeMode::Strict).unwrap();
+        assert!(reader.section_count().is_err());
+        let blocks: Vec<_> = reader.blocks().collect();
+        assert_eq!(blocks.len(), 3);
+        assert!(blocks[..2].iter().all(Result::is_ok) && blocks[2].is_err());
+        let mut reader = open(&damaged, DecodeMode::Lenient).unwrap();
+        assert_eq!(reader.section_count().unwrap(), 1);
+
+        // the last section cut off by the end of the input.
+        let world = crate::utils::raw_cid(b"world", multicodec::Codec::Sha2_256);
+        let mut truncated = car.clone();
+        truncated.push((world.encoded_len() + 5) as u8);
+        truncated.extend_from_slice(&world.to_bytes());
+        truncated.extend_from_slice(b"wo");
+        let mut reader = open(&truncated, DecodeMode::Strict).unwrap();
+        assert!(reader.get_block(&world).is_err());
+        let mut reader = open(&truncated, DecodeMode::Lenient).unwrap();
+        assert_eq!(reader.get_block(&world).unwrap(), None);
+
+        // the section length varint with a redundant continuation byte.
+        let cid = hello.to_bytes();
+        let mut padded = vec![header.len() as u8];
+        padded.extend_from_slice(&header);
+        padded.extend_from_slice(&[(cid.len() + 5) as u8 | 0x80, 0]);
+        padded.extend_from_slice(&cid);
+        padded.extend_from_slice(b"hello");
+        assert!(matches!(
+            open(&padded, DecodeMode::Strict).unwrap().section_count(),
+            Err(CarError::Parsing(_))
+        ));
+        let mut reader = open(&padded, DecodeMode::Lenient).unwrap();
+        assert_eq!(reader.get_block(&hello).unwrap().unwrap(), b"hello");
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let hello = crate::utils::raw_cid(b"hello", multicodec::Codec::Sha2_256);
+        let world = crate::utils::raw_cid(b"world", multicodec::Codec::Sha2_256);
+        // the writer skips the blocks written, write the sections by hand.
+        let header = CarHeader::new_v1(vec![hello]).encode().unwrap();
+        let mut buf = vec![header.len() as u8];
+        buf.extend_from_slice(&header);
+        let mut offsets = Vec::new();
+        for (cid, data) in [(hello, b"hello"), (world, b"world"), (hello, b"hello")] {
+            let cid = cid.to_bytes();
+            buf.push((cid.len() + data.len()) as u8);
+            buf.extend_from_slice(&cid);
+            offsets.push(buf.len() as u64);
+            buf.extend_from_slice(data);
+        }
+        let mut reader = CarReaderV1::new(std::io::Cursor::new(buf)).unwrap();
+        assert_eq!(reader.section_count().unwrap(), 3);
+        let duplicates = reader.duplicates().unwrap();
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].cid, hello);
+        assert_eq!(duplicates[0].offsets, vec![offsets[0], offsets[2]]);
+
+        let file = std::path::Path::new("test").join("carv1-basic.car");
+        let mut reader = CarReaderV1::new(std::fs::File::open(file).unwrap()).unwrap();
+        assert!(reader.duplicates().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_reader_limits() {
+        let path = std::path::Path::new("test").join("carv1-basic.car");
+        let open =
+            |limits| CarReaderV1::new_with_limits(std::fs::File::open(&path).unwrap(), limits);
+        // the sections are checked when they are read.
+        let index = |limits| open(limits).and_then(|mut r| r.section_count().map(|_| r));
+        let exceeded = |r: Result<CarReaderV1<_>, CarError>| match r {
+            Err(CarError::LimitExceeded { name, .. }) => name,
+            _ => panic!("the limit is not checked"),
+        };
+        let limits = ReaderLimits::new();
+        assert_eq!(exceeded(open(limits.with_max_roots(0))), "roots");
+        assert_eq!(
+            exceeded(open(limits.with_max_header_size(16))),
+            "header size"
+        );
+        assert_eq!(
+            exceeded(index(limits.with_max_blocks_in_memory(5))),
+            "blocks in memory"
+        );
+        assert_eq!(exceeded(index(limits.with_max_sections(5))), "sections");
+        assert_eq!(exceeded(index(limits.with_max_cid_len(8))), "cid length");
+        assert!(matches!(
+            index(limits.with_max_section_size(16)),
+            Err(CarError::TooLargeSection(_))
+        ));
+        // the iteration holds no block, only the sections read are limited.
+        let mut reader = open(limits.with_max_blocks_in_memory(1)).unwrap();
+        assert_eq!(reader.blocks().filter(Result::is_ok).count(), 6);
+        let mut reader = open(limits.with_max_sections(5)).unwrap();
+        assert!(reader.blocks().last().unwrap().is_err());
+
+        let mut reader = open(limits.with_max_blocks_in_memory(6)).unwrap();
+        assert_eq!(reader.section_count().unwrap(), 6);
+        // the traversal keeps the pending blocks under the limit too.
+        #[cfg(feature = "cli-utils")]
+        {
+            reader.limits.max_blocks_in_memory = 1;
+            let err = crate::utils::list_call(&mut reader, |_, _| {}).unwrap_err();
+            assert!(matches!(err, CarError::LimitExceeded { .. }));
+        }
+    }
+
+    #[test]
+    fn test_malformed_input() {
+        // the header claims 1GiB, rejected before the allocation.
+        let header = vec![0x80, 0x80, 0x80, 0x80, 0x04];
+        assert!(matches!(
+            CarReaderV1::new(std::io::Cursor::new(header)),
+            Err(CarError::LimitExceeded { .. })
+        ));
+
+        let cid = crate::utils::raw_cid(b"hello", multicodec::Codec::Sha2_256);
+        let mut buf = std::io::Cursor::new(vec![]);
+        {
+            let header = CarHeader::new_v1(vec![cid]);
+            let mut writer = crate::writer::new_v1(&mut buf, header).unwrap();
+            writer.write_block(cid, b"corrupted").unwrap();
+            writer.flush().unwrap();
+        }
+        let buf = buf.into_inner();
+        let mut reader = CarReaderV1::new(std::io::Cursor::new(buf.clone())).unwrap();
+        assert!(matches!(
+            reader.ipld(&cid),
+            Err(CarError::InvalidSection(_))
+        ));
+
+        // the truncated section is reported by the read.
+        let mut reader = CarReaderV1::new(std::io::Cursor::new(&buf[..buf.len() - 2])).unwrap();
+        assert!(reader.read_section_data(&cid).is_err());
+    }
+
+    #[test]
+    fn test_read_over_4gib() {
+        use integer_encoding::VarInt;
+        use std::io::{Seek, SeekFrom, Write};
+
+        // the sparse file of the 32MiB sections of zeros, only the framings are written.
+        let temp_dir = tempdir::TempDir::new("blockless-car-temp-dir").unwrap();
+        let path = temp_dir.path().join("large.car");
+        let mut file = std::fs::File::create(&path).unwrap();
+        let hello = crate::utils::raw_cid(b"hello", multicodec::Codec::Sha2_256);
+        let header = CarHeader::new_v1(vec![hello]).encode().unwrap();
+        file.write_all(&header.len().encode_var_vec()).unwrap();
+        file.write_all(&header).unwrap();
+        let cid_len = hello.to_bytes().len();
+        let zeros = vec![0u8; crate::section::MAX_ALLOWED_SECTION_SIZE - cid_len];
+        let zeros_cid = crate::utils::raw_cid(&zeros, multicodec::Codec::Sha2_256);
+        let mut pos = file.stream_position().unwrap();
+        while pos <= u32::MAX as u64 {
+            file.seek(SeekFrom::Start(pos)).unwrap();
+            let prefix = crate::section::MAX_ALLOWED_SECTION_SIZE.encode_var_vec();
+            file.write_all(&prefix).unwrap();
+            file.write_all(&zeros_cid.to_bytes()).unwrap();
+            pos += (prefix.len() + crate::section::MAX_ALLOWED_SECTION_SIZE) as u64;
+        }
+        file.seek(SeekFrom::Start(pos)).unwrap();
+        file.write_all(&(cid_len + 5).encode_var_vec()).unwrap();
+        file.write_all(&hello.to_bytes()).unwrap();
+        file.write_all(b"hello").unwrap();
+        drop(file);
+
+        let mut reader = CarReaderV1::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let last = reader.sections().unwrap().pop().unwrap();
+        assert!(last.pos() > u32::MAX as u64);
+        assert_eq!(reader.get_block(&hello).unwrap().unwrap(), b"hello");
+        assert_eq!(reader.block_len(&zeros_cid).unwrap(), Some(zeros.len()));
+    }
 }
@@ -0,0 +1,224 @@
+use std::io::Read;
+
+use cid::Cid;
+
+use crate::{
+    error::CarError,
+    header::CarHeader,
+    section::{decode_block, read_section_header_checked},
+    Ipld,
+};
+
+use super::{read_exact_len, DecodeMode, ReaderLimits};
+
+/// the CARv1 reader over the plain `Read`, e.g. the stdin or the socket.
+/// the input is not required to be seekable, the blocks are read one by one in the stream order.
+pub struct StreamCarReaderV1<R> {
+    inner: R,
+    header: CarHeader,
+    limits: ReaderLimits,
+    mode: DecodeMode,
+    /// the count of the sections read.
+    sections: usize,
+    /// the count of the block data read.
+    blocks_read: u64,
+    /// the lenient mode reached the bytes not framing a section.
+    ended: bool,
+}
+
+impl<R> StreamCarReaderV1<R>
+where
+    R: Read,
+{
+    /// read the header from the input.
+    pub fn new(inner: R) -> Result<Self, CarError> {
+        Self::new_with_limits(inner, ReaderLimits::default())
+    }
+
+    /// read the header from the input, the header and the sections are checked with the `limits`.
+    pub fn new_with_limits(inner: R, limits: ReaderLimits) -> Result<Self, CarError> {
+        Self::new_with_mode(inner, limits, DecodeMode::default())
+    }
+
+    /// read the header from the input, the sections are read with the decode `mode`.
+    pub fn new_with_mode(
+        mut inner: R,
+        limits: ReaderLimits,
+        mode: DecodeMode,
+    ) -> Result<Self, CarError> {
+        let header = CarHeader::read_header_with_limits(&mut inner, &limits)?;
+        Ok(Self {
+            inner,
+            header,
+            limits,
+            mode,
+            sections: 0,
+            blocks_read: 0,
+            ended: false,
+        })
+    }
+
+    #[inline(always)]
+    pub fn header(&self) -> &CarHeader {
+        &self.header
+    }
+
+    /// the count of the block data read, the blocks skipped by the lenient mode count too.
+    #[inline(always)]
+    pub fn blocks_read(&self) -> u64 {
+        self.blocks_read
+    }
+
+    /// read the next block, return none at the end of stream. the lenient mode skips the
+    /// blocks of the unknown codecs and ends the stream at the bytes not framing a section.
+    pub fn next_block(&mut self) -> Result<Option<(Cid, Vec<u8>)>, CarError> {
+        while !self.ended {
+            match self.read_block() {
+                Ok(Some((cid, _))) if !self.mode.keep_codec(cid.codec()) => continue,
+                Err(e) if self.mode.is_end(&e) => self.ended = true,
+                rs => return rs,
+            }
+        }
+        Ok(None)
+    }
+
+    fn read_block(&mut self) -> Result<Option<(Cid, Vec<u8>)>, CarError> {
+        let (limits, mode) = (self.limits, self.mode);
+        let check = |len, encoded| {
+            mode.check_varint(len, encoded)?;
+            limits.check_section_size(len)
+        };
+        let (cid, len) = match read_section_header_checked(&mut self.inner, check)? {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+        limits.check_cid_len(cid.encoded_len())?;
+        self.sections += 1;
+        limits.check_sections(self.sections)?;
+        let data = read_exact_len(&mut self.inner, len)?;
+        self.blocks_read += 1;
+        Ok(Some((cid, data)))
+    }
+
+    /// read the next block and decode it to ipld, return none at the end of stream.
+    pub fn next_ipld(&mut self) -> Result<Option<(Cid, Ipld)>, CarError> {
+        match self.next_block()? {
+            Some((cid, data)) => Ok(Some((cid, decode_block(cid, data)?))),
+            None => Ok(None),
+        }
+    }
+
+    /// return the inner reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R> Iterator for StreamCarReaderV1<R>
+where
+    R: Read,
+{
+    type Item = Result<(Cid, Vec<u8>), CarError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_block().transpose()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::reader::{self, CarReader};
+
+    /// the reader only implements `Read` like the pipe.
+    struct Pipe<R>(R);
+
+    impl<R: Read> Read for Pipe<R> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    #[test]
+    fn test_stream_read() {
+        let path = std::path::Path::new("test").join("carv1-basic.car");
+        let file = std::fs::File::open(&path).unwrap();
+        let mut stream = StreamCarReaderV1::new(Pipe(file)).unwrap();
+        let mut reader = reader::new_v1(std::fs::File::open(&path).unwrap()).unwrap();
+        assert_eq!(stream.header().roots(), reader.header().roots());
+        let mut count = 0;
+        for block in stream.by_ref() {
+            let (cid, data) = block.unwrap();
+            assert_eq!(data, reader.read_section_data(&cid).unwrap());
+            count += 1;
+        }
+        assert_eq!(count, reader.sections().unwrap().len());
+        assert!(stream.next().is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "writer")]
+    fn test_stream_read_limits() {
+        let cid = crate::utils::raw_cid(b"hello", multicodec::Codec::Sha2_256);
+        let header = CarHeader::new_v1(vec![cid]).encode().unwrap();
+        let mut car = vec![header.len() as u8];
+        car.extend_from_slice(&header);
+        // the section claims 16MiB but the input is truncated after the cid.
+        car.extend_from_slice(&[0x80, 0x80, 0x80, 0x08]);
+        car.extend_from_slice(&cid.to_bytes());
+
+        let mut stream = StreamCarReaderV1::new(Pipe(&car[..])).unwrap();
+        match stream.next_block() {
+            Err(CarError::IO(e)) => assert_eq!(e.kind(), std::io::ErrorKind::UnexpectedEof),
+            rs => panic!("unexpected result {rs:?}"),
+        }
+
+        let limits = ReaderLimits::new().with_max_section_size(1 << 20);
+        let mut stream = StreamCarReaderV1::new_with_limits(Pipe(&car[..]), limits).unwrap();
+        assert!(matches!(
+            stream.next_block(),
+            Err(CarError::TooLargeSection(_))
+        ));
+
+        let path = std::path::Path::new("test").join("carv1-basic.car");
+        let limits = ReaderLimits::new().with_max_sections(2);
+        let file = std::fs::File::open(&path).unwrap();
+        let stream = StreamCarReaderV1::new_with_limits(Pipe(file), limits).unwrap();
+        let results: Vec<_> = stream.collect();
+        assert!(results[..2].iter().all(|r| r.is_ok()));
+        assert!(matches!(
+            results[2],
+            Err(CarError::LimitExceeded {
+                name: "sections",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "writer")]
+    fn test_stream_decode_mode() {
+        let hello = crate::utils::raw_cid(b"hello", multicodec::Codec::Sha2_256);
+        let unknown = Cid::new_v1(0x300099, *hello.hash());
+        let header = CarHeader::new_v1(vec![hello]).encode().unwrap();
+        let mut car = vec![header.len() as u8];
+        car.extend_from_slice(&header);
+        for cid in [unknown, hello] {
+            let cid = cid.to_bytes();
+            car.extend_from_slice(&[(cid.len() + 5) as u8 | 0x80, 0]);
+            car.extend_from_slice(&cid);
+            car.extend_from_slice(b"hello");
+        }
+        car.extend_from_slice(&[0, 0xff]);
+
+        let open = |mode| {
+            StreamCarReaderV1::new_with_mode(Pipe(&car[..]), ReaderLimits::new(), mode).unwrap()
+        };
+        assert!(matches!(
+            open(DecodeMode::Strict).next_block(),
+            Err(CarError::Parsing(_))
+        ));
+        let blocks: Vec<_> = open(DecodeMode::Lenient).map(Result::unwrap).collect();
+        assert_eq!(blocks, vec![(hello, b"hello".to_vec())]);
+    }
+}
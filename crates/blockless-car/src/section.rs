@@ -1,10 +1,26 @@
 #![allow(unused)]
-use std::io::{Read, Seek, SeekFrom};
+use std::io::{self, Read, Seek, SeekFrom, Write};
 
-use cid::Cid;
+use cid::{
+    multihash::{Code, MultihashDigest},
+    Cid,
+};
+use integer_encoding::{VarInt, VarIntReader, VarIntWriter};
 use ipld::Block;
 
-use crate::{error::CarError, Ipld};
+use crate::{codec::decode_ipld, error::CarError, Ipld};
+
+/// the max bytes of the section, the cid and the block data.
+pub(crate) const MAX_ALLOWED_SECTION_SIZE: usize = 32 << 20;
+
+/// check the section length read from the varint, the lengths are read as u64 so the
+/// check is the same on the 32-bit targets.
+pub(crate) fn checked_section_len(len: u64) -> Result<usize, CarError> {
+    if len > MAX_ALLOWED_SECTION_SIZE as u64 {
+        return Err(CarError::TooLargeSection(len));
+    }
+    Ok(len as usize)
+}
 
 #[derive(Debug, Clone)]
 pub struct Section {
@@ -35,8 +51,7 @@ impl Section {
         T: Seek + Read,
     {
         let data = self.read_data(&mut seeker)?;
-        let block = Block::<ipld::DefaultParams>::new(self.cid, data).unwrap();
-        block.ipld().map_err(|e| CarError::Parsing(e.to_string()))
+        decode_block(self.cid, data)
     }
 
     #[inline(always)]
@@ -55,3 +70,138 @@ impl Section {
         self.len
     }
 }
+
+/// decode the block, the hash is verified unless the hash code is not built in,
+/// e.g. the custom `BlockHasher` of the private-use multicodec. the codec not built in
+/// is decoded by the one of `codec::register_codec`, or is `NotImplemented`.
+pub(crate) fn decode_block(cid: Cid, data: Vec<u8>) -> Result<Ipld, CarError> {
+    let block = if Code::try_from(cid.hash().code()).is_ok() {
+        Block::<ipld::DefaultParams>::new(cid, data)
+            .map_err(|e| CarError::InvalidSection(format!("the block {cid}: {e}")))?
+    } else {
+        Block::<ipld::DefaultParams>::new_unchecked(cid, data)
+    };
+    decode_ipld(cid.codec(), block.data())
+}
+
+/// check the hash of the block data in place, the hash code not built in is not checked
+/// like `decode_block`. with the `StreamCarReaderV1` it validates a CAR stream block by block.
+pub fn check_block(cid: &Cid, data: &[u8]) -> Result<(), CarError> {
+    match Code::try_from(cid.hash().code()) {
+        Ok(code) if code.digest(data) != *cid.hash() => Err(CarError::InvalidSection(format!(
+            "the block {cid}: the hash of the data doesn't match"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// read the framing of the next section, the length varint and the cid, the reader is left
+/// at the block data. return the cid and the length of the block data, or none at the end
+/// of the input. the section over 32MiB is rejected before the data is read.
+pub fn read_section_header<R>(reader: R) -> Result<Option<(Cid, usize)>, CarError>
+where
+    R: Read,
+{
+    read_section_header_checked(reader, |len, _| checked_section_len(len))
+}
+
+/// read the varint byte by byte, return the value and the count of the bytes encoding it,
+/// or none at the end of the input.
+fn read_varint<R>(mut reader: R) -> Result<Option<(u64, usize)>, CarError>
+where
+    R: Read,
+{
+    let mut buf = [0u8; 10];
+    for i in 0..buf.len() {
+        match reader.read_exact(&mut buf[i..=i]) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof && i == 0 => return Ok(None),
+            Err(e) => return Err(CarError::IO(e)),
+        }
+        if buf[i] & 0x80 == 0 {
+            return Ok(u64::decode_var(&buf[..=i]));
+        }
+    }
+    Err(CarError::Parsing("invalid varint".into()))
+}
+
+/// read the framing of the next section like `read_section_header`, the length and the
+/// count of the bytes of its varint are checked by the `check` before the cid is read.
+pub(crate) fn read_section_header_checked<R, F>(
+    mut reader: R,
+    check: F,
+) -> Result<Option<(Cid, usize)>, CarError>
+where
+    R: Read,
+    F: FnOnce(u64, usize) -> Result<usize, CarError>,
+{
+    let len = match read_varint(&mut reader)? {
+        Some((len, encoded)) => check(len, encoded)?,
+        None => return Ok(None),
+    };
+    // the cid can't run over the section.
+    let mut section = reader.take(len as u64);
+    let cid = Cid::read_bytes(&mut section).map_err(|e| CarError::Parsing(e.to_string()))?;
+    Ok(Some((cid, section.limit() as usize)))
+}
+
+/// write the framing of the section of the `data_len` bytes block, the length varint and the
+/// cid, the block data follows. return the bytes written.
+pub fn write_section_header<W>(mut writer: W, cid: &Cid, data_len: usize) -> Result<usize, CarError>
+where
+    W: Write,
+{
+    let cid = cid.to_bytes();
+    let len = cid.len() + data_len;
+    writer.write_varint(len)?;
+    writer.write_all(&cid)?;
+    Ok(len.required_space() + cid.len())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_section_header() {
+        let cid =
+            Cid::try_from("bafkreidtzm4frjuhvbeuzizsgbjqcyuc6pnnhhkcz5rmuttz3wrkvr6zvq").unwrap();
+        let mut buf = Vec::new();
+        let n = write_section_header(&mut buf, &cid, 5).unwrap();
+        assert_eq!(n, buf.len());
+        buf.extend_from_slice(b"hello");
+
+        let mut r = Cursor::new(&buf);
+        assert_eq!(read_section_header(&mut r).unwrap(), Some((cid, 5)));
+        assert_eq!(r.position() as usize, n);
+        r.set_position(buf.len() as u64);
+        assert!(read_section_header(&mut r).unwrap().is_none());
+
+        // the section shorter than the cid.
+        let mut short = vec![4u8];
+        short.extend_from_slice(&cid.to_bytes());
+        assert!(read_section_header(Cursor::new(&short)).is_err());
+        let large = (MAX_ALLOWED_SECTION_SIZE + 1).encode_var_vec();
+        assert!(matches!(
+            read_section_header(Cursor::new(&large)),
+            Err(CarError::TooLargeSection(_))
+        ));
+        // the length over the usize of the 32-bit targets.
+        let large = (1u64 << 33).encode_var_vec();
+        assert!(matches!(
+            read_section_header(Cursor::new(&large)),
+            Err(CarError::TooLargeSection(0x200000000))
+        ));
+    }
+
+    #[test]
+    fn test_check_block() {
+        let cid = Cid::new_v1(0x55, Code::Sha2_256.digest(b"hello"));
+        assert!(check_block(&cid, b"hello").is_ok());
+        assert!(matches!(
+            check_block(&cid, b"hellp"),
+            Err(CarError::InvalidSection(_))
+        ));
+    }
+}
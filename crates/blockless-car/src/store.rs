@@ -0,0 +1,293 @@
+//! the stores of the blocks by cid, the traversals like the extraction and `cat` read the
+//! blocks through the `BlockStore`, so they run over the CAR readers, the memory or any
+//! store of the application, e.g. a flatfs directory or a key value database.
+use std::{
+    collections::HashMap,
+    io::{Read, Seek, Write},
+};
+
+use cid::Cid;
+
+use crate::{
+    error::CarError,
+    header::CarHeader,
+    reader::{CarReader, CarReaderV1, ReaderLimits},
+    section::{check_block, decode_block},
+    Ipld,
+};
+
+/// the store of the blocks by cid.
+pub trait BlockStore {
+    /// the block data, none if the block is not in the store.
+    fn get(&mut self, cid: &Cid) -> Result<Option<Vec<u8>>, CarError>;
+
+    /// put the block to the store, the data is checked against the hash of the `cid`.
+    /// the block already in the store is kept.
+    fn put(&mut self, cid: Cid, data: Vec<u8>) -> Result<(), CarError>;
+
+    /// the block is in the store.
+    #[inline]
+    fn has(&mut self, cid: &Cid) -> Result<bool, CarError> {
+        Ok(self.get_len(cid)?.is_some())
+    }
+
+    /// the length of the block data, none if the block is not in the store.
+    #[inline]
+    fn get_len(&mut self, cid: &Cid) -> Result<Option<usize>, CarError> {
+        Ok(self.get(cid)?.map(|b| b.len()))
+    }
+
+    /// the decoded block, the hash is verified like `CarReader::ipld`.
+    fn get_ipld(&mut self, cid: &Cid) -> Result<Ipld, CarError> {
+        let data = self
+            .get(cid)?
+            .ok_or_else(|| CarError::NotFound(format!("the block {cid}")))?;
+        decode_block(*cid, data)
+    }
+
+    /// write the block data to the `output` after checking the hash, return the bytes
+    /// written or none if the block is not in the store.
+    fn copy_block(&mut self, cid: &Cid, output: &mut dyn Write) -> Result<Option<usize>, CarError> {
+        let Some(data) = self.get(cid)? else {
+            return Ok(None);
+        };
+        check_block(cid, &data)?;
+        output.write_all(&data)?;
+        Ok(Some(data.len()))
+    }
+
+    /// the limits of the traversals over the store.
+    #[inline]
+    fn block_limits(&self) -> ReaderLimits {
+        ReaderLimits::default()
+    }
+}
+
+/// the CAR readers are the read only stores of their blocks.
+impl<R> BlockStore for R
+where
+    R: CarReader + ?Sized,
+{
+    #[inline]
+    fn get(&mut self, cid: &Cid) -> Result<Option<Vec<u8>>, CarError> {
+        self.get_block(cid)
+    }
+
+    fn put(&mut self, cid: Cid, _data: Vec<u8>) -> Result<(), CarError> {
+        Err(CarError::NotImplemented(format!(
+            "put the block {cid} to the CAR reader"
+        )))
+    }
+
+    #[inline]
+    fn get_len(&mut self, cid: &Cid) -> Result<Option<usize>, CarError> {
+        self.block_len(cid)
+    }
+
+    #[inline]
+    fn get_ipld(&mut self, cid: &Cid) -> Result<Ipld, CarError> {
+        self.ipld(cid)
+    }
+
+    #[inline]
+    fn copy_block(&mut self, cid: &Cid, output: &mut dyn Write) -> Result<Option<usize>, CarError> {
+        self.copy_block_to(cid, output)
+    }
+
+    #[inline]
+    fn block_limits(&self) -> ReaderLimits {
+        self.limits()
+    }
+}
+
+/// the store of the blocks in the memory.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryBlockStore {
+    blocks: HashMap<Cid, Vec<u8>>,
+    limits: ReaderLimits,
+}
+
+impl MemoryBlockStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// the limits of the traversals, the `max_blocks_in_memory` bounds the blocks put too.
+    pub fn with_limits(mut self, limits: ReaderLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// load all the blocks of the CAR reader.
+    pub fn from_reader(reader: &mut impl CarReader) -> Result<Self, CarError> {
+        let mut store = Self::new().with_limits(reader.limits());
+        for block in reader.blocks() {
+            let (cid, data) = block?;
+            store.put(cid, data)?;
+        }
+        Ok(store)
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+}
+
+impl BlockStore for MemoryBlockStore {
+    #[inline]
+    fn get(&mut self, cid: &Cid) -> Result<Option<Vec<u8>>, CarError> {
+        Ok(self.blocks.get(cid).cloned())
+    }
+
+    fn put(&mut self, cid: Cid, data: Vec<u8>) -> Result<(), CarError> {
+        if self.blocks.contains_key(&cid) {
+            return Ok(());
+        }
+        check_block(&cid, &data)?;
+        self.limits.check_blocks(self.blocks.len() + 1)?;
+        self.blocks.insert(cid, data);
+        Ok(())
+    }
+
+    #[inline]
+    fn get_len(&mut self, cid: &Cid) -> Result<Option<usize>, CarError> {
+        Ok(self.blocks.get(cid).map(Vec::len))
+    }
+
+    #[inline]
+    fn block_limits(&self) -> ReaderLimits {
+        self.limits
+    }
+}
+
+/// the store of the blocks in the CAR file, the blocks put are appended to the file.
+/// the sections are indexed on demand, like the reader of `reader::new_v1`.
+pub struct CarBlockStore<F> {
+    reader: CarReaderV1<F>,
+}
+
+impl<F> CarBlockStore<F>
+where
+    F: Read + Seek,
+{
+    pub fn new(inner: F) -> Result<Self, CarError> {
+        Self::new_with_limits(inner, ReaderLimits::default())
+    }
+
+    pub fn new_with_limits(inner: F, limits: ReaderLimits) -> Result<Self, CarError> {
+        Ok(Self {
+            reader: CarReaderV1::new_with_limits(inner, limits)?,
+        })
+    }
+
+    #[inline]
+    pub fn header(&self) -> &CarHeader {
+        self.reader.header()
+    }
+
+    /// the count of the blocks in the CAR, all the sections are indexed.
+    #[inline]
+    pub fn len(&mut self) -> Result<usize, CarError> {
+        self.reader.section_count()
+    }
+
+    #[inline]
+    pub fn is_empty(&mut self) -> Result<bool, CarError> {
+        Ok(self.len()? == 0)
+    }
+}
+
+impl<F> BlockStore for CarBlockStore<F>
+where
+    F: Read + Write + Seek,
+{
+    #[inline]
+    fn get(&mut self, cid: &Cid) -> Result<Option<Vec<u8>>, CarError> {
+        self.reader.get_block(cid)
+    }
+
+    fn put(&mut self, cid: Cid, data: Vec<u8>) -> Result<(), CarError> {
+        check_block(&cid, &data)?;
+        self.reader.append_block(cid, &data)
+    }
+
+    #[inline]
+    fn get_len(&mut self, cid: &Cid) -> Result<Option<usize>, CarError> {
+        self.reader.block_len(cid)
+    }
+
+    #[inline]
+    fn get_ipld(&mut self, cid: &Cid) -> Result<Ipld, CarError> {
+        self.reader.ipld(cid)
+    }
+
+    #[inline]
+    fn block_limits(&self) -> ReaderLimits {
+        self.reader.limits()
+    }
+}
+
+#[cfg(all(test, feature = "writer"))]
+mod test {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        reader,
+        utils::raw_cid,
+        writer::{CarWriter, CarWriterV1},
+    };
+
+    #[test]
+    fn test_block_stores() {
+        let hello = b"hello".to_vec();
+        let hello_cid = raw_cid(&hello, multicodec::Codec::Sha2_256);
+        let world = b"world".to_vec();
+        let world_cid = raw_cid(&world, multicodec::Codec::Sha2_256);
+        let mut car = Cursor::new(vec![]);
+        let mut writer = CarWriterV1::new(&mut car, CarHeader::new_v1(vec![hello_cid]));
+        writer.write_block(hello_cid, hello.clone()).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+
+        let check = |store: &mut dyn FnMut(Cid, Vec<u8>) -> Result<(), CarError>| {
+            // the data not matching the cid is rejected.
+            assert!(store(world_cid, hello.clone()).is_err());
+            store(world_cid, world.clone()).unwrap();
+            // the block put again is kept once.
+            store(world_cid, world.clone()).unwrap();
+        };
+
+        let mut memory = MemoryBlockStore::new();
+        memory.put(hello_cid, hello.clone()).unwrap();
+        check(&mut |cid, data| memory.put(cid, data));
+        assert_eq!(memory.len(), 2);
+        assert_eq!(memory.get(&world_cid).unwrap(), Some(world.clone()));
+        assert_eq!(
+            memory.get_ipld(&hello_cid).unwrap(),
+            Ipld::Bytes(hello.clone())
+        );
+
+        let mut store = CarBlockStore::new(Cursor::new(car.get_ref().clone())).unwrap();
+        assert!(store.has(&hello_cid).unwrap());
+        assert!(!store.has(&world_cid).unwrap());
+        check(&mut |cid, data| store.put(cid, data));
+        assert_eq!(store.len().unwrap(), 2);
+        assert_eq!(store.get(&world_cid).unwrap(), Some(world.clone()));
+        // the appended blocks are read back by the readers.
+        let buf = store.reader.into_inner().into_inner();
+        let mut reader = reader::new_v1(Cursor::new(buf)).unwrap();
+        assert_eq!(reader.get(&world_cid).unwrap(), Some(world.clone()));
+        assert_eq!(reader.get_len(&hello_cid).unwrap(), Some(hello.len()));
+        assert!(reader.put(world_cid, world).is_err());
+
+        let memory = MemoryBlockStore::from_reader(&mut reader).unwrap();
+        assert_eq!(memory.len(), 2);
+    }
+}
@@ -1,4 +1,7 @@
-use std::fmt::Display;
+use std::{
+    fmt::Display,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use cid::Cid;
 
@@ -65,6 +68,36 @@ pub struct UnixTime {
     pub fractional_nanoseconds: Option<u32>,
 }
 
+impl UnixTime {
+    /// the time since the unix epoch, the nanoseconds are omitted if zero.
+    pub fn from_system_time(time: SystemTime) -> Self {
+        let (seconds, nanos) = match time.duration_since(UNIX_EPOCH) {
+            Ok(d) => (d.as_secs() as i64, d.subsec_nanos()),
+            // before the epoch, the fraction counts forward from the seconds.
+            Err(e) => {
+                let d = e.duration();
+                match d.subsec_nanos() {
+                    0 => (-(d.as_secs() as i64), 0),
+                    n => (-(d.as_secs() as i64) - 1, 1_000_000_000 - n),
+                }
+            }
+        };
+        Self {
+            seconds,
+            fractional_nanoseconds: (nanos != 0).then_some(nanos),
+        }
+    }
+
+    pub fn to_system_time(&self) -> SystemTime {
+        let nanos = Duration::from_nanos(self.fractional_nanoseconds.unwrap_or(0) as u64);
+        let secs = Duration::from_secs(self.seconds.unsigned_abs());
+        match self.seconds >= 0 {
+            true => UNIX_EPOCH + secs + nanos,
+            false => UNIX_EPOCH - secs + nanos,
+        }
+    }
+}
+
 impl From<pb::unixfs::UnixTime> for UnixTime {
     fn from(value: pb::unixfs::UnixTime) -> Self {
         Self {
@@ -83,6 +116,31 @@ impl From<UnixTime> for pb::unixfs::UnixTime {
     }
 }
 
+/// the extended attribute of the file or directory.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Xattr {
+    pub name: String,
+    pub value: Vec<u8>,
+}
+
+impl<'a> From<pb::unixfs::Xattr<'a>> for Xattr {
+    fn from(value: pb::unixfs::Xattr<'a>) -> Self {
+        Self {
+            name: value.name.into_owned(),
+            value: value.value.into_owned(),
+        }
+    }
+}
+
+impl<'a> From<&'a Xattr> for pb::unixfs::Xattr<'a> {
+    fn from(value: &'a Xattr) -> Self {
+        Self {
+            name: value.name.as_str().into(),
+            value: value.value.as_slice().into(),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Default)]
 pub struct UnixFs {
     pub cid: Option<Cid>,
@@ -95,6 +153,11 @@ pub struct UnixFs {
     pub links: Vec<Link>,
     pub mtime: Option<UnixTime>,
     pub file_name: Option<String>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub xattrs: Vec<Xattr>,
+    /// the `Data` field of the node, the bitfield of the hamt shard or the inline file content.
+    pub data: Option<Vec<u8>>,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Default)]
@@ -117,6 +180,10 @@ impl<'a> From<Data<'a>> for UnixFs {
             fanout: value.fanout,
             mode: value.mode,
             mtime: value.mtime.map(|t| t.into()),
+            uid: value.uid,
+            gid: value.gid,
+            xattrs: value.xattrs.into_iter().map(|x| x.into()).collect(),
+            data: value.Data.map(|d| d.into_owned()),
             links: Default::default(),
         }
     }
@@ -137,6 +204,23 @@ impl UnixFs {
         }
     }
 
+    /// the symlink node, the target is kept as is in the data.
+    pub fn new_symlink(target: &str) -> Self {
+        Self {
+            file_type: FileType::Symlink,
+            data: Some(target.as_bytes().to_vec()),
+            ..Default::default()
+        }
+    }
+
+    /// the target of the symlink node, `None` if the node is not a symlink or the target is not utf8.
+    pub fn symlink_target(&self) -> Option<&str> {
+        match self.file_type {
+            FileType::Symlink => std::str::from_utf8(self.data.as_deref().unwrap_or_default()).ok(),
+            _ => None,
+        }
+    }
+
     #[inline(always)]
     pub fn add_link(&mut self, child: Link) -> usize {
         let idx = self.links.len();
@@ -159,6 +243,21 @@ impl UnixFs {
         self.mode
     }
 
+    #[inline(always)]
+    pub fn uid(&self) -> Option<u32> {
+        self.uid
+    }
+
+    #[inline(always)]
+    pub fn gid(&self) -> Option<u32> {
+        self.gid
+    }
+
+    #[inline(always)]
+    pub fn xattrs(&self) -> &[Xattr] {
+        &self.xattrs
+    }
+
     #[inline(always)]
     pub fn fanout(&self) -> Option<u64> {
         self.fanout
@@ -189,6 +288,11 @@ impl UnixFs {
         self.file_type
     }
 
+    #[inline(always)]
+    pub fn data(&self) -> Option<&[u8]> {
+        self.data.as_deref()
+    }
+
     #[inline(always)]
     pub fn cid(&self) -> Option<Cid> {
         self.cid
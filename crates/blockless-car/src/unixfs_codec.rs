@@ -1,4 +1,4 @@
-use std::collections::BTreeMap;
+use std::{borrow::Cow, collections::BTreeMap};
 
 use cid::Cid;
 use quick_protobuf::{BytesReader, MessageRead, MessageWrite, Writer};
@@ -89,7 +89,7 @@ fn convert_to_ipld(value: &Link) -> Result<Ipld, CarError> {
 impl Encoder<Ipld> for UnixFs {
     fn encode(&self) -> Result<Ipld, CarError> {
         match self.file_type {
-            FileType::Directory | FileType::File => {
+            FileType::Directory | FileType::File | FileType::HAMTShard | FileType::Symlink => {
                 let mut map = BTreeMap::new();
                 let data = Data {
                     mode: self.mode,
@@ -99,7 +99,10 @@ impl Encoder<Ipld> for UnixFs {
                     Type: self.file_type.into(),
                     blocksizes: self.block_sizes.clone(),
                     mtime: self.mtime().map(|s| s.clone().into()),
-                    ..Default::default()
+                    uid: self.uid,
+                    gid: self.gid,
+                    xattrs: self.xattrs.iter().map(|x| x.into()).collect(),
+                    Data: self.data.as_deref().map(Cow::Borrowed),
                 };
                 let mut buf: Vec<u8> = Vec::new();
                 let mut bw = Writer::new(&mut buf);
@@ -1,9 +1,125 @@
-mod pack;
+#[cfg(feature = "cli-utils")]
+mod bundle;
+#[cfg(feature = "unixfs-fs")]
+mod cancel;
+#[cfg(feature = "cli-utils")]
 mod cat;
-mod unpack;
+#[cfg(feature = "cli-utils")]
+mod complete;
+#[cfg(feature = "writer")]
+mod digest;
+#[cfg(feature = "cli-utils")]
+mod entity;
+#[cfg(feature = "cli-utils")]
+mod filter;
+#[cfg(feature = "unixfs-fs")]
+mod glob;
+#[cfg(feature = "cli-utils")]
+mod graph;
+#[cfg(any(feature = "unixfs-fs", feature = "cli-utils"))]
+mod hamt;
+#[cfg(feature = "cli-utils")]
+mod index;
+#[cfg(feature = "cli-utils")]
+mod inspect;
+#[cfg(feature = "unixfs-fs")]
+mod limits;
+#[cfg(feature = "cli-utils")]
+mod listing;
+#[cfg(feature = "cli-utils")]
 mod ls;
+#[cfg(feature = "cli-utils")]
+mod merge;
+#[cfg(any(feature = "unixfs-fs", feature = "cli-utils"))]
+mod ordered;
+#[cfg(feature = "unixfs-fs")]
+mod pack;
+#[cfg(feature = "cli-utils")]
+mod probe;
+#[cfg(feature = "unixfs-fs")]
+mod progress;
+#[cfg(feature = "cli-utils")]
+mod put_blocks;
+#[cfg(feature = "cli-utils")]
+mod repack;
+#[cfg(feature = "cli-utils")]
+mod roots;
+#[cfg(feature = "unixfs-fs")]
+mod roundtrip;
+#[cfg(feature = "cli-utils")]
+mod salvage;
+#[cfg(feature = "cli-utils")]
+mod size;
+#[cfg(feature = "cli-utils")]
+mod split;
+#[cfg(feature = "unixfs-fs")]
+mod temp;
+#[cfg(feature = "cli-utils")]
+mod transform;
+#[cfg(feature = "unixfs-fs")]
+mod unpack;
+#[cfg(feature = "cli-utils")]
+mod verify;
 
-pub use pack::*;
+#[cfg(feature = "cli-utils")]
+pub use bundle::*;
+#[cfg(feature = "unixfs-fs")]
+pub use cancel::*;
+#[cfg(feature = "cli-utils")]
 pub use cat::*;
+#[cfg(feature = "cli-utils")]
+pub use complete::*;
+#[cfg(feature = "writer")]
+pub use digest::*;
+#[cfg(feature = "cli-utils")]
+pub use entity::*;
+#[cfg(feature = "cli-utils")]
+pub use filter::*;
+#[cfg(feature = "unixfs-fs")]
+pub use glob::*;
+#[cfg(feature = "cli-utils")]
+pub use graph::*;
+#[cfg(any(feature = "unixfs-fs", feature = "cli-utils"))]
+pub use hamt::*;
+#[cfg(feature = "cli-utils")]
+pub use index::*;
+#[cfg(feature = "cli-utils")]
+pub use inspect::*;
+#[cfg(feature = "unixfs-fs")]
+pub use limits::*;
+#[cfg(feature = "cli-utils")]
+pub use listing::*;
+#[cfg(feature = "cli-utils")]
+pub use ls::*;
+#[cfg(feature = "cli-utils")]
+pub use merge::*;
+#[cfg(feature = "unixfs-fs")]
+pub use pack::*;
+#[cfg(feature = "cli-utils")]
+pub use probe::*;
+#[cfg(feature = "unixfs-fs")]
+pub use progress::*;
+#[cfg(feature = "cli-utils")]
+pub use put_blocks::*;
+#[cfg(feature = "cli-utils")]
+pub use repack::*;
+#[cfg(feature = "cli-utils")]
+pub use roots::*;
+#[cfg(feature = "unixfs-fs")]
+pub use roundtrip::*;
+#[cfg(feature = "cli-utils")]
+pub use salvage::*;
+#[cfg(feature = "cli-utils")]
+pub use size::*;
+#[cfg(feature = "cli-utils")]
+pub use split::*;
+#[cfg(feature = "cli-utils")]
+pub use transform::*;
+#[cfg(feature = "unixfs-fs")]
 pub use unpack::*;
-pub use ls::*;
\ No newline at end of file
+#[cfg(feature = "cli-utils")]
+pub use verify::*;
+
+/// the multihash code of the identity hash, the digest is the data itself.
+#[cfg(feature = "cli-utils")]
+pub(crate) const IDENTITY_CODE: u64 = 0x00;
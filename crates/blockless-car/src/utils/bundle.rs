@@ -0,0 +1,218 @@
+//! the `manifest.json` of the Blockless bundle, in the schema read by the Blockless runtime
+//! (blocklessnetwork/bls-runtime, the `entry` and the `modules` of its json config, every
+//! module a `BlocklessModule` of the `file`, the `name`, the `type` and the `md5`).
+//! the other fields of the runtime, like the permissions, are not read.
+use serde_json::Value;
+
+use crate::error::CarError;
+
+/// the file name of the bundle manifest.
+pub const MANIFEST_NAME: &str = "manifest.json";
+
+/// the wasm module of the bundle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BundleModule {
+    /// the path of the wasm file, relative to the manifest.
+    pub file: String,
+    pub name: Option<String>,
+    /// `entry` or `module`.
+    pub kind: Option<String>,
+    pub md5: Option<String>,
+}
+
+/// the fields of the bundle manifest checked by the `bundle-info`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BundleManifest {
+    pub name: Option<String>,
+    /// the path of the wasm file started by the runtime, relative to the manifest.
+    pub entry: Option<String>,
+    pub modules: Vec<BundleModule>,
+}
+
+/// the string field of the json object, the field of another type is an error.
+fn str_field(value: &Value, field: &str) -> Result<Option<String>, CarError> {
+    match value.get(field) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(s)) => Ok(Some(s.clone())),
+        Some(_) => Err(CarError::Parsing(format!(
+            "invalid {MANIFEST_NAME}: the {field} is not a string"
+        ))),
+    }
+}
+
+impl BundleManifest {
+    /// parse the manifest, the module without the `file` is an error.
+    pub fn parse(data: &[u8]) -> Result<Self, CarError> {
+        let value: Value = serde_json::from_slice(data)
+            .map_err(|e| CarError::Parsing(format!("invalid {MANIFEST_NAME}: {e}")))?;
+        if !value.is_object() {
+            return Err(CarError::Parsing(format!(
+                "invalid {MANIFEST_NAME}: not an object"
+            )));
+        }
+        let modules = match value.get("modules") {
+            None | Some(Value::Null) => Vec::new(),
+            Some(Value::Array(modules)) => modules
+                .iter()
+                .map(|m| {
+                    let file = str_field(m, "file")?.ok_or_else(|| {
+                        CarError::Parsing(format!(
+                            "invalid {MANIFEST_NAME}: the module has no file"
+                        ))
+                    })?;
+                    Ok(BundleModule {
+                        file,
+                        name: str_field(m, "name")?,
+                        kind: str_field(m, "type")?,
+                        md5: str_field(m, "md5")?,
+                    })
+                })
+                .collect::<Result<_, CarError>>()?,
+            Some(_) => {
+                return Err(CarError::Parsing(format!(
+                    "invalid {MANIFEST_NAME}: the modules is not an array"
+                )))
+            }
+        };
+        Ok(Self {
+            name: str_field(&value, "name")?,
+            entry: str_field(&value, "entry")?,
+            modules,
+        })
+    }
+
+    /// the files referenced by the manifest, the `entry` and the `file` of the `modules`,
+    /// each once in the order of the manifest.
+    pub fn refs(&self) -> Vec<&str> {
+        let mut refs: Vec<&str> = Vec::new();
+        let files = self
+            .entry
+            .iter()
+            .chain(self.modules.iter().map(|m| &m.file));
+        for file in files {
+            if !refs.contains(&file.as_str()) {
+                refs.push(file);
+            }
+        }
+        refs
+    }
+
+    /// the references resolved in the directory `dir` of the manifest, see
+    /// `resolve_bundle_path`.
+    pub fn resolve_refs(&self, dir: &str) -> Result<Vec<(&str, String)>, CarError> {
+        self.refs()
+            .into_iter()
+            .map(|r| Ok((r, resolve_bundle_path(dir, r)?)))
+            .collect()
+    }
+}
+
+/// resolve the `reference` of the manifest in the directory `dir` to the path relative to
+/// the root of the car, the `.` and `..` components are normalized. the bundle is the
+/// directory of the manifest, the absolute reference and the one escaping the directory
+/// are rejected.
+pub fn resolve_bundle_path(dir: &str, reference: &str) -> Result<String, CarError> {
+    if reference.starts_with('/') {
+        return Err(CarError::UnsafePath(reference.to_string()));
+    }
+    let mut parts: Vec<&str> = Vec::new();
+    for part in reference.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                if parts.pop().is_none() {
+                    return Err(CarError::UnsafePath(reference.to_string()));
+                }
+            }
+            part => parts.push(part),
+        }
+    }
+    let dir = dir.trim_matches('/');
+    Ok(match dir {
+        "" => parts.join("/"),
+        dir => format!("{dir}/{}", parts.join("/")),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest = BundleManifest::parse(
+            br#"{
+                "name": "hello",
+                "fs_root_path": "./",
+                "entry": "release.wasm",
+                "permissions": ["https://example.com"],
+                "modules": [
+                    {"file": "release.wasm", "name": "release", "type": "entry", "md5": "abc"},
+                    {"file": "./lib/util.wasm", "name": "util", "type": "module"}
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(manifest.name.as_deref(), Some("hello"));
+        assert_eq!(manifest.entry.as_deref(), Some("release.wasm"));
+        assert_eq!(manifest.modules.len(), 2);
+        assert_eq!(manifest.modules[0].kind.as_deref(), Some("entry"));
+        assert_eq!(manifest.modules[0].md5.as_deref(), Some("abc"));
+        assert_eq!(manifest.modules[1].md5, None);
+        // the entry listed in the modules is referenced once.
+        assert_eq!(manifest.refs(), ["release.wasm", "./lib/util.wasm"]);
+        assert_eq!(
+            manifest.resolve_refs("bundle").unwrap(),
+            [
+                ("release.wasm", "bundle/release.wasm".to_string()),
+                ("./lib/util.wasm", "bundle/lib/util.wasm".to_string()),
+            ]
+        );
+
+        assert_eq!(
+            BundleManifest::parse(b"{}").unwrap(),
+            BundleManifest::default()
+        );
+        for invalid in [
+            &b"[]"[..],
+            b"{",
+            br#"{"entry": 1}"#,
+            br#"{"modules": {}}"#,
+            br#"{"modules": [{"name": "util"}]}"#,
+        ] {
+            assert!(matches!(
+                BundleManifest::parse(invalid),
+                Err(CarError::Parsing(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_resolve_bundle_path() {
+        assert_eq!(resolve_bundle_path("", "a.wasm").unwrap(), "a.wasm");
+        assert_eq!(resolve_bundle_path("b", "./a.wasm").unwrap(), "b/a.wasm");
+        assert_eq!(
+            resolve_bundle_path("b/c", "d/..//e/a.wasm").unwrap(),
+            "b/c/e/a.wasm"
+        );
+        assert_eq!(
+            resolve_bundle_path("b/", "c/../a.wasm").unwrap(),
+            "b/a.wasm"
+        );
+        for (dir, reference) in [
+            ("", "../a.wasm"),
+            ("b", "../a.wasm"),
+            ("b", "c/../../b/a.wasm"),
+            ("b", "/a.wasm"),
+        ] {
+            assert!(matches!(
+                resolve_bundle_path(dir, reference),
+                Err(CarError::UnsafePath(_))
+            ));
+        }
+        // the manifest of the bundle with the escaping module is rejected.
+        let manifest =
+            BundleManifest::parse(br#"{"modules": [{"file": "../../etc/x.wasm"}]}"#).unwrap();
+        assert!(manifest.resolve_refs("bundle").is_err());
+    }
+}
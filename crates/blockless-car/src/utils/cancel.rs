@@ -0,0 +1,36 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use crate::error::CarError;
+
+/// the token for cancelling the running pack or extract from other threads,
+/// e.g. the signal handler or the timer. the clones share the same state.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    #[inline]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// return the `Cancelled` error with the count of the `entries` done if cancelled.
+    #[inline]
+    pub fn check(&self, entries: usize) -> Result<(), CarError> {
+        if self.is_cancelled() {
+            return Err(CarError::Cancelled { entries });
+        }
+        Ok(())
+    }
+}
@@ -5,43 +5,71 @@ use std::{
 };
 
 use cid::Cid;
+use ipld::raw::RawCodec;
 
-use crate::{error::CarError, reader::CarReader, unixfs::UnixFs, Ipld};
+use crate::{
+    error::CarError,
+    reader::CarReader,
+    store::BlockStore,
+    unixfs::{FileType, UnixFs},
+    utils::{file_size, resolve_path},
+    Ipld,
+};
 
 /// write ipld to output
+/// the blocks are read from the `reader`, the CAR reader or any other `BlockStore`.
 /// `file_cid` is the file cid to write
 /// `output` is the out the file write to.
 pub fn ipld_write(
-    reader: &mut impl CarReader,
+    reader: &mut impl BlockStore,
     cid: Cid,
     output: &mut impl Write,
 ) -> Result<(), CarError> {
-    let mut vecq = VecDeque::new();
-    vecq.push_back(cid);
-    ipld_write_inner(reader, &mut vecq, output)
+    ipld_write_inner(reader, cid, &cid.to_string(), output)
 }
 
 /// write ipld to output
 /// `file_cid` is the file cid to write
+/// `path` is the path of the file in the errors.
 /// `output` is the out the file write to.
+/// the DAG is walked depth first, so the chunks under the nested file nodes are written in order.
 fn ipld_write_inner(
-    reader: &mut impl CarReader,
-    vecq: &mut VecDeque<Cid>,
+    reader: &mut impl BlockStore,
+    file_cid: Cid,
+    path: &str,
     output: &mut impl Write,
 ) -> Result<(), CarError> {
-    while let Some(file_cid) = vecq.pop_front() {
-        let file_ipld: Ipld = reader.ipld(&file_cid).unwrap();
+    let raw_code: u64 = RawCodec.into();
+    let limits = reader.block_limits();
+    // the blocks with their parents and their depth in the file DAG.
+    let mut vecq = VecDeque::new();
+    vecq.push_back((file_cid, None, 0));
+    while let Some((file_cid, parent, depth)) = vecq.pop_front() {
+        let traversal = |e: CarError| e.traversal(path, &file_cid, parent.as_ref());
+        limits.check_link_depth(depth)?;
+        // the raw leaves are copied without the decode.
+        if file_cid.codec() == raw_code {
+            match reader.copy_block(&file_cid, output).map_err(traversal)? {
+                Some(_) => continue,
+                None => return Err(traversal(CarError::NotFound("cid not exist".into()))),
+            }
+        }
+        let file_ipld: Ipld = reader.get_ipld(&file_cid).map_err(traversal)?;
 
         match file_ipld {
             Ipld::Bytes(b) => {
                 output.write_all(&b[..])?;
             }
             m @ Ipld::Map(_) => {
-                let unix_fs: Result<UnixFs, CarError> = (file_cid, m).try_into();
-                let ufs = unix_fs?;
-                for link in ufs.links().iter() {
-                    vecq.push_back(link.hash);
+                let ufs: UnixFs = (file_cid, m).try_into().map_err(traversal)?;
+                // the leaf node without raw leaves has the content inline.
+                if let (FileType::File, Some(data)) = (ufs.file_type(), ufs.data()) {
+                    output.write_all(data)?;
+                }
+                for link in ufs.links().iter().rev() {
+                    vecq.push_front((link.hash, Some(file_cid), depth + 1));
                 }
+                limits.check_blocks(vecq.len())?;
             }
             _ => {}
         };
@@ -49,15 +77,320 @@ fn ipld_write_inner(
     Ok(())
 }
 
+/// write the `length` bytes of the file from the `offset` to the output, the whole rest
+/// if the `length` is none. only the leaf blocks overlapping the range are read, the
+/// `blocksizes` of the nodes locate the leaves. return the count of the bytes written,
+/// less than the `length` if the range is out of the file.
+pub fn ipld_write_range(
+    reader: &mut impl BlockStore,
+    cid: Cid,
+    offset: u64,
+    length: Option<u64>,
+    output: &mut impl Write,
+) -> Result<u64, CarError> {
+    ipld_write_range_inner(reader, cid, &cid.to_string(), offset, length, output)
+}
+
+fn ipld_write_range_inner(
+    reader: &mut impl BlockStore,
+    cid: Cid,
+    path: &str,
+    offset: u64,
+    length: Option<u64>,
+    output: &mut impl Write,
+) -> Result<u64, CarError> {
+    let end = length.map_or(u64::MAX, |len| offset.saturating_add(len));
+    let limits = reader.block_limits();
+    let mut written = 0;
+    // the nodes with the offsets of their content in the file, their parents and their depth.
+    let mut stack = vec![(cid, 0u64, None, 0)];
+    while let Some((cid, start, parent, depth)) = stack.pop() {
+        if start >= end {
+            break;
+        }
+        limits.check_link_depth(depth)?;
+        let mut write = |data: &[u8], start: u64| -> Result<(), CarError> {
+            let from = offset.saturating_sub(start).min(data.len() as u64) as usize;
+            let to = (end - start).min(data.len() as u64) as usize;
+            if from < to {
+                output.write_all(&data[from..to])?;
+                written += (to - from) as u64;
+            }
+            Ok(())
+        };
+        let traversal = |e: CarError| e.traversal(path, &cid, parent.as_ref());
+        let m = match reader.get_ipld(&cid).map_err(traversal)? {
+            Ipld::Bytes(b) => {
+                write(&b, start)?;
+                continue;
+            }
+            m @ Ipld::Map(_) => m,
+            _ => continue,
+        };
+        let ufs: UnixFs = (cid, m).try_into().map_err(traversal)?;
+        let mut child_start = start;
+        if let (FileType::File | FileType::Raw, Some(data)) = (ufs.file_type(), ufs.data()) {
+            write(data, start)?;
+            child_start += data.len() as u64;
+        }
+        let block_sizes = ufs.block_sizes();
+        let mut children = Vec::new();
+        for (i, link) in ufs.links().iter().enumerate() {
+            if child_start >= end {
+                break;
+            }
+            // the node without the `blocksizes` reads the size from the child.
+            let size = match block_sizes.get(i) {
+                Some(size) if block_sizes.len() == ufs.links().len() => *size,
+                _ => file_size(reader, &link.hash)
+                    .map_err(|e| e.traversal(path, &link.hash, Some(&cid)))?,
+            };
+            if child_start + size > offset {
+                children.push((link.hash, child_start, Some(cid), depth + 1));
+            }
+            child_start += size;
+        }
+        stack.extend(children.into_iter().rev());
+        limits.check_blocks(stack.len())?;
+    }
+    Ok(written)
+}
+
 #[inline(always)]
-pub fn cat_ipld_str(reader: &mut impl CarReader, cid: &str) -> Result<(), CarError> {
+pub fn cat_ipld_str(reader: &mut impl BlockStore, cid: &str) -> Result<(), CarError> {
     let cid = Cid::from_str(cid).map_err(|e| CarError::Parsing(e.to_string()))?;
     cat_ipld(reader, cid)
 }
 
-pub fn cat_ipld(reader: &mut impl CarReader, file_cid: Cid) -> Result<(), CarError> {
-    let mut stdout = io::stdout();
-    let mut vecq = VecDeque::new();
-    vecq.push_back(file_cid);
-    ipld_write_inner(reader, &mut vecq, &mut stdout)
+pub fn cat_ipld(reader: &mut impl BlockStore, file_cid: Cid) -> Result<(), CarError> {
+    ipld_write_inner(reader, file_cid, &file_cid.to_string(), &mut io::stdout())
+}
+
+/// cat the range of the file, see `ipld_write_range`.
+pub fn cat_ipld_range(
+    reader: &mut impl BlockStore,
+    file_cid: Cid,
+    offset: u64,
+    length: Option<u64>,
+) -> Result<u64, CarError> {
+    ipld_write_range(reader, file_cid, offset, length, &mut io::stdout().lock())
+}
+
+/// cat the range of the file at the `path`, see `resolve_path` and `ipld_write_range`.
+/// the errors of the walk include the `path` rather than the file cid.
+pub fn cat_ipld_path(
+    reader: &mut impl CarReader,
+    path: &str,
+    offset: u64,
+    length: Option<u64>,
+) -> Result<(), CarError> {
+    let cid = resolve_path(reader, path)?;
+    let mut stdout = io::stdout().lock();
+    if offset == 0 && length.is_none() {
+        return ipld_write_inner(reader, cid, path, &mut stdout);
+    }
+    ipld_write_range_inner(reader, cid, path, offset, length, &mut stdout)?;
+    Ok(())
+}
+
+#[cfg(all(test, feature = "unixfs-fs"))]
+mod test {
+    use super::*;
+    use crate::{
+        header::CarHeader,
+        reader::{self, ReaderLimits},
+        store::MemoryBlockStore,
+        utils::{pack_files_with, raw_cid, resolve_path, PackOptions},
+        writer::{self, CarWriter},
+    };
+    use std::{fs, io::Cursor};
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_ipld_write_range() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        fs::create_dir_all(&root_dir).unwrap();
+        // more chunks than the links of a node, so the DAG has two levels.
+        let content: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        fs::write(root_dir.join("video.bin"), &content).unwrap();
+        for raw_leaves in [true, false] {
+            let options = PackOptions::new()
+                .with_chunk_size(1000)
+                .with_raw_leaves(raw_leaves);
+            let mut car = Cursor::new(vec![]);
+            pack_files_with(&root_dir, &mut car, &options).unwrap();
+            let mut reader = reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+            let cid = resolve_path(&mut reader, "root/video.bin").unwrap();
+            let mut store = MemoryBlockStore::from_reader(&mut reader).unwrap();
+            let ranges = [
+                (0, Some(10)),
+                (999, Some(2)),
+                (173_999, Some(2_000)),
+                (199_990, None),
+                (199_990, Some(100)),
+                (0, None),
+                (300_000, Some(1)),
+            ];
+            for (offset, length) in ranges {
+                let mut out = Vec::new();
+                let n = ipld_write_range(&mut reader, cid, offset, length, &mut out).unwrap();
+                let start = (offset as usize).min(content.len());
+                let end = length.map_or(content.len(), |l| (start + l as usize).min(content.len()));
+                assert_eq!(n as usize, out.len());
+                assert_eq!(out, &content[start..end], "{offset} {length:?}");
+                // the same range of the blocks in the memory store.
+                let mut store_out = Vec::new();
+                ipld_write_range(&mut store, cid, offset, length, &mut store_out).unwrap();
+                assert_eq!(store_out, out);
+            }
+            let mut out = Vec::new();
+            ipld_write(&mut store, cid, &mut out).unwrap();
+            assert_eq!(out, content);
+        }
+    }
+
+    #[test]
+    fn test_ipld_write_on_demand() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        fs::create_dir_all(&root_dir).unwrap();
+        let content: Vec<u8> = (0..3000u32).map(|i| (i % 251) as u8).collect();
+        fs::write(root_dir.join("a.bin"), &content).unwrap();
+        let options = PackOptions::new().with_chunk_size(1000);
+        let mut car = Cursor::new(vec![]);
+        pack_files_with(&root_dir, &mut car, &options).unwrap();
+        // the garbage after the last block is never read by the lookups of the file.
+        let mut car = car.into_inner();
+        car.extend_from_slice(&[0, 0xff]);
+        let mut reader = reader::new_v1(Cursor::new(car)).unwrap();
+        let cid = resolve_path(&mut reader, "root/a.bin").unwrap();
+        let mut out = Vec::new();
+        ipld_write(&mut reader, cid, &mut out).unwrap();
+        assert_eq!(out, content);
+        assert!(reader.section_count().is_err());
+    }
+
+    #[test]
+    fn test_ipld_write_nested_order() {
+        use crate::{codec::Encoder, unixfs::Link};
+        use ipld::{pb::DagPbCodec, prelude::Codec};
+
+        let mut store = MemoryBlockStore::new();
+        let leaf = |store: &mut MemoryBlockStore, data: &[u8]| {
+            let cid = raw_cid(data, multicodec::Codec::Sha2_256);
+            store.put(cid, data.to_vec()).unwrap();
+            cid
+        };
+        let node = |store: &mut MemoryBlockStore, links: &[Cid]| {
+            let node = UnixFs {
+                file_type: FileType::File,
+                links: links
+                    .iter()
+                    .map(|hash| Link {
+                        hash: *hash,
+                        ..Default::default()
+                    })
+                    .collect(),
+                ..Default::default()
+            };
+            let bs = DagPbCodec.encode(&node.encode().unwrap()).unwrap();
+            let cid = crate::utils::pb_cid(&bs, multicodec::Codec::Sha2_256);
+            store.put(cid, bs).unwrap();
+            cid
+        };
+        // the leaves at the different depths, the breadth first walk writes "adbc".
+        let (a, b, c, d) = (
+            leaf(&mut store, b"a"),
+            leaf(&mut store, b"b"),
+            leaf(&mut store, b"c"),
+            leaf(&mut store, b"d"),
+        );
+        let inner = node(&mut store, &[b, c]);
+        let root = node(&mut store, &[a, inner, d]);
+        let mut out = Vec::new();
+        ipld_write(&mut store, root, &mut out).unwrap();
+        assert_eq!(out, b"abcd");
+    }
+
+    #[test]
+    fn test_ipld_write_link_depth() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        fs::create_dir_all(&root_dir).unwrap();
+        // more chunks than the links of a node, the leaves are at the depth 2.
+        let content: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        fs::write(root_dir.join("video.bin"), &content).unwrap();
+        let options = PackOptions::new().with_chunk_size(1000);
+        let mut car = Cursor::new(vec![]);
+        pack_files_with(&root_dir, &mut car, &options).unwrap();
+        let open = |limits| reader::new_v1_with_limits(Cursor::new(car.get_ref()), limits);
+
+        let limits = ReaderLimits::new().with_max_link_depth(1);
+        let mut reader = open(limits).unwrap();
+        let cid = resolve_path(&mut reader, "root/video.bin").unwrap();
+        for result in [
+            ipld_write(&mut reader, cid, &mut Vec::new()),
+            ipld_write_range(&mut reader, cid, 0, None, &mut Vec::new()).map(|_| ()),
+        ] {
+            assert!(matches!(
+                result,
+                Err(CarError::LimitExceeded {
+                    name: "link depth",
+                    ..
+                })
+            ));
+        }
+
+        let mut reader = open(limits.with_max_link_depth(2)).unwrap();
+        let mut out = Vec::new();
+        ipld_write(&mut reader, cid, &mut out).unwrap();
+        assert_eq!(out, content);
+    }
+
+    #[test]
+    fn test_ipld_write_traversal_error() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        fs::create_dir_all(&root_dir).unwrap();
+        let content: Vec<u8> = (0..3000u32).map(|i| (i % 251) as u8).collect();
+        fs::write(root_dir.join("a.bin"), &content).unwrap();
+        let options = PackOptions::new().with_chunk_size(1000);
+        let mut car = Cursor::new(vec![]);
+        let root = pack_files_with(&root_dir, &mut car, &options).unwrap();
+        let mut reader = reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+        let file = resolve_path(&mut reader, "root/a.bin").unwrap();
+
+        // the CAR without the second chunk of the file.
+        let chunk = raw_cid(&content[1000..2000], multicodec::Codec::Sha2_256);
+        let mut partial = Cursor::new(vec![]);
+        {
+            let mut writer = writer::new_v1(&mut partial, CarHeader::new_v1(vec![root])).unwrap();
+            for block in reader.blocks() {
+                let (cid, data) = block.unwrap();
+                if cid != chunk {
+                    writer.write_block(cid, data).unwrap();
+                }
+            }
+            writer.flush().unwrap();
+        }
+        let mut reader = reader::new_v1(Cursor::new(partial.into_inner())).unwrap();
+        let err = ipld_write_inner(&mut reader, file, "root/a.bin", &mut Vec::new()).unwrap_err();
+        let CarError::Traversal {
+            path, cid, parent, ..
+        } = &err
+        else {
+            panic!("unexpected error {err}");
+        };
+        assert_eq!(path, "root/a.bin");
+        assert_eq!(cid, &chunk.to_string());
+        assert_eq!(parent, &Some(file.to_string()));
+        assert!(err
+            .to_string()
+            .starts_with(&format!("root/a.bin: the block {chunk} linked by {file}")));
+
+        // the range walk reports the same context.
+        let err = ipld_write_range(&mut reader, file, 1500, Some(10), &mut Vec::new()).unwrap_err();
+        assert!(matches!(err, CarError::Traversal { path, .. } if path == file.to_string()));
+    }
 }
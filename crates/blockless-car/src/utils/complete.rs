@@ -0,0 +1,177 @@
+//! check the DAGs of the roots are complete, every linked block is in the CAR.
+use std::collections::HashSet;
+
+use cid::Cid;
+
+use crate::{
+    error::CarError,
+    reader::CarReader,
+    utils::{is_listing_root, IDENTITY_CODE},
+    Ipld,
+};
+
+/// why the linked block is missing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MissingKind {
+    /// the CAR is complete but doesn't carry the block, e.g. the partial CAR of a dag scope
+    /// linking the content stored elsewhere.
+    External,
+    /// the CAR is cut off, e.g. by an interrupted transfer, the last section is incomplete
+    /// or the root itself is missing.
+    Truncated,
+}
+
+/// the block linked but missing in the CAR.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingBlock {
+    pub cid: Cid,
+    /// the block linking the missing one, none for the roots.
+    pub parent: Option<Cid>,
+    pub kind: MissingKind,
+}
+
+/// the report of the completeness check.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompletenessReport {
+    /// the count of the blocks reached from the roots.
+    pub reachable: usize,
+    /// the last section of the CAR ends after the end of the file.
+    pub truncated: bool,
+    /// the missing blocks in the walk order.
+    pub missing: Vec<MissingBlock>,
+}
+
+impl CompletenessReport {
+    #[inline(always)]
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty()
+    }
+}
+
+fn is_eof(err: &CarError) -> bool {
+    matches!(err, CarError::IO(e) if e.kind() == std::io::ErrorKind::UnexpectedEof)
+}
+
+/// collect the links of the block in the order of the fields.
+pub(crate) fn collect_links(ipld: &Ipld, links: &mut Vec<Cid>) {
+    match ipld {
+        Ipld::Link(cid) => links.push(*cid),
+        Ipld::List(list) => list.iter().for_each(|i| collect_links(i, links)),
+        Ipld::Map(map) => map.values().for_each(|i| collect_links(i, links)),
+        _ => {}
+    }
+}
+
+/// walk the DAGs from the roots through the links of any codec and report the linked
+/// blocks missing in the CAR. the missing blocks are truncated if the last section is cut
+/// off, or if the root is missing, otherwise they are linked externally.
+/// the identity cids carry the data inline and are never missing.
+pub fn check_complete(reader: &mut impl CarReader) -> Result<CompletenessReport, CarError> {
+    let mut report = CompletenessReport::default();
+    if let Some(last) = reader.section_count()?.checked_sub(1) {
+        match reader.block_at(last) {
+            Err(e) if is_eof(&e) => report.truncated = true,
+            Err(e) => return Err(e),
+            Ok(_) => {}
+        }
+    }
+    let kind = match report.truncated {
+        true => MissingKind::Truncated,
+        false => MissingKind::External,
+    };
+    let limits = reader.limits();
+    let mut visited = HashSet::new();
+    let mut stack: Vec<(Cid, Option<Cid>)> = reader
+        .header()
+        .roots()
+        .into_iter()
+        .filter(|r| !is_listing_root(r))
+        .rev()
+        .map(|r| (r, None))
+        .collect();
+    while let Some((cid, parent)) = stack.pop() {
+        if cid.hash().code() == IDENTITY_CODE || !visited.insert(cid) {
+            continue;
+        }
+        let missing = |kind| MissingBlock { cid, parent, kind };
+        match reader.block_len(&cid)? {
+            None if parent.is_none() => report.missing.push(missing(MissingKind::Truncated)),
+            None => report.missing.push(missing(kind)),
+            Some(_) => match reader.ipld(&cid) {
+                Ok(ipld) => {
+                    report.reachable += 1;
+                    let mut links = Vec::new();
+                    collect_links(&ipld, &mut links);
+                    stack.extend(links.into_iter().rev().map(|l| (l, Some(cid))));
+                    limits.check_blocks(stack.len())?;
+                }
+                Err(e) if is_eof(&e) => report.missing.push(missing(MissingKind::Truncated)),
+                Err(e) => return Err(e),
+            },
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(all(test, feature = "unixfs-fs"))]
+mod test {
+    use super::*;
+    use crate::{
+        header::CarHeader,
+        pack_dir, reader,
+        utils::{raw_cid, PackOptions},
+        writer::{self, CarWriter},
+    };
+    use std::{fs, io::Cursor};
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_check_complete() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        fs::create_dir_all(root_dir.join("sub")).unwrap();
+        fs::write(root_dir.join("sub/a.txt"), b"hello").unwrap();
+        fs::write(root_dir.join("b.txt"), b"world").unwrap();
+        let mut car = Cursor::new(vec![]);
+        let root = pack_dir(&root_dir, &mut car, &PackOptions::new()).unwrap();
+        let car = car.into_inner();
+        let mut reader = reader::new_v1(Cursor::new(car.clone())).unwrap();
+        let report = check_complete(&mut reader).unwrap();
+        assert!(report.is_complete());
+        assert_eq!(report.reachable, reader.section_count().unwrap());
+
+        // the CAR without the file block links it externally.
+        let file = raw_cid(b"world", multicodec::Codec::Sha2_256);
+        let mut partial = Cursor::new(vec![]);
+        {
+            let mut writer = writer::new_v1(&mut partial, CarHeader::new_v1(vec![root])).unwrap();
+            for block in reader.blocks() {
+                let (cid, data) = block.unwrap();
+                if cid != file {
+                    writer.write_block(cid, data).unwrap();
+                }
+            }
+            writer.flush().unwrap();
+        }
+        let mut partial = reader::new_v1(Cursor::new(partial.into_inner())).unwrap();
+        let report = check_complete(&mut partial).unwrap();
+        assert!(!report.truncated);
+        assert_eq!(report.missing.len(), 1);
+        assert_eq!(report.missing[0].cid, file);
+        assert_eq!(report.missing[0].kind, MissingKind::External);
+        assert!(report.missing[0].parent.is_some());
+
+        // the blocks are written before their parents, the cut off CAR loses the root.
+        let mut reader = reader::new_v1(Cursor::new(car[..car.len() - 10].to_vec())).unwrap();
+        let report = check_complete(&mut reader).unwrap();
+        assert!(report.truncated);
+        assert_eq!(
+            report.missing,
+            vec![MissingBlock {
+                cid: root,
+                parent: None,
+                kind: MissingKind::Truncated
+            }]
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use std::fmt;
+
+use cid::{
+    multihash::{self, Blake2b256, Multihash, Sha2_256},
+    Cid,
+};
+use ipld::{pb::DagPbCodec, raw::RawCodec};
+
+use crate::error::CarError;
+
+/// the streaming hasher of the blocks, implement it to pack with the custom multihash code,
+/// e.g. the proprietary hash registered under the private-use multicodec.
+pub trait BlockHasher: Send + Sync {
+    /// the multihash code of the digest.
+    fn code(&self) -> u64;
+
+    /// feed the data to the hasher.
+    fn update(&mut self, data: &[u8]);
+
+    /// the digest of the data fed since the last finalize, the hasher is reset after.
+    /// the digest must not be longer than 64 bytes.
+    fn finalize(&mut self) -> Vec<u8>;
+
+    /// a new hasher of the same algorithm with the empty state.
+    fn fresh(&self) -> Box<dyn BlockHasher>;
+}
+
+impl fmt::Debug for dyn BlockHasher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "BlockHasher({:#x})", self.code())
+    }
+}
+
+/// the hasher of the multihash crate.
+struct MultihashHasher<H> {
+    code: u64,
+    inner: H,
+}
+
+impl<H> BlockHasher for MultihashHasher<H>
+where
+    H: multihash::Hasher + Default + Send + Sync + 'static,
+{
+    fn code(&self) -> u64 {
+        self.code
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        self.inner.update(data);
+    }
+
+    fn finalize(&mut self) -> Vec<u8> {
+        let digest = self.inner.finalize().to_vec();
+        self.inner.reset();
+        digest
+    }
+
+    fn fresh(&self) -> Box<dyn BlockHasher> {
+        Box::new(Self {
+            code: self.code,
+            inner: H::default(),
+        })
+    }
+}
+
+/// the built in hasher of the codec, return none for the codec not supported.
+pub fn codec_hasher(hasher_codec: multicodec::Codec) -> Option<Box<dyn BlockHasher>> {
+    let code = u64::from(hasher_codec.code());
+    match hasher_codec {
+        multicodec::Codec::Sha2_256 => Some(Box::new(MultihashHasher {
+            code,
+            inner: Sha2_256::default(),
+        })),
+        multicodec::Codec::Blake2b_256 => Some(Box::new(MultihashHasher {
+            code,
+            inner: Blake2b256::default(),
+        })),
+        _ => None,
+    }
+}
+
+/// finalize the hasher to the multihash.
+pub fn finalize_multihash(hasher: &mut dyn BlockHasher) -> Result<Multihash, CarError> {
+    let digest = hasher.finalize();
+    Multihash::wrap(hasher.code(), &digest).map_err(|e| CarError::Parsing(e.to_string()))
+}
+
+/// the multihash of the `data` with a fresh state of the `hasher`.
+pub fn hash_block(hasher: &dyn BlockHasher, data: &[u8]) -> Result<Multihash, CarError> {
+    let mut hasher = hasher.fresh();
+    hasher.update(data);
+    finalize_multihash(&mut *hasher)
+}
+
+/// panics if the codec has no built in hasher, see `codec_hasher`.
+fn digest(data: &[u8], hasher_codec: multicodec::Codec) -> Multihash {
+    let hasher = codec_hasher(hasher_codec)
+        .unwrap_or_else(|| panic!("no built in hasher of {hasher_codec:?}"));
+    // the built in digests fit in the multihash.
+    hash_block(&*hasher, data).unwrap()
+}
+
+#[inline(always)]
+pub fn empty_pb_cid(hasher_codec: multicodec::Codec) -> Cid {
+    pb_cid(&[], hasher_codec)
+}
+
+#[inline(always)]
+pub fn pb_cid(data: &[u8], hasher_codec: multicodec::Codec) -> Cid {
+    Cid::new_v1(DagPbCodec.into(), digest(data, hasher_codec))
+}
+
+#[inline(always)]
+pub fn raw_cid(data: &[u8], hasher_codec: multicodec::Codec) -> Cid {
+    Cid::new_v1(RawCodec.into(), digest(data, hasher_codec))
+}
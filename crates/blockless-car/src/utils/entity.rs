@@ -0,0 +1,117 @@
+//! export the partial CAR of the start of a file, like the gateway `entity-bytes=0:N`
+//! responses for the previews.
+use std::{collections::HashSet, io::Write};
+
+use cid::Cid;
+use ipld::raw::RawCodec;
+
+use crate::{
+    error::CarError,
+    header::CarHeader,
+    reader::CarReader,
+    unixfs::FileType,
+    utils::file_size,
+    writer::{CarWriter, StreamCarWriterV1},
+};
+
+/// write the CAR rooted at the file `cid` with the blocks of the file DAG covering its first
+/// `max_bytes`, the nodes from the root down and the leaves, in the depth first order.
+/// the root block is always written, the leaves starting after the `max_bytes` are not.
+/// return the count of the blocks written.
+pub fn export_entity_prefix<W>(
+    reader: &mut impl CarReader,
+    cid: Cid,
+    max_bytes: u64,
+    to: W,
+) -> Result<usize, CarError>
+where
+    W: Write,
+{
+    let path = cid.to_string();
+    let limits = reader.limits();
+    let raw_code: u64 = RawCodec.into();
+    let mut writer = StreamCarWriterV1::new(to, CarHeader::new_v1(vec![cid]));
+    let mut written = HashSet::new();
+    // the nodes with the offsets of their content in the file and their parents.
+    let mut stack = vec![(cid, 0u64, None)];
+    while let Some((cid, start, parent)) = stack.pop() {
+        let traversal = |e: CarError| e.traversal(&path, &cid, parent.as_ref());
+        let data = reader
+            .get_block(&cid)
+            .map_err(traversal)?
+            .ok_or_else(|| traversal(CarError::NotFound(format!("the block {cid}"))))?;
+        if written.insert(cid) {
+            writer.write_block(cid, &data)?;
+        }
+        if cid.codec() == raw_code {
+            continue;
+        }
+        let ufs = reader.unixfs(&cid).map_err(traversal)?;
+        if !matches!(ufs.file_type(), FileType::File | FileType::Raw) {
+            return Err(CarError::NotAFile(cid.to_string()));
+        }
+        let mut child_start = start + ufs.data().map_or(0, |d| d.len() as u64);
+        let block_sizes = ufs.block_sizes();
+        let mut children = Vec::new();
+        for (i, link) in ufs.links().iter().enumerate() {
+            if child_start >= max_bytes {
+                break;
+            }
+            children.push((link.hash, child_start, Some(cid)));
+            // the node without the `blocksizes` reads the size from the child.
+            child_start += match block_sizes.get(i) {
+                Some(size) if block_sizes.len() == ufs.links().len() => *size,
+                _ => file_size(reader, &link.hash)
+                    .map_err(|e| e.traversal(&path, &link.hash, Some(&cid)))?,
+            };
+        }
+        stack.extend(children.into_iter().rev());
+        limits.check_blocks(stack.len())?;
+    }
+    writer.flush()?;
+    Ok(written.len())
+}
+
+#[cfg(all(test, feature = "unixfs-fs"))]
+mod test {
+    use super::*;
+    use crate::{
+        reader,
+        utils::{ipld_write_range, pack_files_with, resolve_path, PackOptions},
+    };
+    use std::{fs, io::Cursor};
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_export_entity_prefix() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        fs::create_dir_all(&root_dir).unwrap();
+        // more chunks than the links of a node, so the DAG has two levels.
+        let content: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        fs::write(root_dir.join("video.bin"), &content).unwrap();
+        for raw_leaves in [true, false] {
+            let options = PackOptions::new()
+                .with_chunk_size(1000)
+                .with_raw_leaves(raw_leaves);
+            let mut car = Cursor::new(vec![]);
+            pack_files_with(&root_dir, &mut car, &options).unwrap();
+            let mut reader = reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+            let cid = resolve_path(&mut reader, "root/video.bin").unwrap();
+            // the root, the first inner node and the leaves.
+            for (max_bytes, blocks) in [(0, 1), (1, 3), (2500, 5), (200_000, 203)] {
+                let mut partial = vec![];
+                let n = export_entity_prefix(&mut reader, cid, max_bytes, &mut partial).unwrap();
+                assert_eq!(n, blocks, "{max_bytes}");
+                let mut partial = reader::new_v1(Cursor::new(partial)).unwrap();
+                assert_eq!(partial.header().roots(), vec![cid]);
+                let mut out = vec![];
+                ipld_write_range(&mut partial, cid, 0, Some(max_bytes), &mut out).unwrap();
+                assert_eq!(out, &content[..max_bytes as usize]);
+            }
+            let dir = resolve_path(&mut reader, "root").unwrap();
+            let err = export_entity_prefix(&mut reader, dir, 10, vec![]).unwrap_err();
+            assert!(matches!(err, CarError::NotAFile(_)));
+        }
+    }
+}
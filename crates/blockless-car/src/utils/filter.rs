@@ -0,0 +1,144 @@
+//! copy the subset of the blocks of a CAR, e.g. to strip the private subtrees before sharing.
+use std::{collections::HashSet, io::Write};
+
+use cid::Cid;
+
+use crate::{
+    error::CarError,
+    header::CarHeader,
+    reader::CarReader,
+    utils::{
+        complete::collect_links,
+        transform::{BlockTransform, KeepBlocks, Rewrite},
+    },
+    writer::{CarWriter, StreamCarWriterV1},
+};
+
+/// the report of `filter_car`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FilterReport {
+    pub roots: Vec<Cid>,
+    /// the count of the blocks written.
+    pub blocks: usize,
+    /// the listed cids not in the CAR.
+    pub missing: Vec<Cid>,
+}
+
+/// copy the blocks of the `cids` in the file order to the CARv1 of `to`. with `closed` the
+/// blocks linked from them are copied too, the links out of the CAR are skipped.
+/// the roots of the output are the roots of the CAR copied, or the listed cids in the CAR
+/// if none of the roots is.
+pub fn filter_car<W>(
+    reader: &mut impl CarReader,
+    cids: &[Cid],
+    closed: bool,
+    to: W,
+) -> Result<FilterReport, CarError>
+where
+    W: Write,
+{
+    filter_car_with(reader, cids, closed, &mut KeepBlocks, to)
+}
+
+/// `filter_car` with the blocks copied rewritten by the `transform`, see `transform_car`.
+pub fn filter_car_with<W>(
+    reader: &mut impl CarReader,
+    cids: &[Cid],
+    closed: bool,
+    transform: &mut impl BlockTransform,
+    to: W,
+) -> Result<FilterReport, CarError>
+where
+    W: Write,
+{
+    let mut report = FilterReport::default();
+    let mut keep = HashSet::new();
+    let limits = reader.limits();
+    let mut stack: Vec<Cid> = Vec::new();
+    for cid in cids.iter() {
+        match reader.block_len(cid)? {
+            Some(_) => stack.push(*cid),
+            None => report.missing.push(*cid),
+        }
+    }
+    let listed = stack.clone();
+    while let Some(cid) = stack.pop() {
+        if !keep.insert(cid) || !closed {
+            continue;
+        }
+        let mut links = Vec::new();
+        collect_links(&reader.ipld(&cid)?, &mut links);
+        for link in links {
+            if !keep.contains(&link) && reader.block_len(&link)?.is_some() {
+                stack.push(link);
+            }
+        }
+        limits.check_blocks(stack.len())?;
+    }
+    let mut rewrite = Rewrite::default();
+    rewrite.plan(reader, Some(&keep), transform)?;
+    let mut roots: Vec<Cid> = reader
+        .header()
+        .roots()
+        .into_iter()
+        .filter(|r| keep.contains(r))
+        .collect();
+    if roots.is_empty() {
+        roots = listed;
+    }
+    report.roots = rewrite.roots(roots);
+    let mut writer = StreamCarWriterV1::new(to, CarHeader::new_v1(report.roots.clone()));
+    (report.blocks, _) = rewrite.write(reader, Some(&keep), &mut writer, &mut HashSet::new())?;
+    writer.flush()?;
+    Ok(report)
+}
+
+#[cfg(all(test, feature = "unixfs-fs"))]
+mod test {
+    use super::*;
+    use crate::{
+        reader,
+        utils::{pack_files_with, resolve_path, PackOptions},
+    };
+    use std::{fs, io::Cursor};
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_filter_car() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        fs::create_dir_all(root_dir.join("public")).unwrap();
+        fs::create_dir_all(root_dir.join("private")).unwrap();
+        fs::write(root_dir.join("public/a.txt"), b"public").unwrap();
+        fs::write(root_dir.join("private/b.txt"), b"private").unwrap();
+        let mut car = Cursor::new(vec![]);
+        let root = pack_files_with(&root_dir, &mut car, &PackOptions::new()).unwrap();
+        let mut reader = reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+        let public = resolve_path(&mut reader, "root/public").unwrap();
+        let file = resolve_path(&mut reader, "root/public/a.txt").unwrap();
+        let private = resolve_path(&mut reader, "root/private/b.txt").unwrap();
+        let missing = crate::utils::raw_cid(b"missing", multicodec::Codec::Sha2_256);
+
+        // the listed blocks only, the root isn't listed so the listed cids are the roots.
+        let mut out = vec![];
+        let report = filter_car(&mut reader, &[public, missing], false, &mut out).unwrap();
+        assert_eq!(report.roots, vec![public]);
+        assert_eq!(report.blocks, 1);
+        assert_eq!(report.missing, vec![missing]);
+        let mut subset = reader::new_v1(Cursor::new(out)).unwrap();
+        assert!(subset.get_block(&file).unwrap().is_none());
+
+        // the subtree closed under the links.
+        let mut out = vec![];
+        let report = filter_car(&mut reader, &[public], true, &mut out).unwrap();
+        assert_eq!(report.blocks, 2);
+        let mut subset = reader::new_v1(Cursor::new(out)).unwrap();
+        assert_eq!(subset.get_block(&file).unwrap().unwrap(), b"public");
+        assert!(subset.get_block(&private).unwrap().is_none());
+
+        // the whole DAG from the root keeps the root.
+        let report = filter_car(&mut reader, &[root], true, vec![]).unwrap();
+        assert_eq!(report.roots, vec![root]);
+        assert_eq!(report.blocks, reader.section_count().unwrap());
+    }
+}
@@ -0,0 +1,225 @@
+//! the glob patterns of the paths in the pack, like gitignore.
+//! the pattern without `/` matches the name of the entry at any depth,
+//! otherwise it matches the path relative to the source root.
+use std::{fmt, path::Path, str::FromStr};
+
+use crate::error::CarError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Literal(char),
+    /// `?`, any char except the separator.
+    Any,
+    /// `*`, any chars except the separator.
+    Star,
+    /// `**`, any chars include the separator.
+    DoubleStar,
+    /// `**/`, empty or any directories.
+    Dirs,
+    /// `[a-z]`, `[!a-z]` or `[^a-z]`.
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+impl Token {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Token::Literal(l) => *l == c,
+            Token::Any => c != '/',
+            Token::Class { negated, ranges } => {
+                c != '/' && ranges.iter().any(|(lo, hi)| (*lo..=*hi).contains(&c)) != *negated
+            }
+            Token::Star | Token::DoubleStar | Token::Dirs => unreachable!("not a single char"),
+        }
+    }
+}
+
+/// the glob pattern supports `*`, `**`, `?`, the char classes `[...]` and the `\` escape.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Pattern {
+    source: String,
+    tokens: Vec<Token>,
+    // match the whole relative path instead of the name.
+    anchored: bool,
+}
+
+impl Pattern {
+    pub fn new(pattern: &str) -> Result<Self, CarError> {
+        let invalid = |reason: &str| CarError::InvalidPattern(format!("{pattern}: {reason}"));
+        let anchored = pattern.contains('/');
+        let body = pattern.strip_prefix('/').unwrap_or(pattern);
+        if body.is_empty() {
+            return Err(invalid("empty pattern"));
+        }
+        let mut chars = body.chars().peekable();
+        let mut tokens = Vec::new();
+        while let Some(c) = chars.next() {
+            let token = match c {
+                '?' => Token::Any,
+                '*' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    if chars.peek() == Some(&'/') {
+                        chars.next();
+                        Token::Dirs
+                    } else {
+                        Token::DoubleStar
+                    }
+                }
+                '*' => Token::Star,
+                '\\' => Token::Literal(chars.next().ok_or_else(|| invalid("trailing escape"))?),
+                '[' => {
+                    let negated = matches!(chars.peek(), Some('!' | '^'));
+                    if negated {
+                        chars.next();
+                    }
+                    let mut ranges = Vec::new();
+                    loop {
+                        let lo = match chars.next() {
+                            Some(']') if !ranges.is_empty() => break,
+                            Some('\\') => chars.next(),
+                            c => c,
+                        };
+                        let lo = lo.ok_or_else(|| invalid("unclosed char class"))?;
+                        let hi = match chars.peek() {
+                            Some('-') => {
+                                chars.next();
+                                match chars.next() {
+                                    Some(']') | None => {
+                                        return Err(invalid("unterminated range"));
+                                    }
+                                    Some(hi) => hi,
+                                }
+                            }
+                            _ => lo,
+                        };
+                        if hi < lo {
+                            return Err(invalid("reversed range"));
+                        }
+                        ranges.push((lo, hi));
+                    }
+                    Token::Class { negated, ranges }
+                }
+                c => Token::Literal(c),
+            };
+            tokens.push(token);
+        }
+        Ok(Self {
+            source: pattern.to_string(),
+            tokens,
+            anchored,
+        })
+    }
+
+    /// the pattern as given.
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// match the path relative to the source root, separated by `/`.
+    pub fn matches(&self, path: &str) -> bool {
+        let path = path.trim_matches('/');
+        let text = match self.anchored {
+            true => path,
+            false => path.rsplit('/').next().unwrap_or(path),
+        };
+        let text: Vec<char> = text.chars().collect();
+        match_tokens(&self.tokens, &text)
+    }
+
+    /// match the relative path, the components are joined by `/`.
+    pub fn matches_path(&self, path: impl AsRef<Path>) -> bool {
+        let path: Vec<_> = path
+            .as_ref()
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect();
+        self.matches(&path.join("/"))
+    }
+}
+
+fn match_tokens(tokens: &[Token], text: &[char]) -> bool {
+    match tokens.split_first() {
+        None => text.is_empty(),
+        Some((Token::Star, rest)) => {
+            for i in 0..=text.len() {
+                if match_tokens(rest, &text[i..]) {
+                    return true;
+                }
+                if text.get(i) == Some(&'/') {
+                    break;
+                }
+            }
+            false
+        }
+        Some((Token::DoubleStar, rest)) => (0..=text.len()).any(|i| match_tokens(rest, &text[i..])),
+        Some((Token::Dirs, rest)) => (0..=text.len())
+            .filter(|i| *i == 0 || text[i - 1] == '/')
+            .any(|i| match_tokens(rest, &text[i..])),
+        Some((token, rest)) => match text.split_first() {
+            Some((c, text)) if token.matches(*c) => match_tokens(rest, text),
+            _ => false,
+        },
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = CarError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s)
+    }
+}
+
+impl fmt::Debug for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Pattern({:?})", self.source)
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pattern_matches() {
+        let cases = [
+            ("*.mp4", "a.mp4", true),
+            ("*.mp4", "videos/2024/a.mp4", true),
+            ("*.mp4", "a.mp4.txt", false),
+            ("videos/*.mp4", "videos/a.mp4", true),
+            ("videos/*.mp4", "videos/2024/a.mp4", false),
+            ("/a.txt", "a.txt", true),
+            ("/a.txt", "b/a.txt", false),
+            ("videos/**", "videos/2024/a.mp4", true),
+            ("**/node_modules", "node_modules", true),
+            ("**/node_modules", "web/app/node_modules", true),
+            ("data/**/*.json", "data/x.json", true),
+            ("data/**/*.json", "data/a/b/x.json", true),
+            ("file?.txt", "file1.txt", true),
+            ("file?.txt", "file10.txt", false),
+            ("[a-c]*.log", "b.log", true),
+            ("[!a-c]*.log", "b.log", false),
+            ("[!a-c]*.log", "d.log", true),
+            ("\\*.txt", "*.txt", true),
+            ("\\*.txt", "a.txt", false),
+        ];
+        for (pattern, path, expected) in cases {
+            let p = Pattern::new(pattern).unwrap();
+            assert_eq!(p.matches(path), expected, "{pattern} {path}");
+        }
+        assert!(Pattern::new("*.mp4")
+            .unwrap()
+            .matches_path(Path::new("a").join("b.mp4")));
+        for invalid in ["", "/", "[a-", "[z-a]", "a\\"] {
+            assert!(Pattern::new(invalid).is_err(), "{invalid}");
+        }
+    }
+}
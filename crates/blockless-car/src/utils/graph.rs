@@ -0,0 +1,223 @@
+//! the block graph of the DAGs of a CAR, for the visualization of the DAG structure.
+use std::collections::{HashSet, VecDeque};
+
+use cid::Cid;
+use ipld::{pb::DagPbCodec, raw::RawCodec};
+
+use crate::{
+    error::CarError,
+    reader::CarReader,
+    section::decode_block,
+    unixfs::{FileType, UnixFs},
+    utils::complete::collect_links,
+};
+
+/// the kind of the block in the graph.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphNodeKind {
+    /// the dag-pb block of the unixfs type.
+    Unixfs(FileType),
+    /// the raw block, e.g. the file chunk.
+    Raw,
+    /// the block of another codec.
+    Codec(u64),
+    /// the linked block not in the CAR.
+    Missing,
+}
+
+/// the block in the graph.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphNode {
+    pub cid: Cid,
+    pub kind: GraphNodeKind,
+    /// the bytes of the block data, 0 if missing.
+    pub size: usize,
+    /// the depth of the first path reaching the block, 0 for the roots.
+    pub depth: usize,
+    /// the links of the block are not followed for the depth limit.
+    pub truncated: bool,
+}
+
+/// the link between the blocks, the name of the dag-pb link.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphEdge {
+    pub from: Cid,
+    pub to: Cid,
+    pub name: Option<String>,
+}
+
+/// the graph of the blocks reached from the roots.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DagGraph {
+    /// the blocks in the breadth first order, each once.
+    pub nodes: Vec<GraphNode>,
+    /// the links in the order of the nodes and their fields.
+    pub edges: Vec<GraphEdge>,
+}
+
+/// build the graph of the blocks reached from the `roots` through the links of any codec,
+/// the links of the blocks at the `max_depth` are not followed, none for the whole DAGs.
+pub fn dag_graph(
+    reader: &mut impl CarReader,
+    roots: &[Cid],
+    max_depth: Option<usize>,
+) -> Result<DagGraph, CarError> {
+    let pb_code: u64 = DagPbCodec.into();
+    let raw_code: u64 = RawCodec.into();
+    let limits = reader.limits();
+    let mut graph = DagGraph::default();
+    let mut visited = HashSet::new();
+    let mut queue: VecDeque<(Cid, usize)> = roots.iter().map(|r| (*r, 0)).collect();
+    while let Some((cid, depth)) = queue.pop_front() {
+        if !visited.insert(cid) {
+            continue;
+        }
+        let size = match reader.block_len(&cid)? {
+            Some(size) => size,
+            None => {
+                graph.nodes.push(GraphNode {
+                    cid,
+                    kind: GraphNodeKind::Missing,
+                    size: 0,
+                    depth,
+                    truncated: false,
+                });
+                continue;
+            }
+        };
+        let (kind, links) = match cid.codec() {
+            code if code == raw_code => (GraphNodeKind::Raw, Vec::new()),
+            code if code == pb_code => {
+                let ufs = reader.unixfs(&cid)?;
+                let links = ufs
+                    .links()
+                    .iter()
+                    .map(|l| (l.hash, Some(l.name.clone())))
+                    .collect();
+                (GraphNodeKind::Unixfs(ufs.file_type()), links)
+            }
+            code => {
+                let mut links = Vec::new();
+                collect_links(&reader.ipld(&cid)?, &mut links);
+                let links = links.into_iter().map(|l| (l, None)).collect();
+                (GraphNodeKind::Codec(code), links)
+            }
+        };
+        let truncated = max_depth.is_some_and(|max| depth >= max) && !links.is_empty();
+        graph.nodes.push(GraphNode {
+            cid,
+            kind,
+            size,
+            depth,
+            truncated,
+        });
+        if truncated {
+            continue;
+        }
+        for (to, name) in links {
+            graph.edges.push(GraphEdge {
+                from: cid,
+                to,
+                name,
+            });
+            queue.push_back((to, depth + 1));
+        }
+        limits.check_blocks(queue.len())?;
+    }
+    Ok(graph)
+}
+
+/// the kind of the block decoding only the `data` of the block itself, the dag-pb block
+/// which can't be decoded as unixfs is of the dag-pb codec.
+pub(crate) fn data_kind(cid: &Cid, data: Vec<u8>) -> GraphNodeKind {
+    let pb_code: u64 = DagPbCodec.into();
+    let raw_code: u64 = RawCodec.into();
+    match cid.codec() {
+        code if code == raw_code => GraphNodeKind::Raw,
+        code if code == pb_code => {
+            match decode_block(*cid, data).and_then(|ipld| UnixFs::try_from((*cid, ipld))) {
+                Ok(ufs) => GraphNodeKind::Unixfs(ufs.file_type()),
+                Err(_) => GraphNodeKind::Codec(code),
+            }
+        }
+        code => GraphNodeKind::Codec(code),
+    }
+}
+
+/// the kind of the block of the `cid` decoding only the block itself, e.g. whether a root
+/// is a unixfs directory, a unixfs file, a raw leaf or the IPLD of another codec.
+pub fn block_kind(reader: &mut impl CarReader, cid: &Cid) -> Result<GraphNodeKind, CarError> {
+    Ok(match reader.get_block(cid)? {
+        Some(data) => data_kind(cid, data),
+        None => GraphNodeKind::Missing,
+    })
+}
+
+#[cfg(all(test, feature = "unixfs-fs"))]
+mod test {
+    use super::*;
+    use crate::{
+        reader,
+        utils::{pack_files_with, resolve_path, PackOptions},
+    };
+    use std::{fs, io::Cursor};
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_dag_graph() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        fs::create_dir_all(root_dir.join("sub")).unwrap();
+        fs::write(root_dir.join("sub/a.txt"), b"hello").unwrap();
+        // the duplicate content is one node with two edges.
+        fs::write(root_dir.join("b.txt"), b"hello").unwrap();
+        let mut car = Cursor::new(vec![]);
+        let root = pack_files_with(&root_dir, &mut car, &PackOptions::new()).unwrap();
+        let mut reader = reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+        let file = resolve_path(&mut reader, "root/b.txt").unwrap();
+
+        let graph = dag_graph(&mut reader, &[root], None).unwrap();
+        assert_eq!(graph.nodes.len(), reader.section_count().unwrap());
+        assert_eq!(graph.nodes[0].cid, root);
+        assert_eq!(
+            graph.nodes[0].kind,
+            GraphNodeKind::Unixfs(FileType::Directory)
+        );
+        let to_file: Vec<&GraphEdge> = graph.edges.iter().filter(|e| e.to == file).collect();
+        assert_eq!(to_file.len(), 2);
+        let leaf = graph.nodes.iter().find(|n| n.cid == file).unwrap();
+        assert_eq!((leaf.kind, leaf.size), (GraphNodeKind::Raw, 5));
+
+        // the wrapping directory at the depth 1 is not expanded.
+        let graph = dag_graph(&mut reader, &[root], Some(1)).unwrap();
+        assert_eq!(graph.nodes.len(), 2);
+        assert!(graph.nodes[1].truncated);
+        assert_eq!(graph.edges.len(), 1);
+
+        let missing = crate::utils::raw_cid(b"missing", multicodec::Codec::Sha2_256);
+        let graph = dag_graph(&mut reader, &[missing], None).unwrap();
+        assert_eq!(graph.nodes[0].kind, GraphNodeKind::Missing);
+    }
+
+    #[test]
+    fn test_block_kind() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        fs::create_dir_all(&root_dir).unwrap();
+        fs::write(root_dir.join("a.txt"), b"hello").unwrap();
+        let mut car = Cursor::new(vec![]);
+        let root = pack_files_with(&root_dir, &mut car, &PackOptions::new()).unwrap();
+        let mut reader = reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+        let file = resolve_path(&mut reader, "root/a.txt").unwrap();
+        assert_eq!(
+            block_kind(&mut reader, &root).unwrap(),
+            GraphNodeKind::Unixfs(FileType::Directory)
+        );
+        assert_eq!(block_kind(&mut reader, &file).unwrap(), GraphNodeKind::Raw);
+        let missing = crate::utils::raw_cid(b"missing", multicodec::Codec::Sha2_256);
+        assert_eq!(
+            block_kind(&mut reader, &missing).unwrap(),
+            GraphNodeKind::Missing
+        );
+    }
+}
@@ -0,0 +1,248 @@
+//! the unixfs hamt sharded directories, like go-unixfs the entries are placed in the
+//! slots by the murmur3 hash of their names, the link names are prefixed with the
+//! hex slot index and a link with only the prefix points to the child shard.
+use cid::Cid;
+
+use crate::{
+    error::CarError,
+    store::BlockStore,
+    unixfs::{FileType, Link, UnixFs},
+};
+
+/// the multicodec of the murmur3-x64-64 hash.
+pub(crate) const HAMT_HASH_TYPE: u64 = 0x22;
+
+/// the 64-bit murmur3 hash of go-unixfs, the first half of murmur3-x64-128.
+pub(crate) fn hamt_hash(data: &[u8]) -> [u8; 8] {
+    const C1: u64 = 0x87c37b91114253d5;
+    const C2: u64 = 0x4cf5ad432745937f;
+    fn fmix(mut k: u64) -> u64 {
+        k ^= k >> 33;
+        k = k.wrapping_mul(0xff51afd7ed558ccd);
+        k ^= k >> 33;
+        k = k.wrapping_mul(0xc4ceb9fe1a85ec53);
+        k ^ (k >> 33)
+    }
+    let mix_k1 = |k1: u64| k1.wrapping_mul(C1).rotate_left(31).wrapping_mul(C2);
+    let mix_k2 = |k2: u64| k2.wrapping_mul(C2).rotate_left(33).wrapping_mul(C1);
+    let (mut h1, mut h2) = (0u64, 0u64);
+    let mut blocks = data.chunks_exact(16);
+    for block in blocks.by_ref() {
+        let k1 = u64::from_le_bytes(block[..8].try_into().unwrap());
+        let k2 = u64::from_le_bytes(block[8..].try_into().unwrap());
+        h1 ^= mix_k1(k1);
+        h1 = h1.rotate_left(27).wrapping_add(h2);
+        h1 = h1.wrapping_mul(5).wrapping_add(0x52dce729);
+        h2 ^= mix_k2(k2);
+        h2 = h2.rotate_left(31).wrapping_add(h1);
+        h2 = h2.wrapping_mul(5).wrapping_add(0x38495ab5);
+    }
+    let tail = blocks.remainder();
+    let (mut k1, mut k2) = (0u64, 0u64);
+    for (i, b) in tail.iter().enumerate() {
+        if i < 8 {
+            k1 ^= (*b as u64) << (i * 8);
+        } else {
+            k2 ^= (*b as u64) << ((i - 8) * 8);
+        }
+    }
+    if tail.len() > 8 {
+        h2 ^= mix_k2(k2);
+    }
+    if !tail.is_empty() {
+        h1 ^= mix_k1(k1);
+    }
+    h1 ^= data.len() as u64;
+    h2 ^= data.len() as u64;
+    h1 = h1.wrapping_add(h2);
+    h2 = h2.wrapping_add(h1);
+    h1 = fmix(h1);
+    h2 = fmix(h2);
+    h1 = h1.wrapping_add(h2);
+    h1.to_be_bytes()
+}
+
+/// the bits of the slot index and the length of the hex prefix of the shard.
+fn shard_layout(shard: &UnixFs) -> Result<(u32, usize), CarError> {
+    match shard.fanout() {
+        Some(fanout) if fanout >= 2 && fanout.is_power_of_two() => {
+            Ok((fanout.trailing_zeros(), format!("{:X}", fanout - 1).len()))
+        }
+        fanout => Err(CarError::InvalidSection(format!(
+            "the hamt shard {} has invalid fanout {fanout:?}",
+            shard.cid().map_or_else(String::new, |c| c.to_string())
+        ))),
+    }
+}
+
+/// the slot index at the `depth`, the bits of the hash are consumed from the start.
+fn slot_index(hash: &[u8; 8], depth: usize, bits: u32) -> Option<usize> {
+    let start = depth * bits as usize;
+    if start + bits as usize > 64 {
+        return None;
+    }
+    Some(((u64::from_be_bytes(*hash) << start) >> (64 - bits)) as usize)
+}
+
+fn read_shard(reader: &mut impl BlockStore, cid: Cid) -> Result<UnixFs, CarError> {
+    let shard: UnixFs = (cid, reader.get_ipld(&cid)?).try_into()?;
+    if shard.file_type() != FileType::HAMTShard {
+        return Err(CarError::InvalidSection(format!(
+            "the block {cid} is not hamt shard"
+        )));
+    }
+    Ok(shard)
+}
+
+fn flatten_shard(
+    reader: &mut impl BlockStore,
+    shard: &UnixFs,
+    depth: usize,
+    entries: &mut Vec<Link>,
+) -> Result<(), CarError> {
+    let (bits, prefix_len) = shard_layout(shard)?;
+    // the hash is used up, the shard links back to itself or the parent.
+    if depth * bits as usize >= 64 {
+        return Err(CarError::InvalidSection(
+            "the hamt shard is too deep".into(),
+        ));
+    }
+    for link in shard.links.iter() {
+        match link.name.get(prefix_len..) {
+            Some("") => {
+                let child = read_shard(reader, link.hash)?;
+                flatten_shard(reader, &child, depth + 1, entries)?;
+            }
+            Some(name) => entries.push(Link {
+                name: name.to_string(),
+                ..link.clone()
+            }),
+            None => {
+                return Err(CarError::InvalidSection(format!(
+                    "the hamt link {} has no slot prefix",
+                    link.name
+                )))
+            }
+        }
+    }
+    Ok(())
+}
+
+/// the entries of the directory node, the hamt shards are flattened and the
+/// slot prefixes are stripped from the names, the plain directory returns its links.
+pub fn dir_entries(reader: &mut impl BlockStore, dir: &UnixFs) -> Result<Vec<Link>, CarError> {
+    match dir.file_type() {
+        FileType::HAMTShard => {
+            let mut entries = Vec::new();
+            flatten_shard(reader, dir, 0, &mut entries)?;
+            Ok(entries)
+        }
+        _ => Ok(dir.links.clone()),
+    }
+}
+
+/// find the entry `name` in the directory node, only the shards on the hash path are read.
+pub fn find_entry(
+    reader: &mut impl BlockStore,
+    dir: &UnixFs,
+    name: &str,
+) -> Result<Option<Link>, CarError> {
+    match dir.file_type() {
+        FileType::Directory => return Ok(dir.links.iter().find(|l| l.name == name).cloned()),
+        FileType::HAMTShard => {}
+        _ => return Ok(None),
+    }
+    if dir.hash_type() != Some(HAMT_HASH_TYPE) {
+        return Err(CarError::NotImplemented(format!(
+            "hamt hash type {:?}",
+            dir.hash_type()
+        )));
+    }
+    let hash = hamt_hash(name.as_bytes());
+    let mut child;
+    let mut shard = dir;
+    for depth in 0.. {
+        let (bits, prefix_len) = shard_layout(shard)?;
+        let Some(idx) = slot_index(&hash, depth, bits) else {
+            break;
+        };
+        let prefix = format!("{idx:0prefix_len$X}");
+        let Some(link) = shard.links.iter().find(|l| l.name.starts_with(&prefix)) else {
+            break;
+        };
+        if link.name.len() > prefix_len {
+            let found = link.name[prefix_len..] == *name;
+            return Ok(found.then(|| Link {
+                name: name.to_string(),
+                ..link.clone()
+            }));
+        }
+        child = read_shard(reader, link.hash)?;
+        shard = &child;
+    }
+    Ok(None)
+}
+
+#[cfg(all(test, feature = "unixfs-fs", feature = "cli-utils"))]
+mod test {
+    use super::*;
+    use crate::{
+        reader::{self, CarReader},
+        utils::{list_call, pack_files_with, PackOptions},
+    };
+    use std::{cell::RefCell, fs, io::Cursor};
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_hamt_hash() {
+        assert_eq!(hamt_hash(b""), [0; 8]);
+        assert_eq!(hamt_hash(b"hello"), 0xcbd8a7b341bd9b02u64.to_be_bytes());
+        let fox = b"The quick brown fox jumps over the lazy dog";
+        assert_eq!(hamt_hash(fox), 0xe34bbc7bbc071b6cu64.to_be_bytes());
+    }
+
+    #[test]
+    fn test_dir_entries() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        fs::create_dir_all(&root_dir).unwrap();
+        for i in 0..300 {
+            fs::write(root_dir.join(format!("file-{i}.txt")), i.to_string()).unwrap();
+        }
+        let options = PackOptions::new().with_hamt_threshold(Some(1024));
+        let mut car = Cursor::new(vec![]);
+        let root = pack_files_with(&root_dir, &mut car, &options).unwrap();
+        let mut reader = reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+        let top: UnixFs = (root, reader.ipld(&root).unwrap()).try_into().unwrap();
+        let shard = read_shard(&mut reader, top.links[0].hash).unwrap();
+
+        let mut names: Vec<_> = dir_entries(&mut reader, &shard)
+            .unwrap()
+            .into_iter()
+            .map(|l| l.name)
+            .collect();
+        names.sort();
+        let mut expected: Vec<_> = (0..300).map(|i| format!("file-{i}.txt")).collect();
+        expected.sort();
+        assert_eq!(names, expected);
+
+        let link = find_entry(&mut reader, &shard, "file-42.txt")
+            .unwrap()
+            .unwrap();
+        assert_eq!(link.name, "file-42.txt");
+        assert_eq!(
+            reader.ipld(&link.hash).unwrap(),
+            crate::Ipld::Bytes(b"42".to_vec())
+        );
+        assert_eq!(find_entry(&mut reader, &shard, "missing").unwrap(), None);
+
+        let paths = RefCell::new(Vec::new());
+        list_call(&mut reader, |_, path| {
+            paths.borrow_mut().push(path.to_string())
+        })
+        .unwrap();
+        let paths = paths.into_inner();
+        assert_eq!(paths.len(), 302);
+        assert!(paths.contains(&format!("{root}/root/file-42.txt")));
+    }
+}
@@ -0,0 +1,529 @@
+//! the CARv2 index, embedded after the data payload or in the sidecar `.idx` file like
+//! `go-car index` writes, in the `IndexSorted` or the `MultihashIndexSorted` format.
+use std::{
+    collections::HashMap,
+    io::{Read, Seek, SeekFrom},
+};
+
+use cid::Cid;
+use integer_encoding::VarIntReader;
+
+use crate::{
+    error::CarError,
+    header::CarHeader,
+    reader::{read_block, ReaderLimits},
+    section::{read_section_header, read_section_header_checked, Section},
+    store::BlockStore,
+    utils::{probe::CARV2_HEADER_SIZE, CarV2Header, CARV2_PRAGMA},
+};
+
+/// the multicodec of the index keyed by the digests.
+pub const INDEX_SORTED_CODEC: u64 = 0x0400;
+/// the multicodec of the index keyed by the multihash codes and the digests.
+pub const MULTIHASH_INDEX_SORTED_CODEC: u64 = 0x0401;
+
+/// the decoded index, the offsets of the sections in the data payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CarIndex {
+    codec: u64,
+    /// the offsets by the multihash code and the digest, the code is none in `IndexSorted`.
+    offsets: HashMap<(Option<u64>, Vec<u8>), u64>,
+}
+
+fn read_u32(r: &mut impl Read) -> Result<u32, CarError> {
+    let mut buf = [0u8; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64(r: &mut impl Read) -> Result<u64, CarError> {
+    let mut buf = [0u8; 8];
+    r.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+/// decode the buckets of the records of the same width, the digest and the u64 offset.
+fn read_buckets(
+    r: &mut impl Read,
+    code: Option<u64>,
+    offsets: &mut HashMap<(Option<u64>, Vec<u8>), u64>,
+) -> Result<(), CarError> {
+    for _ in 0..read_u32(r)? {
+        let width = read_u32(r)? as u64;
+        let len = read_u64(r)?;
+        if width <= 8 || len % width != 0 {
+            return Err(CarError::Parsing(format!(
+                "invalid index bucket of {len} bytes with the width {width}"
+            )));
+        }
+        // the records are read one by one, the length is not trusted for the allocation.
+        let mut record = vec![0u8; width as usize];
+        for _ in 0..len / width {
+            r.read_exact(&mut record)?;
+            let (digest, offset) = record.split_at(width as usize - 8);
+            let offset = u64::from_le_bytes(offset.try_into().unwrap());
+            offsets.entry((code, digest.to_vec())).or_insert(offset);
+        }
+    }
+    Ok(())
+}
+
+impl CarIndex {
+    /// decode the index from its multicodec varint on.
+    pub fn decode(r: &mut impl Read) -> Result<Self, CarError> {
+        let codec: u64 = r.read_varint()?;
+        let mut offsets = HashMap::new();
+        match codec {
+            INDEX_SORTED_CODEC => read_buckets(r, None, &mut offsets)?,
+            MULTIHASH_INDEX_SORTED_CODEC => {
+                for _ in 0..read_u32(r)? {
+                    let code = read_u64(r)?;
+                    read_buckets(r, Some(code), &mut offsets)?;
+                }
+            }
+            codec => {
+                return Err(CarError::NotImplemented(format!(
+                    "the index codec 0x{codec:x}"
+                )))
+            }
+        }
+        Ok(Self { codec, offsets })
+    }
+
+    #[inline(always)]
+    pub fn codec(&self) -> u64 {
+        self.codec
+    }
+
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    /// the offset of the section of the cid in the data payload, matched by the multihash.
+    pub fn get(&self, cid: &Cid) -> Option<u64> {
+        let hash = cid.hash();
+        let code = match self.codec {
+            MULTIHASH_INDEX_SORTED_CODEC => Some(hash.code()),
+            _ => None,
+        };
+        self.offsets.get(&(code, hash.digest().to_vec())).copied()
+    }
+}
+
+/// where the location of the block is from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexSource {
+    /// the index embedded in the CARv2.
+    Embedded,
+    /// the index given by the caller, e.g. the sidecar `.idx` file.
+    Sidecar,
+    /// the sections are scanned without the index.
+    Scan,
+}
+
+/// the location of the block in the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockLocation {
+    /// the offset of the length varint of the section in the file.
+    pub offset: u64,
+    /// the bytes of the section, the length varint, the cid and the block data.
+    pub length: u64,
+    pub source: IndexSource,
+}
+
+/// the start and the end offsets of the data payload of the CARv1 or CARv2 file, with the
+/// index embedded in the CARv2, which is read if `read_index` is set.
+fn data_payload<R>(r: &mut R, read_index: bool) -> Result<(u64, u64, Option<CarIndex>), CarError>
+where
+    R: Read + Seek,
+{
+    let file_len = r.seek(SeekFrom::End(0))?;
+    r.rewind()?;
+    let mut pragma = [0u8; CARV2_PRAGMA.len()];
+    let is_v2 = r.read_exact(&mut pragma).is_ok() && pragma == CARV2_PRAGMA;
+    if !is_v2 {
+        return Ok((0, file_len, None));
+    }
+    let mut header = [0u8; CARV2_HEADER_SIZE];
+    r.read_exact(&mut header)?;
+    let v2 = CarV2Header::decode(&header);
+    let end = v2.data_offset.saturating_add(v2.data_size).min(file_len);
+    let embedded = match read_index && v2.index_offset > 0 {
+        true => {
+            r.seek(SeekFrom::Start(v2.index_offset))?;
+            Some(CarIndex::decode(r)?)
+        }
+        false => None,
+    };
+    Ok((v2.data_offset, end, embedded))
+}
+
+/// read the section header at the position, return the cid and the section length.
+fn section_at<R>(r: &mut R, pos: u64, end: u64) -> Result<Option<(Cid, u64)>, CarError>
+where
+    R: Read + Seek,
+{
+    r.seek(SeekFrom::Start(pos))?;
+    let header = read_section_header((&mut *r).take(end.saturating_sub(pos)))?;
+    let prefix = r.stream_position()? - pos;
+    Ok(header.map(|(cid, data_len)| (cid, prefix + data_len as u64)))
+}
+
+/// locate the section of the block in the CARv1 or CARv2 file by the `sidecar` index,
+/// or the index embedded in the CARv2, or else by scanning the sections.
+/// the section the index points to must have the multihash of the cid.
+pub fn locate_block<R>(
+    r: &mut R,
+    sidecar: Option<&CarIndex>,
+    cid: &Cid,
+) -> Result<Option<BlockLocation>, CarError>
+where
+    R: Read + Seek,
+{
+    let (start, end, embedded) = data_payload(r, sidecar.is_none())?;
+    let index = match (sidecar, &embedded) {
+        (Some(index), _) => Some((index, IndexSource::Sidecar)),
+        (None, Some(index)) => Some((index, IndexSource::Embedded)),
+        (None, None) => None,
+    };
+    if let Some((index, source)) = index {
+        let offset = match index.get(cid) {
+            Some(offset) => start + offset,
+            None => return Ok(None),
+        };
+        return match section_at(r, offset, end)? {
+            Some((found, length)) if found.hash() == cid.hash() => Ok(Some(BlockLocation {
+                offset,
+                length,
+                source,
+            })),
+            Some((found, _)) => Err(CarError::InvalidSection(format!(
+                "the index points {cid} to the section of {found} at {offset}"
+            ))),
+            None => Err(CarError::InvalidSection(format!(
+                "the index points {cid} to {offset} out of the data payload"
+            ))),
+        };
+    }
+
+    r.seek(SeekFrom::Start(start))?;
+    if read_block(&mut *r)?.is_none() {
+        return Err(CarError::Parsing("Invalid Header".into()));
+    }
+    let mut offset = r.stream_position()?;
+    while let Some((found, length)) = section_at(r, offset, end)? {
+        if found == *cid {
+            return Ok(Some(BlockLocation {
+                offset,
+                length,
+                source: IndexSource::Scan,
+            }));
+        }
+        offset += length;
+    }
+    Ok(None)
+}
+
+/// the store of the blocks in the CARv1 or CARv2 file located by the index, the index
+/// embedded in the CARv2 or the sidecar index of `with_index`, so the sections are not
+/// scanned on open. without the index the sections are scanned once, on the first lookup.
+/// the store is read only, the blocks put are rejected.
+pub struct IndexedBlockStore<R> {
+    inner: R,
+    header: CarHeader,
+    /// the start of the data payload the index offsets are from, and its end.
+    data_offset: u64,
+    end: u64,
+    /// the offset of the first section after the header.
+    first_section: u64,
+    index: Option<CarIndex>,
+    /// the sections by the cid, scanned on the first lookup without the index.
+    scanned: Option<HashMap<Cid, Section>>,
+    limits: ReaderLimits,
+    /// the count of the block data read.
+    blocks_read: u64,
+}
+
+impl<R> IndexedBlockStore<R>
+where
+    R: Read + Seek,
+{
+    pub fn new(inner: R) -> Result<Self, CarError> {
+        Self::new_with_limits(inner, ReaderLimits::default())
+    }
+
+    /// open the store with the `limits`, the header and the embedded index are read.
+    pub fn new_with_limits(mut inner: R, limits: ReaderLimits) -> Result<Self, CarError> {
+        let (data_offset, end, index) = data_payload(&mut inner, true)?;
+        inner.seek(SeekFrom::Start(data_offset))?;
+        let payload = (&mut inner).take(end.saturating_sub(data_offset));
+        let header = CarHeader::read_header_with_limits(payload, &limits)?;
+        let first_section = inner.stream_position()?;
+        Ok(Self {
+            inner,
+            header,
+            data_offset,
+            end,
+            first_section,
+            index,
+            scanned: None,
+            limits,
+            blocks_read: 0,
+        })
+    }
+
+    /// locate the blocks by the sidecar `index` instead of the embedded index.
+    pub fn with_index(mut self, index: CarIndex) -> Self {
+        self.index = Some(index);
+        self
+    }
+
+    #[inline]
+    pub fn header(&self) -> &CarHeader {
+        &self.header
+    }
+
+    /// the blocks are located by the index rather than the scan of the sections.
+    #[inline]
+    pub fn is_indexed(&self) -> bool {
+        self.index.is_some()
+    }
+
+    /// the count of the block data read, a block read twice is counted twice.
+    #[inline]
+    pub fn blocks_read(&self) -> u64 {
+        self.blocks_read
+    }
+
+    /// read the section header at the `offset`, return the section of the block data.
+    fn section_at(&mut self, offset: u64) -> Result<Option<Section>, CarError> {
+        self.inner.seek(SeekFrom::Start(offset))?;
+        let limits = self.limits;
+        let input = (&mut self.inner).take(self.end.saturating_sub(offset));
+        let Some((cid, len)) =
+            read_section_header_checked(input, |len, _| limits.check_section_size(len))?
+        else {
+            return Ok(None);
+        };
+        let pos = self.inner.stream_position()?;
+        if pos + len as u64 > self.end {
+            return Err(CarError::InvalidSection(format!(
+                "the section of {cid} at {offset} runs over the data payload"
+            )));
+        }
+        Ok(Some(Section::new(cid, pos, len)))
+    }
+
+    /// the section of the block, by the index or by the sections scanned.
+    fn locate(&mut self, cid: &Cid) -> Result<Option<Section>, CarError> {
+        if let Some(index) = &self.index {
+            let offset = match index.get(cid) {
+                Some(offset) => self.data_offset + offset,
+                None => return Ok(None),
+            };
+            // the index is keyed by the multihash, the blocks of the same multihash are equal.
+            return match self.section_at(offset)? {
+                Some(section) if section.cid().hash() == cid.hash() => Ok(Some(section)),
+                Some(section) => Err(CarError::InvalidSection(format!(
+                    "the index points {cid} to the section of {} at {offset}",
+                    section.cid()
+                ))),
+                None => Err(CarError::InvalidSection(format!(
+                    "the index points {cid} to {offset} out of the data payload"
+                ))),
+            };
+        }
+        if self.scanned.is_none() {
+            let mut scanned = HashMap::new();
+            let mut offset = self.first_section;
+            let mut count = 0;
+            while let Some(section) = self.section_at(offset)? {
+                count += 1;
+                self.limits.check_sections(count)?;
+                self.limits.check_blocks(scanned.len() + 1)?;
+                offset = section.pos() + section.len() as u64;
+                scanned.entry(section.cid()).or_insert(section);
+            }
+            self.scanned = Some(scanned);
+        }
+        Ok(self.scanned.as_ref().and_then(|s| s.get(cid)).cloned())
+    }
+}
+
+impl<R> BlockStore for IndexedBlockStore<R>
+where
+    R: Read + Seek,
+{
+    fn get(&mut self, cid: &Cid) -> Result<Option<Vec<u8>>, CarError> {
+        let Some(section) = self.locate(cid)? else {
+            return Ok(None);
+        };
+        let data = section.read_data(&mut self.inner)?;
+        self.blocks_read += 1;
+        Ok(Some(data))
+    }
+
+    fn put(&mut self, cid: Cid, _data: Vec<u8>) -> Result<(), CarError> {
+        Err(CarError::NotImplemented(format!(
+            "put the block {cid} to the indexed CAR"
+        )))
+    }
+
+    #[inline]
+    fn get_len(&mut self, cid: &Cid) -> Result<Option<usize>, CarError> {
+        Ok(self.locate(cid)?.map(|s| s.len()))
+    }
+
+    #[inline]
+    fn block_limits(&self) -> ReaderLimits {
+        self.limits
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::reader::{self, CarReader};
+    use integer_encoding::VarInt;
+    use std::io::Cursor;
+
+    /// encode the `IndexSorted` of one bucket, or the `MultihashIndexSorted` of one code.
+    fn encode_index(codec: u64, records: &[(Cid, u64)]) -> Vec<u8> {
+        let digests: Vec<&[u8]> = records.iter().map(|(c, _)| c.hash().digest()).collect();
+        let mut buf = codec.encode_var_vec();
+        if codec == MULTIHASH_INDEX_SORTED_CODEC {
+            buf.extend_from_slice(&1u32.to_le_bytes());
+            buf.extend_from_slice(&records[0].0.hash().code().to_le_bytes());
+        }
+        let width = digests[0].len() as u32 + 8;
+        buf.extend_from_slice(&1u32.to_le_bytes());
+        buf.extend_from_slice(&width.to_le_bytes());
+        buf.extend_from_slice(&(width as u64 * records.len() as u64).to_le_bytes());
+        for (digest, (_, offset)) in digests.iter().zip(records.iter()) {
+            buf.extend_from_slice(digest);
+            buf.extend_from_slice(&offset.to_le_bytes());
+        }
+        buf
+    }
+
+    /// wrap the CARv1 to the CARv2 of the embedded `index` after the data payload.
+    fn encode_v2(car: &[u8], index: &[u8]) -> Vec<u8> {
+        let mut v2 = CARV2_PRAGMA.to_vec();
+        v2.extend_from_slice(&[0u8; 16]);
+        v2.extend_from_slice(&51u64.to_le_bytes());
+        v2.extend_from_slice(&(car.len() as u64).to_le_bytes());
+        v2.extend_from_slice(&(51 + car.len() as u64).to_le_bytes());
+        v2.extend_from_slice(car);
+        v2.extend_from_slice(index);
+        v2
+    }
+
+    #[test]
+    fn test_locate_block() {
+        let car = std::fs::read(std::path::Path::new("test").join("carv1-basic.car")).unwrap();
+        let mut reader = reader::new_v1(Cursor::new(&car)).unwrap();
+        let cids: Vec<Cid> = reader.sections().unwrap().iter().map(|s| s.cid()).collect();
+        let mut records = Vec::new();
+        for cid in cids.iter() {
+            let location = locate_block(&mut Cursor::new(&car), None, cid)
+                .unwrap()
+                .unwrap();
+            assert_eq!(location.source, IndexSource::Scan);
+            let block = reader.get_block(cid).unwrap().unwrap();
+            let framed = &car[location.offset as usize..][..location.length as usize];
+            assert!(framed.ends_with(&block));
+            records.push((*cid, location.offset));
+        }
+        let missing = crate::utils::raw_cid(b"missing", multicodec::Codec::Sha2_256);
+        assert!(locate_block(&mut Cursor::new(&car), None, &missing)
+            .unwrap()
+            .is_none());
+
+        // the CARv2 of the embedded index after the data payload.
+        let v2 = encode_v2(&car, &encode_index(INDEX_SORTED_CODEC, &records));
+        for (cid, offset) in records.iter() {
+            let location = locate_block(&mut Cursor::new(&v2), None, cid)
+                .unwrap()
+                .unwrap();
+            assert_eq!(location.source, IndexSource::Embedded);
+            assert_eq!(location.offset, 51 + offset);
+        }
+        assert!(locate_block(&mut Cursor::new(&v2), None, &missing)
+            .unwrap()
+            .is_none());
+
+        // the sidecar index of the CARv1.
+        let sidecar = encode_index(MULTIHASH_INDEX_SORTED_CODEC, &records);
+        let sidecar = CarIndex::decode(&mut &sidecar[..]).unwrap();
+        assert_eq!(sidecar.len(), records.len());
+        let location = locate_block(&mut Cursor::new(&car), Some(&sidecar), &cids[1])
+            .unwrap()
+            .unwrap();
+        assert_eq!(location.source, IndexSource::Sidecar);
+        assert_eq!(location.offset, records[1].1);
+
+        // the index pointing to the other section.
+        let wrong = encode_index(INDEX_SORTED_CODEC, &[(cids[0], records[1].1)]);
+        let wrong = CarIndex::decode(&mut &wrong[..]).unwrap();
+        assert!(locate_block(&mut Cursor::new(&car), Some(&wrong), &cids[0]).is_err());
+        assert!(CarIndex::decode(&mut &0x0402u64.encode_var_vec()[..]).is_err());
+    }
+
+    #[test]
+    fn test_indexed_block_store() {
+        let car = std::fs::read(std::path::Path::new("test").join("carv1-basic.car")).unwrap();
+        let mut reader = reader::new_v1(Cursor::new(&car)).unwrap();
+        let cids: Vec<Cid> = reader.sections().unwrap().iter().map(|s| s.cid()).collect();
+        let mut records = Vec::new();
+        for cid in cids.iter() {
+            let location = locate_block(&mut Cursor::new(&car), None, cid)
+                .unwrap()
+                .unwrap();
+            records.push((*cid, location.offset));
+        }
+        let missing = crate::utils::raw_cid(b"missing", multicodec::Codec::Sha2_256);
+        let mut check = |store: &mut IndexedBlockStore<Cursor<&Vec<u8>>>| {
+            assert_eq!(store.header().roots(), reader.header().roots());
+            for cid in cids.iter() {
+                let block = reader.get_block(cid).unwrap();
+                assert_eq!(store.get_len(cid).unwrap(), block.as_ref().map(Vec::len));
+                assert_eq!(store.get(cid).unwrap(), block);
+            }
+            assert_eq!(store.get(&missing).unwrap(), None);
+            assert!(store.put(missing, b"missing".to_vec()).is_err());
+        };
+
+        // the CARv1 without the index is scanned on the first lookup.
+        let mut store = IndexedBlockStore::new(Cursor::new(&car)).unwrap();
+        assert!(!store.is_indexed() && store.scanned.is_none());
+        check(&mut store);
+        assert!(store.scanned.is_some());
+
+        // the embedded index of the CARv2 and the sidecar index, the sections are not scanned.
+        let v2 = encode_v2(&car, &encode_index(INDEX_SORTED_CODEC, &records));
+        let mut store = IndexedBlockStore::new(Cursor::new(&v2)).unwrap();
+        assert!(store.is_indexed());
+        check(&mut store);
+        let sidecar = encode_index(MULTIHASH_INDEX_SORTED_CODEC, &records);
+        let sidecar = CarIndex::decode(&mut &sidecar[..]).unwrap();
+        let mut store = IndexedBlockStore::new(Cursor::new(&car))
+            .unwrap()
+            .with_index(sidecar);
+        check(&mut store);
+        assert!(store.scanned.is_none());
+
+        // the index pointing to the other section.
+        let wrong = encode_index(INDEX_SORTED_CODEC, &[(cids[0], records[1].1)]);
+        let wrong = CarIndex::decode(&mut &wrong[..]).unwrap();
+        let mut store = IndexedBlockStore::new(Cursor::new(&car))
+            .unwrap()
+            .with_index(wrong);
+        assert!(store.get(&cids[0]).is_err());
+    }
+}
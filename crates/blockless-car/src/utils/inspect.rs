@@ -0,0 +1,284 @@
+//! inspect the framing of the CAR section by section, like `go-car inspect`, the malformed
+//! sections are reported with their offsets instead of failing the read.
+use std::io::{self, Read, Seek, SeekFrom};
+
+use cid::Cid;
+use integer_encoding::VarInt;
+
+use crate::{
+    error::CarError,
+    header::CarHeader,
+    reader::ReaderLimits,
+    section::MAX_ALLOWED_SECTION_SIZE,
+    utils::{graph::data_kind, probe::CARV2_HEADER_SIZE, CarV2Header, GraphNodeKind, CARV2_PRAGMA},
+};
+
+/// the size of the CARv2 pragma and the fixed header.
+pub(crate) const CARV2_PREFIX_LEN: u64 = (CARV2_PRAGMA.len() + CARV2_HEADER_SIZE) as u64;
+
+/// the max bytes of the varint of u64.
+pub(crate) const MAX_VARINT_LEN: usize = 10;
+
+/// the max bytes of the cid, the version, codec, hash code and digest size varints
+/// with the 64 bytes digest.
+pub(crate) const MAX_CID_LEN: usize = 4 * MAX_VARINT_LEN + 64;
+
+/// the malformed section, or the header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MalformedSection {
+    /// the offset of the length varint of the section in the file.
+    pub offset: u64,
+    pub reason: String,
+}
+
+/// the report of the inspection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inspect {
+    /// the version of the CAR, 2 if the file starts with the CARv2 pragma.
+    pub version: u64,
+    /// the CARv2 header if the version is 2.
+    pub v2: Option<CarV2Header>,
+    /// the roots of the header, empty if the header is malformed.
+    pub roots: Vec<Cid>,
+    /// the kinds of the roots decoded from the root blocks in the order of the roots,
+    /// missing if the block is not in a well framed section.
+    pub root_kinds: Vec<GraphNodeKind>,
+    /// the bytes of the CARv1 header, the length varint and the dag-cbor header.
+    pub header_size: u64,
+    /// the count of the well framed sections.
+    pub sections: usize,
+    /// the malformed header and sections in the file order. the scan stops at the framing
+    /// errors since the next section can't be located, the invalid cids are skipped.
+    pub malformed: Vec<MalformedSection>,
+}
+
+impl Inspect {
+    #[inline(always)]
+    pub fn is_ok(&self) -> bool {
+        self.malformed.is_empty()
+    }
+}
+
+/// read the bytes as many as possible up to the buffer size.
+pub(crate) fn read_full(r: &mut impl Read, buf: &mut [u8]) -> Result<usize, CarError> {
+    let mut n = 0;
+    while n < buf.len() {
+        match r.read(&mut buf[n..]) {
+            Ok(0) => break,
+            Ok(i) => n += i,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(n)
+}
+
+/// decode the length varint at the `offset`, the input ends at the `end`.
+/// return the length and the bytes of the varint, or the reason of the malformed varint.
+/// the reader is left after the varint.
+pub(crate) fn read_length<R>(
+    r: &mut R,
+    offset: u64,
+    end: u64,
+) -> Result<Result<(u64, u64), String>, CarError>
+where
+    R: Read + Seek,
+{
+    r.seek(SeekFrom::Start(offset))?;
+    let mut buf = [0u8; MAX_VARINT_LEN];
+    let n = read_full(
+        r,
+        &mut buf[..(end - offset).min(MAX_VARINT_LEN as u64) as usize],
+    )?;
+    Ok(match u64::decode_var(&buf[..n]) {
+        Some((len, varint_len)) => {
+            // the reads continue after the varint.
+            r.seek(SeekFrom::Start(offset + varint_len as u64))?;
+            Ok((len, varint_len as u64))
+        }
+        None if n == MAX_VARINT_LEN => Err("invalid length varint".into()),
+        None => Err(format!("the length varint is cut off at the end {end}")),
+    })
+}
+
+/// inspect the CARv1 or CARv2 file, the header is dumped and the sections of the data
+/// payload are located one by one by their length varints, the block data is not read.
+pub fn inspect<R>(r: &mut R) -> Result<Inspect, CarError>
+where
+    R: Read + Seek,
+{
+    let file_len = r.seek(SeekFrom::End(0))?;
+    r.rewind()?;
+    let mut pragma = [0u8; CARV2_PRAGMA.len()];
+    let n = read_full(r, &mut pragma)?;
+    let mut rs = Inspect {
+        version: 1,
+        v2: None,
+        roots: Vec::new(),
+        root_kinds: Vec::new(),
+        header_size: 0,
+        sections: 0,
+        malformed: Vec::new(),
+    };
+    let malformed = |offset, reason: String| MalformedSection { offset, reason };
+    let (start, end) = if n == CARV2_PRAGMA.len() && pragma == CARV2_PRAGMA {
+        rs.version = 2;
+        let mut header = [0u8; CARV2_HEADER_SIZE];
+        if read_full(r, &mut header)? < header.len() {
+            rs.malformed
+                .push(malformed(0, "the CARv2 header is truncated".into()));
+            return Ok(rs);
+        }
+        let v2 = CarV2Header::decode(&header);
+        rs.v2 = Some(v2);
+        let end = v2.data_offset.saturating_add(v2.data_size);
+        if v2.data_offset < CARV2_PREFIX_LEN || end > file_len {
+            let reason = format!(
+                "the data payload {}..{end} is out of the file of {file_len} bytes",
+                v2.data_offset
+            );
+            rs.malformed.push(malformed(0, reason));
+            return Ok(rs);
+        }
+        (v2.data_offset, end)
+    } else {
+        (0, file_len)
+    };
+
+    // the CARv1 header.
+    let (len, varint_len) = match read_length(r, start, end)? {
+        Ok(l) => l,
+        Err(reason) => {
+            rs.malformed
+                .push(malformed(start, format!("the header: {reason}")));
+            return Ok(rs);
+        }
+    };
+    let max_header_size = ReaderLimits::default().max_header_size as u64;
+    if len > max_header_size || start + varint_len + len > end {
+        let reason = format!("the header of {len} bytes is too large or cut off");
+        rs.malformed.push(malformed(start, reason));
+        return Ok(rs);
+    }
+    let mut header = vec![0u8; len as usize];
+    r.read_exact(&mut header)?;
+    match CarHeader::decode(&header) {
+        Ok(header) => {
+            rs.roots = header.roots();
+            rs.root_kinds = vec![GraphNodeKind::Missing; rs.roots.len()];
+        }
+        Err(e) => {
+            rs.malformed
+                .push(malformed(start, format!("the header: {e}")));
+            return Ok(rs);
+        }
+    }
+    rs.header_size = varint_len + len;
+
+    let mut offset = start + rs.header_size;
+    while offset < end {
+        let (len, varint_len) = match read_length(r, offset, end)? {
+            Ok(l) => l,
+            Err(reason) => {
+                rs.malformed.push(malformed(offset, reason));
+                break;
+            }
+        };
+        let next = offset + varint_len + len;
+        if next > end {
+            let reason = format!("the section of {len} bytes is cut off at the end {end}");
+            rs.malformed.push(malformed(offset, reason));
+            break;
+        }
+        if len == 0 {
+            rs.malformed
+                .push(malformed(offset, "the section is empty".into()));
+            offset = next;
+            continue;
+        }
+        let mut prefix = [0u8; MAX_CID_LEN];
+        let n = read_full(r, &mut prefix[..len.min(MAX_CID_LEN as u64) as usize])?;
+        let mut cid_bytes = &prefix[..n];
+        match Cid::read_bytes(&mut cid_bytes) {
+            Err(e) => rs
+                .malformed
+                .push(malformed(offset, format!("invalid cid: {e}"))),
+            Ok(_) if len > MAX_ALLOWED_SECTION_SIZE as u64 => {
+                let reason = format!("the section of {len} bytes is too large");
+                rs.malformed.push(malformed(offset, reason));
+            }
+            Ok(cid) => {
+                rs.sections += 1;
+                // only the root blocks are read.
+                let cid_len = (n - cid_bytes.len()) as u64;
+                for (i, root) in rs.roots.iter().enumerate() {
+                    if *root == cid && rs.root_kinds[i] == GraphNodeKind::Missing {
+                        r.seek(SeekFrom::Start(offset + varint_len + cid_len))?;
+                        let mut data = vec![0u8; (len - cid_len) as usize];
+                        r.read_exact(&mut data)?;
+                        rs.root_kinds[i] = data_kind(&cid, data);
+                    }
+                }
+            }
+        }
+        offset = next;
+    }
+    Ok(rs)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_inspect() {
+        let car = std::fs::read(std::path::Path::new("test").join("carv1-basic.car")).unwrap();
+        let rs = inspect(&mut Cursor::new(&car)).unwrap();
+        assert!(rs.is_ok(), "{:?}", rs.malformed);
+        assert_eq!(rs.version, 1);
+        assert_eq!(rs.roots.len(), 1);
+        assert_eq!(
+            rs.root_kinds,
+            vec![GraphNodeKind::Unixfs(crate::unixfs::FileType::Directory)]
+        );
+        assert!(rs.sections > 0);
+        let header_size = rs.header_size;
+
+        // the CARv2 wrapping the CARv1 with 8 bytes padding before the payload.
+        let mut v2 = CARV2_PRAGMA.to_vec();
+        v2.extend_from_slice(&[0u8; 16]);
+        v2.extend_from_slice(&59u64.to_le_bytes());
+        v2.extend_from_slice(&(car.len() as u64).to_le_bytes());
+        v2.extend_from_slice(&0u64.to_le_bytes());
+        v2.extend_from_slice(&[0u8; 8]);
+        v2.extend_from_slice(&car);
+        let v2_rs = inspect(&mut Cursor::new(&v2)).unwrap();
+        assert!(v2_rs.is_ok());
+        assert_eq!(v2_rs.version, 2);
+        assert_eq!(v2_rs.v2.unwrap().data_offset, 59);
+        assert_eq!(v2_rs.sections, rs.sections);
+
+        // the invalid cid of the first section is skipped, the rest are scanned.
+        let mut bad = car.clone();
+        let first = header_size as usize;
+        let (_, varint_len) = u64::decode_var(&bad[first..]).unwrap();
+        bad[first + varint_len] = 0x7f;
+        let bad_rs = inspect(&mut Cursor::new(&bad)).unwrap();
+        assert_eq!(bad_rs.malformed.len(), 1);
+        assert_eq!(bad_rs.malformed[0].offset, header_size);
+        assert_eq!(bad_rs.sections, rs.sections - 1);
+
+        // the cut off section stops the scan.
+        let cut = &car[..car.len() - 1];
+        let cut_rs = inspect(&mut Cursor::new(cut)).unwrap();
+        assert_eq!(cut_rs.malformed.len(), 1);
+        assert!(cut_rs.malformed[0].reason.contains("cut off"));
+        assert_eq!(cut_rs.sections, rs.sections - 1);
+
+        let junk = inspect(&mut Cursor::new(b"hello world, not a car")).unwrap();
+        assert!(junk.roots.is_empty());
+        assert_eq!(junk.malformed.len(), 1);
+        assert_eq!(junk.malformed[0].offset, 0);
+    }
+}
@@ -0,0 +1,71 @@
+use std::path::Path;
+
+use crate::error::CarError;
+
+/// the default maximum depth of nested entries, the root is depth 0.
+pub const DEFAULT_MAX_PATH_DEPTH: usize = 256;
+
+/// the default maximum length in bytes of a single link name.
+pub const DEFAULT_MAX_NAME_LEN: usize = 255;
+
+/// limits of the paths handled during pack and extract,
+/// protect against pathological or malicious trees.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathLimits {
+    /// the maximum depth of nested entries, the root is depth 0.
+    pub max_depth: usize,
+    /// the maximum length in bytes of a single link name.
+    pub max_name_len: usize,
+}
+
+impl Default for PathLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_PATH_DEPTH,
+            max_name_len: DEFAULT_MAX_NAME_LEN,
+        }
+    }
+}
+
+impl PathLimits {
+    pub fn new(max_depth: usize, max_name_len: usize) -> Self {
+        Self {
+            max_depth,
+            max_name_len,
+        }
+    }
+
+    /// check the depth of the `path`.
+    #[inline]
+    pub fn check_depth(&self, path: impl AsRef<Path>, depth: usize) -> Result<(), CarError> {
+        if depth > self.max_depth {
+            return Err(CarError::PathTooDeep {
+                path: path.as_ref().display().to_string(),
+                depth,
+                limit: self.max_depth,
+            });
+        }
+        Ok(())
+    }
+
+    /// check the length of the link `name`, `path` is the path of the entry.
+    #[inline]
+    pub fn check_name(&self, path: impl AsRef<Path>, name: &str) -> Result<(), CarError> {
+        if name.len() > self.max_name_len {
+            return Err(CarError::NameTooLong {
+                path: path.as_ref().display().to_string(),
+                len: name.len(),
+                limit: self.max_name_len,
+            });
+        }
+        Ok(())
+    }
+
+    /// check both the depth and the name of the entry.
+    #[inline]
+    pub fn check(&self, path: impl AsRef<Path>, name: &str, depth: usize) -> Result<(), CarError> {
+        let path = path.as_ref();
+        self.check_name(path, name)?;
+        self.check_depth(path, depth)
+    }
+}
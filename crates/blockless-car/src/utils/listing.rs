@@ -0,0 +1,231 @@
+//! the listing of the entries embedded in the CAR as dag-cbor blocks, so the listing of
+//! a large CAR reads a few blocks instead of walking every directory.
+//! the listing root is the last root of the header, it links the pages of the entries.
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    fs::OpenOptions,
+    io::{Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use cid::{
+    multihash::{Code, MultihashDigest},
+    Cid,
+};
+use integer_encoding::VarIntWriter;
+use ipld::codec::Codec;
+use ipld_cbor::DagCborCodec;
+
+use crate::{
+    error::CarError,
+    reader::{self, CarReader},
+    utils::{file_size, list_call, replace_roots},
+    Ipld,
+};
+
+/// the version of the listing format, stored in the listing root.
+const LISTING_VERSION: i128 = 1;
+
+/// the estimated bytes of the entries in a page, the pages are far under the block limits.
+const PAGE_SIZE: usize = 128 << 10;
+
+/// the entry of the listing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListingEntry {
+    /// the path printed by `ls`, starts with the root cid.
+    pub path: String,
+    pub cid: Cid,
+    /// the size of the file content, none for the directories and the symlinks.
+    pub size: Option<u64>,
+}
+
+/// the listing roots are the dag-cbor roots, the unixfs roots are dag-pb or raw.
+#[inline(always)]
+pub fn is_listing_root(cid: &Cid) -> bool {
+    cid.codec() == u64::from(DagCborCodec)
+}
+
+/// walk the roots and collect the entries in the `ls` order.
+pub fn build_listing(reader: &mut impl CarReader) -> Result<Vec<ListingEntry>, CarError> {
+    let walked = RefCell::new(Vec::new());
+    list_call(reader, |cid, path| {
+        walked.borrow_mut().push((*cid, path.to_string()))
+    })?;
+    let mut entries = Vec::with_capacity(walked.borrow().len());
+    for (cid, path) in walked.into_inner() {
+        // the sizes read the file nodes again, the content blocks are not read.
+        let size = match file_size(reader, &cid) {
+            Ok(size) => Some(size),
+            Err(CarError::NotAFile(_)) => None,
+            Err(e) => return Err(e),
+        };
+        entries.push(ListingEntry { path, cid, size });
+    }
+    Ok(entries)
+}
+
+fn cbor_block(ipld: &Ipld) -> Result<(Cid, Vec<u8>), CarError> {
+    let data = DagCborCodec
+        .encode(ipld)
+        .map_err(|e| CarError::Parsing(e.to_string()))?;
+    let cid = Cid::new_v1(DagCborCodec.into(), Code::Sha2_256.digest(&data));
+    Ok((cid, data))
+}
+
+/// encode the entries into the page blocks and the listing root block, the root is last.
+fn encode_listing(entries: &[ListingEntry]) -> Result<Vec<(Cid, Vec<u8>)>, CarError> {
+    let mut blocks = Vec::new();
+    let mut page = Vec::new();
+    let mut page_size = 0;
+    for (i, entry) in entries.iter().enumerate() {
+        page_size += entry.path.len() + entry.cid.encoded_len() + 16;
+        page.push(Ipld::List(vec![
+            Ipld::String(entry.path.clone()),
+            Ipld::Link(entry.cid),
+            entry.size.map_or(Ipld::Null, |s| Ipld::Integer(s as i128)),
+        ]));
+        if page_size >= PAGE_SIZE || i + 1 == entries.len() {
+            let entries = Ipld::List(std::mem::take(&mut page));
+            blocks.push(cbor_block(&Ipld::Map(BTreeMap::from([(
+                "entries".to_string(),
+                entries,
+            )])))?);
+            page_size = 0;
+        }
+    }
+    let pages = blocks.iter().map(|(cid, _)| Ipld::Link(*cid)).collect();
+    let root = Ipld::Map(BTreeMap::from([
+        ("listing".to_string(), Ipld::Integer(LISTING_VERSION)),
+        ("entries".to_string(), Ipld::Integer(entries.len() as i128)),
+        ("pages".to_string(), Ipld::List(pages)),
+    ]));
+    blocks.push(cbor_block(&root)?);
+    Ok(blocks)
+}
+
+/// walk the CARv1 file at the `path`, append the listing blocks and add the listing root to
+/// the header, the listing root embedded before is replaced. return the listing root.
+/// the header is rewritten in place if it fits, otherwise the car is rewritten, see
+/// `replace_roots`.
+pub fn embed_listing(path: impl AsRef<Path>) -> Result<Cid, CarError> {
+    let path = path.as_ref();
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    let mut reader = reader::new_v1(&mut file)?;
+    let mut roots: Vec<Cid> = reader
+        .header()
+        .roots()
+        .into_iter()
+        .filter(|r| !is_listing_root(r))
+        .collect();
+    let entries = build_listing(&mut reader)?;
+    drop(reader);
+
+    let blocks = encode_listing(&entries)?;
+    let listing = blocks[blocks.len() - 1].0;
+    file.seek(SeekFrom::End(0))?;
+    for (cid, data) in blocks {
+        let cid = cid.to_bytes();
+        file.write_varint(cid.len() + data.len())?;
+        file.write_all(&cid)?;
+        file.write_all(&data)?;
+    }
+    file.flush()?;
+    drop(file);
+    roots.push(listing);
+    replace_roots(path, roots)?;
+    Ok(listing)
+}
+
+/// read the listing embedded by `embed_listing`, none if the CAR has no listing root.
+pub fn read_listing(reader: &mut impl CarReader) -> Result<Option<Vec<ListingEntry>>, CarError> {
+    let Some(root) = reader.header().roots().into_iter().rfind(is_listing_root) else {
+        return Ok(None);
+    };
+    let invalid = |reason: &str| CarError::InvalidSection(format!("the listing {root}: {reason}"));
+    let Ipld::Map(mut root_map) = reader.ipld(&root)? else {
+        return Err(invalid("expect a map"));
+    };
+    match root_map.get("listing") {
+        Some(Ipld::Integer(LISTING_VERSION)) => {}
+        _ => return Err(invalid("unsupported version")),
+    }
+    let Some(Ipld::List(pages)) = root_map.remove("pages") else {
+        return Err(invalid("expect the pages"));
+    };
+    let mut entries = Vec::new();
+    for page in pages {
+        let Ipld::Link(page) = page else {
+            return Err(invalid("expect the page link"));
+        };
+        let Ipld::Map(mut page) = reader.ipld(&page)? else {
+            return Err(invalid("expect the page map"));
+        };
+        let Some(Ipld::List(page)) = page.remove("entries") else {
+            return Err(invalid("expect the page entries"));
+        };
+        for entry in page {
+            let entry = match entry {
+                Ipld::List(entry) => <[Ipld; 3]>::try_from(entry).ok(),
+                _ => None,
+            };
+            let entry = match entry {
+                Some([Ipld::String(path), Ipld::Link(cid), size]) => ListingEntry {
+                    path,
+                    cid,
+                    size: match size {
+                        Ipld::Integer(size) => Some(size as u64),
+                        _ => None,
+                    },
+                },
+                _ => return Err(invalid("expect the [path, cid, size] entry")),
+            };
+            entries.push(entry);
+        }
+    }
+    Ok(Some(entries))
+}
+
+#[cfg(all(test, feature = "unixfs-fs"))]
+mod test {
+    use super::*;
+    use crate::utils::{pack_files_with, verify_blocks, PackOptions};
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_embed_listing() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        fs::create_dir_all(root_dir.join("sub")).unwrap();
+        fs::write(root_dir.join("small.txt"), b"hello world").unwrap();
+        fs::write(root_dir.join("sub/big.bin"), vec![7u8; 300_000]).unwrap();
+        // enough entries for more than one page.
+        for i in 0..3000 {
+            fs::write(root_dir.join(format!("sub/{i:0>40}.txt")), i.to_string()).unwrap();
+        }
+        let car = temp_dir.path().join("root.car");
+        let file = fs::File::create(&car).unwrap();
+        let root = pack_files_with(&root_dir, file, &PackOptions::new()).unwrap();
+        let mut reader = reader::new_v1(fs::File::open(&car).unwrap()).unwrap();
+        let expected = build_listing(&mut reader).unwrap();
+        assert_eq!(read_listing(&mut reader).unwrap(), None);
+
+        let listing = embed_listing(&car).unwrap();
+        let mut reader = reader::new_v1(fs::File::open(&car).unwrap()).unwrap();
+        assert_eq!(reader.header().roots(), vec![root, listing]);
+        let entries = read_listing(&mut reader).unwrap().unwrap();
+        assert_eq!(entries, expected);
+        let big = format!("{root}/root/sub/big.bin");
+        let big = entries.iter().find(|e| e.path == big).unwrap();
+        assert_eq!(big.size, Some(300_000));
+        // the listing root is skipped by the walk.
+        assert_eq!(build_listing(&mut reader).unwrap(), expected);
+
+        // the listing embedded again replaces the old one.
+        assert_eq!(embed_listing(&car).unwrap(), listing);
+        let mut reader = reader::new_v1(fs::File::open(&car).unwrap()).unwrap();
+        assert_eq!(reader.header().roots(), vec![root, listing]);
+        assert!(verify_blocks(&mut reader).unwrap().is_ok());
+    }
+}
@@ -1,49 +1,123 @@
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 
 use cid::Cid;
 use ipld::raw::RawCodec;
 
 use crate::{
     error::CarError,
-    reader::CarReader,
+    reader::{CarReader, ReaderLimits},
+    section::decode_block,
+    store::BlockStore,
     unixfs::{FileType, UnixFs},
+    utils::{dir_entries, is_listing_root},
     Ipld,
 };
 
+/// the directories of the car read in one pass of the blocks in the file order, the other
+/// blocks are dropped once read, so the listing needs neither the section index nor the
+/// random reads. the blocks failing to decode are kept, their errors are returned if the
+/// walk reaches them.
+struct Directories {
+    blocks: HashMap<Cid, Vec<u8>>,
+    /// the other blocks not raw, the walk reaching the block not read is an error.
+    nodes: HashSet<Cid>,
+    limits: ReaderLimits,
+}
+
+impl Directories {
+    fn read(reader: &mut impl CarReader) -> Result<Self, CarError> {
+        let raw_code: u64 = RawCodec.into();
+        let limits = reader.limits();
+        let mut blocks = HashMap::new();
+        let mut nodes = HashSet::new();
+        for block in reader.blocks() {
+            let (cid, data) = block?;
+            if cid.codec() == raw_code || blocks.contains_key(&cid) {
+                continue;
+            }
+            let is_dir = match decode_block(cid, data.clone()) {
+                Ok(m @ Ipld::Map(_)) => UnixFs::try_from((cid, m)).map_or(true, |u| {
+                    matches!(u.file_type(), FileType::Directory | FileType::HAMTShard)
+                }),
+                Ok(_) => false,
+                Err(_) => true,
+            };
+            match is_dir {
+                true => {
+                    limits.check_blocks(blocks.len() + 1)?;
+                    blocks.insert(cid, data);
+                }
+                false => {
+                    nodes.insert(cid);
+                }
+            }
+        }
+        Ok(Self {
+            blocks,
+            nodes,
+            limits,
+        })
+    }
+
+    /// the unixfs directory of the `cid`, none for the other blocks.
+    fn directory(&mut self, cid: &Cid) -> Result<Option<UnixFs>, CarError> {
+        if self.nodes.contains(cid) {
+            return Ok(None);
+        }
+        match self.get_ipld(cid)? {
+            m @ Ipld::Map(_) => (*cid, m).try_into().map(Some),
+            _ => Ok(None),
+        }
+    }
+}
+
+impl BlockStore for Directories {
+    #[inline]
+    fn get(&mut self, cid: &Cid) -> Result<Option<Vec<u8>>, CarError> {
+        Ok(self.blocks.get(cid).cloned())
+    }
+
+    fn put(&mut self, _: Cid, _: Vec<u8>) -> Result<(), CarError> {
+        Err(CarError::NotImplemented(
+            "put the block to the listing".into(),
+        ))
+    }
+
+    #[inline]
+    fn block_limits(&self) -> ReaderLimits {
+        self.limits
+    }
+}
+
 /// walk the node and print the files in the directory.
 fn walk<F>(
-    vecq: &mut VecDeque<Cid>,
-    reader: &mut impl CarReader,
+    vecq: &mut VecDeque<(Cid, String)>,
+    dirs: &mut Directories,
     list_f: &F,
 ) -> Result<(), CarError>
 where
     F: Fn(&Cid, &str),
 {
-    let mut cache: HashMap<Cid, String> = HashMap::new();
     let raw_code: u64 = RawCodec.into();
-    while let Some(file_cid) = vecq.pop_front() {
+    let limits = dirs.limits;
+    // the path is carried with the cid, the same block may be at many paths.
+    while let Some((file_cid, file_n)) = vecq.pop_front() {
         let codec = file_cid.codec();
-        let file_n = cache
-            .get(&file_cid)
-            .map_or(file_cid.to_string(), |c| c.to_string());
         list_f(&file_cid, &file_n);
         // if the codec is RawCodec, the block is the file content block,
         // it don't conatian the file info. we don't need walk continue.
         if codec == raw_code {
             continue;
         }
-        let file_ipld: Ipld = reader.ipld(&file_cid)?;
-        if let m @ Ipld::Map(_) = file_ipld {
-            let unixfs: UnixFs = m.try_into()?;
+        if let Some(unixfs) = dirs.directory(&file_cid)? {
             match unixfs.file_type() {
-                FileType::Directory => {}
+                FileType::Directory | FileType::HAMTShard => {}
                 _ => continue,
             }
-            for n in unixfs.links().into_iter() {
-                let cid = n.hash;
-                cache.insert(cid, file_n.clone() + "/" + &n.name);
-                vecq.push_back(cid);
+            for n in dir_entries(dirs, &unixfs)?.iter() {
+                vecq.push_back((n.hash, format!("{file_n}/{}", n.name)));
             }
+            limits.check_blocks(vecq.len())?;
         }
     }
     Ok(())
@@ -62,15 +136,204 @@ pub fn list_cid(reader: &mut impl CarReader) -> Result<(), CarError> {
 /// the list car file  by reader.
 /// `reader` is the CarReader
 /// `list_f` is the list call function, first parameter is cid, the second is cid path.
+/// the blocks are read once in the file order and only the directories are kept.
 pub fn list_call<F>(reader: &mut impl CarReader, list_f: F) -> Result<(), CarError>
 where
     F: Fn(&Cid, &str),
 {
     let roots = reader.header().roots();
-    let mut queue: VecDeque<Cid> = VecDeque::new();
-    for r in roots.iter() {
-        queue.push_front(*r);
-        walk(&mut queue, reader, &list_f)?;
+    let mut dirs = Directories::read(reader)?;
+    let mut queue: VecDeque<(Cid, String)> = VecDeque::new();
+    // the listing roots are not unixfs, see `embed_listing`.
+    for r in roots.iter().filter(|r| !is_listing_root(r)) {
+        queue.push_front((*r, r.to_string()));
+        walk(&mut queue, &mut dirs, &list_f)?;
     }
     Ok(())
 }
+
+/// the entry of the tree walk, the entries are given in the depth first order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeEntry {
+    pub cid: Cid,
+    /// the name in the parent directory, the cid of the root.
+    pub name: String,
+    /// the depth of the entry, 0 for the roots.
+    pub depth: usize,
+    /// whether the entry is the last one of the parent directory, or the last root.
+    pub last: bool,
+}
+
+/// walk the unixfs hierarchy of the roots in the depth first order for the tree rendering.
+/// the directories deeper than the `max_depth` are not expanded, none for the whole tree.
+/// the blocks are read once in the file order like `list_call`.
+pub fn list_tree<F>(
+    reader: &mut impl CarReader,
+    max_depth: Option<usize>,
+    mut list_f: F,
+) -> Result<(), CarError>
+where
+    F: FnMut(&TreeEntry),
+{
+    let raw_code: u64 = RawCodec.into();
+    let limits = reader.limits();
+    let mut dirs = Directories::read(reader)?;
+    let roots: Vec<Cid> = reader
+        .header()
+        .roots()
+        .into_iter()
+        .filter(|r| !is_listing_root(r))
+        .collect();
+    // the stack pops the entries in the order of the directory.
+    let mut stack: Vec<TreeEntry> = roots
+        .iter()
+        .enumerate()
+        .rev()
+        .map(|(i, cid)| TreeEntry {
+            cid: *cid,
+            name: cid.to_string(),
+            depth: 0,
+            last: i == roots.len() - 1,
+        })
+        .collect();
+    while let Some(entry) = stack.pop() {
+        list_f(&entry);
+        if entry.cid.codec() == raw_code || max_depth.is_some_and(|d| entry.depth >= d) {
+            continue;
+        }
+        if let Some(unixfs) = dirs.directory(&entry.cid)? {
+            match unixfs.file_type() {
+                FileType::Directory | FileType::HAMTShard => {}
+                _ => continue,
+            }
+            let links = dir_entries(&mut dirs, &unixfs)?;
+            stack.extend(links.iter().enumerate().rev().map(|(i, n)| TreeEntry {
+                cid: n.hash,
+                name: n.name.clone(),
+                depth: entry.depth + 1,
+                last: i == links.len() - 1,
+            }));
+            limits.check_blocks(stack.len())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "unixfs-fs"))]
+mod test {
+    use super::*;
+    use crate::{
+        reader,
+        utils::{pack_files_with, PackOptions},
+    };
+    use std::{cell::RefCell, fs, io::Cursor};
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_list_duplicate_content() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        fs::create_dir_all(root_dir.join("a")).unwrap();
+        fs::create_dir_all(root_dir.join("b")).unwrap();
+        fs::write(root_dir.join("a/x.txt"), b"same").unwrap();
+        fs::write(root_dir.join("b/x.txt"), b"same").unwrap();
+        fs::write(root_dir.join("y.txt"), b"same").unwrap();
+        let mut car = Cursor::new(vec![]);
+        pack_files_with(&root_dir, &mut car, &PackOptions::new()).unwrap();
+        car.set_position(0);
+        let mut reader = reader::new_v1(car).unwrap();
+
+        let paths = RefCell::new(vec![]);
+        list_call(&mut reader, |_, path| {
+            let path = path.split_once('/').map_or("", |(_, p)| p);
+            paths.borrow_mut().push(path.to_string());
+        })
+        .unwrap();
+        let mut paths = paths.into_inner();
+        paths.sort();
+        assert_eq!(
+            paths,
+            [
+                "",
+                "root",
+                "root/a",
+                "root/a/x.txt",
+                "root/b",
+                "root/b/x.txt",
+                "root/y.txt"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_list_directories_only() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        fs::create_dir_all(root_dir.join("sub")).unwrap();
+        let content: Vec<u8> = (0..256u32).map(|i| i as u8).collect();
+        fs::write(root_dir.join("sub/big.bin"), &content).unwrap();
+        let mut car = Cursor::new(vec![]);
+        let options = PackOptions::new().with_chunk_size(16);
+        pack_files_with(&root_dir, &mut car, &options).unwrap();
+        car.set_position(0);
+        // the 16 chunks and the file node are read but not kept, only the 3 directories.
+        let limits = ReaderLimits::new().with_max_blocks_in_memory(3);
+        let mut reader = reader::new_v1_with_limits(car, limits).unwrap();
+
+        let paths = RefCell::new(vec![]);
+        list_call(&mut reader, |_, path| {
+            let path = path.split_once('/').map_or("", |(_, p)| p);
+            paths.borrow_mut().push(path.to_string());
+        })
+        .unwrap();
+        assert_eq!(
+            paths.into_inner(),
+            ["", "root", "root/sub", "root/sub/big.bin"]
+        );
+        // the index of all the sections is over the limit.
+        assert!(matches!(
+            reader.section_count(),
+            Err(CarError::LimitExceeded { .. })
+        ));
+    }
+
+    #[test]
+    fn test_list_tree() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        fs::create_dir_all(root_dir.join("a/b")).unwrap();
+        fs::write(root_dir.join("a/b/c.txt"), b"c").unwrap();
+        fs::write(root_dir.join("a/d.txt"), b"d").unwrap();
+        fs::write(root_dir.join("e.txt"), b"e").unwrap();
+        let mut car = Cursor::new(vec![]);
+        pack_files_with(&root_dir, &mut car, &PackOptions::new()).unwrap();
+        car.set_position(0);
+        let mut reader = reader::new_v1(car).unwrap();
+
+        let mut entries = vec![];
+        list_tree(&mut reader, None, |e| {
+            entries.push((e.name.clone(), e.depth, e.last))
+        })
+        .unwrap();
+        let names: Vec<_> = entries[1..]
+            .iter()
+            .map(|(n, d, l)| (n.as_str(), *d, *l))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("root", 1, true),
+                ("a", 2, false),
+                ("b", 3, false),
+                ("c.txt", 4, true),
+                ("d.txt", 3, true),
+                ("e.txt", 2, true)
+            ]
+        );
+        assert_eq!((entries[0].1, entries[0].2), (0, true));
+
+        let mut depths = vec![];
+        list_tree(&mut reader, Some(2), |e| depths.push(e.depth)).unwrap();
+        assert_eq!(depths, [0, 1, 2, 2]);
+    }
+}
@@ -0,0 +1,117 @@
+//! merge the CARs into one, e.g. the shards of the upload packed per directory.
+use std::{collections::HashSet, io::Write};
+
+use cid::Cid;
+
+use crate::{
+    error::CarError,
+    header::CarHeader,
+    reader::CarReader,
+    utils::transform::{BlockTransform, KeepBlocks, Rewrite},
+    writer::{CarWriter, StreamCarWriterV1},
+};
+
+/// the counts of the merged CAR.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MergeStats {
+    pub roots: usize,
+    /// the blocks written, each cid once.
+    pub blocks: usize,
+    /// the blocks skipped for the cid already written from an earlier CAR or section.
+    pub duplicates: usize,
+}
+
+/// write the CARv1 of the union of the roots of the `readers` in their order, and the
+/// blocks of the readers in the order, the block of the repeated cid is written once.
+pub fn merge_cars<R, W>(readers: &mut [R], to: W) -> Result<MergeStats, CarError>
+where
+    R: CarReader,
+    W: Write,
+{
+    merge_cars_with(readers, &mut KeepBlocks, to)
+}
+
+/// `merge_cars` with the blocks rewritten by the `transform`, see `transform_car`. the links
+/// are fixed up within each CAR, a block is transformed once for all the CARs.
+pub fn merge_cars_with<R, W>(
+    readers: &mut [R],
+    transform: &mut impl BlockTransform,
+    to: W,
+) -> Result<MergeStats, CarError>
+where
+    R: CarReader,
+    W: Write,
+{
+    let mut rewrite = Rewrite::default();
+    for reader in readers.iter_mut() {
+        rewrite.plan(reader, None, transform)?;
+    }
+    let mut roots: Vec<Cid> = Vec::new();
+    for reader in readers.iter() {
+        for root in rewrite.roots(reader.header().roots()) {
+            if !roots.contains(&root) {
+                roots.push(root);
+            }
+        }
+    }
+    let mut stats = MergeStats {
+        roots: roots.len(),
+        blocks: 0,
+        duplicates: 0,
+    };
+    let mut writer = StreamCarWriterV1::new(to, CarHeader::new_v1(roots));
+    let mut written = HashSet::new();
+    for reader in readers.iter_mut() {
+        let (blocks, duplicates) = rewrite.write(reader, None, &mut writer, &mut written)?;
+        stats.blocks += blocks;
+        stats.duplicates += duplicates;
+    }
+    writer.flush()?;
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{reader, utils::raw_cid};
+    use std::io::Cursor;
+
+    fn car_of(roots: Vec<Cid>, datas: &[&[u8]]) -> Vec<u8> {
+        let mut car = vec![];
+        let mut writer = StreamCarWriterV1::new(&mut car, CarHeader::new_v1(roots));
+        for data in datas {
+            writer
+                .write_block(raw_cid(data, multicodec::Codec::Sha2_256), data)
+                .unwrap();
+        }
+        writer.flush().unwrap();
+        drop(writer);
+        car
+    }
+
+    #[test]
+    fn test_merge_cars() {
+        let cid = |d: &[u8]| raw_cid(d, multicodec::Codec::Sha2_256);
+        let a = car_of(vec![cid(b"a")], &[b"a", b"shared"]);
+        let b = car_of(vec![cid(b"b"), cid(b"a")], &[b"shared", b"b"]);
+        let mut readers = vec![
+            reader::new_v1(Cursor::new(a)).unwrap(),
+            reader::new_v1(Cursor::new(b)).unwrap(),
+        ];
+        let mut merged = vec![];
+        let stats = merge_cars(&mut readers, &mut merged).unwrap();
+        assert_eq!(
+            stats,
+            MergeStats {
+                roots: 2,
+                blocks: 3,
+                duplicates: 1,
+            }
+        );
+        let mut reader = reader::new_v1(Cursor::new(merged)).unwrap();
+        assert_eq!(reader.header().roots(), vec![cid(b"a"), cid(b"b")]);
+        let cids: Vec<Cid> = reader.blocks().map(|b| b.unwrap().0).collect();
+        assert_eq!(cids, vec![cid(b"a"), cid(b"shared"), cid(b"b")]);
+        assert_eq!(reader.get_block(&cid(b"b")).unwrap().unwrap(), b"b");
+    }
+}
@@ -0,0 +1,103 @@
+//! the results of the parallel workers in the order of their inputs, the outputs of the
+//! parallel operations don't depend on the count of the workers or their timing.
+use std::{collections::BTreeMap, sync::mpsc::Receiver};
+
+/// the iterator of the results sent by the workers with the index of their input, the
+/// results are yielded with their index in the index order from 0, the early ones are held
+/// until their turn. it ends at the first index never sent.
+pub(crate) struct OrderedReceiver<T> {
+    rx: Receiver<(usize, T)>,
+    next: usize,
+    pending: BTreeMap<usize, T>,
+    // yield the results as received.
+    relaxed: bool,
+}
+
+impl<T> OrderedReceiver<T> {
+    pub(crate) fn new(rx: Receiver<(usize, T)>) -> Self {
+        Self {
+            rx,
+            next: 0,
+            pending: BTreeMap::new(),
+            relaxed: false,
+        }
+    }
+
+    /// the receiver yielding the results as received, with their index.
+    #[cfg(feature = "unixfs-fs")]
+    pub(crate) fn relaxed(rx: Receiver<(usize, T)>) -> Self {
+        Self {
+            relaxed: true,
+            ..Self::new(rx)
+        }
+    }
+
+    fn pop(&mut self) -> Option<(usize, T)> {
+        let idx = match self.relaxed {
+            true => *self.pending.keys().next()?,
+            false => self.next,
+        };
+        let item = self.pending.remove(&idx)?;
+        self.next += 1;
+        Some((idx, item))
+    }
+
+    /// the next result if it is received already, without blocking.
+    #[cfg(feature = "unixfs-fs")]
+    pub(crate) fn try_next(&mut self) -> Option<(usize, T)> {
+        loop {
+            if let Some(item) = self.pop() {
+                return Some(item);
+            }
+            let (idx, item) = self.rx.try_recv().ok()?;
+            self.pending.insert(idx, item);
+        }
+    }
+}
+
+impl<T> Iterator for OrderedReceiver<T> {
+    type Item = (usize, T);
+
+    fn next(&mut self) -> Option<(usize, T)> {
+        loop {
+            if let Some(item) = self.pop() {
+                return Some(item);
+            }
+            let (idx, item) = self.rx.recv().ok()?;
+            self.pending.insert(idx, item);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_ordered_receiver() {
+        let send = |indexes: &[usize]| {
+            let (tx, rx) = mpsc::channel();
+            for idx in indexes {
+                tx.send((*idx, idx * 10)).unwrap();
+            }
+            rx
+        };
+        // the index 4 is never sent, the 5 is dropped.
+        let results: Vec<usize> = OrderedReceiver::new(send(&[2, 0, 3, 1, 5]))
+            .map(|(_, r)| r)
+            .collect();
+        assert_eq!(results, vec![0, 10, 20, 30]);
+
+        #[cfg(feature = "unixfs-fs")]
+        {
+            let mut ordered = OrderedReceiver::new(send(&[1, 0]));
+            assert_eq!(ordered.try_next(), Some((0, 0)));
+            assert_eq!(ordered.try_next(), Some((1, 10)));
+            assert_eq!(ordered.try_next(), None);
+
+            let relaxed = OrderedReceiver::relaxed(send(&[2, 0, 1]));
+            assert_eq!(relaxed.count(), 3);
+        }
+    }
+}
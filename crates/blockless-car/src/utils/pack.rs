@@ -1,8 +1,11 @@
 use std::{
-    collections::{HashMap, VecDeque},
-    fs, io,
-    path::{Path, PathBuf},
+    collections::{HashMap, HashSet, VecDeque},
+    fs,
+    io::{self, Read},
+    path::{Component, Path, PathBuf},
     rc::Rc,
+    sync::{mpsc, Arc, Mutex},
+    thread,
 };
 
 use crate::{
@@ -10,25 +13,106 @@ use crate::{
     error::CarError,
     header::CarHeaderV1,
     unixfs::{FileType, Link, UnixFs},
-    writer::{CarWriter, CarWriterV1, WriteStream},
+    utils::{
+        finalize_multihash, ordered::OrderedReceiver, temp::TempDir, BlockHasher, ProgressCounter,
+        ProgressReader, ProgressWriter,
+    },
+    writer::{CarWriter, CarWriterV1, StreamCarWriterV1, WriteStream},
     CarHeader, Ipld,
 };
-use cid::{
-    multihash::{Blake2b256, Code, Hasher, Multihash, MultihashDigest, Sha2_256},
-    Cid,
-};
+use cid::Cid;
 use ipld::{pb::DagPbCodec, prelude::Codec, raw::RawCodec};
 
+mod cache;
+mod dedup;
+mod filter;
+mod hamt;
+mod ignore;
+mod manifest;
+mod meta;
+mod options;
+mod reader;
+pub use cache::{CacheReport, PackCache};
+use dedup::{chunk_key, ChunkCache, ChunkKey, FileDedup};
+pub use dedup::{ChunkDedupReport, DedupFile, DedupReport, PackReport};
+pub use filter::*;
+pub use hamt::DEFAULT_HAMT_THRESHOLD;
+use hamt::{estimated_dir_size, write_hamt_dir};
+use ignore::IgnoreRules;
+use manifest::unixfs_path;
+pub use manifest::{PackResult, PackedEntry};
+use meta::NodeMeta;
+pub use options::*;
+pub use reader::pack_reader;
+use reader::SeekBuf;
+
 type WalkPath = (Rc<PathBuf>, Option<usize>);
 type WalkPathCache = HashMap<Rc<PathBuf>, UnixFs>;
 type Size = usize;
 
-const MAX_SECTION_SIZE: usize = 262144;
+pub(crate) const MAX_SECTION_SIZE: usize = 262144;
 const MAX_LINK_COUNT: usize = 174;
 
+/// the chunks read ahead of the writer for every hashing worker, the chunks read and not
+/// written yet are bounded by it, so are the hashed blocks held for their turn.
+const READ_AHEAD_PER_JOB: usize = 2;
+
+/// the state shared by all the paths of a pack.
+struct PackState {
+    report: PackReport,
+    files: FileDedup,
+    chunks: Option<ChunkCache>,
+    // the count of the files and directories packed, reported when cancelled.
+    entries: usize,
+    // the total size of the root directory.
+    root_tsize: u64,
+    // the files packed, only collected for the manifest.
+    manifest: Option<Vec<PackedEntry>>,
+    progress: Option<Rc<ProgressCounter>>,
+    // the top node name of the source, the prefix of the paths in the pack cache.
+    cache_prefix: Option<String>,
+}
+
+impl PackState {
+    fn new(options: &PackOptions) -> Self {
+        Self {
+            report: PackReport::default(),
+            files: FileDedup::default(),
+            chunks: options.chunk_cache.map(ChunkCache::new),
+            entries: 0,
+            root_tsize: 0,
+            manifest: None,
+            progress: ProgressCounter::new(options.progress.as_ref()),
+            cache_prefix: None,
+        }
+    }
+
+    /// open the file to pack, the bytes read are reported to the progress.
+    fn open(&self, path: &Path) -> Result<ProgressReader<fs::File>, CarError> {
+        let file = fs::OpenOptions::new().read(true).open(path)?;
+        Ok(ProgressReader::new(file, self.progress.clone()))
+    }
+
+    fn file_packed(&self) {
+        if let Some(progress) = self.progress.as_ref() {
+            progress.update(|p| p.files += 1);
+        }
+    }
+
+    /// the report with the blocks written by the `writer`.
+    fn into_report(mut self, writer: &impl CarWriter) -> PackReport {
+        self.report.blocks = writer.blocks_written();
+        if let Some(chunks) = self.chunks {
+            self.report.chunks = chunks.report;
+        }
+        self.report
+    }
+}
+
 /// archive the directory to the target CAR format file
 /// `path` is the directory archived in to the CAR file.
 /// `to_carfile` is the target file.
+#[deprecated(note = "use `pack_dir` or `pack_file` with the `PackOptions`")]
 pub fn pack_files<T>(
     path: impl AsRef<Path>,
     to_carfile: T,
@@ -38,28 +122,295 @@ pub fn pack_files<T>(
 where
     T: std::io::Write + std::io::Seek,
 {
+    let options = PackOptions::new()
+        .with_hasher_codec(hasher_codec)
+        .with_no_wrap_file(no_wrap_file);
+    pack_files_with(path, to_carfile, &options)
+}
+
+/// archive the directory to the target CAR format file with the options.
+/// `path` is the directory archived in to the CAR file.
+/// `to_carfile` is the target file.
+/// `options` is the pack options.
+pub fn pack_files_with<T>(
+    path: impl AsRef<Path>,
+    to_carfile: T,
+    options: &PackOptions,
+) -> Result<Cid, CarError>
+where
+    T: std::io::Write + std::io::Seek,
+{
+    pack_files_with_report(path, to_carfile, options).map(|(cid, _)| cid)
+}
+
+/// archive the directory to the target CAR format file with the options,
+/// return the root cid and the report of the pack.
+/// `path` is the directory archived in to the CAR file.
+/// `to_carfile` is the target file.
+/// `options` is the pack options.
+pub fn pack_files_with_report<T>(
+    path: impl AsRef<Path>,
+    to_carfile: T,
+    options: &PackOptions,
+) -> Result<(Cid, PackReport), CarError>
+where
+    T: std::io::Write + std::io::Seek,
+{
+    // ensure sufficient file block size for head, after the root cid generated using the content, fill back the head.
+    let header = CarHeader::new_v1(vec![options.pb_cid(&[])?]);
+    let mut writer = CarWriterV1::new(to_carfile, header)
+        .with_dedup(options.block_dedup)
+        .with_buffer_size(options.write_buffer_size);
+    let (root_cid, report) = pack_into(path, &mut writer, options)?;
+    let header = CarHeader::V1(CarHeaderV1::new(vec![root_cid]));
+    writer.rewrite_header(header)?;
+    Ok((root_cid, report))
+}
+
+/// archive the directory to the target CAR format file with the options like
+/// `pack_files_with_report`, return the `PackResult` listing the files packed with their
+/// cids and sizes, e.g. for the upload manifest without reading the CAR back.
+/// `path` is the directory archived in to the CAR file.
+/// `to_carfile` is the target file.
+/// `options` is the pack options.
+pub fn pack_files_with_manifest<T>(
+    path: impl AsRef<Path>,
+    to_carfile: T,
+    options: &PackOptions,
+) -> Result<PackResult, CarError>
+where
+    T: std::io::Write + std::io::Seek,
+{
+    options.check()?;
+    let header = CarHeader::new_v1(vec![options.pb_cid(&[])?]);
+    let mut writer = CarWriterV1::new(to_carfile, header)
+        .with_dedup(options.block_dedup)
+        .with_buffer_size(options.write_buffer_size);
+    let mut state = PackState::new(options);
+    state.manifest = Some(Vec::new());
+    let root = pack_source(path, &mut writer, options, &mut state)?;
+    writer.rewrite_header(CarHeader::new_v1(vec![root]))?;
+    let files = state.manifest.take().unwrap_or_default();
+    Ok(PackResult {
+        root,
+        files,
+        stats: state.into_report(&writer),
+    })
+}
+
+/// archive the directory to the non-seekable target, e.g. the stdout or the socket.
+/// the roots must be written before the blocks, so the source is read twice,
+/// the first pass only computes the root cid. with the `options.spill_dir` the source
+/// is read once to a temp CAR in the directory, which is copied to the target after.
+/// `path` is the directory archived in to the CAR file.
+/// `to_carfile` is the target stream.
+/// `options` is the pack options.
+pub fn pack_files_to_stream<T>(
+    path: impl AsRef<Path>,
+    mut to_carfile: T,
+    options: &PackOptions,
+) -> Result<(Cid, PackReport), CarError>
+where
+    T: std::io::Write,
+{
+    if let Some(spill_dir) = options.spill_dir.as_deref() {
+        let temp = TempDir::new_in(Some(spill_dir), "blockless-car-spill")?;
+        let spill = temp.path().join("spill.car");
+        let rs = pack_files_with_report(path, fs::File::create(&spill)?, options)?;
+        io::copy(&mut fs::File::open(&spill)?, &mut to_carfile)?;
+        to_carfile.flush()?;
+        return Ok(rs);
+    }
+    let header = CarHeader::new_v1(vec![options.pb_cid(&[])?]);
+    let mut writer = StreamCarWriterV1::new(io::sink(), header)
+        .with_dedup(options.block_dedup)
+        .with_buffer_size(options.write_buffer_size);
+    // the progress of the pass computing the root is not reported.
+    let first_pass = PackOptions {
+        progress: None,
+        ..options.clone()
+    };
+    let (root_cid, _) = pack_into(&path, &mut writer, &first_pass)?;
+    let header = CarHeader::new_v1(vec![root_cid]);
+    let mut writer = StreamCarWriterV1::new(to_carfile, header)
+        .with_dedup(options.block_dedup)
+        .with_buffer_size(options.write_buffer_size);
+    let (cid, report) = pack_into(&path, &mut writer, options)?;
+    if cid != root_cid {
+        return Err(CarError::InvalidFile(
+            "the source is changed during the pack".into(),
+        ));
+    }
+    writer.flush()?;
+    Ok((root_cid, report))
+}
+
+/// archive the directory into the `writer`, e.g. the `TeeCarWriter` of the archive file
+/// and the upload stream. return the root cid and the report of the pack, the header of
+/// the writer is not touched, so the stream writers must be given the final roots up front.
+/// `path` is the directory archived in to the CAR file.
+/// `writer` is the target writer.
+/// `options` is the pack options.
+pub fn pack_files_into<W>(
+    path: impl AsRef<Path>,
+    writer: &mut W,
+    options: &PackOptions,
+) -> Result<(Cid, PackReport), CarError>
+where
+    W: CarWriter,
+{
+    pack_into(path, writer, options)
+}
+
+/// archive the content of the `reader`, e.g. the stdin, as a file to the target CAR format
+/// file, the root is the file cid. the chunking and the leaves of the `options` apply, the
+/// metadata and the wrapping directory don't.
+pub(crate) fn pack_reader_with<R, T>(
+    reader: &mut R,
+    to_carfile: T,
+    options: &PackOptions,
+) -> Result<Cid, CarError>
+where
+    R: std::io::Read,
+    T: std::io::Write + std::io::Seek,
+{
+    options.check()?;
+    let header = CarHeader::new_v1(vec![options.pb_cid(&[])?]);
+    let mut writer = CarWriterV1::new(to_carfile, header)
+        .with_dedup(options.block_dedup)
+        .with_buffer_size(options.write_buffer_size);
+    let mut state = PackState::new(options);
+    let check_cancel = || options.cancel.check(0);
+    let (root_cid, _) = process_stream(
+        &mut ProgressReader::new(reader, state.progress.clone()),
+        &mut ProgressWriter::new(&mut writer, state.progress.clone()),
+        options,
+        state.chunks.as_mut(),
+        &NodeMeta::default(),
+        &check_cancel,
+    )?;
+    state.file_packed();
+    writer.rewrite_header(CarHeader::new_v1(vec![root_cid]))?;
+    Ok(root_cid)
+}
+
+/// write the blocks of the `path` to the `writer`, return the root cid, the header is not touched.
+fn pack_into<W>(
+    path: impl AsRef<Path>,
+    writer: &mut W,
+    options: &PackOptions,
+) -> Result<(Cid, PackReport), CarError>
+where
+    W: CarWriter,
+{
+    options.check()?;
+    let mut state = PackState::new(options);
+    let root_cid = pack_source(path, writer, options, &mut state)?;
+    Ok((root_cid, state.into_report(writer)))
+}
+
+/// write the blocks of the source `path` to the `writer` with the state shared by the sources.
+fn pack_source<W>(
+    path: impl AsRef<Path>,
+    writer: &mut W,
+    options: &PackOptions,
+    state: &mut PackState,
+) -> Result<Cid, CarError>
+where
+    W: CarWriter,
+{
+    state.root_tsize = 0;
+    let writer = &mut ProgressWriter::new(writer, state.progress.clone());
     let src_path = path.as_ref().to_path_buf();
     if !src_path.exists() {
         return Err(CarError::IO(io::ErrorKind::NotFound.into()));
     }
-    // ensure sufficient file block size for head, after the root cid generated using the content, fill back the head.
-    let mut root_cid = empty_pb_cid(hasher_codec);
-    let header = CarHeader::new_v1(vec![root_cid]);
-    let mut writer = CarWriterV1::new(to_carfile, header);
-
-    if src_path.is_file() {
+    let mut root_cid = options.pb_cid(&[])?;
+    let file_type = fs::metadata(&src_path)?.file_type();
+    let is_special = !file_type.is_file() && !file_type.is_dir();
+    // opening the named pipe blocks until the writer opens it.
+    if is_special && options.special_files == SpecialFiles::Reject {
+        return Err(CarError::InvalidFile(format!(
+            "the source {} is not a regular file or directory",
+            src_path.display()
+        )));
+    }
+    if file_type.is_file() || is_special {
         // if the source is a file then do not walk directory tree, process the file directly
-        let mut file = fs::OpenOptions::new().read(true).open(&src_path)?;
-        let file_size = file.metadata()?.len() as usize;
-        let (hash, size) = process_file(&mut file, &mut writer, file_size, hasher_codec)?;
-        if no_wrap_file {
-            root_cid = hash;
-        } else {
+        let mut file = state.open(&src_path)?;
+        let meta = NodeMeta::read(&src_path, options)?;
+        let name = src_path.file_name().map(Path::new).unwrap_or(&src_path);
+        let file_options = options.file_options(name);
+        let chunks = state.chunks.as_mut();
+        let check_cancel = || options.cancel.check(0);
+        // the content length of the file, the special files are counted while read.
+        let (hash, size, file_len) = match (is_special, options.special_files) {
+            (false, _) => {
+                let file_size = fs::metadata(&src_path)?.len();
+                let (hash, size) = process_file(
+                    &mut file,
+                    writer,
+                    file_size as usize,
+                    &file_options,
+                    chunks,
+                    &meta,
+                    &check_cancel,
+                )?;
+                (hash, size, file_size)
+            }
+            (true, SpecialFiles::Reject) => unreachable!("the special source is rejected"),
+            (true, SpecialFiles::UntilEof) => {
+                let mut file = SeekBuf::new(file);
+                let (hash, size) = process_stream(
+                    &mut file,
+                    writer,
+                    &file_options,
+                    chunks,
+                    &meta,
+                    &check_cancel,
+                )?;
+                (hash, size, file.read_len())
+            }
+            (true, SpecialFiles::Size(len)) => {
+                let mut file = SeekBuf::new(file.take(len));
+                let rs = process_file(
+                    &mut file,
+                    writer,
+                    len as usize,
+                    &file_options,
+                    chunks,
+                    &meta,
+                    &check_cancel,
+                )?;
+                if file.read_len() != len {
+                    return Err(CarError::InvalidFile(format!(
+                        "the source {} is {} bytes, expect {len}",
+                        src_path.display(),
+                        file.read_len()
+                    )));
+                }
+                (rs.0, rs.1, len)
+            }
+        };
+        let name = match options.no_wrap_file {
+            true => None,
+            false => top_node_name(&src_path, options)?,
+        };
+        state.file_packed();
+        if let Some(manifest) = state.manifest.as_mut() {
+            let path = name.clone().unwrap_or_default();
+            manifest.push(PackedEntry {
+                path,
+                cid: hash,
+                size: file_len,
+            });
+        }
+        if let Some(name) = name {
             // wrap file into a directory entry
             let link = Link {
                 hash,
                 file_type: FileType::Directory,
-                name: src_path.file_name().unwrap().to_str().unwrap().to_owned(),
+                name,
                 tsize: size as u64,
             };
             let unix_fs = UnixFs {
@@ -71,35 +422,42 @@ where
             let bs = DagPbCodec
                 .encode(&dir_ipld)
                 .map_err(|e| CarError::Parsing(e.to_string()))?;
-            let cid = pb_cid(&bs, hasher_codec);
+            let cid = options.pb_cid(&bs)?;
             writer.write_block(cid, bs)?;
             root_cid = cid;
+        } else {
+            root_cid = hash;
         }
     } else {
         //source is a directory, walk the directory tree
-        let (walk_paths, mut path_cache) = walk_path(&path)?;
+        state.cache_prefix = top_node_name(&src_path, options)?;
+        let (walk_paths, mut path_cache) = walk_path_with(&path, options)?;
         for walk_path in &walk_paths {
             process_path(
                 &src_path,
                 &mut root_cid,
-                &mut writer,
+                writer,
                 walk_path,
                 &mut path_cache,
-                hasher_codec,
+                options,
+                state,
             )?;
         }
         // add an additional top node like in go-car
-        let root_node = path_cache.get(&src_path).unwrap();
-        let tsize: u64 = DagPbCodec
-            .encode(&root_node.encode()?)
-            .map_err(|e| CarError::Parsing(e.to_string()))?
-            .len() as u64
-            + root_node.links.iter().map(|link| link.tsize).sum::<u64>();
+        let Some(name) = top_node_name(&src_path, options)? else {
+            return Ok(root_cid);
+        };
+        if let Some(manifest) = state.manifest.as_mut() {
+            for file in manifest.iter_mut() {
+                file.path = format!("{name}/{}", file.path);
+            }
+        }
+        let tsize = state.root_tsize;
         let unix_fs = UnixFs {
             links: vec![Link {
                 hash: root_cid,
                 file_type: FileType::Directory,
-                name: src_path.file_name().unwrap().to_str().unwrap().to_string(),
+                name,
                 tsize,
             }],
             file_type: FileType::Directory,
@@ -110,12 +468,110 @@ where
         let bs = DagPbCodec
             .encode(&ipld)
             .map_err(|e| CarError::Parsing(e.to_string()))?;
-        root_cid = pb_cid(&bs, hasher_codec);
+        root_cid = options.pb_cid(&bs)?;
         writer.write_block(root_cid, bs)?;
     }
+    Ok(root_cid)
+}
+
+/// archive every source as an independent DAG to the target CAR format file,
+/// the header lists the roots in the order of the sources.
+/// the blocks shared by the sources are written once.
+/// `paths` is the list of the source files and directories.
+/// `to_carfile` is the target file.
+/// `options` is the pack options, applied to every source.
+pub fn pack_files_multi_root<T, P>(
+    paths: impl IntoIterator<Item = P>,
+    to_carfile: T,
+    options: &PackOptions,
+) -> Result<(Vec<Cid>, PackReport), CarError>
+where
+    T: std::io::Write + std::io::Seek,
+    P: AsRef<Path>,
+{
+    options.check()?;
+    let paths: Vec<P> = paths.into_iter().collect();
+    if paths.is_empty() {
+        return Err(CarError::InvalidFile("no source to pack".into()));
+    }
+    let header = CarHeader::new_v1(vec![options.pb_cid(&[])?; paths.len()]);
+    let mut writer = CarWriterV1::new(to_carfile, header)
+        .with_dedup(options.block_dedup)
+        .with_buffer_size(options.write_buffer_size);
+    let mut state = PackState::new(options);
+    let mut roots = Vec::with_capacity(paths.len());
+    for path in &paths {
+        roots.push(pack_source(path, &mut writer, options, &mut state)?);
+    }
+    let header = CarHeader::V1(CarHeaderV1::new(roots.clone()));
+    writer.rewrite_header(header)?;
+    Ok((roots, state.into_report(&writer)))
+}
+
+/// the name of the top node link to the source, none if the top node is omitted.
+fn top_node_name(src_path: &Path, options: &PackOptions) -> Result<Option<String>, CarError> {
+    let name = match &options.top_node {
+        TopNode::Omit => return Ok(None),
+        TopNode::Named(name) => {
+            if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+                return Err(CarError::InvalidFile(format!(
+                    "invalid top node name {name:?}"
+                )));
+            }
+            name.clone()
+        }
+        TopNode::Basename => src_path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .ok_or_else(|| CarError::InvalidFile(format!("no basename of {}", src_path.display())))?
+            .to_string(),
+    };
+    options.limits.check_name(src_path, &name)?;
+    Ok(Some(name))
+}
+
+/// archive the listed paths to the target CAR format file, like `tar -T`,
+/// the root of the CAR is the directory contains the top level entries of the paths.
+/// the parent directories of the paths are added, the listed directories are not walked,
+/// so the CAR contains exactly the listed files and directories.
+/// `base` is the directory the paths are relative to.
+/// `paths` is the list of the file and directory paths.
+/// `to_carfile` is the target file.
+/// `options` is the pack options.
+pub fn pack_file_list<T, P>(
+    base: impl AsRef<Path>,
+    paths: impl IntoIterator<Item = P>,
+    to_carfile: T,
+    options: &PackOptions,
+) -> Result<(Cid, PackReport), CarError>
+where
+    T: std::io::Write + std::io::Seek,
+    P: AsRef<Path>,
+{
+    options.check()?;
+    let mut state = PackState::new(options);
+    let base = base.as_ref().to_path_buf();
+    let mut root_cid = options.pb_cid(&[])?;
+    let header = CarHeader::new_v1(vec![root_cid]);
+    let mut writer = CarWriterV1::new(to_carfile, header)
+        .with_dedup(options.block_dedup)
+        .with_buffer_size(options.write_buffer_size);
+    let (walk_paths, mut path_cache) = walk_list(&base, paths, options)?;
+    let mut progress_writer = ProgressWriter::new(&mut writer, state.progress.clone());
+    for walk_path in &walk_paths {
+        process_path(
+            &base,
+            &mut root_cid,
+            &mut progress_writer,
+            walk_path,
+            &mut path_cache,
+            options,
+            &mut state,
+        )?;
+    }
     let header = CarHeader::V1(CarHeaderV1::new(vec![root_cid]));
     writer.rewrite_header(header)?;
-    Ok(root_cid)
+    Ok((root_cid, state.into_report(&writer)))
 }
 
 pub fn pack_buffer<W, R>(
@@ -128,182 +584,603 @@ where
     W: std::io::Write + std::io::Seek,
     R: std::io::Read + std::io::Seek,
 {
-    let header = CarHeader::new_v1(vec![empty_pb_cid(hasher_codec)]);
-    let mut writer = CarWriterV1::new(writer, header);
-    let (hash, _) = process_file(reader, &mut writer, size, hasher_codec)?;
+    let options = PackOptions::new().with_hasher_codec(hasher_codec);
+    let header = CarHeader::new_v1(vec![options.pb_cid(&[])?]);
+    let mut writer = CarWriterV1::new(writer, header)
+        .with_dedup(options.block_dedup)
+        .with_buffer_size(options.write_buffer_size);
+    let meta = NodeMeta::default();
+    let (hash, _) = process_file(reader, &mut writer, size, &options, None, &meta, &|| Ok(()))?;
     let header = CarHeader::V1(CarHeaderV1::new(vec![hash]));
     writer.rewrite_header(header)?;
     Ok(hash)
 }
 
-trait HasherCodec {
-    fn codec(&self) -> multicodec::Codec;
-}
-
-impl HasherCodec for Sha2_256 {
-    fn codec(&self) -> multicodec::Codec {
-        multicodec::Codec::Sha2_256
-    }
-}
-
-impl HasherCodec for Blake2b256 {
-    fn codec(&self) -> multicodec::Codec {
-        multicodec::Codec::Blake2b_256
-    }
-}
-
-fn cid_gen<H: Hasher + Default + HasherCodec>(
+/// the cid generator of the raw block streamed to the writer.
+fn raw_cid_gen(
+    mut hasher: Box<dyn BlockHasher>,
 ) -> impl FnMut(WriteStream) -> Option<Result<Cid, CarError>> {
-    let mut hasher = H::default();
     move |w: WriteStream| match w {
         WriteStream::Bytes(bs) => {
             hasher.update(bs);
             None
         }
         WriteStream::End => {
-            let code = hasher.codec();
-            let bs = hasher.finalize();
-            let h = match Multihash::wrap(code.code() as u64, bs) {
-                Ok(h) => h,
-                Err(e) => return Some(Err(CarError::Parsing(e.to_string()))),
-            };
-            Some(Ok(Cid::new_v1(RawCodec.into(), h)))
+            Some(finalize_multihash(&mut *hasher).map(|h| Cid::new_v1(RawCodec.into(), h)))
+        }
+    }
+}
+
+/// hash the whole stream, return the raw cid of the content.
+fn hash_stream<R>(r: &mut R, options: &PackOptions) -> Result<Cid, CarError>
+where
+    R: std::io::Read,
+{
+    let mut hasher = options.hasher()?;
+    let mut buffer = [0u8; 10240];
+    loop {
+        let n = r.read(&mut buffer)?;
+        if n == 0 {
+            break;
         }
+        hasher.update(&buffer[0..n]);
     }
+    let h = finalize_multihash(&mut *hasher)?;
+    Ok(Cid::new_v1(RawCodec.into(), h))
+}
+
+/// encode the chunk as a dag-pb file leaf, like `ipfs add` without the raw leaves.
+fn leaf_node(data: Vec<u8>) -> UnixFs {
+    UnixFs {
+        file_type: FileType::File,
+        file_size: Some(data.len() as u64),
+        // the empty leaf has no data field, same as go-unixfs.
+        data: Some(data).filter(|d| !d.is_empty()),
+        ..Default::default()
+    }
+}
+
+fn encode_node(unix_fs: &UnixFs) -> Result<Vec<u8>, CarError> {
+    DagPbCodec
+        .encode(&unix_fs.encode()?)
+        .map_err(|e| CarError::Parsing(e.to_string()))
 }
 
-fn stream_block<R, W>(
-    writer: &mut CarWriterV1<W>,
-    stream_len: usize,
+/// write the symlink node of the `path`, the target is stored as is without resolving.
+fn write_symlink<W: CarWriter>(
+    path: &Path,
+    writer: &mut W,
+    options: &PackOptions,
+) -> Result<(Cid, u64), CarError> {
+    let target = fs::read_link(path)?;
+    let target = target.to_str().ok_or_else(|| {
+        CarError::InvalidFile(format!(
+            "the symlink target of {} is not utf8",
+            path.display()
+        ))
+    })?;
+    let bs = encode_node(&UnixFs::new_symlink(target))?;
+    let size = bs.len() as u64;
+    let cid = options.pb_cid(&bs)?;
+    writer.write_block(cid, bs)?;
+    Ok((cid, size))
+}
+
+/// the leaf block of the chunk.
+enum Leaf {
+    /// the chunk found in the chunk cache, the cid and the size of the block written before.
+    Cached(Cid, u64),
+    /// the chunk hashed into the block, with its key and bytes for the chunk cache.
+    Encoded {
+        cid: Cid,
+        block: Vec<u8>,
+        chunk: Option<(ChunkKey, Vec<u8>)>,
+    },
+}
+
+/// write the chunk of the stream, return the cid and the size of the leaf block.
+/// with the chunk cache, the chunk found in the cache is not hashed and written again.
+fn write_chunk<R, W>(
+    writer: &mut W,
+    size: usize,
     r: &mut R,
-    hasher_codec: multicodec::Codec,
-) -> Result<Cid, CarError>
+    options: &PackOptions,
+    cache: Option<&mut ChunkCache>,
+) -> Result<(Cid, u64), CarError>
 where
-    W: std::io::Write + std::io::Seek,
+    W: CarWriter,
     R: std::io::Read + std::io::Seek,
 {
-    match hasher_codec {
-        multicodec::Codec::Sha2_256 => writer.stream_block(cid_gen::<Sha2_256>(), stream_len, r),
-        multicodec::Codec::Blake2b_256 => {
-            writer.stream_block(cid_gen::<Blake2b256>(), stream_len, r)
+    if options.raw_leaves && cache.is_none() {
+        let cid = writer.stream_block(raw_cid_gen(options.hasher()?), size, r)?;
+        return Ok((cid, size as u64));
+    }
+    let mut buf = vec![0u8; size];
+    r.read_exact(&mut buf)?;
+    let leaf = match cache.as_deref() {
+        Some(cache) => match lookup_chunk(cache, buf, options) {
+            Ok(leaf) => leaf,
+            Err((key, buf)) => encode_leaf(buf, Some(key), options)?,
+        },
+        None => encode_leaf(buf, None, options)?,
+    };
+    put_leaf(writer, leaf, size, cache)
+}
+
+/// look up the chunk in the chunk cache, return the key and the chunk if not found.
+fn lookup_chunk(
+    cache: &ChunkCache,
+    buf: Vec<u8>,
+    options: &PackOptions,
+) -> Result<Leaf, (ChunkKey, Vec<u8>)> {
+    let key = chunk_key(&buf, options.raw_leaves);
+    match cache.get(&key, &buf) {
+        Some((cid, tsize)) => Ok(Leaf::Cached(cid, tsize)),
+        None => Err((key, buf)),
+    }
+}
+
+/// hash the chunk into the raw leaf or the dag-pb leaf, return the cid and the block.
+fn encode_chunk(buf: Vec<u8>, options: &PackOptions) -> Result<(Cid, Vec<u8>), CarError> {
+    if options.raw_leaves {
+        Ok((options.raw_cid(&buf)?, buf))
+    } else {
+        let bs = encode_node(&leaf_node(buf))?;
+        Ok((options.pb_cid(&bs)?, bs))
+    }
+}
+
+/// hash the chunk into the leaf, the chunk with the `key` is kept for the chunk cache.
+fn encode_leaf(
+    buf: Vec<u8>,
+    key: Option<ChunkKey>,
+    options: &PackOptions,
+) -> Result<Leaf, CarError> {
+    let chunk = key.map(|key| (key, buf.clone()));
+    let (cid, block) = encode_chunk(buf, options)?;
+    Ok(Leaf::Encoded { cid, block, chunk })
+}
+
+/// write the leaf block of the chunk of `size` bytes unless found in the chunk cache,
+/// return the cid and the size of the block.
+fn put_leaf<W: CarWriter>(
+    writer: &mut W,
+    leaf: Leaf,
+    size: usize,
+    cache: Option<&mut ChunkCache>,
+) -> Result<(Cid, u64), CarError> {
+    let Some(cache) = cache else {
+        let Leaf::Encoded { cid, block, .. } = leaf else {
+            unreachable!("the leaf is cached without the chunk cache");
+        };
+        let tsize = block.len() as u64;
+        writer.write_block(cid, block)?;
+        return Ok((cid, tsize));
+    };
+    cache.report.chunks += 1;
+    cache.report.bytes += size as u64;
+    match leaf {
+        Leaf::Cached(cid, tsize) => {
+            cache.report.chunks_deduped += 1;
+            cache.report.bytes_deduped += size as u64;
+            Ok((cid, tsize))
+        }
+        Leaf::Encoded { cid, block, chunk } => {
+            let tsize = block.len() as u64;
+            writer.write_block(cid, block)?;
+            if let Some((key, data)) = chunk {
+                cache.insert(key, data, cid, tsize);
+            }
+            Ok((cid, tsize))
         }
-        _ => unimplemented!(),
     }
 }
 
+/// write the chunks of the `sizes` by the `jobs` hashing workers, the chunks are read and
+/// the blocks are written by the current thread. with `Determinism::Strict` the blocks are
+/// written in the order of the chunks, the same as the sequential pack. the chunks found in
+/// the chunk cache are not sent to the workers, the same chunks in flight are all hashed.
+#[allow(clippy::too_many_arguments)]
+fn write_chunks_parallel<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    sizes: &[usize],
+    jobs: usize,
+    options: &PackOptions,
+    mut cache: Option<&mut ChunkCache>,
+    check_cancel: &dyn Fn() -> Result<(), CarError>,
+) -> Result<Vec<Link>, CarError>
+where
+    W: CarWriter,
+    R: std::io::Read,
+{
+    let mut links: Vec<Option<Link>> = sizes.iter().map(|_| None).collect();
+    let mut put = |idx: usize,
+                   leaf: Result<Leaf, CarError>,
+                   writer: &mut W,
+                   cache: Option<&mut ChunkCache>|
+     -> Result<(), CarError> {
+        let (hash, tsize) = put_leaf(writer, leaf?, sizes[idx], cache)?;
+        links[idx] = Some(Link {
+            hash,
+            file_type: FileType::Raw,
+            name: String::default(),
+            tsize,
+        });
+        Ok(())
+    };
+    let max_in_flight = jobs * READ_AHEAD_PER_JOB;
+    thread::scope(|scope| {
+        let (chunk_tx, chunk_rx) =
+            mpsc::sync_channel::<(usize, Vec<u8>, Option<ChunkKey>)>(max_in_flight);
+        let chunk_rx = Arc::new(Mutex::new(chunk_rx));
+        // the channels hold all the chunks in flight, so the sends never wait for each other.
+        let (leaf_tx, leaf_rx) = mpsc::sync_channel(max_in_flight);
+        for _ in 0..jobs {
+            let chunk_rx = chunk_rx.clone();
+            let leaf_tx = leaf_tx.clone();
+            scope.spawn(move || loop {
+                // the lock is released before hashing.
+                let received = chunk_rx.lock().unwrap().recv();
+                let Ok((idx, buf, key)) = received else {
+                    break;
+                };
+                if leaf_tx.send((idx, encode_leaf(buf, key, options))).is_err() {
+                    break;
+                }
+            });
+        }
+        let mut leaves = match options.determinism {
+            Determinism::Strict => OrderedReceiver::new(leaf_rx),
+            Determinism::Relaxed => OrderedReceiver::relaxed(leaf_rx),
+        };
+        // the sender is dropped on the errors too, so the workers stop.
+        let mut written = 0;
+        for (idx, size) in sizes.iter().enumerate() {
+            check_cancel()?;
+            // wait for the leaves in flight to be written before reading more chunks.
+            while idx - written >= max_in_flight {
+                let Some((idx, leaf)) = leaves.next() else {
+                    break;
+                };
+                put(idx, leaf, writer, cache.as_deref_mut())?;
+                written += 1;
+            }
+            let mut buf = vec![0u8; *size];
+            reader.read_exact(&mut buf)?;
+            // the cached leaf is sent in the order of the chunks, without hashing.
+            let lookup = match cache.as_deref() {
+                Some(cache) => lookup_chunk(cache, buf, options).map_err(|(k, b)| (Some(k), b)),
+                None => Err((None, buf)),
+            };
+            match lookup {
+                Ok(leaf) => {
+                    let _ = leaf_tx.send((idx, Ok(leaf)));
+                }
+                // the workers only stop when the channel is closed.
+                Err((key, buf)) => {
+                    let _ = chunk_tx.send((idx, buf, key));
+                }
+            }
+            while let Some((idx, leaf)) = leaves.try_next() {
+                put(idx, leaf, writer, cache.as_deref_mut())?;
+                written += 1;
+            }
+        }
+        drop(chunk_tx);
+        drop(leaf_tx);
+        for (idx, leaf) in leaves {
+            put(idx, leaf, writer, cache.as_deref_mut())?;
+        }
+        Ok::<_, CarError>(())
+    })?;
+    links
+        .into_iter()
+        .map(|l| l.ok_or_else(|| CarError::InvalidFile("a chunk is not hashed".into())))
+        .collect()
+}
+
 fn process_file<W, R>(
     reader: &mut R,
-    writer: &mut CarWriterV1<W>,
+    writer: &mut W,
     size: usize,
-    hasher_codec: multicodec::Codec,
+    options: &PackOptions,
+    mut cache: Option<&mut ChunkCache>,
+    meta: &NodeMeta,
+    check_cancel: &dyn Fn() -> Result<(), CarError>,
 ) -> Result<(Cid, Size), CarError>
 where
-    W: std::io::Write + std::io::Seek,
+    W: CarWriter,
     R: std::io::Read + std::io::Seek,
 {
-    if size < MAX_SECTION_SIZE {
-        Ok((stream_block(writer, size, reader, hasher_codec)?, size))
+    let chunk_size = options.chunk_size;
+    if size < chunk_size {
+        if !options.raw_leaves && !meta.is_empty() {
+            // the leaf node carries the metadata itself.
+            let mut buf = vec![0u8; size];
+            reader.read_exact(&mut buf)?;
+            let mut unix_fs = leaf_node(buf);
+            meta.apply(&mut unix_fs);
+            let bs = encode_node(&unix_fs)?;
+            let cid = options.pb_cid(&bs)?;
+            let size = bs.len();
+            writer.write_block(cid, bs)?;
+            return Ok((cid, size));
+        }
+        let (hash, tsize) = write_chunk(writer, size, reader, options, cache)?;
+        if meta.is_empty() || !options.raw_leaves {
+            return Ok((hash, tsize as usize));
+        }
+        // the raw leaf can't carry the metadata, wrap it into a file node.
+        let mut unix_fs = UnixFs {
+            links: vec![Link {
+                hash,
+                file_type: FileType::Raw,
+                name: String::default(),
+                tsize: size as u64,
+            }],
+            file_type: FileType::File,
+            file_size: Some(size as u64),
+            block_sizes: vec![size as u64],
+            ..Default::default()
+        };
+        meta.apply(&mut unix_fs);
+        let bs = encode_node(&unix_fs)?;
+        let cid = options.pb_cid(&bs)?;
+        let size = size + bs.len();
+        writer.write_block(cid, bs)?;
+        Ok((cid, size))
     } else {
-        let mut secs = size / MAX_SECTION_SIZE;
-        if size % MAX_SECTION_SIZE > 0 {
+        let mut secs = size / chunk_size;
+        if !size.is_multiple_of(chunk_size) {
             secs += 1;
         }
-        let mut block_sizes = vec![];
-        let mut links = (0..secs)
-            .map(|i| {
-                let size = if i < secs - 1 {
-                    MAX_SECTION_SIZE
-                } else {
-                    size % MAX_SECTION_SIZE
-                };
-                block_sizes.push(size as u64);
-                let cid = stream_block(writer, size, reader, hasher_codec);
-                cid.map(|cid| Link {
+        let sizes: Vec<usize> = (0..secs)
+            .map(|i| match i < secs - 1 {
+                true => chunk_size,
+                false => size - i * chunk_size,
+            })
+            .collect();
+        let block_sizes = sizes.iter().map(|s| *s as u64).collect();
+        let jobs = options.workers().min(secs);
+        if jobs > 1 {
+            let links =
+                write_chunks_parallel(reader, writer, &sizes, jobs, options, cache, check_cancel)?;
+            return write_file_links(writer, links, block_sizes, options, meta);
+        }
+        let links = sizes
+            .into_iter()
+            .map(|size| {
+                check_cancel()?;
+                let chunk = write_chunk(writer, size, reader, options, cache.as_deref_mut());
+                chunk.map(|(cid, tsize)| Link {
                     hash: cid,
                     file_type: FileType::Raw,
                     name: String::default(),
-                    tsize: size as u64,
+                    tsize,
                 })
             })
             .collect::<Result<Vec<Link>, CarError>>()?;
-        while links.len() > MAX_LINK_COUNT {
-            let mut new_links = vec![];
-            let mut new_block_sizes = vec![];
-            let mut link_count = links.len() / MAX_LINK_COUNT;
-            if links.len() % MAX_LINK_COUNT > 0 {
-                link_count += 1;
-            }
-            for _ in 0..link_count {
-                let len = if links.len() >= MAX_LINK_COUNT {
-                    MAX_LINK_COUNT
-                } else {
-                    links.len()
-                };
-                let links_size = block_sizes.as_slice()[0..len].iter().sum();
-                let unix_fs = UnixFs {
-                    links: links.drain(0..len).collect(),
-                    file_type: FileType::File,
-                    file_size: Some(links_size),
-                    block_sizes: block_sizes.drain(0..len).collect(),
-                    ..Default::default()
-                };
-                let ipld = unix_fs.encode()?;
-                let bs = DagPbCodec
-                    .encode(&ipld)
-                    .map_err(|e| CarError::Parsing(e.to_string()))?;
-                let size = links_size + bs.len() as u64;
-                let cid = pb_cid(&bs, hasher_codec);
-                writer.write_block(cid, bs)?;
-                let new_link = Link {
-                    hash: cid,
-                    file_type: FileType::File,
-                    name: String::default(),
-                    tsize: size,
-                };
-                new_links.push(new_link);
-                new_block_sizes.push(links_size);
-            }
-            links = new_links;
-            block_sizes = new_block_sizes;
+        write_file_links(writer, links, block_sizes, options, meta)
+    }
+}
+
+/// pack the stream of the unknown size as a file, the chunks are read until the end of
+/// the stream, the layout is the same as `process_file` of the size.
+fn process_stream<W, R>(
+    reader: &mut R,
+    writer: &mut W,
+    options: &PackOptions,
+    mut cache: Option<&mut ChunkCache>,
+    meta: &NodeMeta,
+    check_cancel: &dyn Fn() -> Result<(), CarError>,
+) -> Result<(Cid, Size), CarError>
+where
+    W: CarWriter,
+    R: std::io::Read,
+{
+    let chunk_size = options.chunk_size;
+    let mut links = vec![];
+    let mut block_sizes = vec![];
+    loop {
+        check_cancel()?;
+        let mut buf = Vec::with_capacity(chunk_size);
+        (&mut *reader)
+            .take(chunk_size as u64)
+            .read_to_end(&mut buf)?;
+        let size = buf.len();
+        if links.is_empty() && size < chunk_size {
+            // the content smaller than a chunk.
+            let mut buf = io::Cursor::new(buf);
+            return process_file(&mut buf, writer, size, options, cache, meta, check_cancel);
         }
-        let links_size = links.iter().map(|link| link.tsize as usize).sum::<usize>();
-        let unix_fs = UnixFs {
-            file_size: Some(block_sizes.iter().sum()),
-            links,
-            file_type: FileType::File,
-            block_sizes,
-            ..Default::default()
-        };
-        let file_ipld = unix_fs.encode()?;
-        let bs = DagPbCodec
-            .encode(&file_ipld)
-            .map_err(|e| CarError::Parsing(e.to_string()))?;
-        let size = links_size + bs.len();
-        let cid = pb_cid(&bs, hasher_codec);
-        writer.write_block(cid, bs)?;
-        Ok((cid, size))
+        if size == 0 {
+            break;
+        }
+        let mut buf = io::Cursor::new(buf);
+        let (hash, tsize) = write_chunk(writer, size, &mut buf, options, cache.as_deref_mut())?;
+        block_sizes.push(size as u64);
+        links.push(Link {
+            hash,
+            file_type: FileType::Raw,
+            name: String::default(),
+            tsize,
+        });
+        if size < chunk_size {
+            break;
+        }
+    }
+    write_file_links(writer, links, block_sizes, options, meta)
+}
+
+/// write the file node of the chunk `links`, the links over `MAX_LINK_COUNT` are
+/// grouped into the intermediate nodes.
+fn write_file_links<W: CarWriter>(
+    writer: &mut W,
+    mut links: Vec<Link>,
+    mut block_sizes: Vec<u64>,
+    options: &PackOptions,
+    meta: &NodeMeta,
+) -> Result<(Cid, Size), CarError> {
+    while links.len() > MAX_LINK_COUNT {
+        let mut new_links = vec![];
+        let mut new_block_sizes = vec![];
+        let mut link_count = links.len() / MAX_LINK_COUNT;
+        if !links.len().is_multiple_of(MAX_LINK_COUNT) {
+            link_count += 1;
+        }
+        for _ in 0..link_count {
+            let len = if links.len() >= MAX_LINK_COUNT {
+                MAX_LINK_COUNT
+            } else {
+                links.len()
+            };
+            let links_size = block_sizes.as_slice()[0..len].iter().sum();
+            let unix_fs = UnixFs {
+                links: links.drain(0..len).collect(),
+                file_type: FileType::File,
+                file_size: Some(links_size),
+                block_sizes: block_sizes.drain(0..len).collect(),
+                ..Default::default()
+            };
+            let bs = encode_node(&unix_fs)?;
+            let tsize = unix_fs.links.iter().map(|l| l.tsize).sum::<u64>();
+            let size = tsize + bs.len() as u64;
+            let cid = options.pb_cid(&bs)?;
+            writer.write_block(cid, bs)?;
+            let new_link = Link {
+                hash: cid,
+                file_type: FileType::File,
+                name: String::default(),
+                tsize: size,
+            };
+            new_links.push(new_link);
+            new_block_sizes.push(links_size);
+        }
+        links = new_links;
+        block_sizes = new_block_sizes;
     }
+    let links_size = links.iter().map(|link| link.tsize as usize).sum::<usize>();
+    let mut unix_fs = UnixFs {
+        file_size: Some(block_sizes.iter().sum()),
+        links,
+        file_type: FileType::File,
+        block_sizes,
+        ..Default::default()
+    };
+    meta.apply(&mut unix_fs);
+    let bs = encode_node(&unix_fs)?;
+    let size = links_size + bs.len();
+    let cid = options.pb_cid(&bs)?;
+    writer.write_block(cid, bs)?;
+    Ok((cid, size))
 }
 
-fn process_path<W: std::io::Write + std::io::Seek>(
+fn process_path<W: CarWriter>(
     root_path: impl AsRef<Path>,
     root_cid: &mut Cid,
-    writer: &mut CarWriterV1<W>,
+    writer: &mut W,
     (abs_path, parent_idx): &(Rc<PathBuf>, Option<usize>),
     path_cache: &mut WalkPathCache,
-    hasher_codec: multicodec::Codec,
+    options: &PackOptions,
+    state: &mut PackState,
 ) -> Result<(), CarError> {
     let unix_fs = path_cache.get_mut(abs_path).unwrap();
     let mut parent_tsize = 0;
     for link in unix_fs.links.iter_mut() {
         if let FileType::File = link.file_type {
-            let mut file = fs::OpenOptions::new()
-                .read(true)
-                .open(&abs_path.join(&link.name))?;
-            let file_size = file.metadata()?.len() as usize;
-            let (hash, size) = process_file(&mut file, writer, file_size, hasher_codec)?;
+            let entries = state.entries;
+            let check_cancel = || options.cancel.check(entries);
+            check_cancel()?;
+            let file_path = abs_path.join(&link.name);
+            let file_size = fs::metadata(&file_path)?.len();
+            let meta = NodeMeta::read(&file_path, options)?;
+            let rel_path = file_path.strip_prefix(&root_path).unwrap_or(&file_path);
+            let file_options = options.file_options(rel_path);
+            let entry_path = state.manifest.is_some().then(|| unixfs_path(rel_path));
+            let cached = match options.cache.as_ref() {
+                Some(cache) => {
+                    let path = unixfs_path(rel_path);
+                    let path = match state.cache_prefix.as_ref() {
+                        Some(prefix) => format!("{prefix}/{path}"),
+                        None => path,
+                    };
+                    cache.copy_file(&path, &file_path, file_size, &meta, &file_options, writer)?
+                }
+                None => None,
+            };
+            let (hash, size) = match cached {
+                Some((hash, size)) => {
+                    state.report.cache.files += 1;
+                    state.report.cache.bytes += file_size;
+                    (hash, size)
+                }
+                None => {
+                    let mut file = state.open(&file_path)?;
+                    if options.dedup_files {
+                        let (digest, packed) =
+                            state
+                                .files
+                                .lookup(&file_path, file_size, &file_options, &meta)?;
+                        match packed {
+                            Some((hash, tsize, original)) => {
+                                state.report.dedup.files.push(DedupFile {
+                                    path: file_path,
+                                    original,
+                                    cid: hash,
+                                    size: file_size,
+                                });
+                                (hash, tsize as usize)
+                            }
+                            None => {
+                                let (hash, size) = process_file(
+                                    &mut file,
+                                    writer,
+                                    file_size as usize,
+                                    &file_options,
+                                    state.chunks.as_mut(),
+                                    &meta,
+                                    &check_cancel,
+                                )?;
+                                state.files.insert(
+                                    file_path,
+                                    file_size,
+                                    digest,
+                                    (hash, size as u64),
+                                    meta,
+                                    &file_options,
+                                );
+                                (hash, size)
+                            }
+                        }
+                    } else {
+                        process_file(
+                            &mut file,
+                            writer,
+                            file_size as usize,
+                            &file_options,
+                            state.chunks.as_mut(),
+                            &meta,
+                            &check_cancel,
+                        )?
+                    }
+                }
+            };
             link.hash = hash;
             link.tsize = size as u64;
+            if let Some((manifest, path)) = state.manifest.as_mut().zip(entry_path) {
+                manifest.push(PackedEntry {
+                    path,
+                    cid: hash,
+                    size: file_size,
+                });
+            }
+            state.file_packed();
+            state.entries += 1;
+        } else if link.file_type == FileType::Symlink {
+            options.cancel.check(state.entries)?;
+            (link.hash, link.tsize) = write_symlink(&abs_path.join(&link.name), writer, options)?;
+            state.entries += 1;
         }
         parent_tsize += link.tsize;
     }
@@ -314,17 +1191,32 @@ fn process_path<W: std::io::Write + std::io::Seek>(
             true => std::cmp::Ordering::Greater,
             false => std::cmp::Ordering::Less,
         });
-    let fs_ipld: Ipld = unix_fs.encode()?;
-    let bs = DagPbCodec
-        .encode(&fs_ipld)
-        .map_err(|e| CarError::Parsing(e.to_string()))?;
-    parent_tsize += bs.len() as u64;
-    let cid = pb_cid(&bs, hasher_codec);
+    let meta = NodeMeta::read(abs_path, options)?;
+    let cid = match options.hamt_threshold {
+        Some(threshold) if estimated_dir_size(&unix_fs.links) > threshold => {
+            let links = unix_fs.links.clone();
+            let (cid, tsize) = write_hamt_dir(links, writer, options, &meta)?;
+            parent_tsize = tsize;
+            cid
+        }
+        _ => {
+            meta.apply(unix_fs);
+            let fs_ipld: Ipld = unix_fs.encode()?;
+            let bs = DagPbCodec
+                .encode(&fs_ipld)
+                .map_err(|e| CarError::Parsing(e.to_string()))?;
+            parent_tsize += bs.len() as u64;
+            let cid = options.pb_cid(&bs)?;
+            writer.write_block(cid, bs)?;
+            cid
+        }
+    };
     if root_path.as_ref() == abs_path.as_ref() {
         *root_cid = cid;
+        state.root_tsize = parent_tsize;
     }
-    writer.write_block(cid, bs)?;
     unix_fs.cid = Some(cid);
+    state.entries += 1;
     match abs_path.parent() {
         Some(parent) => {
             let parent = Rc::new(parent.to_path_buf());
@@ -338,42 +1230,57 @@ fn process_path<W: std::io::Write + std::io::Seek>(
     Ok(())
 }
 
-fn digest(data: &[u8], hasher_codec: multicodec::Codec) -> Multihash {
-    match hasher_codec {
-        multicodec::Codec::Sha2_256 => Code::Sha2_256.digest(data),
-        multicodec::Codec::Blake2b_256 => Code::Blake2b256.digest(data),
-        _ => unimplemented!(),
-    }
-}
-
-#[inline(always)]
-pub fn empty_pb_cid(hasher_codec: multicodec::Codec) -> Cid {
-    pb_cid(&[], hasher_codec)
-}
-
-#[inline(always)]
-pub fn pb_cid(data: &[u8], hasher_codec: multicodec::Codec) -> Cid {
-    Cid::new_v1(DagPbCodec.into(), digest(data, hasher_codec))
-}
-
-#[inline(always)]
-pub fn raw_cid(data: &[u8], hasher_codec: multicodec::Codec) -> Cid {
-    Cid::new_v1(RawCodec.into(), digest(data, hasher_codec))
-}
-
 /// walk all directory, and record the directory informations.
 /// `WalkPath` contain the index in children.
 pub fn walk_path(path: impl AsRef<Path>) -> Result<(Vec<WalkPath>, WalkPathCache), CarError> {
+    walk_path_with(path, &PackOptions::default())
+}
+
+/// walk all directory with the options, and record the directory informations.
+/// the path depth and name length are checked with the `options.limits`,
+/// the entries not kept by the `options.walk_filters`, the `exclude` and `include` patterns
+/// or the `.gitignore` files, or skipped by the `options.hook` are left out.
+/// the symlinks are not followed, they are packed as the unixfs symlink nodes.
+pub fn walk_path_with(
+    path: impl AsRef<Path>,
+    options: &PackOptions,
+) -> Result<(Vec<WalkPath>, WalkPathCache), CarError> {
+    let limits = &options.limits;
     let root_path: Rc<PathBuf> = Rc::new(path.as_ref().into());
-    let mut queue = VecDeque::from(vec![root_path.clone()]);
+    let mut queue = VecDeque::from(vec![(root_path.clone(), 0, IgnoreRules::default())]);
     let mut path_cache = HashMap::new();
     let mut walk_paths = Vec::new();
-    while let Some(dir_path) = queue.pop_back() {
+    while let Some((dir_path, depth, ignore)) = queue.pop_back() {
         let mut unix_dir = UnixFs::new_directory();
+        let ignore = match options.gitignore {
+            true => {
+                let rel_dir = dir_path.strip_prefix(&*root_path).unwrap_or(&dir_path);
+                ignore.with_dir(&dir_path, rel_dir)?
+            }
+            false => ignore,
+        };
         for entry in fs::read_dir(&*dir_path)? {
             let entry = entry?;
             let file_type = entry.file_type()?;
             let name = entry.file_name().to_str().unwrap_or("").to_string();
+            if file_type.is_file() || file_type.is_dir() || file_type.is_symlink() {
+                let path = entry.path();
+                let rel_path = path.strip_prefix(&*root_path).unwrap_or(&path);
+                let is_dir = file_type.is_dir();
+                if !options.keep_entry(rel_path, is_dir) || ignore.is_ignored(rel_path, is_dir) {
+                    continue;
+                }
+                if let Some(hook) = options.hook.as_ref() {
+                    match hook.check(&path, &entry.metadata()?) {
+                        Decision::Keep => {}
+                        Decision::Skip => continue,
+                        Decision::Reject(reason) => {
+                            return Err(CarError::Rejected(format!("{}: {reason}", path.display())))
+                        }
+                    }
+                }
+                limits.check(&path, &name, depth + 1)?;
+            }
             if file_type.is_file() {
                 unix_dir.add_link(Link {
                     name,
@@ -390,7 +1297,13 @@ pub fn walk_path(path: impl AsRef<Path>) -> Result<(Vec<WalkPath>, WalkPathCache
                     ..Default::default()
                 });
                 walk_paths.push((rc_abs_path.clone(), Some(idx)));
-                queue.push_back(rc_abs_path);
+                queue.push_back((rc_abs_path, depth + 1, ignore.clone()));
+            } else if file_type.is_symlink() {
+                unix_dir.add_link(Link {
+                    name,
+                    file_type: FileType::Symlink,
+                    ..Default::default()
+                });
             }
         }
         path_cache.insert(dir_path, unix_dir);
@@ -402,9 +1315,127 @@ pub fn walk_path(path: impl AsRef<Path>) -> Result<(Vec<WalkPath>, WalkPathCache
     Ok((walk_paths, path_cache))
 }
 
+/// the names of the path components, the leading root is stripped like `tar`.
+fn list_path_names(path: &Path) -> Result<Vec<String>, CarError> {
+    let mut names = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => match name.to_str() {
+                Some(name) => names.push(name.to_string()),
+                None => {
+                    return Err(CarError::InvalidFile(format!(
+                        "the path {} is not utf8",
+                        path.display()
+                    )))
+                }
+            },
+            Component::CurDir => {}
+            _ => {
+                return Err(CarError::InvalidFile(format!(
+                    "the path {} is out of the base directory",
+                    path.display()
+                )))
+            }
+        }
+    }
+    Ok(names)
+}
+
+/// the listed `path` relative to the `base`, the absolute paths must be under the `base`.
+fn list_path_relative<'a>(base: &Path, path: &'a Path) -> Result<&'a Path, CarError> {
+    if !path.is_absolute() {
+        return Ok(path);
+    }
+    if let Ok(rel) = path.strip_prefix(base) {
+        return Ok(rel);
+    }
+    // the relative base, e.g. `.`, is compared by the absolute path.
+    fs::canonicalize(base)
+        .ok()
+        .and_then(|base| path.strip_prefix(base).ok())
+        .ok_or_else(|| {
+            CarError::InvalidFile(format!(
+                "the path {} is out of the base directory",
+                path.display()
+            ))
+        })
+}
+
+/// build the directory tree of the listed paths relative to the `base`.
+/// the parent directories of the paths are added, the listed directories are not walked.
+fn walk_list<P: AsRef<Path>>(
+    base: &Path,
+    paths: impl IntoIterator<Item = P>,
+    options: &PackOptions,
+) -> Result<(Vec<WalkPath>, WalkPathCache), CarError> {
+    let root_path = Rc::new(base.to_path_buf());
+    let mut path_cache: WalkPathCache = HashMap::new();
+    path_cache.insert(root_path.clone(), UnixFs::new_directory());
+    let mut added = HashSet::new();
+    let mut dirs = Vec::new();
+    for path in paths {
+        let path = path.as_ref();
+        let names = list_path_names(list_path_relative(base, path)?)?;
+        if names.is_empty() {
+            continue;
+        }
+        // the same path is stat here and read by the pack.
+        let source = names
+            .iter()
+            .fold(base.to_path_buf(), |p, name| p.join(name));
+        let file_type = fs::symlink_metadata(source)?.file_type();
+        if !file_type.is_file() && !file_type.is_dir() && !file_type.is_symlink() {
+            return Err(CarError::InvalidFile(format!(
+                "the path {} is not a file, directory or symlink",
+                path.display()
+            )));
+        }
+        let mut parent = root_path.clone();
+        for (i, name) in names.iter().enumerate() {
+            let depth = i + 1;
+            let child = Rc::new(parent.join(name));
+            options.limits.check(&*child, name, depth)?;
+            let is_dir = depth < names.len() || file_type.is_dir();
+            if added.insert(child.clone()) {
+                let file_type = if is_dir {
+                    FileType::Directory
+                } else if file_type.is_symlink() {
+                    FileType::Symlink
+                } else {
+                    FileType::File
+                };
+                let idx = path_cache.get_mut(&parent).unwrap().add_link(Link {
+                    name: name.clone(),
+                    file_type,
+                    ..Default::default()
+                });
+                if is_dir {
+                    path_cache.insert(child.clone(), UnixFs::new_directory());
+                    dirs.push((child.clone(), Some(idx), depth));
+                }
+            }
+            parent = child;
+        }
+    }
+    // the children must be processed before the parent.
+    dirs.sort_by_key(|d| std::cmp::Reverse(d.2));
+    let mut walk_paths: Vec<WalkPath> = dirs.into_iter().map(|(p, idx, _)| (p, idx)).collect();
+    walk_paths.push((root_path, None));
+    Ok((walk_paths, path_cache))
+}
+
 #[cfg(test)]
+#[allow(deprecated)]
 mod test {
     use super::*;
+    use crate::{
+        reader::CarReader,
+        utils::{
+            codec_hasher, extract_ipld_with, raw_cid, BlockHasher, CancelToken, ExtractOptions,
+            PathLimits, Pattern, Progress,
+        },
+        writer::BlockDedup,
+    };
     use rand::prelude::*;
     use rand_chacha::ChaCha8Rng;
     use std::{
@@ -412,6 +1443,7 @@ mod test {
         fs::File,
         io::{BufWriter, Cursor, Write},
         str::FromStr,
+        sync::atomic::{AtomicUsize, Ordering},
     };
     use tempdir::TempDir;
 
@@ -698,4 +1730,1045 @@ mod test {
 
         assert_eq!(test_cid, reference);
     }
+
+    #[test]
+    fn test_pack_files_path_limits() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        std::fs::create_dir_all(root_dir.join("a/b/c")).unwrap();
+        File::create(root_dir.join("a/b/c/test.txt")).unwrap();
+
+        let options = PackOptions::new().with_limits(PathLimits::new(3, 255));
+        let car_file = Cursor::new(vec![]);
+        let result = pack_files_with(&root_dir, car_file, &options);
+        assert!(matches!(
+            result,
+            Err(CarError::PathTooDeep {
+                depth: 4,
+                limit: 3,
+                ..
+            })
+        ));
+
+        let options = PackOptions::new().with_limits(PathLimits::new(4, 255));
+        let car_file = Cursor::new(vec![]);
+        assert!(pack_files_with(&root_dir, car_file, &options).is_ok());
+
+        let options = PackOptions::new().with_limits(PathLimits::new(4, 4));
+        let car_file = Cursor::new(vec![]);
+        match pack_files_with(&root_dir, car_file, &options) {
+            Err(CarError::NameTooLong { path, len, limit }) => {
+                assert!(path.ends_with("test.txt"));
+                assert_eq!(len, 8);
+                assert_eq!(limit, 4);
+            }
+            r => panic!("unexpected result {r:?}"),
+        }
+    }
+
+    #[test]
+    fn test_pack_files_dedup_files() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        std::fs::create_dir_all(root_dir.join("a")).unwrap();
+        std::fs::create_dir_all(root_dir.join("b")).unwrap();
+        write_large_file(&root_dir.join("a/data.bin"), 1000000);
+        write_large_file(&root_dir.join("b/data.bin"), 1000000);
+        std::fs::write(root_dir.join("a/test.txt"), b"hello world").unwrap();
+        std::fs::write(root_dir.join("b/test.txt"), b"hello car!!").unwrap();
+
+        let options = PackOptions::new();
+        let (reference, report) =
+            pack_files_with_report(&root_dir, Cursor::new(vec![]), &options).unwrap();
+        assert_eq!(report.dedup.files_deduped(), 0);
+
+        let options = PackOptions::new().with_dedup_files(true);
+        let (root, report) =
+            pack_files_with_report(&root_dir, Cursor::new(vec![]), &options).unwrap();
+        assert_eq!(root, reference);
+        assert_eq!(report.dedup.files_deduped(), 1);
+        assert_eq!(report.dedup.bytes_saved(), 1000000);
+        let dedup = &report.dedup.files[0];
+        assert!(dedup.path.ends_with("data.bin"));
+        assert!(dedup.original.ends_with("data.bin"));
+        assert_ne!(dedup.path, dedup.original);
+    }
+
+    #[test]
+    fn test_pack_files_dedup_roundtrip() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        // the identical files and the identical subdirectory, the `.bin` files are all the same.
+        let files = [
+            "a/data.bin",
+            "b/data.bin",
+            "a/sub/x.txt",
+            "a/sub/y.bin",
+            "b/sub/x.txt",
+            "b/sub/y.bin",
+        ];
+        for name in files {
+            let path = root_dir.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            if name.ends_with(".bin") {
+                write_large_file(&path, 300000);
+            } else {
+                std::fs::write(&path, b"hello world").unwrap();
+            }
+        }
+
+        let options = PackOptions::new().with_dedup_files(true);
+        let mut car = Cursor::new(vec![]);
+        let (root, report) = pack_files_with_report(&root_dir, &mut car, &options).unwrap();
+        assert_eq!(report.dedup.files_deduped(), 4);
+
+        car.set_position(0);
+        let mut reader = crate::reader::new_v1(car).unwrap();
+        let output = TempDir::new("blockless-car-temp-output-dir").unwrap();
+        extract_ipld_with(
+            &mut reader,
+            root,
+            Some(output.path()),
+            &ExtractOptions::new(),
+        )
+        .unwrap();
+        for name in files {
+            let extracted = std::fs::read(output.path().join("root").join(name)).unwrap();
+            assert_eq!(
+                extracted,
+                std::fs::read(root_dir.join(name)).unwrap(),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_pack_files_cache() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        std::fs::create_dir_all(root_dir.join("a")).unwrap();
+        write_large_file(&root_dir.join("a/data.bin"), MAX_SECTION_SIZE * 3 + 100);
+        std::fs::write(root_dir.join("a/test.txt"), b"hello world").unwrap();
+        std::fs::write(root_dir.join("empty.txt"), b"").unwrap();
+        let cache_car = temp_dir.path().join("cache.car");
+        let pack = |options: &PackOptions| {
+            let mut car = Cursor::new(vec![]);
+            let (root, report) = pack_files_with_report(&root_dir, &mut car, options).unwrap();
+            (root, car.into_inner(), report.cache)
+        };
+
+        let options = PackOptions::new().with_preserve_metadata(true);
+        pack_files_with(
+            &root_dir,
+            std::fs::File::create(&cache_car).unwrap(),
+            &options,
+        )
+        .unwrap();
+        let cache = Arc::new(PackCache::open(&cache_car).unwrap());
+        assert_eq!(cache.len(), 3);
+        let (reference, reference_car, _) = pack(&options);
+        let (root, car, report) = pack(&options.clone().with_cache(cache.clone()));
+        assert_eq!(root, reference);
+        assert_eq!(car, reference_car);
+        assert_eq!(report.files, 3);
+        assert_eq!(report.bytes, MAX_SECTION_SIZE as u64 * 3 + 111);
+
+        // the changed file and the other layout are packed again.
+        std::fs::write(root_dir.join("a/test.txt"), b"hello car!!!").unwrap();
+        let (reference, _, _) = pack(&options);
+        let (root, _, report) = pack(&options.clone().with_cache(cache.clone()));
+        assert_eq!((root, report.files), (reference, 2));
+        let options = options.with_raw_leaves(false);
+        let (reference, _, _) = pack(&options);
+        let (root, _, report) = pack(&options.with_cache(cache));
+        assert_eq!((root, report.files), (reference, 0));
+
+        // without the metadata, the files modified before the cache are unchanged.
+        let options = PackOptions::new();
+        pack_files_with(
+            &root_dir,
+            std::fs::File::create(&cache_car).unwrap(),
+            &options,
+        )
+        .unwrap();
+        let hour_later = std::time::SystemTime::now() + std::time::Duration::from_secs(3600);
+        let file = std::fs::File::options()
+            .write(true)
+            .open(&cache_car)
+            .unwrap();
+        file.set_modified(hour_later).unwrap();
+        let cache = Arc::new(PackCache::open(&cache_car).unwrap());
+        let (reference, reference_car, _) = pack(&options);
+        let (root, car, report) = pack(&options.clone().with_cache(cache));
+        assert_eq!((root, report.files), (reference, 3));
+        assert_eq!(car, reference_car);
+        let hour_ago = std::time::SystemTime::now() - std::time::Duration::from_secs(3600);
+        file.set_modified(hour_ago).unwrap();
+        let cache = Arc::new(PackCache::open(&cache_car).unwrap());
+        let (_, _, report) = pack(&options.with_cache(cache));
+        assert_eq!(report.files, 0);
+    }
+
+    #[test]
+    fn test_pack_files_block_dedup() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        std::fs::create_dir_all(&root_dir).unwrap();
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            std::fs::write(root_dir.join(name), b"same").unwrap();
+        }
+        let pack = |dedup: BlockDedup| {
+            let mut car = Cursor::new(vec![]);
+            let options = PackOptions::new().with_block_dedup(dedup);
+            let root = pack_files_with(&root_dir, &mut car, &options).unwrap();
+            let mut reader = crate::reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+            (root, reader.section_count().unwrap())
+        };
+        let (root, sections) = pack(BlockDedup::All);
+        // the content block is written for every file.
+        assert_eq!(pack(BlockDedup::Off), (root, sections + 2));
+        assert_eq!(pack(BlockDedup::Recent(1)), (root, sections));
+    }
+
+    #[test]
+    fn test_pack_files_chunk_cache() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        std::fs::create_dir_all(&root_dir).unwrap();
+        // the files share the first 4 chunks.
+        write_large_file(&root_dir.join("a.bin"), MAX_SECTION_SIZE * 4);
+        write_large_file(&root_dir.join("b.bin"), MAX_SECTION_SIZE * 4 + 100);
+
+        let options = PackOptions::new();
+        let mut reference_car = Cursor::new(vec![]);
+        let (reference, _) =
+            pack_files_with_report(&root_dir, &mut reference_car, &options).unwrap();
+
+        let options = PackOptions::new().with_chunk_cache(Some(16));
+        let mut car = Cursor::new(vec![]);
+        let (root, report) = pack_files_with_report(&root_dir, &mut car, &options).unwrap();
+        assert_eq!(root, reference);
+        assert_eq!(car.get_ref(), reference_car.get_ref());
+        assert_eq!(report.chunks.chunks, 9);
+        assert_eq!(report.chunks.chunks_deduped, 4);
+        assert_eq!(report.chunks.bytes_deduped, MAX_SECTION_SIZE as u64 * 4);
+        assert!(report.chunks.ratio() > 0.49);
+        // the chunks shared are written once.
+        let mut reader = crate::reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+        assert_eq!(report.blocks, reader.section_count().unwrap() as u64);
+    }
+
+    #[test]
+    fn test_chunk_cache_bounded() {
+        let mut cache = ChunkCache::new(2);
+        let chunks: Vec<Vec<u8>> = (0..3u8).map(|i| vec![i]).collect();
+        for chunk in chunks.iter() {
+            let cid = raw_cid(chunk, multicodec::Codec::Sha2_256);
+            cache.insert(chunk_key(chunk, true), chunk.clone(), cid, 1);
+        }
+        let get = |chunk: &[u8], raw_leaves| cache.get(&chunk_key(chunk, raw_leaves), chunk);
+        assert!(get(&chunks[0], true).is_none());
+        assert!(get(&chunks[1], true).is_some());
+        assert_eq!(
+            get(&chunks[2], true),
+            Some((raw_cid(&chunks[2], multicodec::Codec::Sha2_256), 1))
+        );
+        // the leaf layout is in the key, and the bytes of the chunk found are compared.
+        assert!(get(&chunks[2], false).is_none());
+        assert!(cache
+            .get(&chunk_key(&chunks[2], true), &chunks[1])
+            .is_none());
+    }
+
+    /// the sha2-256 hasher counting the digests of all its fresh hashers.
+    struct CountingHasher {
+        inner: Box<dyn BlockHasher>,
+        digests: Arc<AtomicUsize>,
+    }
+
+    impl CountingHasher {
+        fn new(digests: Arc<AtomicUsize>) -> Self {
+            Self {
+                inner: codec_hasher(multicodec::Codec::Sha2_256).unwrap(),
+                digests,
+            }
+        }
+    }
+
+    impl BlockHasher for CountingHasher {
+        fn code(&self) -> u64 {
+            self.inner.code()
+        }
+
+        fn update(&mut self, data: &[u8]) {
+            self.inner.update(data);
+        }
+
+        fn finalize(&mut self) -> Vec<u8> {
+            self.digests.fetch_add(1, Ordering::Relaxed);
+            self.inner.finalize()
+        }
+
+        fn fresh(&self) -> Box<dyn BlockHasher> {
+            Box::new(Self::new(self.digests.clone()))
+        }
+    }
+
+    #[test]
+    fn test_chunk_cache_skips_hashing() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        std::fs::create_dir_all(&root_dir).unwrap();
+        // the files share the first 4 chunks.
+        write_large_file(&root_dir.join("a.bin"), MAX_SECTION_SIZE * 4);
+        write_large_file(&root_dir.join("b.bin"), MAX_SECTION_SIZE * 4 + 100);
+        for (jobs, raw_leaves) in [(1, true), (1, false), (4, true), (4, false)] {
+            let pack = |chunk_cache| {
+                let digests = Arc::new(AtomicUsize::new(0));
+                let options = PackOptions::new()
+                    .with_block_hasher(Arc::new(CountingHasher::new(digests.clone())))
+                    .with_raw_leaves(raw_leaves)
+                    .with_jobs(jobs)
+                    .with_chunk_cache(chunk_cache);
+                let mut car = Cursor::new(vec![]);
+                let (_, report) = pack_files_with_report(&root_dir, &mut car, &options).unwrap();
+                (car.into_inner(), digests.load(Ordering::Relaxed), report)
+            };
+            let (reference, reference_digests, _) = pack(None);
+            let (car, digests, report) = pack(Some(16));
+            assert_eq!(car, reference);
+            // the chunks found in the cache are not hashed again.
+            assert_eq!(report.chunks.chunks_deduped, 4);
+            assert_eq!(digests, reference_digests - 4);
+        }
+    }
+
+    #[test]
+    fn test_pack_file_list() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let base = temp_dir.path().join("base");
+        std::fs::create_dir_all(base.join("a")).unwrap();
+        std::fs::create_dir_all(base.join("b/c")).unwrap();
+        std::fs::write(base.join("a/x.txt"), b"hello world").unwrap();
+        std::fs::write(base.join("a/y.txt"), b"not listed").unwrap();
+        std::fs::write(base.join("b/c/z.txt"), b"not listed").unwrap();
+
+        // the directory with exactly the listed entries.
+        let expected = temp_dir.path().join("expected");
+        std::fs::create_dir_all(expected.join("a")).unwrap();
+        std::fs::create_dir_all(expected.join("b")).unwrap();
+        std::fs::write(expected.join("a/x.txt"), b"hello world").unwrap();
+        let mut car = Cursor::new(vec![]);
+        pack_files(&expected, &mut car, multicodec::Codec::Sha2_256, false).unwrap();
+        let mut reader = crate::reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+        let top = reader.header().roots()[0];
+        let top: UnixFs = (top, reader.ipld(&top).unwrap()).try_into().unwrap();
+
+        let paths = ["./a/x.txt", "b", "a/x.txt"];
+        let options = PackOptions::new();
+        let (root, _) = pack_file_list(&base, paths, Cursor::new(vec![]), &options).unwrap();
+        assert_eq!(root, top.links()[0].hash);
+
+        let result = pack_file_list(&base, ["../base/a"], Cursor::new(vec![]), &options);
+        assert!(matches!(result, Err(CarError::InvalidFile(_))));
+    }
+
+    #[test]
+    fn test_pack_file_list_absolute() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let base = temp_dir.path().join("base");
+        std::fs::create_dir_all(base.join("a")).unwrap();
+        std::fs::write(base.join("a/x.txt"), b"hello world").unwrap();
+        std::fs::write(temp_dir.path().join("outside.txt"), b"outside").unwrap();
+        let options = PackOptions::new();
+        let (expected, _) =
+            pack_file_list(&base, ["a/x.txt"], Cursor::new(vec![]), &options).unwrap();
+
+        // the absolute paths under the base are packed like the relative ones, `find /abs`.
+        let absolute = [base.join("a/x.txt")];
+        let (root, _) = pack_file_list(&base, absolute, Cursor::new(vec![]), &options).unwrap();
+        assert_eq!(root, expected);
+
+        // the absolute paths out of the base are rejected instead of read under the base.
+        for path in [
+            temp_dir.path().join("outside.txt"),
+            PathBuf::from("/a/x.txt"),
+            base.join("../outside.txt"),
+        ] {
+            let result = pack_file_list(&base, [&path], Cursor::new(vec![]), &options);
+            assert!(
+                matches!(result, Err(CarError::InvalidFile(_))),
+                "{}",
+                path.display()
+            );
+        }
+    }
+
+    #[test]
+    fn test_pack_files_cancel() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        std::fs::create_dir_all(&root_dir).unwrap();
+        std::fs::write(root_dir.join("test.txt"), b"hello world").unwrap();
+
+        let cancel = CancelToken::new();
+        let options = PackOptions::new().with_cancel(cancel.clone());
+        assert!(pack_files_with(&root_dir, Cursor::new(vec![]), &options).is_ok());
+        cancel.cancel();
+        let result = pack_files_with(&root_dir, Cursor::new(vec![]), &options);
+        assert!(matches!(result, Err(CarError::Cancelled { entries: 0 })));
+    }
+
+    #[test]
+    fn test_pack_files_progress() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        std::fs::create_dir_all(&root_dir).unwrap();
+        std::fs::write(root_dir.join("a.txt"), b"hello world").unwrap();
+        write_large_file(&root_dir.join("big.bin"), 600_000);
+
+        let last = Arc::new(Mutex::new(Progress::default()));
+        let hook = {
+            let last = last.clone();
+            move |p: &Progress| *last.lock().unwrap() = *p
+        };
+        let options = PackOptions::new().with_progress(Arc::new(hook));
+        let mut car = Cursor::new(vec![]);
+        pack_files_with(&root_dir, &mut car, &options).unwrap();
+        let progress = *last.lock().unwrap();
+        assert_eq!(progress.files, 2);
+        assert_eq!(progress.bytes_hashed, 600_011);
+        assert_eq!(progress.bytes_written, car.get_ref().len() as u64);
+    }
+
+    #[test]
+    fn test_pack_files_to_stream() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        std::fs::create_dir_all(root_dir.join("sub")).unwrap();
+        std::fs::write(root_dir.join("test.txt"), b"hello world").unwrap();
+        write_large_file(&root_dir.join("sub/big.bin"), MAX_SECTION_SIZE * 2 + 1);
+
+        let options = PackOptions::new();
+        let mut car = Cursor::new(vec![]);
+        let root = pack_files_with(&root_dir, &mut car, &options).unwrap();
+        let mut stream = vec![];
+        let (stream_root, _) = pack_files_to_stream(&root_dir, &mut stream, &options).unwrap();
+        assert_eq!(stream_root, root);
+        let car = car.into_inner();
+        assert_eq!(stream, car);
+
+        // the spill reads the source once, the temp CAR is removed after.
+        let spill_dir = temp_dir.path().join("spill");
+        std::fs::create_dir(&spill_dir).unwrap();
+        let options = options.with_spill_dir(&spill_dir);
+        let mut stream = vec![];
+        let (stream_root, _) = pack_files_to_stream(&root_dir, &mut stream, &options).unwrap();
+        assert_eq!(stream_root, root);
+        assert_eq!(stream, car);
+        assert_eq!(std::fs::read_dir(&spill_dir).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_pack_files_chunk_size() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let file = temp_dir.path().join("test.bin");
+        write_large_file(&file, MAX_CHUNK_SIZE * 2 + 100);
+        let options = PackOptions::new()
+            .with_chunk_size(MAX_CHUNK_SIZE)
+            .with_no_wrap_file(true);
+        let mut car = Cursor::new(vec![]);
+        let root = pack_files_with(&file, &mut car, &options).unwrap();
+        let mut reader = crate::reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+        let node: UnixFs = (root, reader.ipld(&root).unwrap()).try_into().unwrap();
+        let sizes = [MAX_CHUNK_SIZE as u64, MAX_CHUNK_SIZE as u64, 100];
+        assert_eq!(node.block_sizes, sizes);
+
+        for chunk_size in [0, MAX_CHUNK_SIZE + 1] {
+            let options = PackOptions::new().with_chunk_size(chunk_size);
+            let result = pack_files_with(&file, Cursor::new(vec![]), &options);
+            assert!(matches!(result, Err(CarError::InvalidChunkSize(_))));
+        }
+    }
+
+    #[test]
+    fn test_pack_files_with_manifest() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        std::fs::create_dir_all(root_dir.join("sub")).unwrap();
+        std::fs::write(root_dir.join("sub/a.txt"), b"hello").unwrap();
+        std::fs::write(root_dir.join("b.txt"), b"hello").unwrap();
+        std::fs::write(root_dir.join("c.txt"), b"c").unwrap();
+        let options = PackOptions::new().with_dedup_files(true);
+        let mut car = Cursor::new(vec![]);
+        let result = pack_files_with_manifest(&root_dir, &mut car, &options).unwrap();
+        let mut expected = Cursor::new(vec![]);
+        let (root, stats) = pack_files_with_report(&root_dir, &mut expected, &options).unwrap();
+        assert_eq!((result.root, &result.stats), (root, &stats));
+        let car = car.into_inner();
+        assert_eq!(car, expected.into_inner());
+        #[cfg(feature = "cli-utils")]
+        {
+            let mut reader = crate::reader::new_v1(Cursor::new(car)).unwrap();
+            for file in result.files.iter() {
+                let cid = crate::utils::resolve_path(&mut reader, &file.path).unwrap();
+                assert_eq!(cid, file.cid, "{}", file.path);
+            }
+        }
+        let mut files: Vec<(&str, u64)> = result
+            .files
+            .iter()
+            .map(|f| (f.path.as_str(), f.size))
+            .collect();
+        files.sort();
+        assert_eq!(
+            files,
+            vec![("root/b.txt", 5), ("root/c.txt", 1), ("root/sub/a.txt", 5)]
+        );
+        // the deduplicated file has the cid of the original.
+        let cids: HashSet<Cid> = result.files.iter().map(|f| f.cid).collect();
+        assert_eq!(cids.len(), 2);
+
+        // the root is the file without the wrapping directory.
+        let options = PackOptions::new().with_no_wrap_file(true);
+        let file = root_dir.join("c.txt");
+        let result = pack_files_with_manifest(&file, Cursor::new(vec![]), &options).unwrap();
+        assert_eq!(result.files.len(), 1);
+        assert_eq!(result.files[0].path, "");
+        assert_eq!(
+            (result.files[0].cid, result.files[0].size),
+            (result.root, 1)
+        );
+    }
+
+    #[test]
+    fn test_pack_files_deterministic() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        std::fs::create_dir_all(root_dir.join("sub")).unwrap();
+        write_large_file(&root_dir.join("sub/large.bin"), 300 * 1024 + 7);
+        write_large_file(&root_dir.join("copy.bin"), 64 * 1024);
+        std::fs::write(root_dir.join("a.txt"), b"a").unwrap();
+        let pack = |options: PackOptions| {
+            let mut car = Cursor::new(vec![]);
+            let options = options.with_chunk_size(1024).with_chunk_cache(Some(16));
+            let root = pack_files_with(&root_dir, &mut car, &options).unwrap();
+            (root, car.into_inner())
+        };
+        // the same bytes whatever the count of the workers.
+        let (root, car) = pack(PackOptions::new().with_jobs(1));
+        for jobs in [0, 2, 8] {
+            let strict = PackOptions::new()
+                .with_jobs(jobs)
+                .with_determinism(Determinism::Strict);
+            assert_eq!(pack(strict), (root, car.clone()), "jobs {jobs}");
+        }
+        let relaxed = PackOptions::new()
+            .with_jobs(8)
+            .with_determinism(Determinism::Relaxed);
+        let (relaxed_root, relaxed) = pack(relaxed);
+        assert_eq!((relaxed_root, relaxed.len()), (root, car.len()));
+        let mut reader = crate::reader::new_v1(Cursor::new(relaxed)).unwrap();
+        let limits = crate::utils::MemoryLimits::default();
+        let tree = crate::utils::extract_to_memory(&mut reader, root, limits).unwrap();
+        let large = std::fs::read(root_dir.join("sub/large.bin")).unwrap();
+        assert_eq!(tree.file("root/sub/large.bin"), Some(&large[..]));
+    }
+
+    #[test]
+    fn test_pack_files_chunk_rules() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        std::fs::create_dir_all(root_dir.join("media")).unwrap();
+        let big = root_dir.join("media/a.bin");
+        write_large_file(&big, MAX_SECTION_SIZE * 2 + 1);
+        let small = root_dir.join("notes.txt");
+        std::fs::write(&small, b"hello world").unwrap();
+        let options = PackOptions::new()
+            .with_chunk_rule("media/*.bin=1MiB".parse().unwrap())
+            .with_chunk_rule("*.txt=pb".parse().unwrap())
+            .with_chunk_rule("*=size-1024".parse().unwrap());
+        let mut car = Cursor::new(vec![]);
+        let root = pack_files_with(&root_dir, &mut car, &options).unwrap();
+        let mut reader = crate::reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+        let top = dir_node(&mut reader, root, "root");
+        let media_cid = top.links.iter().find(|l| l.name == "media").unwrap().hash;
+        let media: UnixFs = (media_cid, reader.ipld(&media_cid).unwrap())
+            .try_into()
+            .unwrap();
+
+        // same as packing the files alone with the options of the rules.
+        let single = |path: &Path, options: PackOptions| {
+            let options = options.with_no_wrap_file(true);
+            pack_files_with(path, Cursor::new(vec![]), &options).unwrap()
+        };
+        let expected = single(&big, PackOptions::new().with_chunk_size(MAX_CHUNK_SIZE));
+        assert_eq!(media.links[0].hash, expected);
+        let expected = single(&small, PackOptions::new().with_raw_leaves(false));
+        let notes = top.links.iter().find(|l| l.name == "notes.txt").unwrap();
+        assert_eq!(notes.hash, expected);
+
+        let rule: ChunkRule = "*.txt=64KiB,raw".parse().unwrap();
+        assert_eq!(rule.chunk_size, Some(64 << 10));
+        assert_eq!(rule.raw_leaves, Some(true));
+        for invalid in ["*.txt", "*.txt=", "*.txt=1GB", "[a=1MiB"] {
+            assert!(invalid.parse::<ChunkRule>().is_err(), "{invalid}");
+        }
+        let options = PackOptions::new().with_chunk_rule("*=size-0".parse().unwrap());
+        let result = pack_files_with(&root_dir, Cursor::new(vec![]), &options);
+        assert!(matches!(result, Err(CarError::InvalidChunkSize(0))));
+    }
+
+    #[test]
+    fn test_pack_reader() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let file = temp_dir.path().join("big.bin");
+        write_large_file(&file, MAX_SECTION_SIZE * 2 + 1);
+        let data = std::fs::read(&file).unwrap();
+        for raw_leaves in [true, false] {
+            let options = PackOptions::new().with_raw_leaves(raw_leaves);
+            let expected = {
+                let options = options.clone().with_no_wrap_file(true);
+                pack_files_with(&file, Cursor::new(vec![]), &options).unwrap()
+            };
+            let header = CarHeader::new_v1(vec![options.pb_cid(&[]).unwrap()]);
+            let mut car = Cursor::new(vec![]);
+            let mut writer = CarWriterV1::new(&mut car, header);
+            // the slice is read only, not seekable.
+            let link = pack_reader(
+                "big.bin",
+                &data[..],
+                data.len() as u64,
+                &options,
+                &mut writer,
+            )
+            .unwrap();
+            assert_eq!(link.hash, expected);
+            assert_eq!(link.name, "big.bin");
+            writer
+                .rewrite_header(CarHeader::new_v1(vec![link.hash]))
+                .unwrap();
+            drop(writer);
+            let mut reader = crate::reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+            let limits = crate::utils::MemoryLimits::default();
+            let tree = crate::utils::extract_to_memory(&mut reader, link.hash, limits).unwrap();
+            assert_eq!(tree.file(&link.hash.to_string()), Some(&data[..]));
+        }
+
+        let options = PackOptions::new();
+        let mut writer = CarWriterV1::new(Cursor::new(vec![]), CarHeader::new_v1(vec![]));
+        let len = data.len() as u64 + 1;
+        let result = pack_reader("big.bin", &data[..], len, &options, &mut writer);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_pack_files_walk_filter() {
+        struct SkipTmp;
+        impl WalkFilter for SkipTmp {
+            fn keep(&self, path: &Path, is_dir: bool) -> bool {
+                is_dir || path.extension().is_none_or(|e| e != "tmp")
+            }
+        }
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        for dir in [".git", "node_modules/a", "src/target", "src/lib"] {
+            std::fs::create_dir_all(root_dir.join(dir)).unwrap();
+        }
+        for file in [
+            ".git/HEAD",
+            "node_modules/a/b.js",
+            "src/target/out",
+            "src/lib/a.rs",
+        ] {
+            std::fs::write(root_dir.join(file), file).unwrap();
+        }
+        std::fs::write(root_dir.join("src/a.tmp"), b"tmp").unwrap();
+        let options = PackOptions::new()
+            .with_walk_filter(std::sync::Arc::new(PruneDefaults))
+            .with_walk_filter(std::sync::Arc::new(SkipTmp));
+        let mut car = Cursor::new(vec![]);
+        let root = pack_files_with(&root_dir, &mut car, &options).unwrap();
+        let mut reader = crate::reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+        let limits = crate::utils::MemoryLimits::default();
+        let tree = crate::utils::extract_to_memory(&mut reader, root, limits).unwrap();
+        let files: Vec<String> = tree.files().into_iter().map(|(path, _)| path).collect();
+        assert_eq!(files, vec!["root/src/lib/a.rs"]);
+
+        // the pruned entries are packed without the filters.
+        let root = pack_files_with(&root_dir, Cursor::new(vec![]), &PackOptions::new()).unwrap();
+        assert_ne!(
+            root,
+            pack_files_with(&root_dir, Cursor::new(vec![]), &options).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_pack_files_exclude_include() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        for dir in ["node_modules/a", "src/gen", "docs"] {
+            std::fs::create_dir_all(root_dir.join(dir)).unwrap();
+        }
+        for file in [
+            "node_modules/a/b.js",
+            "src/main.rs",
+            "src/main.rs.bak",
+            "src/gen/out.rs",
+            "src/gen/keep.rs",
+            "docs/a.md",
+            "build.log",
+        ] {
+            std::fs::write(root_dir.join(file), file).unwrap();
+        }
+        std::fs::write(
+            root_dir.join(".gitignore"),
+            "# build\n*.log\n/node_modules/\n",
+        )
+        .unwrap();
+        std::fs::write(root_dir.join("src/.gitignore"), "gen/*\n!gen/keep.rs\n").unwrap();
+        let packed = |options: &PackOptions| {
+            let mut car = Cursor::new(vec![]);
+            let root = pack_files_with(&root_dir, &mut car, options).unwrap();
+            let mut reader = crate::reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+            let limits = crate::utils::MemoryLimits::default();
+            let tree = crate::utils::extract_to_memory(&mut reader, root, limits).unwrap();
+            let files: Vec<String> = tree.files().into_iter().map(|(path, _)| path).collect();
+            files
+        };
+        let pattern = |p: &str| Pattern::new(p).unwrap();
+
+        let options = PackOptions::new()
+            .with_exclude(pattern("node_modules"))
+            .with_exclude(pattern("*.bak"));
+        assert_eq!(
+            packed(&options),
+            vec![
+                "root/.gitignore",
+                "root/build.log",
+                "root/docs/a.md",
+                "root/src/.gitignore",
+                "root/src/gen/keep.rs",
+                "root/src/gen/out.rs",
+                "root/src/main.rs",
+            ]
+        );
+
+        let options = options.with_include(pattern("*.rs"));
+        assert_eq!(
+            packed(&options),
+            vec![
+                "root/src/gen/keep.rs",
+                "root/src/gen/out.rs",
+                "root/src/main.rs"
+            ]
+        );
+
+        let options = PackOptions::new().with_gitignore(true);
+        assert_eq!(
+            packed(&options),
+            vec![
+                "root/.gitignore",
+                "root/docs/a.md",
+                "root/src/.gitignore",
+                "root/src/gen/keep.rs",
+                "root/src/main.rs",
+                "root/src/main.rs.bak",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pack_files_hook() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        std::fs::create_dir_all(root_dir.join("cache")).unwrap();
+        std::fs::write(root_dir.join("cache/big"), vec![0u8; 4096]).unwrap();
+        std::fs::write(root_dir.join("a.txt"), b"a").unwrap();
+        std::fs::write(root_dir.join("big.bin"), vec![1u8; 4096]).unwrap();
+
+        let skip_cache = |path: &Path, metadata: &fs::Metadata| match path.file_name() {
+            Some(name) if metadata.is_dir() && name == "cache" => Decision::Skip,
+            _ => Decision::Keep,
+        };
+        let options = PackOptions::new().with_hook(std::sync::Arc::new(skip_cache));
+        let mut car = Cursor::new(vec![]);
+        let root = pack_files_with(&root_dir, &mut car, &options).unwrap();
+        let mut reader = crate::reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+        let limits = crate::utils::MemoryLimits::default();
+        let tree = crate::utils::extract_to_memory(&mut reader, root, limits).unwrap();
+        let files: Vec<String> = tree.files().into_iter().map(|(path, _)| path).collect();
+        assert_eq!(files, vec!["root/a.txt", "root/big.bin"]);
+
+        let max_size = |_: &Path, metadata: &fs::Metadata| match metadata.len() > 1024 {
+            true if metadata.is_file() => Decision::Reject("over 1024 bytes".into()),
+            _ => Decision::Keep,
+        };
+        let options = options.with_hook(std::sync::Arc::new(max_size));
+        let err = pack_files_with(&root_dir, Cursor::new(vec![]), &options).unwrap_err();
+        assert!(
+            matches!(&err, CarError::Rejected(reason) if reason.ends_with("over 1024 bytes")),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_pack_files_hamt_sharding() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        std::fs::create_dir_all(&root_dir).unwrap();
+        for i in 0..300 {
+            std::fs::write(root_dir.join(format!("file-{i}.txt")), i.to_string()).unwrap();
+        }
+        let options = PackOptions::new().with_hamt_threshold(Some(1024));
+        let mut car = Cursor::new(vec![]);
+        let root = pack_files_with(&root_dir, &mut car, &options).unwrap();
+        let mut reader = crate::reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+        let top: UnixFs = (root, reader.ipld(&root).unwrap()).try_into().unwrap();
+        let shard = top.links[0].hash;
+        let shard: UnixFs = (shard, reader.ipld(&shard).unwrap()).try_into().unwrap();
+        assert_eq!(shard.file_type, FileType::HAMTShard);
+        assert_eq!(shard.fanout, Some(256));
+        assert_eq!(shard.hash_type, Some(0x22));
+        assert!(shard.data.is_some());
+        assert!(shard.links.iter().all(|l| l.name.len() >= 2));
+        let names = shard.links.iter().map(|l| l.name.as_bytes());
+        assert!(names.clone().zip(names.skip(1)).all(|(a, b)| a < b));
+
+        let options = PackOptions::new().with_hamt_threshold(None);
+        let mut car = Cursor::new(vec![]);
+        let root = pack_files_with(&root_dir, &mut car, &options).unwrap();
+        let mut reader = crate::reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+        let top: UnixFs = (root, reader.ipld(&root).unwrap()).try_into().unwrap();
+        let dir = top.links[0].hash;
+        let dir: UnixFs = (dir, reader.ipld(&dir).unwrap()).try_into().unwrap();
+        assert_eq!(dir.file_type, FileType::Directory);
+        assert_eq!(dir.links.len(), 300);
+    }
+
+    #[test]
+    fn test_pack_files_no_raw_leaves() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        std::fs::create_dir_all(&root_dir).unwrap();
+        std::fs::write(root_dir.join("small.txt"), b"hello world").unwrap();
+        write_large_file(&root_dir.join("big.bin"), MAX_SECTION_SIZE * 2 + 1);
+        let options = PackOptions::new().with_raw_leaves(false);
+        let mut car = Cursor::new(vec![]);
+        let root = pack_files_with(&root_dir, &mut car, &options).unwrap();
+        let mut reader = crate::reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+        let raw_codec: u64 = RawCodec.into();
+        assert!(reader.blocks().all(|b| b.unwrap().0.codec() != raw_codec));
+
+        let dir = dir_node(&mut reader, root, "root");
+        let small = crate::utils::find_entry(&mut reader, &dir, "small.txt")
+            .unwrap()
+            .unwrap();
+        let small: UnixFs = (small.hash, reader.ipld(&small.hash).unwrap())
+            .try_into()
+            .unwrap();
+        assert_eq!(small.file_type, FileType::File);
+        assert_eq!(small.data(), Some(&b"hello world"[..]));
+
+        let limits = crate::utils::MemoryLimits::default();
+        let tree = crate::utils::extract_to_memory(&mut reader, root, limits).unwrap();
+        assert_eq!(tree.file("root/small.txt"), Some(&b"hello world"[..]));
+        let big = std::fs::read(root_dir.join("big.bin")).unwrap();
+        assert_eq!(tree.file("root/big.bin"), Some(&big[..]));
+    }
+
+    #[test]
+    fn test_pack_files_cid_v0() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        std::fs::create_dir_all(&root_dir).unwrap();
+        std::fs::write(root_dir.join("small.txt"), b"hello world").unwrap();
+        write_large_file(&root_dir.join("big.bin"), MAX_SECTION_SIZE * 2 + 1);
+        let options = PackOptions::new().with_cid_version(cid::Version::V0);
+        let mut car = Cursor::new(vec![]);
+        let root = pack_files_with(&root_dir, &mut car, &options).unwrap();
+        assert_eq!(root.version(), cid::Version::V0);
+        assert!(root.to_string().starts_with("Qm"));
+        let mut reader = crate::reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+        assert_eq!(reader.header().roots(), vec![root]);
+        let raw_codec: u64 = RawCodec.into();
+        assert!(reader.blocks().all(|b| {
+            let cid = b.unwrap().0;
+            (cid.codec() == raw_codec) == (cid.version() == cid::Version::V1)
+        }));
+        let limits = crate::utils::MemoryLimits::default();
+        let tree = crate::utils::extract_to_memory(&mut reader, root, limits).unwrap();
+        assert_eq!(tree.file("root/small.txt"), Some(&b"hello world"[..]));
+
+        let options = options.with_hasher_codec(multicodec::Codec::Blake2b_256);
+        let err = pack_files_with(&root_dir, Cursor::new(vec![]), &options).unwrap_err();
+        assert!(matches!(err, CarError::InvalidCidVersion(_)));
+    }
+
+    /// the toy hasher under the private-use multicodec, folds the data into 8 bytes.
+    #[derive(Default)]
+    struct FoldHasher {
+        state: [u8; 8],
+        len: usize,
+    }
+
+    impl BlockHasher for FoldHasher {
+        fn code(&self) -> u64 {
+            0x300000
+        }
+
+        fn update(&mut self, data: &[u8]) {
+            for b in data {
+                self.state[self.len % 8] ^= b.rotate_left(self.len as u32 % 7);
+                self.len += 1;
+            }
+        }
+
+        fn finalize(&mut self) -> Vec<u8> {
+            std::mem::take(self).state.to_vec()
+        }
+
+        fn fresh(&self) -> Box<dyn BlockHasher> {
+            Box::<Self>::default()
+        }
+    }
+
+    #[test]
+    fn test_pack_files_block_hasher() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        std::fs::create_dir_all(&root_dir).unwrap();
+        std::fs::write(root_dir.join("small.txt"), b"hello world").unwrap();
+        write_large_file(&root_dir.join("big.bin"), MAX_SECTION_SIZE * 2 + 1);
+        let options = PackOptions::new()
+            .with_block_hasher(std::sync::Arc::new(FoldHasher::default()))
+            .with_dedup_files(true);
+        let mut car = Cursor::new(vec![]);
+        let root = pack_files_with(&root_dir, &mut car, &options).unwrap();
+        let mut reader = crate::reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+        assert!(reader
+            .blocks()
+            .all(|b| b.unwrap().0.hash().code() == 0x300000));
+        let limits = crate::utils::MemoryLimits::default();
+        let tree = crate::utils::extract_to_memory(&mut reader, root, limits).unwrap();
+        assert_eq!(tree.file("root/small.txt"), Some(&b"hello world"[..]));
+        let big = std::fs::read(root_dir.join("big.bin")).unwrap();
+        assert_eq!(tree.file("root/big.bin"), Some(&big[..]));
+    }
+
+    #[test]
+    fn test_pack_files_top_node() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        std::fs::create_dir_all(&root_dir).unwrap();
+        std::fs::write(root_dir.join("small.txt"), b"hello world").unwrap();
+        let pack = |path: &Path, top_node: TopNode| {
+            let options = PackOptions::new().with_top_node(top_node);
+            let mut car = Cursor::new(vec![]);
+            let root = pack_files_with(path, &mut car, &options).unwrap();
+            let mut reader = crate::reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+            let node: UnixFs = (root, reader.ipld(&root).unwrap()).try_into().unwrap();
+            (root, node)
+        };
+        let (_, basename) = pack(&root_dir, TopNode::Basename);
+        assert_eq!(basename.links[0].name, "root");
+        let (_, named) = pack(&root_dir, TopNode::Named("data".into()));
+        assert_eq!(named.links[0].name, "data");
+        assert_eq!(named.links[0].hash, basename.links[0].hash);
+        let (root, omitted) = pack(&root_dir, TopNode::Omit);
+        assert_eq!(root, basename.links[0].hash);
+        assert_eq!(omitted.links[0].name, "small.txt");
+
+        let (_, file) = pack(&root_dir.join("small.txt"), TopNode::Named("a.txt".into()));
+        assert_eq!(file.links[0].name, "a.txt");
+        let options = PackOptions::new().with_top_node(TopNode::Omit);
+        let root = pack_files_with(root_dir.join("small.txt"), Cursor::new(vec![]), &options);
+        assert_eq!(root.unwrap(), file.links[0].hash);
+
+        let options = PackOptions::new().with_top_node(TopNode::Named("a/b".into()));
+        let err = pack_files_with(&root_dir, Cursor::new(vec![]), &options).unwrap_err();
+        assert!(matches!(err, CarError::InvalidFile(_)));
+    }
+
+    #[test]
+    fn test_pack_files_multi_root() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let dir_a = temp_dir.path().join("a");
+        let dir_b = temp_dir.path().join("b");
+        std::fs::create_dir_all(&dir_a).unwrap();
+        std::fs::create_dir_all(&dir_b).unwrap();
+        std::fs::write(dir_a.join("small.txt"), b"hello world").unwrap();
+        std::fs::write(dir_b.join("small.txt"), b"hello world").unwrap();
+        let file = temp_dir.path().join("c.txt");
+        std::fs::write(&file, b"hello c").unwrap();
+        let options = PackOptions::new();
+        let mut car = Cursor::new(vec![]);
+        let sources = [&dir_a, &dir_b, &file];
+        let (roots, _) = pack_files_multi_root(sources, &mut car, &options).unwrap();
+        for (source, root) in sources.iter().zip(roots.iter()) {
+            let single = pack_files_with(source, Cursor::new(vec![]), &options).unwrap();
+            assert_eq!(&single, root);
+        }
+        let mut reader = crate::reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+        assert_eq!(reader.header().roots(), roots);
+        // the identical file is written once.
+        let cids: Vec<Cid> = reader.blocks().map(|b| b.unwrap().0).collect();
+        let unique: std::collections::HashSet<&Cid> = cids.iter().collect();
+        assert_eq!(unique.len(), cids.len());
+        let limits = crate::utils::MemoryLimits::default();
+        let tree = crate::utils::extract_to_memory(&mut reader, roots[1], limits).unwrap();
+        assert_eq!(tree.file("b/small.txt"), Some(&b"hello world"[..]));
+
+        let sources: [&Path; 0] = [];
+        assert!(pack_files_multi_root(sources, Cursor::new(vec![]), &options).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_pack_special_files() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let fifo = temp_dir.path().join("fifo");
+        let regular = temp_dir.path().join("regular");
+        let c_path = std::ffi::CString::new(fifo.to_str().unwrap()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+        // the roots are compared without the top node named by the basename.
+        let options = PackOptions::new()
+            .with_chunk_size(1024)
+            .with_top_node(TopNode::Omit);
+        assert!(pack_files_with(&fifo, Cursor::new(vec![]), &options).is_err());
+
+        // the small, the exact multiple and the multiple chunks content.
+        for len in [10usize, 4096, 5000] {
+            let data: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+            fs::write(&regular, &data).unwrap();
+            let expect = pack_files_with(&regular, Cursor::new(vec![]), &options).unwrap();
+            for special in [SpecialFiles::UntilEof, SpecialFiles::Size(len as u64)] {
+                let writer = {
+                    let (fifo, data) = (fifo.clone(), data.clone());
+                    std::thread::spawn(move || fs::write(fifo, data).unwrap())
+                };
+                let options = options.clone().with_special_files(special);
+                let root = pack_files_with(&fifo, Cursor::new(vec![]), &options).unwrap();
+                writer.join().unwrap();
+                assert_eq!(root, expect, "{len} bytes with {special:?}");
+            }
+        }
+
+        // the stream shorter than the declared size.
+        let writer = {
+            let fifo = fifo.clone();
+            std::thread::spawn(move || fs::write(fifo, b"short").unwrap())
+        };
+        let options = options.with_special_files(SpecialFiles::Size(10));
+        assert!(pack_files_with(&fifo, Cursor::new(vec![]), &options).is_err());
+        writer.join().unwrap();
+    }
+
+    fn dir_node(reader: &mut impl CarReader, root: Cid, name: &str) -> UnixFs {
+        let top: UnixFs = (root, reader.ipld(&root).unwrap()).try_into().unwrap();
+        let link = top.links.iter().find(|l| l.name == name).unwrap();
+        (link.hash, reader.ipld(&link.hash).unwrap())
+            .try_into()
+            .unwrap()
+    }
 }
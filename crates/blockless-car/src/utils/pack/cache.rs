@@ -0,0 +1,247 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt, fs,
+    io::BufReader,
+    path::Path,
+    sync::{Mutex, PoisonError},
+    time::SystemTime,
+};
+
+use cid::Cid;
+use ipld::raw::RawCodec;
+
+use crate::{
+    error::CarError,
+    reader::{CarReader, CarReaderV1},
+    section::check_block,
+    unixfs::{FileType, UnixFs},
+    utils::dir_entries,
+    writer::CarWriter,
+};
+
+use super::{NodeMeta, PackOptions, Size};
+
+/// the report of the files copied from the pack cache.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheReport {
+    /// the count of the files whose DAG was copied from the cache.
+    pub files: u64,
+    /// the content bytes of the copied files, which were not read and hashed again.
+    pub bytes: u64,
+}
+
+/// the CAR packed before from the same source, the files unchanged since are copied from it
+/// instead of read and hashed again, see `PackOptions::with_cache`.
+///
+/// a file is copied when the CAR has the file at the same path with the same size, metadata,
+/// hasher, cid version and leaf layout, and the file is unchanged: the mtime stored in the
+/// node is the same, or without the stored mtime the file was not modified after the CAR.
+/// the chunk size is checked on the first leaf only, so the CAR must be packed with the same
+/// chunker, and the files modified without changing the mtime and size are not detected.
+pub struct PackCache {
+    reader: Mutex<CarReaderV1<BufReader<fs::File>>>,
+    /// the files of the CAR by the unixfs path from the root, with the top node.
+    files: HashMap<String, Cid>,
+    /// the modification time of the CAR.
+    modified: SystemTime,
+}
+
+impl fmt::Debug for PackCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PackCache")
+            .field("files", &self.files.len())
+            .field("modified", &self.modified)
+            .finish()
+    }
+}
+
+impl PackCache {
+    /// open the CAR at the `path` and index its files, the content blocks are not read.
+    /// the files of all the roots are indexed, the first root wins if the paths collide.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, CarError> {
+        let file = fs::File::open(path)?;
+        let modified = file.metadata()?.modified()?;
+        let mut reader = CarReaderV1::new(BufReader::new(file))?;
+        let mut files = HashMap::new();
+        for root in reader.header().roots() {
+            index_files(&mut reader, root, &mut files)?;
+        }
+        Ok(Self {
+            reader: Mutex::new(reader),
+            files,
+            modified,
+        })
+    }
+
+    /// the count of the files indexed.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// copy the DAG of the file at the unixfs `path` to the `writer` if the `source` is
+    /// unchanged, return the cid and the tsize of the DAG, none if the file is packed again.
+    /// the blocks are written in the order of the pack, the leaves first and the root last.
+    pub(crate) fn copy_file<W: CarWriter>(
+        &self,
+        path: &str,
+        source: &Path,
+        size: u64,
+        meta: &NodeMeta,
+        options: &PackOptions,
+        writer: &mut W,
+    ) -> Result<Option<(Cid, Size)>, CarError> {
+        let Some(cid) = self.files.get(path).copied() else {
+            return Ok(None);
+        };
+        let mut reader = self.reader.lock().unwrap_or_else(PoisonError::into_inner);
+        let reader = &mut *reader;
+        if !self.reusable(reader, cid, source, size, meta, options)? {
+            return Ok(None);
+        }
+        let limits = reader.limits();
+        let raw_code: u64 = RawCodec.into();
+        let mut levels = vec![vec![cid]];
+        let mut tsize = 0;
+        while let Some(level) = levels.last() {
+            let mut next = Vec::new();
+            for cid in level {
+                tsize += reader
+                    .block_len(cid)?
+                    .ok_or_else(|| CarError::NotFound(format!("the block {cid}")))?;
+                if cid.codec() != raw_code {
+                    let node = reader.unixfs(cid)?;
+                    next.extend(node.links.iter().map(|l| l.hash));
+                }
+            }
+            if next.is_empty() {
+                break;
+            }
+            limits.check_blocks(next.len())?;
+            limits.check_link_depth(levels.len())?;
+            levels.push(next);
+        }
+        for cid in levels.iter().rev().flatten() {
+            let data = reader
+                .get_block(cid)?
+                .ok_or_else(|| CarError::NotFound(format!("the block {cid}")))?;
+            check_block(cid, &data)?;
+            writer.write_block(*cid, data)?;
+        }
+        Ok(Some((cid, tsize)))
+    }
+
+    /// the file DAG at `cid` is what the `options` pack from the unchanged `source`.
+    fn reusable(
+        &self,
+        reader: &mut CarReaderV1<BufReader<fs::File>>,
+        cid: Cid,
+        source: &Path,
+        size: u64,
+        meta: &NodeMeta,
+        options: &PackOptions,
+    ) -> Result<bool, CarError> {
+        if cid.hash().code() != options.hasher()?.code() {
+            return Ok(false);
+        }
+        let raw_code: u64 = RawCodec.into();
+        let chunk_size = options.chunk_size as u64;
+        let layout = if cid.codec() == raw_code {
+            // the raw root is the single chunk without the metadata.
+            options.raw_leaves
+                && meta.is_empty()
+                && size < chunk_size
+                && reader.block_len(&cid)? == Some(size as usize)
+        } else {
+            let node = reader.unixfs(&cid)?;
+            let node_size = node.file_size().unwrap_or_else(|| {
+                let inline = node.data().map_or(0, |d| d.len() as u64);
+                node.block_sizes().iter().sum::<u64>() + inline
+            });
+            cid.version() == options.cid_version
+                && node.file_type() == FileType::File
+                && NodeMeta::of(&node) == *meta
+                && node_size == size
+                && first_leaf_matches(reader, node, size, options)?
+        };
+        if !layout {
+            return Ok(false);
+        }
+        // the mtime stored in the node equals the source, checked with the metadata.
+        if meta.mtime.is_some() {
+            return Ok(true);
+        }
+        Ok(fs::metadata(source)?.modified()? < self.modified)
+    }
+}
+
+/// the first leaf of the file `node` has the leaf layout and the chunk size of the `options`.
+fn first_leaf_matches(
+    reader: &mut impl CarReader,
+    mut node: UnixFs,
+    size: u64,
+    options: &PackOptions,
+) -> Result<bool, CarError> {
+    let raw_code: u64 = RawCodec.into();
+    let chunk_size = options.chunk_size as u64;
+    let limits = reader.limits();
+    let mut depth = 0;
+    loop {
+        let Some(link) = node.links.first() else {
+            // the dag-pb leaf with the chunk inline.
+            let len = node.data().map_or(0, |d| d.len() as u64);
+            return Ok(!options.raw_leaves && (size < chunk_size || len == chunk_size));
+        };
+        if link.hash.codec() == raw_code {
+            let len = reader.block_len(&link.hash)?.unwrap_or_default() as u64;
+            return Ok(options.raw_leaves && (size < chunk_size || len == chunk_size));
+        }
+        depth += 1;
+        limits.check_link_depth(depth)?;
+        node = reader.unixfs(&link.hash)?;
+    }
+}
+
+/// index the files of the DAG at the `root`, the directories and the file root nodes are read.
+fn index_files(
+    reader: &mut impl CarReader,
+    root: Cid,
+    files: &mut HashMap<String, Cid>,
+) -> Result<(), CarError> {
+    let raw_code: u64 = RawCodec.into();
+    let limits = reader.limits();
+    let mut queue = VecDeque::from([(root, String::new())]);
+    while let Some((cid, path)) = queue.pop_front() {
+        if cid.codec() == raw_code {
+            files.entry(path).or_insert(cid);
+            continue;
+        }
+        let node = reader
+            .unixfs(&cid)
+            .map_err(|e| e.traversal(&path, &cid, None))?;
+        match node.file_type() {
+            FileType::Directory | FileType::HAMTShard => {
+                let entries =
+                    dir_entries(reader, &node).map_err(|e| e.traversal(&path, &cid, None))?;
+                for link in entries {
+                    let path = match path.is_empty() {
+                        true => link.name,
+                        false => format!("{path}/{}", link.name),
+                    };
+                    queue.push_back((link.hash, path));
+                }
+                limits.check_blocks(queue.len())?;
+            }
+            FileType::File => {
+                files.entry(path).or_insert(cid);
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
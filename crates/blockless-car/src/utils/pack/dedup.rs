@@ -0,0 +1,226 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, VecDeque},
+    fs,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+};
+
+use cid::Cid;
+
+use crate::error::CarError;
+
+use super::{hash_stream, CacheReport, NodeMeta, PackOptions};
+
+/// the file which reused the DAG of an identical file packed before.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DedupFile {
+    /// the path of the duplicated file.
+    pub path: PathBuf,
+    /// the path of the file whose DAG is reused.
+    pub original: PathBuf,
+    /// the cid of the reused file DAG.
+    pub cid: Cid,
+    /// the size of the file content.
+    pub size: u64,
+}
+
+/// the report of the identical files deduplicated during pack.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DedupReport {
+    pub files: Vec<DedupFile>,
+}
+
+impl DedupReport {
+    /// the count of the files reused the DAG of an identical file.
+    #[inline]
+    pub fn files_deduped(&self) -> usize {
+        self.files.len()
+    }
+
+    /// the content bytes which were not hashed and written again.
+    #[inline]
+    pub fn bytes_saved(&self) -> u64 {
+        self.files.iter().map(|f| f.size).sum()
+    }
+}
+
+/// the report of the chunks deduplicated by the chunk cache during pack.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChunkDedupReport {
+    /// the count of the chunks processed.
+    pub chunks: u64,
+    /// the count of the chunks found in the cache, which were not hashed and written again.
+    pub chunks_deduped: u64,
+    /// the bytes of the chunks processed.
+    pub bytes: u64,
+    /// the bytes of the chunks found in the cache.
+    pub bytes_deduped: u64,
+}
+
+impl ChunkDedupReport {
+    /// the ratio of the deduplicated bytes in all chunk bytes.
+    #[inline]
+    pub fn ratio(&self) -> f64 {
+        if self.bytes == 0 {
+            return 0.;
+        }
+        self.bytes_deduped as f64 / self.bytes as f64
+    }
+}
+
+/// the report of the pack.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PackReport {
+    pub dedup: DedupReport,
+    pub chunks: ChunkDedupReport,
+    pub cache: CacheReport,
+    /// the blocks written to the car, the duplicates skipped by the writer are not counted.
+    pub blocks: u64,
+}
+
+/// the packed file `(cid, tsize, path)`.
+type Packed = (Cid, u64, PathBuf);
+
+struct PackedFile {
+    path: PathBuf,
+    // the digest of the whole file content, computed only when a file with the same size comes.
+    digest: Option<Cid>,
+    hash: Cid,
+    tsize: u64,
+    meta: NodeMeta,
+    // the chunk size and the raw leaves of the DAG, the chunk rules may differ per file.
+    layout: (usize, bool),
+}
+
+/// the packed files grouped by size, used for detecting the identical files.
+#[derive(Default)]
+pub(crate) struct FileDedup {
+    files: HashMap<u64, Vec<PackedFile>>,
+}
+
+impl FileDedup {
+    /// find the packed file identical with the file in `path` and has the same metadata and layout.
+    /// the whole file digest is computed only when there are packed files with the same size.
+    /// return the digest of the file, and the packed file `(cid, tsize, path)` if found.
+    pub(crate) fn lookup(
+        &mut self,
+        path: &Path,
+        size: u64,
+        options: &PackOptions,
+        meta: &NodeMeta,
+    ) -> Result<(Option<Cid>, Option<Packed>), CarError> {
+        let packed = match self.files.get_mut(&size) {
+            Some(packed) => packed,
+            None => return Ok((None, None)),
+        };
+        let digest = file_digest(path, options)?;
+        let layout = (options.chunk_size, options.raw_leaves);
+        for file in packed
+            .iter_mut()
+            .filter(|f| f.meta == *meta && f.layout == layout)
+        {
+            let file_digest = match file.digest {
+                Some(d) => d,
+                None => {
+                    let d = file_digest(&file.path, options)?;
+                    file.digest = Some(d);
+                    d
+                }
+            };
+            if file_digest == digest {
+                return Ok((
+                    Some(digest),
+                    Some((file.hash, file.tsize, file.path.clone())),
+                ));
+            }
+        }
+        Ok((Some(digest), None))
+    }
+
+    /// record the packed file.
+    pub(crate) fn insert(
+        &mut self,
+        path: PathBuf,
+        size: u64,
+        digest: Option<Cid>,
+        (hash, tsize): (Cid, u64),
+        meta: NodeMeta,
+        options: &PackOptions,
+    ) {
+        self.files.entry(size).or_default().push(PackedFile {
+            path,
+            digest,
+            hash,
+            tsize,
+            meta,
+            layout: (options.chunk_size, options.raw_leaves),
+        });
+    }
+}
+
+/// the key of the chunk in the chunk cache, the fast hash of the chunk bytes and whether
+/// the leaf is raw.
+pub(crate) type ChunkKey = (u64, bool);
+
+/// the key of the chunk of the leaf layout, computed without the block hasher.
+pub(crate) fn chunk_key(data: &[u8], raw_leaves: bool) -> ChunkKey {
+    let mut hasher = DefaultHasher::new();
+    data.hash(&mut hasher);
+    (hasher.finish(), raw_leaves)
+}
+
+struct CachedChunk {
+    data: Vec<u8>,
+    cid: Cid,
+    tsize: u64,
+}
+
+/// the bounded cache of the chunks written, the oldest chunk is evicted when the cache is full.
+/// the chunks are looked up by their key and the bytes are compared, so the chunk found is not
+/// hashed by the block hasher nor written again. the cache holds the bytes of the chunks, up to
+/// the capacity times the chunk size.
+pub(crate) struct ChunkCache {
+    capacity: usize,
+    chunks: HashMap<ChunkKey, CachedChunk>,
+    order: VecDeque<ChunkKey>,
+    pub(crate) report: ChunkDedupReport,
+}
+
+impl ChunkCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            chunks: HashMap::new(),
+            order: VecDeque::new(),
+            report: ChunkDedupReport::default(),
+        }
+    }
+
+    /// the cid and the size of the leaf block of the chunk written before.
+    pub(crate) fn get(&self, key: &ChunkKey, data: &[u8]) -> Option<(Cid, u64)> {
+        let chunk = self.chunks.get(key)?;
+        (chunk.data == data).then_some((chunk.cid, chunk.tsize))
+    }
+
+    /// record the chunk written, the other chunk of the same key is replaced.
+    pub(crate) fn insert(&mut self, key: ChunkKey, data: Vec<u8>, cid: Cid, tsize: u64) {
+        if self.capacity == 0 {
+            return;
+        }
+        let chunk = CachedChunk { data, cid, tsize };
+        if self.chunks.insert(key, chunk).is_some() {
+            return;
+        }
+        self.order.push_back(key);
+        if self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.chunks.remove(&oldest);
+            }
+        }
+    }
+}
+
+fn file_digest(path: &Path, options: &PackOptions) -> Result<Cid, CarError> {
+    let mut file = fs::File::open(path)?;
+    hash_stream(&mut file, options)
+}
@@ -0,0 +1,62 @@
+use std::{fmt, fs::Metadata, path::Path};
+
+/// the directories pruned by `PruneDefaults`, the vcs metadata and the build outputs.
+pub const DEFAULT_PRUNED_DIRS: &[&str] = &[".git", "target", "node_modules"];
+
+/// decide the entries of the directory walk, the embedders supply their own pruning
+/// through `PackOptions::with_walk_filter`. the pruned directories are not descended.
+pub trait WalkFilter: Send + Sync {
+    /// keep the entry of the `path` relative to the source root.
+    fn keep(&self, path: &Path, is_dir: bool) -> bool;
+}
+
+impl fmt::Debug for dyn WalkFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("WalkFilter")
+    }
+}
+
+/// the decision of the `PackHook` on an entry of the walk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Decision {
+    /// pack the entry.
+    Keep,
+    /// leave the entry out of the pack, the skipped directories are not descended.
+    Skip,
+    /// abort the pack with `CarError::Rejected` and the reason.
+    Reject(String),
+}
+
+/// the hook of the entries found by the directory walk, called before the files are
+/// opened or hashed, e.g. to reject the files over the policy limits or to scan them.
+/// the closures `Fn(&Path, &Metadata) -> Decision` are hooks.
+pub trait PackHook: Send + Sync {
+    /// decide the entry of the `path`, which is the source root joined with the relative path.
+    fn check(&self, path: &Path, metadata: &Metadata) -> Decision;
+}
+
+impl<F> PackHook for F
+where
+    F: Fn(&Path, &Metadata) -> Decision + Send + Sync,
+{
+    fn check(&self, path: &Path, metadata: &Metadata) -> Decision {
+        self(path, metadata)
+    }
+}
+
+impl fmt::Debug for dyn PackHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PackHook")
+    }
+}
+
+/// prune the directories named in `DEFAULT_PRUNED_DIRS` at any depth.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PruneDefaults;
+
+impl WalkFilter for PruneDefaults {
+    fn keep(&self, path: &Path, is_dir: bool) -> bool {
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        !(is_dir && DEFAULT_PRUNED_DIRS.contains(&name))
+    }
+}
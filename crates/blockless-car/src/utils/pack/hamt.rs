@@ -0,0 +1,143 @@
+//! write the unixfs hamt sharded directory, compatible with go-unixfs.
+//! every level consumes one byte of the name hash, so a shard has 256 slots.
+use std::collections::BTreeMap;
+
+use cid::Cid;
+use ipld::{pb::DagPbCodec, prelude::Codec};
+
+use crate::{
+    codec::Encoder,
+    error::CarError,
+    unixfs::{FileType, Link, UnixFs},
+    utils::{hamt_hash, HAMT_HASH_TYPE},
+    writer::CarWriter,
+};
+
+use super::{meta::NodeMeta, PackOptions};
+
+/// the default threshold of the estimated directory size, same as the `HAMTShardingSize` of kubo.
+pub const DEFAULT_HAMT_THRESHOLD: usize = 256 << 10;
+
+/// the width of the shards.
+const HAMT_FANOUT: u64 = 256;
+
+/// the estimated size of the directory node, like go-unixfs, the names and the cids of the links.
+pub(crate) fn estimated_dir_size(links: &[Link]) -> usize {
+    links
+        .iter()
+        .map(|link| link.name.len() + link.hash.encoded_len())
+        .sum()
+}
+
+enum Slot {
+    Entry(Link),
+    Shard(Shard),
+}
+
+#[derive(Default)]
+struct Shard {
+    slots: BTreeMap<u8, Slot>,
+}
+
+impl Shard {
+    fn insert(&mut self, link: Link, depth: usize) -> Result<(), CarError> {
+        let hash = hamt_hash(link.name.as_bytes());
+        let Some(idx) = hash.get(depth) else {
+            return Err(CarError::InvalidFile(format!(
+                "the hash of the entry {} is collided",
+                link.name
+            )));
+        };
+        let slot = match self.slots.remove(idx) {
+            None => Slot::Entry(link),
+            Some(Slot::Shard(mut shard)) => {
+                shard.insert(link, depth + 1)?;
+                Slot::Shard(shard)
+            }
+            // the slot is taken, move both entries into a child shard.
+            Some(Slot::Entry(prev)) => {
+                let mut shard = Shard::default();
+                shard.insert(prev, depth + 1)?;
+                shard.insert(link, depth + 1)?;
+                Slot::Shard(shard)
+            }
+        };
+        self.slots.insert(*idx, slot);
+        Ok(())
+    }
+
+    /// the bitfield of the taken slots, big-endian with the leading zero bytes trimmed.
+    fn bitfield(&self) -> Vec<u8> {
+        let mut bitfield = [0u8; HAMT_FANOUT as usize / 8];
+        for idx in self.slots.keys() {
+            let idx = *idx as usize;
+            bitfield[bitfield.len() - 1 - idx / 8] |= 1 << (idx % 8);
+        }
+        let start = bitfield
+            .iter()
+            .position(|b| *b != 0)
+            .unwrap_or(bitfield.len());
+        bitfield[start..].to_vec()
+    }
+
+    /// write the child shards and then the shard, return the cid and the total size.
+    fn write<W: CarWriter>(
+        self,
+        writer: &mut W,
+        options: &PackOptions,
+        meta: Option<&NodeMeta>,
+    ) -> Result<(Cid, u64), CarError> {
+        let data = self.bitfield();
+        let mut links = Vec::with_capacity(self.slots.len());
+        for (idx, slot) in self.slots {
+            links.push(match slot {
+                Slot::Entry(link) => Link {
+                    name: format!("{idx:02X}{}", link.name),
+                    ..link
+                },
+                Slot::Shard(shard) => {
+                    let (hash, tsize) = shard.write(writer, options, None)?;
+                    Link {
+                        hash,
+                        file_type: FileType::HAMTShard,
+                        name: format!("{idx:02X}"),
+                        tsize,
+                    }
+                }
+            });
+        }
+        let mut unix_fs = UnixFs {
+            file_type: FileType::HAMTShard,
+            data: Some(data),
+            hash_type: Some(HAMT_HASH_TYPE),
+            fanout: Some(HAMT_FANOUT),
+            links,
+            ..Default::default()
+        };
+        if let Some(meta) = meta {
+            meta.apply(&mut unix_fs);
+        }
+        let bs = DagPbCodec
+            .encode(&unix_fs.encode()?)
+            .map_err(|e| CarError::Parsing(e.to_string()))?;
+        let tsize = bs.len() as u64 + unix_fs.links.iter().map(|l| l.tsize).sum::<u64>();
+        let cid = options.pb_cid(&bs)?;
+        writer.write_block(cid, bs)?;
+        Ok((cid, tsize))
+    }
+}
+
+/// write the directory entries as a hamt sharded directory, return the root shard cid
+/// and the total size. the `meta` is kept in the root shard.
+pub(crate) fn write_hamt_dir<W: CarWriter>(
+    links: Vec<Link>,
+    writer: &mut W,
+    options: &PackOptions,
+    meta: &NodeMeta,
+) -> Result<(Cid, u64), CarError> {
+    let mut shard = Shard::default();
+    for link in links {
+        shard.insert(link, 0)?;
+    }
+    shard.write(writer, options, Some(meta))
+}
@@ -0,0 +1,95 @@
+//! the `.gitignore` rules of the directory walk, the rules of a directory apply to
+//! its entries at any depth and the last matched rule decides.
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::{error::CarError, utils::Pattern};
+
+#[derive(Clone)]
+struct IgnoreRule {
+    // the directory of the `.gitignore` relative to the source root.
+    base: PathBuf,
+    pattern: Pattern,
+    // the `!` prefix, the matched entry is packed again.
+    negated: bool,
+    // the trailing `/`, only the directories are matched.
+    dir_only: bool,
+}
+
+impl IgnoreRule {
+    fn parse(base: &Path, line: &str) -> Option<Self> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+        // the invalid patterns are skipped like git.
+        let pattern = Pattern::new(line).ok()?;
+        Some(Self {
+            base: base.to_path_buf(),
+            pattern,
+            negated,
+            dir_only,
+        })
+    }
+
+    fn matches(&self, rel_path: &Path, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        match rel_path.strip_prefix(&self.base) {
+            Ok(path) => self.pattern.matches_path(path),
+            Err(_) => false,
+        }
+    }
+}
+
+/// the rules of the `.gitignore` files from the source root to the walked directory.
+#[derive(Clone, Default)]
+pub(crate) struct IgnoreRules {
+    rules: Rc<Vec<IgnoreRule>>,
+}
+
+impl IgnoreRules {
+    /// the rules extended with the `.gitignore` of the `dir`, `rel_dir` is the `dir`
+    /// relative to the source root.
+    pub(crate) fn with_dir(&self, dir: &Path, rel_dir: &Path) -> Result<Self, CarError> {
+        let content = match fs::read(dir.join(".gitignore")) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(self.clone()),
+            Err(e) => return Err(e.into()),
+        };
+        let content = String::from_utf8_lossy(&content);
+        let mut rules = content
+            .lines()
+            .filter_map(|line| IgnoreRule::parse(rel_dir, line))
+            .peekable();
+        if rules.peek().is_none() {
+            return Ok(self.clone());
+        }
+        let mut extended = self.rules.to_vec();
+        extended.extend(rules);
+        Ok(Self {
+            rules: Rc::new(extended),
+        })
+    }
+
+    /// the entry of the `rel_path` relative to the source root is ignored.
+    pub(crate) fn is_ignored(&self, rel_path: &Path, is_dir: bool) -> bool {
+        self.rules
+            .iter()
+            .rev()
+            .find(|r| r.matches(rel_path, is_dir))
+            .is_some_and(|r| !r.negated)
+    }
+}
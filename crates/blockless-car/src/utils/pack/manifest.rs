@@ -0,0 +1,34 @@
+use std::path::Path;
+
+use cid::Cid;
+
+use super::PackReport;
+
+/// the file packed, the entry of the upload manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackedEntry {
+    /// the unixfs path from the root, `/` separated, with the wrapping directory.
+    /// empty for the file packed without the wrapping directory, the root is the file.
+    pub path: String,
+    pub cid: Cid,
+    /// the size of the file content.
+    pub size: u64,
+}
+
+/// the result of `pack_files_with_manifest`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackResult {
+    pub root: Cid,
+    /// the files in the order packed, the deduplicated files are listed too.
+    pub files: Vec<PackedEntry>,
+    pub stats: PackReport,
+}
+
+/// the `/` separated unixfs path of the relative path.
+pub(super) fn unixfs_path(path: &Path) -> String {
+    let names: Vec<_> = path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect();
+    names.join("/")
+}
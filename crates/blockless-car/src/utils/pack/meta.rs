@@ -0,0 +1,118 @@
+use std::path::Path;
+
+use crate::{
+    error::CarError,
+    unixfs::{UnixFs, UnixTime, Xattr},
+};
+
+use super::PackOptions;
+
+/// the metadata of the file or directory kept in its unixfs node.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct NodeMeta {
+    pub(crate) uid: Option<u32>,
+    pub(crate) gid: Option<u32>,
+    pub(crate) xattrs: Vec<Xattr>,
+    pub(crate) mode: Option<u32>,
+    pub(crate) mtime: Option<UnixTime>,
+}
+
+impl NodeMeta {
+    /// read the metadata of the `path` enabled in the `options`.
+    pub(crate) fn read(path: &Path, options: &PackOptions) -> Result<Self, CarError> {
+        let mut meta = Self::default();
+        if options.preserve_owner {
+            meta.read_owner(path)?;
+        }
+        if options.preserve_metadata {
+            meta.read_mode_mtime(path)?;
+        }
+        #[cfg(feature = "xattr")]
+        if options.preserve_xattrs {
+            meta.read_xattrs(path)?;
+        }
+        Ok(meta)
+    }
+
+    #[inline]
+    pub(crate) fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// the metadata stored in the unixfs node.
+    pub(crate) fn of(unix_fs: &UnixFs) -> Self {
+        Self {
+            uid: unix_fs.uid,
+            gid: unix_fs.gid,
+            xattrs: unix_fs.xattrs.clone(),
+            mode: unix_fs.mode,
+            mtime: unix_fs.mtime.clone(),
+        }
+    }
+
+    /// store the metadata in the unixfs node.
+    pub(crate) fn apply(&self, unix_fs: &mut UnixFs) {
+        unix_fs.uid = self.uid;
+        unix_fs.gid = self.gid;
+        unix_fs.xattrs = self.xattrs.clone();
+        unix_fs.mode = self.mode;
+        unix_fs.mtime = self.mtime.clone();
+    }
+
+    /// the permission bits of the mode like the UnixFS 1.5, and the modification time.
+    fn read_mode_mtime(&mut self, path: &Path) -> Result<(), CarError> {
+        let metadata = std::fs::metadata(path)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            self.mode = Some(metadata.permissions().mode() & 0o7777);
+        }
+        self.mtime = Some(UnixTime::from_system_time(metadata.modified()?));
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn read_owner(&mut self, path: &Path) -> Result<(), CarError> {
+        use std::os::unix::fs::MetadataExt;
+        let metadata = std::fs::metadata(path)?;
+        self.uid = Some(metadata.uid());
+        self.gid = Some(metadata.gid());
+        Ok(())
+    }
+
+    /// the owner is not available on the platform.
+    #[cfg(not(unix))]
+    fn read_owner(&mut self, _path: &Path) -> Result<(), CarError> {
+        Ok(())
+    }
+
+    /// read the extended attributes sorted by name, so the node is the same
+    /// whatever the order the file system lists them.
+    #[cfg(all(unix, feature = "xattr"))]
+    fn read_xattrs(&mut self, path: &Path) -> Result<(), CarError> {
+        let mut xattrs = Vec::new();
+        for name in xattr::list(path)? {
+            let value = match xattr::get(path, &name)? {
+                Some(value) => value,
+                // the attribute is removed after listed.
+                None => continue,
+            };
+            let name = name.into_string().map_err(|name| {
+                CarError::InvalidFile(format!(
+                    "the xattr name {name:?} of {} is not utf8",
+                    path.display()
+                ))
+            })?;
+            xattrs.push(Xattr { name, value });
+        }
+        xattrs.sort_by(|a, b| a.name.cmp(&b.name));
+        self.xattrs = xattrs;
+        Ok(())
+    }
+
+    /// the extended attributes are not available on the platform.
+    #[cfg(all(not(unix), feature = "xattr"))]
+    fn read_xattrs(&mut self, _path: &Path) -> Result<(), CarError> {
+        Ok(())
+    }
+}
@@ -0,0 +1,497 @@
+use std::{
+    borrow::Cow,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    thread,
+};
+
+use cid::{
+    multihash::{Code, MultihashDigest},
+    Cid, Version,
+};
+use ipld::{pb::DagPbCodec, raw::RawCodec};
+
+use crate::{
+    error::CarError,
+    utils::{
+        codec_hasher, hash_block, BlockHasher, CancelToken, PathLimits, Pattern, ProgressHook,
+    },
+    writer::{BlockDedup, DEFAULT_WRITE_BUFFER_SIZE},
+};
+
+use super::{PackCache, PackHook, WalkFilter, DEFAULT_HAMT_THRESHOLD, MAX_SECTION_SIZE};
+
+/// the default chunk size, same as the default chunker of kubo.
+pub const DEFAULT_CHUNK_SIZE: usize = MAX_SECTION_SIZE;
+
+/// the max chunk size, same as the limit of the kubo chunker.
+pub const MAX_CHUNK_SIZE: usize = 1 << 20;
+
+/// the extra top node wrapping the source, like go-car.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum TopNode {
+    /// the top node links the source by its basename.
+    #[default]
+    Basename,
+    /// the top node links the source by the name.
+    Named(String),
+    /// no top node, the root is the source itself.
+    Omit,
+}
+
+/// how the special source of the pack is read, like the named pipe, `/dev/stdin` or
+/// the character and block devices.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SpecialFiles {
+    /// the special source is rejected.
+    #[default]
+    Reject,
+    /// the source is read as the file until the end of the stream.
+    UntilEof,
+    /// the source is read as the file of the declared bytes, the shorter stream fails.
+    Size(u64),
+}
+
+/// the guarantee of the block order of the CAR packed by the parallel workers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Determinism {
+    /// the blocks are written in the order of the sequential pack whatever the count of the
+    /// workers, the same inputs and options always give the byte-identical CAR.
+    #[default]
+    Strict,
+    /// the blocks may be written in the order the workers finish them, the root and the
+    /// blocks are the same but the bytes of the CAR may differ between the runs.
+    Relaxed,
+}
+
+/// override the chunk size or the leaf layout of the files matching the pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkRule {
+    pub pattern: Pattern,
+    /// the chunk size of the matched files, `None` keeps the `PackOptions::chunk_size`.
+    pub chunk_size: Option<usize>,
+    /// the leaf layout of the matched files, `None` keeps the `PackOptions::raw_leaves`.
+    pub raw_leaves: Option<bool>,
+}
+
+impl ChunkRule {
+    pub fn new(pattern: Pattern) -> Self {
+        Self {
+            pattern,
+            chunk_size: None,
+            raw_leaves: None,
+        }
+    }
+
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = Some(chunk_size);
+        self
+    }
+
+    pub fn with_raw_leaves(mut self, raw_leaves: bool) -> Self {
+        self.raw_leaves = Some(raw_leaves);
+        self
+    }
+}
+
+/// parse the size of `<n>`, `size-<n>` or with the suffix `KiB`, `MiB`.
+fn parse_size(s: &str) -> Option<usize> {
+    let s = s.strip_prefix("size-").unwrap_or(s);
+    let (n, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => s.split_at(i),
+        None => (s, ""),
+    };
+    let unit = match unit.to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kib" => 1 << 10,
+        "m" | "mib" => 1 << 20,
+        _ => return None,
+    };
+    n.parse::<usize>().ok()?.checked_mul(unit)
+}
+
+/// parse the rule `<glob>=<spec>`, the spec is the comma separated chunk size
+/// (e.g. `1MiB`, `size-65536`) and the leaf layout, `raw` for the raw leaves
+/// or `pb` for the dag-pb leaves, e.g. `*.mp4=1MiB` or `*.txt=64KiB,pb`.
+impl FromStr for ChunkRule {
+    type Err = CarError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| CarError::InvalidChunkRule(format!("{s}: {reason}"));
+        let (pattern, spec) = s
+            .rsplit_once('=')
+            .ok_or_else(|| invalid("expect <glob>=<spec>"))?;
+        let mut rule = ChunkRule::new(Pattern::new(pattern)?);
+        for item in spec.split(',').map(str::trim) {
+            match item {
+                "raw" => rule.raw_leaves = Some(true),
+                "pb" => rule.raw_leaves = Some(false),
+                size => {
+                    let size = parse_size(size).ok_or_else(|| invalid("invalid spec"))?;
+                    rule.chunk_size = Some(size);
+                }
+            }
+        }
+        Ok(rule)
+    }
+}
+
+/// the options of packing files into the CAR file.
+#[derive(Debug, Clone)]
+pub struct PackOptions {
+    /// the hashing algorithm of the blocks.
+    pub hasher_codec: multicodec::Codec,
+    /// the custom hasher of the blocks, overrides the `hasher_codec`.
+    pub block_hasher: Option<Arc<dyn BlockHasher>>,
+    /// don't wrap the file into a directory (applies to files only).
+    pub no_wrap_file: bool,
+    /// the top node wrapping the source, `TopNode::Omit` for the files is same as `no_wrap_file`.
+    pub top_node: TopNode,
+    /// the version of the dag-pb node cids, the version 0 requires the sha2-256 hasher.
+    /// the raw leaves keep the version 1 since the version 0 can't be raw.
+    pub cid_version: Version,
+    /// the size of the file chunks, like the `size-<n>` chunker of kubo.
+    /// the files smaller than the chunk size are stored in one raw block.
+    pub chunk_size: usize,
+    /// write the file chunks as raw blocks, otherwise the chunks are wrapped into
+    /// dag-pb file nodes like `ipfs add` without `--raw-leaves`.
+    pub raw_leaves: bool,
+    /// the chunk size and leaf layout of the files matching the patterns,
+    /// the first matched rule applies.
+    pub chunk_rules: Vec<ChunkRule>,
+    /// shard the directories into hamt nodes when the estimated size of the directory
+    /// node (the names and cids of the entries) exceeds the threshold, like kubo.
+    /// `None` disables the sharding.
+    pub hamt_threshold: Option<usize>,
+    /// the limits of the path depth and name length.
+    pub limits: PathLimits,
+    /// the filters of the directory walk, an entry is packed only if all filters keep it.
+    /// the paths listed to `pack_file_list` are not filtered.
+    pub walk_filters: Vec<Arc<dyn WalkFilter>>,
+    /// the entries matching any of the patterns are not packed, the excluded directories
+    /// are not descended.
+    pub exclude: Vec<Pattern>,
+    /// only the files and symlinks matching any of the patterns are packed if not empty,
+    /// the directories are walked whether matched or not.
+    pub include: Vec<Pattern>,
+    /// skip the entries ignored by the `.gitignore` files in the walked directories.
+    pub gitignore: bool,
+    /// the hook of the entries kept by the `walk_filters`, like them it is not called
+    /// on the paths listed to `pack_file_list`.
+    pub hook: Option<Arc<dyn PackHook>>,
+    /// reuse the DAG of the identical files(same size and content hash) packed before.
+    pub dedup_files: bool,
+    /// the blocks the writer remembers to skip the repeated ones, all by default.
+    /// `BlockDedup::Off` saves the memory of the cids for the inputs known unique.
+    pub block_dedup: BlockDedup,
+    /// the bytes the writer buffers before writing to the target, 0 writes every section
+    /// through, e.g. to the slow network filesystems a larger buffer saves the syscalls.
+    pub write_buffer_size: usize,
+    /// the capacity of the chunk cache, the identical chunks across files are hashed and
+    /// written once. the cache holds the bytes of the chunks, `None` disables the chunk cache.
+    pub chunk_cache: Option<usize>,
+    /// the CAR packed before from the same source, the unchanged files of the directory
+    /// sources are copied from it instead of read and hashed again.
+    pub cache: Option<Arc<PackCache>>,
+    /// store the uid and gid of the files and directories in the unixfs nodes.
+    pub preserve_owner: bool,
+    /// store the mode and mtime of the files and directories in the unixfs nodes,
+    /// the mode is only read on unix.
+    pub preserve_metadata: bool,
+    /// store the extended attributes of the files and directories in the unixfs nodes.
+    #[cfg(feature = "xattr")]
+    pub preserve_xattrs: bool,
+    /// the token for cancelling the pack, checked between the files and the chunks.
+    pub cancel: CancelToken,
+    /// the hook of the files packed, the bytes read from them and the bytes of the CAR
+    /// written. `pack_files_to_stream` reports only the pass writing the target.
+    pub progress: Option<Arc<dyn ProgressHook>>,
+    /// the directory of the temp files of the large operations, the OS temp directory if none.
+    /// `pack_files_to_stream` spills the blocks to a temp CAR in the directory and reads
+    /// the source once, without it the source is read twice.
+    pub spill_dir: Option<PathBuf>,
+    /// how the special source given to the pack is read, the special entries found in
+    /// the walk are always skipped.
+    pub special_files: SpecialFiles,
+    /// the count of the workers hashing the chunks of a file, 0 means the available
+    /// parallelism. the chunks of the large files are hashed in parallel while the blocks are
+    /// written by the current thread, the files are packed one by one.
+    pub jobs: usize,
+    /// the block order of the parallel pack, the sequential pack gives the same CAR for both.
+    pub determinism: Determinism,
+}
+
+impl Default for PackOptions {
+    fn default() -> Self {
+        Self {
+            hasher_codec: multicodec::Codec::Sha2_256,
+            block_hasher: None,
+            no_wrap_file: false,
+            top_node: TopNode::Basename,
+            cid_version: Version::V1,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            raw_leaves: true,
+            chunk_rules: Vec::new(),
+            hamt_threshold: Some(DEFAULT_HAMT_THRESHOLD),
+            limits: PathLimits::default(),
+            walk_filters: Vec::new(),
+            exclude: Vec::new(),
+            include: Vec::new(),
+            gitignore: false,
+            hook: None,
+            dedup_files: false,
+            block_dedup: BlockDedup::All,
+            write_buffer_size: DEFAULT_WRITE_BUFFER_SIZE,
+            chunk_cache: None,
+            cache: None,
+            preserve_owner: false,
+            preserve_metadata: false,
+            #[cfg(feature = "xattr")]
+            preserve_xattrs: false,
+            cancel: CancelToken::default(),
+            progress: None,
+            spill_dir: None,
+            special_files: SpecialFiles::Reject,
+            jobs: 1,
+            determinism: Determinism::Strict,
+        }
+    }
+}
+
+impl PackOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_hasher_codec(mut self, hasher_codec: multicodec::Codec) -> Self {
+        self.hasher_codec = hasher_codec;
+        self
+    }
+
+    pub fn with_block_hasher(mut self, block_hasher: Arc<dyn BlockHasher>) -> Self {
+        self.block_hasher = Some(block_hasher);
+        self
+    }
+
+    pub fn with_no_wrap_file(mut self, no_wrap_file: bool) -> Self {
+        self.no_wrap_file = no_wrap_file;
+        self
+    }
+
+    pub fn with_top_node(mut self, top_node: TopNode) -> Self {
+        self.top_node = top_node;
+        self
+    }
+
+    pub fn with_cid_version(mut self, cid_version: Version) -> Self {
+        self.cid_version = cid_version;
+        self
+    }
+
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    pub fn with_raw_leaves(mut self, raw_leaves: bool) -> Self {
+        self.raw_leaves = raw_leaves;
+        self
+    }
+
+    pub fn with_chunk_rule(mut self, rule: ChunkRule) -> Self {
+        self.chunk_rules.push(rule);
+        self
+    }
+
+    pub fn with_hamt_threshold(mut self, threshold: Option<usize>) -> Self {
+        self.hamt_threshold = threshold;
+        self
+    }
+
+    pub fn with_limits(mut self, limits: PathLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn with_walk_filter(mut self, filter: Arc<dyn WalkFilter>) -> Self {
+        self.walk_filters.push(filter);
+        self
+    }
+
+    pub fn with_exclude(mut self, pattern: Pattern) -> Self {
+        self.exclude.push(pattern);
+        self
+    }
+
+    pub fn with_include(mut self, pattern: Pattern) -> Self {
+        self.include.push(pattern);
+        self
+    }
+
+    pub fn with_gitignore(mut self, gitignore: bool) -> Self {
+        self.gitignore = gitignore;
+        self
+    }
+
+    pub fn with_hook(mut self, hook: Arc<dyn PackHook>) -> Self {
+        self.hook = Some(hook);
+        self
+    }
+
+    pub fn with_dedup_files(mut self, dedup_files: bool) -> Self {
+        self.dedup_files = dedup_files;
+        self
+    }
+
+    pub fn with_block_dedup(mut self, block_dedup: BlockDedup) -> Self {
+        self.block_dedup = block_dedup;
+        self
+    }
+
+    pub fn with_write_buffer_size(mut self, size: usize) -> Self {
+        self.write_buffer_size = size;
+        self
+    }
+
+    pub fn with_chunk_cache(mut self, capacity: Option<usize>) -> Self {
+        self.chunk_cache = capacity;
+        self
+    }
+
+    pub fn with_cache(mut self, cache: Arc<PackCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    pub fn with_preserve_owner(mut self, preserve_owner: bool) -> Self {
+        self.preserve_owner = preserve_owner;
+        self
+    }
+
+    pub fn with_preserve_metadata(mut self, preserve_metadata: bool) -> Self {
+        self.preserve_metadata = preserve_metadata;
+        self
+    }
+
+    #[cfg(feature = "xattr")]
+    pub fn with_preserve_xattrs(mut self, preserve_xattrs: bool) -> Self {
+        self.preserve_xattrs = preserve_xattrs;
+        self
+    }
+
+    pub fn with_cancel(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    pub fn with_progress(mut self, progress: Arc<dyn ProgressHook>) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    pub fn with_spill_dir(mut self, spill_dir: impl Into<PathBuf>) -> Self {
+        self.spill_dir = Some(spill_dir.into());
+        self
+    }
+
+    pub fn with_special_files(mut self, special_files: SpecialFiles) -> Self {
+        self.special_files = special_files;
+        self
+    }
+
+    pub fn with_jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
+    pub fn with_determinism(mut self, determinism: Determinism) -> Self {
+        self.determinism = determinism;
+        self
+    }
+
+    /// check the options are valid before the pack.
+    pub fn check(&self) -> Result<(), CarError> {
+        let rule_sizes = self.chunk_rules.iter().filter_map(|r| r.chunk_size);
+        for chunk_size in rule_sizes.chain([self.chunk_size]) {
+            if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+                return Err(CarError::InvalidChunkSize(chunk_size));
+            }
+        }
+        let code = self.hasher()?.code();
+        if self.cid_version == Version::V0 && code != u64::from(multicodec::Codec::Sha2_256.code())
+        {
+            return Err(CarError::InvalidCidVersion(format!(
+                "the version 0 requires the sha2-256 hasher, not {code:#x}"
+            )));
+        }
+        Ok(())
+    }
+
+    /// the options of the file in the `path` relative to the source root, with the chunk rule applied.
+    pub(crate) fn file_options(&self, path: &Path) -> Cow<'_, PackOptions> {
+        let Some(rule) = self
+            .chunk_rules
+            .iter()
+            .find(|r| r.pattern.matches_path(path))
+        else {
+            return Cow::Borrowed(self);
+        };
+        let mut options = self.clone();
+        options.chunk_size = rule.chunk_size.unwrap_or(self.chunk_size);
+        options.raw_leaves = rule.raw_leaves.unwrap_or(self.raw_leaves);
+        Cow::Owned(options)
+    }
+
+    /// the entry of the walk in the `path` relative to the source root is kept by the
+    /// `walk_filters`, the `exclude` and the `include` patterns.
+    pub(crate) fn keep_entry(&self, path: &Path, is_dir: bool) -> bool {
+        if self.exclude.iter().any(|p| p.matches_path(path)) {
+            return false;
+        }
+        if !is_dir && !self.include.is_empty() && !self.include.iter().any(|p| p.matches_path(path))
+        {
+            return false;
+        }
+        self.walk_filters.iter().all(|f| f.keep(path, is_dir))
+    }
+
+    /// the count of the hashing workers, the `jobs` or the available parallelism.
+    pub(crate) fn workers(&self) -> usize {
+        match self.jobs {
+            0 => thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            n => n,
+        }
+    }
+
+    /// a fresh hasher of the blocks, the `block_hasher` or the built in of the `hasher_codec`.
+    pub(crate) fn hasher(&self) -> Result<Box<dyn BlockHasher>, CarError> {
+        match self.block_hasher.as_ref() {
+            Some(hasher) => Ok(hasher.fresh()),
+            None => codec_hasher(self.hasher_codec).ok_or_else(|| {
+                CarError::NotImplemented(format!("the hasher {:?}", self.hasher_codec))
+            }),
+        }
+    }
+
+    /// the cid of the dag-pb node in the `cid_version`.
+    pub(crate) fn pb_cid(&self, data: &[u8]) -> Result<Cid, CarError> {
+        match self.cid_version {
+            // the version 0 is always dag-pb and sha2-256.
+            Version::V0 => Ok(Cid::new_v0(Code::Sha2_256.digest(data)).unwrap()),
+            Version::V1 => Ok(Cid::new_v1(
+                DagPbCodec.into(),
+                hash_block(&*self.hasher()?, data)?,
+            )),
+        }
+    }
+
+    /// the cid of the raw block.
+    pub(crate) fn raw_cid(&self, data: &[u8]) -> Result<Cid, CarError> {
+        Ok(Cid::new_v1(
+            RawCodec.into(),
+            hash_block(&*self.hasher()?, data)?,
+        ))
+    }
+}
@@ -0,0 +1,123 @@
+//! pack the content from the `Read` streams, e.g. the generated data or the HTTP bodies.
+use std::{
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use crate::{
+    error::CarError,
+    unixfs::{FileType, Link},
+    writer::CarWriter,
+};
+
+use super::{meta::NodeMeta, process_file, PackOptions};
+
+/// the seekable adapter of the stream, the streamed blocks are read twice for the cid.
+/// after the first seek the bytes from the last seek target are kept, so seeking back
+/// to the start of the current chunk works and the buffer is bounded by the chunk size.
+pub(super) struct SeekBuf<R> {
+    inner: R,
+    buf: Vec<u8>,
+    // the stream position of the `buf[0]`.
+    buf_start: u64,
+    pos: u64,
+    // the reads are buffered only after seeking, the chunks read once are not.
+    buffered: bool,
+}
+
+impl<R: Read> SeekBuf<R> {
+    pub(super) fn new(inner: R) -> Self {
+        Self {
+            inner,
+            buf: Vec::new(),
+            buf_start: 0,
+            pos: 0,
+            buffered: false,
+        }
+    }
+
+    /// the count of the bytes read from the stream.
+    pub(super) fn read_len(&self) -> u64 {
+        self.buf_start + self.buf.len() as u64
+    }
+}
+
+impl<R: Read> Read for SeekBuf<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let offset = (self.pos - self.buf_start) as usize;
+        let n = if offset < self.buf.len() {
+            let n = out.len().min(self.buf.len() - offset);
+            out[..n].copy_from_slice(&self.buf[offset..offset + n]);
+            n
+        } else {
+            let n = self.inner.read(out)?;
+            match self.buffered {
+                true => self.buf.extend_from_slice(&out[..n]),
+                false => self.buf_start += n as u64,
+            }
+            n
+        };
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read> Seek for SeekBuf<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(p) => Some(p),
+            SeekFrom::Current(off) => self.pos.checked_add_signed(off),
+            SeekFrom::End(_) => None,
+        };
+        let target = target
+            .filter(|t| (self.buf_start..=self.read_len()).contains(t))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "seek out of the buffer"))?;
+        self.buf.drain(..(target - self.buf_start) as usize);
+        self.buf_start = target;
+        self.pos = target;
+        self.buffered = true;
+        Ok(target)
+    }
+}
+
+/// pack the `len` bytes of the `reader` as a file into the `writer`, the content never
+/// touches the file system. return the link of the file named `name`, the header of
+/// the writer is not touched, so the link can be added to a directory of the caller
+/// or its hash written as the root. the chunk rules of the `options` match the `name`.
+pub fn pack_reader<R, W>(
+    name: &str,
+    reader: R,
+    len: u64,
+    options: &PackOptions,
+    writer: &mut W,
+) -> Result<Link, CarError>
+where
+    R: Read,
+    W: CarWriter,
+{
+    options.check()?;
+    options.limits.check_name(name, name)?;
+    let options = options.file_options(Path::new(name));
+    let mut reader = SeekBuf::new(reader.take(len));
+    let (hash, tsize) = process_file(
+        &mut reader,
+        writer,
+        len as usize,
+        &options,
+        None,
+        &NodeMeta::default(),
+        &|| options.cancel.check(0),
+    )?;
+    if reader.read_len() != len {
+        return Err(CarError::InvalidFile(format!(
+            "the content of {name} is {} bytes, expect {len}",
+            reader.read_len()
+        )));
+    }
+    Ok(Link {
+        hash,
+        file_type: FileType::File,
+        name: name.to_string(),
+        tsize: tsize as u64,
+    })
+}
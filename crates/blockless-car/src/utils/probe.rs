@@ -0,0 +1,197 @@
+use std::io::{self, Read};
+
+use cid::Cid;
+
+use crate::{error::CarError, header::CarHeader, reader::read_block};
+
+/// the CARv2 pragma, the varint length and the dag-cbor `{"version": 2}`.
+pub const CARV2_PRAGMA: [u8; 11] = [
+    0x0a, 0xa1, 0x67, 0x76, 0x65, 0x72, 0x73, 0x69, 0x6f, 0x6e, 0x02,
+];
+pub(crate) const CARV2_HEADER_SIZE: usize = 40;
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// the format of the probed file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbeFormat {
+    CarV1,
+    CarV2,
+    Gzip,
+    Zstd,
+    Unknown,
+}
+
+/// the fixed header of the CARv2 file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CarV2Header {
+    pub characteristics: u128,
+    pub data_offset: u64,
+    pub data_size: u64,
+    pub index_offset: u64,
+}
+
+impl CarV2Header {
+    pub(crate) fn decode(buf: &[u8; CARV2_HEADER_SIZE]) -> Self {
+        let u64_at = |i: usize| u64::from_le_bytes(buf[i..i + 8].try_into().unwrap());
+        Self {
+            characteristics: u128::from_le_bytes(buf[0..16].try_into().unwrap()),
+            data_offset: u64_at(16),
+            data_size: u64_at(24),
+            index_offset: u64_at(32),
+        }
+    }
+
+    /// the data payload is fully indexed, the high bit of the first characteristics byte.
+    /// the characteristics are decoded little endian, so the first byte is the lowest.
+    #[inline]
+    pub fn is_fully_indexed(&self) -> bool {
+        self.characteristics & 0x80 != 0
+    }
+}
+
+/// the result of the probe.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Probe {
+    pub format: ProbeFormat,
+    /// the roots of the CAR, empty if the format is not CAR.
+    pub roots: Vec<Cid>,
+    /// the CARv2 header if the format is CARv2.
+    pub v2: Option<CarV2Header>,
+    /// the reason why the file is not recognized as CAR.
+    pub error: Option<String>,
+}
+
+impl Probe {
+    fn new(format: ProbeFormat) -> Self {
+        Self {
+            format,
+            roots: Vec::new(),
+            v2: None,
+            error: None,
+        }
+    }
+
+    fn unknown(error: impl ToString) -> Self {
+        Self {
+            error: Some(error.to_string()),
+            ..Self::new(ProbeFormat::Unknown)
+        }
+    }
+
+    #[inline]
+    pub fn is_car(&self) -> bool {
+        matches!(self.format, ProbeFormat::CarV1 | ProbeFormat::CarV2)
+    }
+}
+
+/// read the bytes as many as possible up to the buffer size.
+fn read_full(r: &mut impl Read, buf: &mut [u8]) -> Result<usize, CarError> {
+    let mut n = 0;
+    while n < buf.len() {
+        match r.read(&mut buf[n..]) {
+            Ok(0) => break,
+            Ok(i) => n += i,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(n)
+}
+
+fn read_v1_header(r: &mut impl Read) -> Result<Vec<Cid>, CarError> {
+    match read_block(r)? {
+        Some(data) => Ok(CarHeader::decode(&data)?.roots()),
+        None => Err(CarError::Parsing("Invalid Header".into())),
+    }
+}
+
+/// classify the file from the start of the stream, the stream is read only
+/// until the format and the header are recognized.
+/// the compressed files are reported as `Gzip` or `Zstd`, the caller can
+/// probe again with the decompressed stream.
+pub fn probe(r: &mut impl Read) -> Result<Probe, CarError> {
+    let mut magic = [0u8; CARV2_PRAGMA.len()];
+    let n = read_full(r, &mut magic)?;
+    let magic = &magic[..n];
+    if magic.starts_with(&GZIP_MAGIC) {
+        return Ok(Probe::new(ProbeFormat::Gzip));
+    }
+    if magic.starts_with(&ZSTD_MAGIC) {
+        return Ok(Probe::new(ProbeFormat::Zstd));
+    }
+    if magic == CARV2_PRAGMA {
+        let mut header = [0u8; CARV2_HEADER_SIZE];
+        if read_full(r, &mut header)? < CARV2_HEADER_SIZE {
+            return Ok(Probe::unknown("the CARv2 header is truncated"));
+        }
+        let v2 = CarV2Header::decode(&header);
+        let skip = v2
+            .data_offset
+            .saturating_sub((CARV2_PRAGMA.len() + CARV2_HEADER_SIZE) as u64);
+        io::copy(&mut r.take(skip), &mut io::sink())?;
+        return Ok(match read_v1_header(r) {
+            Ok(roots) => Probe {
+                roots,
+                v2: Some(v2),
+                ..Probe::new(ProbeFormat::CarV2)
+            },
+            Err(e) => Probe::unknown(format!("the CARv2 data payload is invalid: {e}")),
+        });
+    }
+    let mut r = magic.chain(r);
+    Ok(match read_v1_header(&mut r) {
+        Ok(roots) => Probe {
+            roots,
+            ..Probe::new(ProbeFormat::CarV1)
+        },
+        Err(e) => Probe::unknown(e),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_probe() {
+        let car = std::fs::read(std::path::Path::new("test").join("carv1-basic.car")).unwrap();
+        let rs = probe(&mut &car[..]).unwrap();
+        assert_eq!(rs.format, ProbeFormat::CarV1);
+        assert_eq!(rs.roots.len(), 1);
+
+        // wrap the CARv1 into a CARv2 with 8 bytes padding before the payload.
+        let mut v2 = CARV2_PRAGMA.to_vec();
+        v2.extend_from_slice(&[0u8; 16]);
+        v2.extend_from_slice(&59u64.to_le_bytes());
+        v2.extend_from_slice(&(car.len() as u64).to_le_bytes());
+        v2.extend_from_slice(&0u64.to_le_bytes());
+        v2.extend_from_slice(&[0u8; 8]);
+        v2.extend_from_slice(&car);
+        let rs = probe(&mut &v2[..]).unwrap();
+        assert_eq!(rs.format, ProbeFormat::CarV2);
+        assert_eq!(rs.roots.len(), 1);
+        assert_eq!(rs.v2.unwrap().data_size, car.len() as u64);
+        assert!(!rs.v2.unwrap().is_fully_indexed());
+
+        // the fully indexed flag is the high bit of the first byte.
+        let mut indexed = v2.clone();
+        indexed[CARV2_PRAGMA.len()] = 0x80;
+        let rs = probe(&mut &indexed[..]).unwrap();
+        assert!(rs.v2.unwrap().is_fully_indexed());
+        // the other bits are not the flag.
+        let mut other = v2.clone();
+        other[CARV2_PRAGMA.len()] = 0x7f;
+        other[CARV2_PRAGMA.len() + 15] = 0x80;
+        let rs = probe(&mut &other[..]).unwrap();
+        assert!(!rs.v2.unwrap().is_fully_indexed());
+
+        let rs = probe(&mut &[0x1f, 0x8b, 0x08, 0x00][..]).unwrap();
+        assert_eq!(rs.format, ProbeFormat::Gzip);
+        let rs = probe(&mut &b"hello world, not a car"[..]).unwrap();
+        assert_eq!(rs.format, ProbeFormat::Unknown);
+        assert!(rs.error.is_some());
+        let rs = probe(&mut &b""[..]).unwrap();
+        assert_eq!(rs.format, ProbeFormat::Unknown);
+    }
+}
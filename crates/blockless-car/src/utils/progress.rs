@@ -0,0 +1,211 @@
+use std::{
+    cell::Cell,
+    fmt,
+    io::{self, Read, Seek, SeekFrom},
+    rc::Rc,
+    sync::Arc,
+};
+
+use cid::Cid;
+
+use crate::{
+    error::CarError,
+    header::CarHeader,
+    writer::{CarWriter, WriteStream},
+};
+
+/// the counters of the running pack or extract.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Progress {
+    /// the files packed or extracted.
+    pub files: u64,
+    /// the bytes of the file content read and hashed by the pack,
+    /// or the bytes of the blocks read and verified by the extract.
+    pub bytes_hashed: u64,
+    /// the bytes of the CAR written by the pack, or of the file content written by the extract.
+    pub bytes_written: u64,
+}
+
+/// the hook of the progress of the pack or the extract, called with the counters every
+/// time they change, e.g. per chunk. the hook should be cheap, the rendering should be
+/// rate limited by the hook. the closures `Fn(&Progress)` are hooks.
+pub trait ProgressHook: Send + Sync {
+    fn progress(&self, progress: &Progress);
+}
+
+impl<F> ProgressHook for F
+where
+    F: Fn(&Progress) + Send + Sync,
+{
+    fn progress(&self, progress: &Progress) {
+        self(progress)
+    }
+}
+
+impl fmt::Debug for dyn ProgressHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ProgressHook")
+    }
+}
+
+/// the counters shared by the readers and the writers of a pack or an extract.
+pub(crate) struct ProgressCounter {
+    hook: Arc<dyn ProgressHook>,
+    progress: Cell<Progress>,
+}
+
+impl ProgressCounter {
+    pub(crate) fn new(hook: Option<&Arc<dyn ProgressHook>>) -> Option<Rc<Self>> {
+        hook.map(|hook| {
+            Rc::new(Self {
+                hook: hook.clone(),
+                progress: Cell::new(Progress::default()),
+            })
+        })
+    }
+
+    /// update the counters, the hook is called if they changed.
+    pub(crate) fn update(&self, f: impl FnOnce(&mut Progress)) {
+        let mut progress = self.progress.get();
+        f(&mut progress);
+        if progress != self.progress.get() {
+            self.progress.set(progress);
+            self.hook.progress(&progress);
+        }
+    }
+}
+
+/// count the bytes read from the `inner` as hashed, the bytes read again after seeking
+/// back, e.g. the streamed blocks read twice for their cids, are counted once.
+pub(crate) struct ProgressReader<R> {
+    inner: R,
+    counter: Option<Rc<ProgressCounter>>,
+    pos: u64,
+    // the highest position read.
+    read: u64,
+}
+
+impl<R> ProgressReader<R> {
+    pub(crate) fn new(inner: R, counter: Option<Rc<ProgressCounter>>) -> Self {
+        Self {
+            inner,
+            counter,
+            pos: 0,
+            read: 0,
+        }
+    }
+}
+
+impl<R: Read> Read for ProgressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.pos += n as u64;
+        if self.pos > self.read {
+            let new = self.pos - self.read;
+            self.read = self.pos;
+            if let Some(counter) = self.counter.as_ref() {
+                counter.update(|p| p.bytes_hashed += new);
+            }
+        }
+        Ok(n)
+    }
+}
+
+impl<R: Seek> Seek for ProgressReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.pos = self.inner.seek(pos)?;
+        Ok(self.pos)
+    }
+}
+
+/// report the bytes written by the `inner` writer after each block.
+pub(crate) struct ProgressWriter<'a, W> {
+    inner: &'a mut W,
+    counter: Option<Rc<ProgressCounter>>,
+}
+
+impl<'a, W: CarWriter> ProgressWriter<'a, W> {
+    pub(crate) fn new(inner: &'a mut W, counter: Option<Rc<ProgressCounter>>) -> Self {
+        Self { inner, counter }
+    }
+
+    fn report(&self) {
+        if let Some(counter) = self.counter.as_ref() {
+            let written = self.inner.bytes_written();
+            counter.update(|p| p.bytes_written = written);
+        }
+    }
+}
+
+impl<W: CarWriter> CarWriter for ProgressWriter<'_, W> {
+    fn write_block<T>(&mut self, cid: Cid, data: T) -> Result<(), CarError>
+    where
+        T: AsRef<[u8]>,
+    {
+        self.inner.write_block(cid, data)?;
+        self.report();
+        Ok(())
+    }
+
+    fn stream_block<F, R>(
+        &mut self,
+        cid_f: F,
+        stream_len: usize,
+        r: &mut R,
+    ) -> Result<Cid, CarError>
+    where
+        R: Read + Seek,
+        F: FnMut(WriteStream) -> Option<Result<Cid, CarError>>,
+    {
+        let cid = self.inner.stream_block(cid_f, stream_len, r)?;
+        self.report();
+        Ok(cid)
+    }
+
+    #[inline(always)]
+    fn rewrite_header(&mut self, header: CarHeader) -> Result<(), CarError> {
+        self.inner.rewrite_header(header)
+    }
+
+    #[inline(always)]
+    fn flush(&mut self) -> Result<(), CarError> {
+        self.inner.flush()
+    }
+
+    #[inline(always)]
+    fn bytes_written(&self) -> u64 {
+        self.inner.bytes_written()
+    }
+
+    #[inline(always)]
+    fn blocks_written(&self) -> u64 {
+        self.inner.blocks_written()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{io::Cursor, sync::Mutex};
+
+    #[test]
+    fn test_progress_reader() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let hook: Arc<dyn ProgressHook> = {
+            let seen = seen.clone();
+            Arc::new(move |p: &Progress| seen.lock().unwrap().push(p.bytes_hashed))
+        };
+        let counter = ProgressCounter::new(Some(&hook));
+        let mut reader = ProgressReader::new(Cursor::new(vec![1u8; 100]), counter.clone());
+        let mut buf = [0u8; 60];
+        reader.read_exact(&mut buf).unwrap();
+        // the bytes read again are not counted.
+        reader.rewind().unwrap();
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(*seen.lock().unwrap(), vec![60]);
+        let mut rest = vec![];
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(seen.lock().unwrap().last(), Some(&100));
+        assert!(ProgressCounter::new(None).is_none());
+    }
+}
@@ -0,0 +1,158 @@
+//! assemble the CAR from the raw blocks, e.g. exported from a blockstore, without any
+//! unixfs logic.
+use std::{
+    collections::HashSet,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use cid::Cid;
+
+use crate::{
+    error::CarError,
+    header::CarHeader,
+    section::checked_section_len,
+    utils::verify_block,
+    writer::{CarWriter, StreamCarWriterV1},
+};
+
+/// the block of `put_blocks`, the data is read from the file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockFile {
+    pub cid: Cid,
+    pub path: PathBuf,
+}
+
+fn parse_cid(s: &str) -> Result<Cid, CarError> {
+    Cid::try_from(s).map_err(|e| CarError::Parsing(format!("invalid cid {s}: {e}")))
+}
+
+/// parse the block list of one `<cid> <file>` per line, the blank lines are skipped.
+/// the relative files are resolved against the `base`, e.g. the directory of the list.
+pub fn parse_block_list(list: &str, base: &Path) -> Result<Vec<BlockFile>, CarError> {
+    let mut blocks = Vec::new();
+    for (i, line) in list.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (cid, file) = line
+            .split_once(char::is_whitespace)
+            .map(|(cid, file)| (cid, file.trim_start()))
+            .ok_or_else(|| {
+                CarError::InvalidFile(format!("line {}: expect `<cid> <file>`", i + 1))
+            })?;
+        blocks.push(BlockFile {
+            cid: parse_cid(cid)?,
+            path: base.join(file),
+        });
+    }
+    Ok(blocks)
+}
+
+/// the block files of the directory named by the cids of their blocks, the extension
+/// like `.data` is ignored. the blocks are sorted by the file name.
+pub fn block_dir(dir: impl AsRef<Path>) -> Result<Vec<BlockFile>, CarError> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            paths.push(entry.path());
+        }
+    }
+    paths.sort();
+    paths
+        .into_iter()
+        .map(|path| {
+            let stem = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or_default();
+            Ok(BlockFile {
+                cid: parse_cid(stem)?,
+                path,
+            })
+        })
+        .collect()
+}
+
+/// write the CAR of the `roots` and the blocks in the order, the roots are not required
+/// to be in the blocks. the data of each block is checked against its cid, the repeated
+/// cids are written once. return the count of the blocks written.
+pub fn put_blocks<W>(roots: Vec<Cid>, blocks: &[BlockFile], to: W) -> Result<usize, CarError>
+where
+    W: Write,
+{
+    let mut writer = StreamCarWriterV1::new(to, CarHeader::new_v1(roots));
+    let mut written = HashSet::new();
+    for block in blocks.iter() {
+        if written.contains(&block.cid) {
+            continue;
+        }
+        checked_section_len(fs::metadata(&block.path)?.len())?;
+        let data = fs::read(&block.path)?;
+        if !verify_block(&block.cid, &data)? {
+            return Err(CarError::InvalidSection(format!(
+                "the data of {} does not match the cid {}",
+                block.path.display(),
+                block.cid
+            )));
+        }
+        writer.write_block(block.cid, &data)?;
+        written.insert(block.cid);
+    }
+    writer.flush()?;
+    Ok(written.len())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        reader::{self, CarReader},
+        utils::raw_cid,
+    };
+    use std::io::Cursor;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_put_blocks() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let dir = temp_dir.path().join("blocks");
+        fs::create_dir(&dir).unwrap();
+        let datas: [&[u8]; 3] = [b"a", b"bb", b"ccc"];
+        let cids: Vec<Cid> = datas
+            .iter()
+            .map(|d| raw_cid(d, multicodec::Codec::Sha2_256))
+            .collect();
+        for (cid, data) in cids.iter().zip(datas.iter()) {
+            fs::write(dir.join(format!("{cid}.data")), data).unwrap();
+        }
+
+        let list = format!(
+            "{} blocks/{}.data\n\n{}  blocks/{}.data\n{} blocks/{}.data\n",
+            cids[2], cids[2], cids[0], cids[0], cids[2], cids[2]
+        );
+        let blocks = parse_block_list(&list, temp_dir.path()).unwrap();
+        assert_eq!(blocks.len(), 3);
+        let mut car = vec![];
+        assert_eq!(put_blocks(vec![cids[2]], &blocks, &mut car).unwrap(), 2);
+        let mut reader = reader::new_v1(Cursor::new(car)).unwrap();
+        assert_eq!(reader.header().roots(), vec![cids[2]]);
+        let written: Vec<Cid> = reader.blocks().map(|b| b.unwrap().0).collect();
+        assert_eq!(written, vec![cids[2], cids[0]]);
+
+        let blocks = block_dir(&dir).unwrap();
+        let mut car = vec![];
+        assert_eq!(put_blocks(vec![cids[0]], &blocks, &mut car).unwrap(), 3);
+        let mut reader = reader::new_v1(Cursor::new(car)).unwrap();
+        assert_eq!(reader.get_block(&cids[1]).unwrap().unwrap(), b"bb");
+
+        // the data not matching the cid.
+        fs::write(&blocks[0].path, b"changed").unwrap();
+        assert!(put_blocks(vec![cids[0]], &blocks, vec![]).is_err());
+        assert!(parse_block_list("bafy-not-a-cid a.data", temp_dir.path()).is_err());
+        assert!(parse_block_list(&cids[0].to_string(), temp_dir.path()).is_err());
+    }
+}
@@ -0,0 +1,86 @@
+use std::io::Write;
+
+use integer_encoding::{VarInt, VarIntWriter};
+
+use crate::{error::CarError, reader::CarReader, section::Section};
+
+use super::stuffing;
+
+/// copy the car to `to` with the block payloads starting at the offsets aligned to `align`,
+/// e.g. the page size for mmap or `sendfile` serving.
+/// the space before the payloads is filled with the identity cid stuffing blocks,
+/// so the output is still a valid CARv1 file.
+/// return the sections of the output, the `pos` of the sections are the aligned payload offsets.
+pub fn repack_aligned<W>(
+    reader: &mut impl CarReader,
+    mut to: W,
+    align: usize,
+) -> Result<Vec<Section>, CarError>
+where
+    W: Write,
+{
+    if align == 0 {
+        return Err(CarError::InvalidSection(
+            "the alignment must not be 0".into(),
+        ));
+    }
+    let header = reader.header().encode()?;
+    to.write_varint(header.len())?;
+    to.write_all(&header)?;
+    let mut pos = (header.len().required_space() + header.len()) as u64;
+    let align = align as u64;
+    let mut sections = Vec::new();
+    for block in reader.blocks() {
+        let (cid, data) = block?;
+        let cid_bytes = cid.to_bytes();
+        let len = cid_bytes.len() + data.len();
+        let prefix = (len.required_space() + cid_bytes.len()) as u64;
+        let mut gap = match (pos + prefix) % align {
+            0 => 0,
+            rem => align - rem,
+        };
+        while gap > 0 {
+            // the small gaps can't be filled by the stuffing blocks, skip to the next boundary.
+            if let Some(buf) = stuffing(gap as usize)? {
+                to.write_all(&buf)?;
+                break;
+            }
+            gap += align;
+        }
+        pos += gap;
+        to.write_varint(len)?;
+        to.write_all(&cid_bytes)?;
+        to.write_all(&data)?;
+        sections.push(Section::new(cid, pos + prefix, data.len()));
+        pos += prefix + data.len() as u64;
+    }
+    to.flush()?;
+    Ok(sections)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{reader, utils::verify_blocks};
+    use std::io::Cursor;
+
+    #[test]
+    fn test_repack_aligned() {
+        let file = std::path::Path::new("test").join("carv1-basic.car");
+        let mut reader = reader::new_v1(std::fs::File::open(file).unwrap()).unwrap();
+        for align in [1, 7, 4096] {
+            let mut out = vec![];
+            let sections = repack_aligned(&mut reader, &mut out, align).unwrap();
+            assert_eq!(sections.len(), reader.section_count().unwrap());
+            for s in sections.iter() {
+                assert_eq!(s.pos() % align as u64, 0);
+                let data = reader.read_section_data(&s.cid()).unwrap();
+                let pos = s.pos() as usize;
+                assert_eq!(&out[pos..pos + s.len()], &data[..]);
+            }
+            let mut repacked = reader::new_v1(Cursor::new(out)).unwrap();
+            assert_eq!(repacked.header().roots(), reader.header().roots());
+            assert!(verify_blocks(&mut repacked).unwrap().is_ok());
+        }
+    }
+}
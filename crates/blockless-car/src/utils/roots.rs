@@ -0,0 +1,314 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use cid::{multihash::Multihash, Cid};
+use integer_encoding::VarInt;
+use ipld::raw::RawCodec;
+
+use crate::{
+    error::CarError,
+    header::CarHeader,
+    reader::{read_section, DecodeMode, ReaderLimits},
+    utils::IDENTITY_CODE,
+};
+
+/// the max digest size of the identity cid.
+const MAX_IDENTITY_DIGEST: usize = 64;
+
+/// the gap is filled with the largest blocks until it can be filled by the table.
+const STUFFING_TABLE_SIZE: usize = 400;
+
+/// encode the stuffing section, the cid is the identity cid of the `digest_size` zero bytes
+/// and the data is the same zero bytes, so the section passes the verification.
+fn stuffing_section(digest_size: usize) -> Result<Vec<u8>, CarError> {
+    let digest = vec![0u8; digest_size];
+    let hash =
+        Multihash::wrap(IDENTITY_CODE, &digest).map_err(|e| CarError::Parsing(e.to_string()))?;
+    let cid = Cid::new_v1(RawCodec.into(), hash).to_bytes();
+    let mut section = (cid.len() + digest_size).encode_var_vec();
+    section.extend_from_slice(&cid);
+    section.extend_from_slice(&digest);
+    Ok(section)
+}
+
+/// compose the stuffing sections with exactly `gap` bytes in total,
+/// return none if the gap is too small to be filled.
+pub(crate) fn stuffing(mut gap: usize) -> Result<Option<Vec<u8>>, CarError> {
+    let mut sections = Vec::with_capacity(MAX_IDENTITY_DIGEST + 1);
+    for digest_size in 0..=MAX_IDENTITY_DIGEST {
+        sections.push(stuffing_section(digest_size)?);
+    }
+    let largest = &sections[MAX_IDENTITY_DIGEST];
+    let mut buf = Vec::with_capacity(gap);
+    while gap > STUFFING_TABLE_SIZE {
+        buf.extend_from_slice(largest);
+        gap -= largest.len();
+    }
+    // the fewest sections for every size up to the gap, the value is the last section.
+    let mut table: Vec<Option<(usize, usize)>> = vec![None; gap + 1];
+    table[0] = Some((0, 0));
+    for size in 1..=gap {
+        for (i, section) in sections.iter().enumerate() {
+            let Some(rest) = size.checked_sub(section.len()) else {
+                continue;
+            };
+            if let Some((count, _)) = table[rest] {
+                if table[size].is_none_or(|(c, _)| count + 1 < c) {
+                    table[size] = Some((count + 1, i));
+                }
+            }
+        }
+    }
+    if table[gap].is_none() {
+        return Ok(None);
+    }
+    while gap > 0 {
+        let (_, i) = table[gap].unwrap();
+        buf.extend_from_slice(&sections[i]);
+        gap -= sections[i].len();
+    }
+    Ok(Some(buf))
+}
+
+/// the section is the stuffing block, the raw block of the identity cid of the zero bytes.
+fn is_stuffing(cid: &Cid, data: &[u8]) -> bool {
+    let hash = cid.hash();
+    cid.codec() == u64::from(RawCodec)
+        && hash.code() == IDENTITY_CODE
+        && hash.digest() == data
+        && data.iter().all(|b| *b == 0)
+}
+
+/// the end of the stuffing sections from the `pos` right after the header, the stuffing
+/// written by an earlier replace is the room of the new header.
+fn stuffing_end<F>(file: &mut F, mut pos: u64) -> Result<u64, CarError>
+where
+    F: Read + Seek,
+{
+    let limits = ReaderLimits::default();
+    loop {
+        file.seek(SeekFrom::Start(pos))?;
+        let section = match read_section(&mut *file, &limits, DecodeMode::Strict) {
+            Ok(Some(section)) if section.len() <= MAX_IDENTITY_DIGEST => section,
+            _ => return Ok(pos),
+        };
+        let data = section.read_data(&mut *file)?;
+        if !is_stuffing(&section.cid(), &data) {
+            return Ok(pos);
+        }
+        pos = section.pos() + section.len() as u64;
+    }
+}
+
+/// replace the roots of the CARv1 file in place without copying the blocks.
+/// the new header must encode to no more bytes than the old one with the stuffing blocks
+/// after it, the rest is filled with the identity cid stuffing blocks. so the roots shrunk
+/// by an earlier replace can grow back in place.
+/// return false and leave the file untouched if the new header doesn't fit,
+/// the caller should rewrite the whole file in this case.
+pub fn replace_roots_in_place<F>(file: &mut F, roots: Vec<Cid>) -> Result<bool, CarError>
+where
+    F: Read + Write + Seek,
+{
+    if roots.is_empty() {
+        return Err(CarError::InvalidFile("car roots is empty".into()));
+    }
+    file.rewind()?;
+    // the old header must be a valid CARv1 header, the size includes the length varint.
+    CarHeader::read_header(&mut *file)?;
+    let header_end = file.stream_position()?;
+    let old_size = stuffing_end(file, header_end)? as usize;
+
+    let header = CarHeader::new_v1(roots).encode()?;
+    let mut buf = header.len().encode_var_vec();
+    buf.extend_from_slice(&header);
+    let Some(gap) = old_size.checked_sub(buf.len()) else {
+        return Ok(false);
+    };
+    if gap > 0 {
+        match stuffing(gap)? {
+            Some(stuffing) => buf.extend(stuffing),
+            None => return Ok(false),
+        }
+    }
+    file.rewind()?;
+    file.write_all(&buf)?;
+    file.flush()?;
+    Ok(true)
+}
+
+/// copy the CARv1 `input` to the `output` with the header of the `roots`,
+/// the stuffing blocks after the old header are dropped.
+pub fn rewrite_roots<R, W>(input: &mut R, output: &mut W, roots: Vec<Cid>) -> Result<(), CarError>
+where
+    R: Read + Seek,
+    W: Write,
+{
+    if roots.is_empty() {
+        return Err(CarError::InvalidFile("car roots is empty".into()));
+    }
+    input.rewind()?;
+    CarHeader::read_header(&mut *input)?;
+    let header_end = input.stream_position()?;
+    let blocks_start = stuffing_end(input, header_end)?;
+    let header = CarHeader::new_v1(roots).encode()?;
+    output.write_all(&header.len().encode_var_vec())?;
+    output.write_all(&header)?;
+    input.seek(SeekFrom::Start(blocks_start))?;
+    io::copy(input, output)?;
+    output.flush()?;
+    Ok(())
+}
+
+/// create the temp file next to the `path` exclusively, so it can be renamed over the `path`.
+fn create_sibling(path: &Path) -> Result<(PathBuf, File), CarError> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| CarError::InvalidFile(format!("{} is not a file", path.display())))?;
+    let parent = path.parent().unwrap_or(Path::new(""));
+    for i in 0.. {
+        let temp = parent.join(format!(
+            ".{}.roots-{}-{i}",
+            name.to_string_lossy(),
+            std::process::id()
+        ));
+        match OpenOptions::new().write(true).create_new(true).open(&temp) {
+            Ok(file) => return Ok((temp, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    unreachable!()
+}
+
+/// replace the roots of the CARv1 file at the `path`, in place if the new header fits.
+/// otherwise the car is rewritten to a temp file next to it, which is renamed over the car,
+/// see `rewrite_roots`. return true if the roots are set in place.
+pub fn replace_roots(path: impl AsRef<Path>, roots: Vec<Cid>) -> Result<bool, CarError> {
+    let path = path.as_ref();
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    if replace_roots_in_place(&mut file, roots.clone())? {
+        return Ok(true);
+    }
+    let (temp, output) = create_sibling(path)?;
+    let rewrite = || -> Result<(), CarError> {
+        let mut output = BufWriter::new(output);
+        rewrite_roots(&mut BufReader::new(file), &mut output, roots)?;
+        let output = output.into_inner().map_err(|e| e.into_error())?;
+        output.set_permissions(fs::metadata(path)?.permissions())?;
+        output.sync_all()?;
+        fs::rename(&temp, path)?;
+        Ok(())
+    };
+    match rewrite() {
+        Ok(()) => Ok(false),
+        Err(e) => {
+            let _ = fs::remove_file(&temp);
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        reader::{self, CarReader},
+        utils::{raw_cid, verify_blocks},
+        writer::{self, CarWriter},
+    };
+    use std::io::Cursor;
+
+    #[test]
+    fn test_stuffing() {
+        for gap in [1, 2, 3, 4, 6, 8] {
+            assert!(stuffing(gap).unwrap().is_none());
+        }
+        for gap in [5, 7, 9, 10, 128, 129, 131, 401, 1000, 4097] {
+            let buf = stuffing(gap).unwrap().unwrap();
+            assert_eq!(buf.len(), gap);
+        }
+    }
+
+    #[test]
+    fn test_replace_roots_in_place() {
+        let blocks: Vec<_> = (0..3u8)
+            .map(|i| {
+                let data = vec![i; 16];
+                (raw_cid(&data, multicodec::Codec::Sha2_256), data)
+            })
+            .collect();
+        let roots: Vec<Cid> = blocks.iter().map(|(cid, _)| *cid).collect();
+        let mut buf = Cursor::new(vec![]);
+        {
+            let mut writer = writer::new_v1(&mut buf, CarHeader::new_v1(roots.clone())).unwrap();
+            for (cid, data) in blocks.iter() {
+                writer.write_block(*cid, data).unwrap();
+            }
+            writer.flush().unwrap();
+        }
+        let len = buf.get_ref().len();
+
+        assert!(replace_roots_in_place(&mut buf, vec![roots[2], roots[1]]).unwrap());
+        assert!(replace_roots_in_place(&mut buf, vec![roots[0]]).unwrap());
+        assert_eq!(buf.get_ref().len(), len);
+        let mut reader = reader::new_v1(Cursor::new(buf.get_ref().clone())).unwrap();
+        assert_eq!(reader.header().roots(), vec![roots[0]]);
+        for (cid, data) in blocks.iter() {
+            assert_eq!(&reader.read_section_data(cid).unwrap(), data);
+        }
+        assert!(verify_blocks(&mut reader).unwrap().is_ok());
+
+        // the header with more roots doesn't fit, the file is untouched.
+        let before = buf.get_ref().clone();
+        assert!(!replace_roots_in_place(&mut buf, roots.repeat(4)).unwrap());
+        assert_eq!(buf.get_ref(), &before);
+
+        // the car is rewritten for the larger header without the stuffing blocks.
+        let mut out = vec![];
+        rewrite_roots(&mut buf, &mut out, roots.repeat(4)).unwrap();
+        let mut reader = reader::new_v1(Cursor::new(out)).unwrap();
+        assert_eq!(reader.header().roots(), roots.repeat(4));
+        for (cid, data) in blocks.iter() {
+            assert_eq!(&reader.read_section_data(cid).unwrap(), data);
+        }
+        assert_eq!(reader.section_count().unwrap(), blocks.len());
+        assert!(verify_blocks(&mut reader).unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_replace_roots_shrink_grow() {
+        let blocks: Vec<_> = (0..3u8)
+            .map(|i| {
+                let data = vec![i; 16];
+                (raw_cid(&data, multicodec::Codec::Sha2_256), data)
+            })
+            .collect();
+        let roots: Vec<Cid> = blocks.iter().map(|(cid, _)| *cid).collect();
+        let mut buf = Cursor::new(vec![]);
+        {
+            let mut writer = writer::new_v1(&mut buf, CarHeader::new_v1(roots.clone())).unwrap();
+            for (cid, data) in blocks.iter() {
+                writer.write_block(*cid, data).unwrap();
+            }
+            writer.flush().unwrap();
+        }
+        let original = buf.get_ref().clone();
+
+        // the stuffing of the shrink is the room of the roots growing back.
+        assert!(replace_roots_in_place(&mut buf, vec![roots[1]]).unwrap());
+        assert!(replace_roots_in_place(&mut buf, roots.clone()).unwrap());
+        assert_eq!(buf.get_ref(), &original);
+        assert!(replace_roots_in_place(&mut buf, vec![roots[0]]).unwrap());
+        assert!(replace_roots_in_place(&mut buf, vec![roots[2], roots[0]]).unwrap());
+        assert_eq!(buf.get_ref().len(), original.len());
+        let mut reader = reader::new_v1(Cursor::new(buf.get_ref().clone())).unwrap();
+        assert_eq!(reader.header().roots(), vec![roots[2], roots[0]]);
+        let cids: Vec<Cid> = reader.blocks().map(|b| b.unwrap().0).collect();
+        assert_eq!(&cids[cids.len() - 3..], &roots[..]);
+        assert!(verify_blocks(&mut reader).unwrap().is_ok());
+    }
+}
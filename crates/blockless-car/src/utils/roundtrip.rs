@@ -0,0 +1,269 @@
+//! the self check of the pack, the source is packed to a temp CAR, extracted to a temp
+//! directory and the trees are compared.
+use std::{
+    collections::HashSet,
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use cid::Cid;
+
+use crate::{
+    error::CarError,
+    reader,
+    unixfs::FileType,
+    utils::{
+        extract_ipld_with, pack_files_with, temp::TempDir, walk_path_with, ExtractOptions,
+        PackOptions, TopNode,
+    },
+};
+
+/// the difference between the source and the extracted tree, the paths are relative to the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RoundtripDiff {
+    /// the packed entry is not extracted.
+    Missing(PathBuf),
+    /// the extracted entry is not packed.
+    Extra(PathBuf),
+    /// the entry is extracted as another type, e.g. a directory as a file.
+    Kind(PathBuf),
+    /// the file content or the symlink target differs.
+    Content(PathBuf),
+    /// the mode or the mtime differs, compared with the `preserve_metadata` on unix.
+    Metadata(PathBuf),
+}
+
+/// the report of the round trip.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoundtripReport {
+    /// the root of the packed CAR.
+    pub root: Cid,
+    /// the size of the packed CAR.
+    pub car_size: u64,
+    /// the count of the entries compared, the source root included.
+    pub entries: usize,
+    pub diffs: Vec<RoundtripDiff>,
+}
+
+impl RoundtripReport {
+    #[inline(always)]
+    pub fn is_ok(&self) -> bool {
+        self.diffs.is_empty()
+    }
+}
+
+/// pack the `source` with the `options` to a temp CAR, extract it to a temp directory
+/// and compare the trees byte for byte, the temp files are removed after.
+/// the temp files are in the `options.spill_dir`, the OS temp directory if none.
+/// the entries left out by the filters of the `options` are not expected in the extraction,
+/// the mode and the mtime are compared if the `options.preserve_metadata` is set.
+pub fn verify_roundtrip(
+    source: impl AsRef<Path>,
+    options: &PackOptions,
+) -> Result<RoundtripReport, CarError> {
+    let source = source.as_ref();
+    let temp = TempDir::new_in(options.spill_dir.as_deref(), "blockless-car-roundtrip")?;
+    let car_path = temp.path().join("roundtrip.car");
+    let root = pack_files_with(source, fs::File::create(&car_path)?, options)?;
+    let car_size = fs::metadata(&car_path)?.len();
+
+    let mut reader = reader::new_v1(fs::File::open(&car_path)?)?;
+    let extract_options = ExtractOptions::new()
+        .with_limits(options.limits)
+        .with_preserve_metadata(options.preserve_metadata)
+        .with_cancel(options.cancel.clone());
+    let out = temp.path().join("out");
+    extract_ipld_with(&mut reader, root, Some(&out), &extract_options)?;
+
+    let wrapped = match source.is_file() {
+        true => !options.no_wrap_file,
+        false => true,
+    };
+    let target = match (wrapped, &options.top_node) {
+        (true, TopNode::Omit) | (false, _) => out,
+        // the top node is the only entry of the extraction.
+        (true, _) => match fs::read_dir(&out)?.next() {
+            Some(entry) => entry?.path(),
+            None => return Err(CarError::NotFound(format!("the top node of {root}"))),
+        },
+    };
+    let (entries, diffs) = diff_trees(source, &target, options)?;
+    Ok(RoundtripReport {
+        root,
+        car_size,
+        entries,
+        diffs,
+    })
+}
+
+/// the entries packed from the `source`, relative to the `source`.
+fn packed_entries(source: &Path, options: &PackOptions) -> Result<Vec<PathBuf>, CarError> {
+    let mut entries = vec![PathBuf::new()];
+    if source.is_file() {
+        return Ok(entries);
+    }
+    let (_, path_cache) = walk_path_with(source, options)?;
+    for (dir, unix_fs) in path_cache.iter() {
+        let rel_dir = dir.strip_prefix(source).unwrap_or(dir);
+        entries.extend(unix_fs.links.iter().map(|l| rel_dir.join(&l.name)));
+    }
+    entries.sort();
+    Ok(entries)
+}
+
+/// compare the packed entries of the `source` with the `target`, return the count of
+/// the entries compared and the differences.
+fn diff_trees(
+    source: &Path,
+    target: &Path,
+    options: &PackOptions,
+) -> Result<(usize, Vec<RoundtripDiff>), CarError> {
+    let expected = packed_entries(source, options)?;
+    let mut diffs = Vec::new();
+    for rel in expected.iter() {
+        let (src, dst) = (join(source, rel), join(target, rel));
+        let src_meta = fs::symlink_metadata(&src)?;
+        let dst_meta = match fs::symlink_metadata(&dst) {
+            Ok(meta) => meta,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                diffs.push(RoundtripDiff::Missing(rel.clone()));
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if file_type(&src_meta) != file_type(&dst_meta) {
+            diffs.push(RoundtripDiff::Kind(rel.clone()));
+            continue;
+        }
+        let same = match file_type(&src_meta) {
+            FileType::File => src_meta.len() == dst_meta.len() && same_content(&src, &dst)?,
+            FileType::Symlink => fs::read_link(&src)? == fs::read_link(&dst)?,
+            _ => true,
+        };
+        if !same {
+            diffs.push(RoundtripDiff::Content(rel.clone()));
+        } else if options.preserve_metadata && !same_metadata(&src_meta, &dst_meta)? {
+            diffs.push(RoundtripDiff::Metadata(rel.clone()));
+        }
+    }
+    let expected: HashSet<&PathBuf> = expected.iter().collect();
+    let mut stack = vec![PathBuf::new()];
+    while let Some(rel) = stack.pop() {
+        let dir = join(target, &rel);
+        if !fs::symlink_metadata(&dir)?.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(&dir)? {
+            let rel = rel.join(entry?.file_name());
+            match expected.contains(&rel) {
+                true => stack.push(rel),
+                false => diffs.push(RoundtripDiff::Extra(rel)),
+            }
+        }
+    }
+    Ok((expected.len(), diffs))
+}
+
+/// join the relative path, the empty `rel` is the `base` itself which may be a file.
+fn join(base: &Path, rel: &Path) -> PathBuf {
+    match rel.as_os_str().is_empty() {
+        true => base.to_path_buf(),
+        false => base.join(rel),
+    }
+}
+
+fn file_type(meta: &fs::Metadata) -> FileType {
+    let file_type = meta.file_type();
+    if file_type.is_symlink() {
+        FileType::Symlink
+    } else if file_type.is_dir() {
+        FileType::Directory
+    } else {
+        FileType::File
+    }
+}
+
+/// compare the contents in the chunks, the large files are not loaded.
+fn same_content(a: &Path, b: &Path) -> Result<bool, CarError> {
+    let (mut a, mut b) = (fs::File::open(a)?, fs::File::open(b)?);
+    let (mut buf_a, mut buf_b) = (vec![0u8; 64 << 10], vec![0u8; 64 << 10]);
+    loop {
+        let n = read_full(&mut a, &mut buf_a)?;
+        if n != read_full(&mut b, &mut buf_b)? || buf_a[..n] != buf_b[..n] {
+            return Ok(false);
+        }
+        if n == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+fn read_full(r: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut n = 0;
+    while n < buf.len() {
+        match r.read(&mut buf[n..])? {
+            0 => break,
+            len => n += len,
+        }
+    }
+    Ok(n)
+}
+
+/// the mode and the mtime are stored and restored on unix only, the symlinks have none.
+#[cfg(unix)]
+fn same_metadata(src: &fs::Metadata, dst: &fs::Metadata) -> Result<bool, CarError> {
+    use std::os::unix::fs::PermissionsExt;
+    if src.is_symlink() {
+        return Ok(true);
+    }
+    let mode = |m: &fs::Metadata| m.permissions().mode() & 0o7777;
+    Ok(mode(src) == mode(dst) && src.modified()? == dst.modified()?)
+}
+
+#[cfg(not(unix))]
+fn same_metadata(_src: &fs::Metadata, _dst: &fs::Metadata) -> Result<bool, CarError> {
+    Ok(true)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_verify_roundtrip() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        fs::create_dir_all(root_dir.join("a/b")).unwrap();
+        fs::create_dir_all(root_dir.join("empty")).unwrap();
+        fs::write(root_dir.join("a/b/test.txt"), b"hello world").unwrap();
+        fs::write(root_dir.join("a/big.bin"), vec![7u8; 300 << 10]).unwrap();
+        // the files with the same content share the blocks.
+        fs::write(root_dir.join("a/same.txt"), b"hello world").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("b/test.txt", root_dir.join("a/link")).unwrap();
+
+        let options = PackOptions::new()
+            .with_preserve_metadata(true)
+            .with_exclude("*.bin".parse().unwrap());
+        let report = verify_roundtrip(&root_dir, &options).unwrap();
+        assert!(report.is_ok(), "{:?}", report.diffs);
+        assert_eq!(report.entries, if cfg!(unix) { 7 } else { 6 });
+        let options = options.with_top_node(TopNode::Omit);
+        assert!(verify_roundtrip(&root_dir, &options).unwrap().is_ok());
+        let file = root_dir.join("a/b/test.txt");
+        assert!(verify_roundtrip(&file, &PackOptions::new())
+            .unwrap()
+            .is_ok());
+
+        let copy = temp_dir.path().join("copy");
+        fs::create_dir_all(copy.join("a/b")).unwrap();
+        fs::write(copy.join("a/b/test.txt"), b"hello earth").unwrap();
+        fs::write(copy.join("extra"), b"").unwrap();
+        let (_, diffs) = diff_trees(&root_dir, &copy, &PackOptions::new()).unwrap();
+        assert!(diffs.contains(&RoundtripDiff::Content("a/b/test.txt".into())));
+        assert!(diffs.contains(&RoundtripDiff::Missing("empty".into())));
+        assert!(diffs.contains(&RoundtripDiff::Extra("extra".into())));
+    }
+}
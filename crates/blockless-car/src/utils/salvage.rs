@@ -0,0 +1,293 @@
+//! salvage the valid sections of a truncated or corrupt CAR, e.g. the transfer died
+//! mid-stream. the scan resyncs on the next valid section after the damaged bytes.
+use std::{
+    collections::HashSet,
+    io::{Read, Seek, SeekFrom, Write},
+};
+
+use cid::{multihash::Code, Cid};
+use integer_encoding::VarInt;
+
+use crate::{
+    error::CarError,
+    header::CarHeader,
+    reader::ReaderLimits,
+    section::MAX_ALLOWED_SECTION_SIZE,
+    utils::{
+        inspect::{read_full, read_length, CARV2_PREFIX_LEN, MAX_CID_LEN, MAX_VARINT_LEN},
+        probe::CARV2_HEADER_SIZE,
+        verify_block, CarV2Header, CARV2_PRAGMA, IDENTITY_CODE,
+    },
+    writer::{CarWriter, StreamCarWriterV1},
+};
+
+/// the bytes read at once while looking for the next section.
+const RESYNC_WINDOW: usize = 64 << 10;
+
+/// the damaged bytes skipped by the salvage.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedRange {
+    /// the offset of the first damaged section in the file.
+    pub offset: u64,
+    pub len: u64,
+    /// why the first section of the range is not valid.
+    pub reason: String,
+}
+
+/// the report of `salvage_car`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SalvageReport {
+    pub roots: Vec<Cid>,
+    /// the count of the blocks written, each cid once.
+    pub blocks: usize,
+    pub skipped: Vec<SkippedRange>,
+}
+
+/// the section at the offset with the offset after it, or why it is not valid with the
+/// offset after it if the length can be trusted.
+type SectionAt = Result<(Cid, Vec<u8>, u64), (String, Option<u64>)>;
+
+/// read the section at the `offset`, with `verified` the block of the hash which can't be
+/// verified is not valid either.
+fn read_section_at<R>(
+    r: &mut R,
+    offset: u64,
+    end: u64,
+    verified: bool,
+) -> Result<SectionAt, CarError>
+where
+    R: Read + Seek,
+{
+    let (len, varint_len) = match read_length(r, offset, end)? {
+        Ok(l) => l,
+        Err(reason) => return Ok(Err((reason, None))),
+    };
+    let next = offset + varint_len + len;
+    if len == 0 {
+        return Ok(Err(("the section is empty".into(), Some(next))));
+    }
+    if len > MAX_ALLOWED_SECTION_SIZE as u64 {
+        return Ok(Err((
+            format!("the section of {len} bytes is too large"),
+            None,
+        )));
+    }
+    if next > end {
+        let reason = format!("the section of {len} bytes is cut off at the end {end}");
+        return Ok(Err((reason, None)));
+    }
+    let mut section = vec![0u8; len as usize];
+    r.read_exact(&mut section)?;
+    let mut data = &section[..];
+    let cid = match Cid::read_bytes(&mut data) {
+        Ok(cid) => cid,
+        Err(e) => return Ok(Err((format!("invalid cid: {e}"), Some(next)))),
+    };
+    match verify_block(&cid, data) {
+        Ok(true) => Ok(Ok((cid, data.to_vec(), next))),
+        Err(_) if !verified => Ok(Ok((cid, data.to_vec(), next))),
+        Ok(false) => Ok(Err((format!("the data doesn't match {cid}"), Some(next)))),
+        Err(e) => Ok(Err((e.to_string(), Some(next)))),
+    }
+}
+
+/// the bytes look like the start of the section of a verifiable block.
+fn is_section_start(buf: &[u8]) -> bool {
+    let Some((len, varint_len)) = u64::decode_var(buf) else {
+        return false;
+    };
+    if len == 0 || len > MAX_ALLOWED_SECTION_SIZE as u64 {
+        return false;
+    }
+    let mut rest = &buf[varint_len..];
+    let Ok(cid) = Cid::read_bytes(&mut rest) else {
+        return false;
+    };
+    let cid_len = buf.len() - varint_len - rest.len();
+    let code = cid.hash().code();
+    cid_len as u64 <= len && (code == IDENTITY_CODE || Code::try_from(code).is_ok())
+}
+
+/// the offset of the next bytes from the `from` which look like a section start, or the `end`.
+fn next_section_start<R>(r: &mut R, from: u64, end: u64) -> Result<u64, CarError>
+where
+    R: Read + Seek,
+{
+    let overlap = MAX_VARINT_LEN + MAX_CID_LEN;
+    let mut buf = vec![0u8; RESYNC_WINDOW];
+    let mut pos = from;
+    while pos < end {
+        r.seek(SeekFrom::Start(pos))?;
+        let n = read_full(
+            r,
+            &mut buf[..(end - pos).min(RESYNC_WINDOW as u64) as usize],
+        )?;
+        if n == 0 {
+            break;
+        }
+        // the offsets with the whole prefix in the window, or all of them in the last one.
+        let scan = match pos + n as u64 >= end {
+            true => n,
+            false => n - overlap,
+        };
+        if let Some(i) = (0..scan).find(|i| is_section_start(&buf[*i..n])) {
+            return Ok(pos + i as u64);
+        }
+        pos += scan as u64;
+    }
+    Ok(end)
+}
+
+/// write the valid sections of the CARv1 or CARv2 file to the CARv1 of `to` with the roots
+/// of the header, the damaged bytes are skipped until the next section of a verified block.
+/// the header must be readable for the roots.
+pub fn salvage_car<R, W>(r: &mut R, to: W) -> Result<SalvageReport, CarError>
+where
+    R: Read + Seek,
+    W: Write,
+{
+    let file_len = r.seek(SeekFrom::End(0))?;
+    r.rewind()?;
+    let mut pragma = [0u8; CARV2_PRAGMA.len()];
+    let n = read_full(r, &mut pragma)?;
+    let (start, end) = if n == CARV2_PRAGMA.len() && pragma == CARV2_PRAGMA {
+        let mut header = [0u8; CARV2_HEADER_SIZE];
+        if read_full(r, &mut header)? < header.len() {
+            return Err(CarError::InvalidFile(
+                "the CARv2 header is truncated".into(),
+            ));
+        }
+        let v2 = CarV2Header::decode(&header);
+        if v2.data_offset < CARV2_PREFIX_LEN || v2.data_offset >= file_len {
+            return Err(CarError::InvalidFile(format!(
+                "the data payload at {} is out of the file",
+                v2.data_offset
+            )));
+        }
+        let end = match v2.data_size {
+            0 => file_len,
+            size => v2.data_offset.saturating_add(size).min(file_len),
+        };
+        (v2.data_offset, end)
+    } else {
+        (0, file_len)
+    };
+
+    let (len, varint_len) = read_length(r, start, end)?
+        .map_err(|reason| CarError::InvalidFile(format!("the header: {reason}")))?;
+    if len > ReaderLimits::default().max_header_size as u64 || start + varint_len + len > end {
+        return Err(CarError::InvalidFile(format!(
+            "the header of {len} bytes is too large or cut off"
+        )));
+    }
+    let mut header = vec![0u8; len as usize];
+    r.read_exact(&mut header)?;
+    let header = CarHeader::decode(&header)?;
+
+    let mut report = SalvageReport {
+        roots: header.roots(),
+        ..Default::default()
+    };
+    let mut writer = StreamCarWriterV1::new(to, header);
+    let mut written = HashSet::new();
+    // the start of the damaged bytes being skipped and the reason.
+    let mut damaged: Option<(u64, String)> = None;
+    let mut offset = start + varint_len + len;
+    while offset < end {
+        match read_section_at(r, offset, end, damaged.is_some())? {
+            Ok((cid, data, next)) => {
+                if let Some((from, reason)) = damaged.take() {
+                    let len = offset - from;
+                    report.skipped.push(SkippedRange {
+                        offset: from,
+                        len,
+                        reason,
+                    });
+                }
+                if written.insert(cid) {
+                    writer.write_block(cid, &data)?;
+                    report.blocks += 1;
+                }
+                offset = next;
+            }
+            Err((reason, next)) => {
+                if damaged.is_none() {
+                    damaged = Some((offset, reason));
+                    // the block data is damaged but the length is fine.
+                    if let Some(next) = next.filter(|n| *n < end) {
+                        if read_section_at(r, next, end, true)?.is_ok() {
+                            offset = next;
+                            continue;
+                        }
+                    }
+                }
+                offset = next_section_start(r, offset + 1, end)?;
+            }
+        }
+    }
+    if let Some((from, reason)) = damaged {
+        let len = end - from;
+        report.skipped.push(SkippedRange {
+            offset: from,
+            len,
+            reason,
+        });
+    }
+    writer.flush()?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        reader::{self, CarReader},
+        utils::raw_cid,
+    };
+    use std::io::Cursor;
+
+    #[test]
+    fn test_salvage_car() {
+        let datas: Vec<Vec<u8>> = (0..5u8).map(|i| vec![i; 100]).collect();
+        let cids: Vec<Cid> = datas
+            .iter()
+            .map(|d| raw_cid(d, multicodec::Codec::Sha2_256))
+            .collect();
+        let mut car = vec![];
+        {
+            let mut writer = StreamCarWriterV1::new(&mut car, CarHeader::new_v1(vec![cids[0]]));
+            for (cid, data) in cids.iter().zip(datas.iter()) {
+                writer.write_block(*cid, data).unwrap();
+            }
+            writer.flush().unwrap();
+        }
+        let mut reader = reader::new_v1(Cursor::new(car.clone())).unwrap();
+        let offsets: Vec<u64> = reader.sections().unwrap().iter().map(|s| s.pos()).collect();
+        let ok = salvage_car(&mut Cursor::new(&car), vec![]).unwrap();
+        assert_eq!((ok.blocks, ok.skipped.len()), (5, 0));
+        assert_eq!(ok.roots, vec![cids[0]]);
+
+        // the data of the block 1 is flipped, the length varint of the block 3 is broken
+        // and the file is cut off in the block 4, the last two are one damaged range.
+        let mut broken = car.clone();
+        broken[offsets[1] as usize + 10] ^= 0xff;
+        let section_3 = offsets[3] as usize - cids[3].to_bytes().len() - 2;
+        broken[section_3 + 1] = 0xff;
+        broken.truncate(offsets[4] as usize + 50);
+        let mut out = vec![];
+        let report = salvage_car(&mut Cursor::new(&broken), &mut out).unwrap();
+        assert_eq!(report.blocks, 2);
+        assert_eq!(report.skipped.len(), 2);
+        assert!(report.skipped[0].reason.contains("doesn't match"));
+        assert!(report.skipped[1].reason.contains("cut off"));
+        let last = &report.skipped[1];
+        assert_eq!(last.offset, section_3 as u64);
+        assert_eq!(last.offset + last.len, broken.len() as u64);
+        let mut recovered = reader::new_v1(Cursor::new(out)).unwrap();
+        assert_eq!(recovered.get_block(&cids[2]).unwrap().unwrap(), datas[2]);
+        assert!(recovered.get_block(&cids[1]).unwrap().is_none());
+
+        // the header is needed for the roots.
+        assert!(salvage_car(&mut Cursor::new(&car[..10]), vec![]).is_err());
+    }
+}
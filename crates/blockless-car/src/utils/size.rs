@@ -0,0 +1,295 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    str::FromStr,
+};
+
+use cid::Cid;
+use ipld::raw::RawCodec;
+
+use crate::{
+    error::CarError,
+    reader::CarReader,
+    store::BlockStore,
+    unixfs::{FileType, UnixFs},
+    utils::find_entry,
+};
+
+/// the default limit of the symlinks followed by a path resolution, like `MAXSYMLINKS` of linux.
+pub const DEFAULT_MAX_SYMLINK_HOPS: usize = 40;
+
+/// the options of `resolve_path_with`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResolveOptions {
+    /// the maximum count of the symlinks followed by a resolution.
+    pub max_symlink_hops: usize,
+    /// follow the symlink at the end of the path too, by default the symlink entry is resolved.
+    pub follow_last_symlink: bool,
+}
+
+impl Default for ResolveOptions {
+    fn default() -> Self {
+        Self {
+            max_symlink_hops: DEFAULT_MAX_SYMLINK_HOPS,
+            follow_last_symlink: false,
+        }
+    }
+}
+
+impl ResolveOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_symlink_hops(mut self, max_symlink_hops: usize) -> Self {
+        self.max_symlink_hops = max_symlink_hops;
+        self
+    }
+
+    pub fn with_follow_last_symlink(mut self, follow_last_symlink: bool) -> Self {
+        self.follow_last_symlink = follow_last_symlink;
+        self
+    }
+}
+
+/// resolve the `path` to the cid of the entry, the path starts with the cid like
+/// the `ls` output or `/ipfs/<cid>` like kubo, or it's relative to the first root.
+/// the symlinks in the path are followed with the default `ResolveOptions`.
+pub fn resolve_path(reader: &mut impl CarReader, path: &str) -> Result<Cid, CarError> {
+    resolve_path_with(reader, path, &ResolveOptions::default())
+}
+
+/// resolve the `path` like `resolve_path` with the `options`. the symlink targets are
+/// relative to the directory of the symlink, the absolute targets to the start of the path,
+/// and `..` stops at the start. the resolution fails with `SymlinkHops` after
+/// the `max_symlink_hops` symlinks, and with `SymlinkCycle` if a symlink is met again
+/// in the same directory with the same rest of the path.
+pub fn resolve_path_with(
+    reader: &mut impl CarReader,
+    path: &str,
+    options: &ResolveOptions,
+) -> Result<Cid, CarError> {
+    let root = reader.header().roots().first().copied();
+    resolve_path_from(reader, root, path, options)
+}
+
+/// resolve the `path` in the `store` like `resolve_path_with`, the path not starting
+/// with the cid is relative to the `root`.
+pub fn resolve_path_from(
+    reader: &mut impl BlockStore,
+    root: Option<Cid>,
+    path: &str,
+    options: &ResolveOptions,
+) -> Result<Cid, CarError> {
+    let mut names: VecDeque<String> = path
+        .split('/')
+        .filter(|n| !n.is_empty())
+        .map(String::from)
+        .collect();
+    // the `ipfs` namespace is stripped only before a cid, it may be a name of the root.
+    if names.front().is_some_and(|n| n == "ipfs")
+        && names.get(1).is_some_and(|n| Cid::from_str(n).is_ok())
+    {
+        names.pop_front();
+    }
+    let start = match names.front().map(|n| Cid::from_str(n)) {
+        Some(Ok(cid)) => {
+            names.pop_front();
+            cid
+        }
+        _ => root.ok_or_else(|| CarError::NotFound("the car has no root".into()))?,
+    };
+    let raw_code: u64 = RawCodec.into();
+    let mut cid = start;
+    // the directories from the start to the current one, the last is the parent.
+    let mut dirs: Vec<Cid> = Vec::new();
+    // the node of the current cid if decoded already.
+    let mut node: Option<UnixFs> = None;
+    let mut hops = 0;
+    let mut seen = HashSet::new();
+    while let Some(name) = names.pop_front() {
+        match name.as_str() {
+            "." => continue,
+            ".." => {
+                if let Some(dir) = dirs.pop() {
+                    cid = dir;
+                    node = None;
+                }
+                continue;
+            }
+            _ => {}
+        }
+        let (link, dir) = if cid.codec() == raw_code {
+            (None, None)
+        } else {
+            let traversal = |e: CarError| e.traversal(path, &cid, dirs.last());
+            let dir = match node.take() {
+                Some(dir) => dir,
+                None => unixfs(reader, &cid).map_err(traversal)?,
+            };
+            (
+                find_entry(reader, &dir, &name).map_err(traversal)?,
+                Some(dir),
+            )
+        };
+        let child = link
+            .ok_or_else(|| CarError::NotFound(format!("{name} of the path {path}")))?
+            .hash;
+        let follow = !names.is_empty() || options.follow_last_symlink;
+        if follow && child.codec() != raw_code {
+            let child_node =
+                unixfs(reader, &child).map_err(|e| e.traversal(path, &child, Some(&cid)))?;
+            if child_node.file_type() == FileType::Symlink {
+                hops += 1;
+                if hops > options.max_symlink_hops {
+                    return Err(CarError::SymlinkHops {
+                        path: path.into(),
+                        limit: options.max_symlink_hops,
+                    });
+                }
+                if !seen.insert((cid, child, names.clone())) {
+                    return Err(CarError::SymlinkCycle {
+                        path: path.into(),
+                        cid: child.to_string(),
+                    });
+                }
+                let target = child_node.symlink_target().ok_or_else(|| {
+                    CarError::InvalidSection(format!("the symlink {child} has no utf8 target"))
+                })?;
+                if target.starts_with('/') {
+                    cid = start;
+                    dirs.clear();
+                    node = None;
+                } else {
+                    node = dir;
+                }
+                for name in target.split('/').rev().filter(|n| !n.is_empty()) {
+                    names.push_front(name.into());
+                }
+                continue;
+            }
+            node = Some(child_node);
+        }
+        dirs.push(cid);
+        cid = child;
+    }
+    Ok(cid)
+}
+
+/// the unixfs node of the block, the hash is verified.
+fn unixfs(reader: &mut impl BlockStore, cid: &Cid) -> Result<UnixFs, CarError> {
+    (*cid, reader.get_ipld(cid)?).try_into()
+}
+
+/// the size of the file content, the content blocks are not read. the `filesize` of the
+/// unixfs node is used, the node without it sums the `blocksizes` and the inline data.
+pub fn file_size(reader: &mut impl BlockStore, cid: &Cid) -> Result<u64, CarError> {
+    let raw_code: u64 = RawCodec.into();
+    if cid.codec() == raw_code {
+        return match reader.get_len(cid)? {
+            Some(len) => Ok(len as u64),
+            None => Err(CarError::NotFound(format!("the block {cid}"))),
+        };
+    }
+    let node = unixfs(reader, cid)?;
+    match node.file_type() {
+        FileType::File | FileType::Raw => {}
+        _ => return Err(CarError::NotAFile(cid.to_string())),
+    }
+    Ok(node.file_size().unwrap_or_else(|| {
+        let inline = node.data().map_or(0, |d| d.len() as u64);
+        node.block_sizes().iter().sum::<u64>() + inline
+    }))
+}
+
+/// the size of the file at the `path`, see `resolve_path`.
+pub fn file_size_of_path(reader: &mut impl CarReader, path: &str) -> Result<u64, CarError> {
+    let cid = resolve_path(reader, path)?;
+    file_size(reader, &cid)
+}
+
+#[cfg(all(test, feature = "unixfs-fs"))]
+mod test {
+    use super::*;
+    use crate::{
+        reader,
+        utils::{pack_files_with, PackOptions},
+    };
+    use std::{fs, io::Cursor};
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_file_size() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        fs::create_dir_all(root_dir.join("sub")).unwrap();
+        fs::write(root_dir.join("small.txt"), b"hello world").unwrap();
+        fs::write(root_dir.join("sub/big.bin"), vec![7u8; 300_000]).unwrap();
+        for raw_leaves in [true, false] {
+            let options = PackOptions::new()
+                .with_chunk_size(100_000)
+                .with_raw_leaves(raw_leaves);
+            let mut car = Cursor::new(vec![]);
+            let root = pack_files_with(&root_dir, &mut car, &options).unwrap();
+            let mut reader = reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+            assert_eq!(
+                file_size_of_path(&mut reader, "root/small.txt").unwrap(),
+                11
+            );
+            let big = format!("{root}/root/sub/big.bin");
+            assert_eq!(file_size_of_path(&mut reader, &big).unwrap(), 300_000);
+            let big = format!("/ipfs/{root}/root/sub/big.bin");
+            assert_eq!(file_size_of_path(&mut reader, &big).unwrap(), 300_000);
+            assert!(matches!(
+                file_size_of_path(&mut reader, "root/sub"),
+                Err(CarError::NotAFile(_))
+            ));
+            assert!(matches!(
+                file_size_of_path(&mut reader, "root/missing"),
+                Err(CarError::NotFound(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_resolve_symlinks() {
+        use std::os::unix::fs::symlink;
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        fs::create_dir_all(root_dir.join("sub")).unwrap();
+        fs::write(root_dir.join("sub/file.txt"), b"hello world").unwrap();
+        symlink("sub", root_dir.join("link")).unwrap();
+        symlink("../root/sub", root_dir.join("up")).unwrap();
+        symlink("/root/sub/file.txt", root_dir.join("abs")).unwrap();
+        symlink("loop", root_dir.join("loop")).unwrap();
+        symlink("link", root_dir.join("hop2")).unwrap();
+        symlink("hop2", root_dir.join("hop3")).unwrap();
+        let mut car = Cursor::new(vec![]);
+        pack_files_with(&root_dir, &mut car, &PackOptions::new()).unwrap();
+        let mut reader = reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+        let file = resolve_path(&mut reader, "root/sub/file.txt").unwrap();
+        for path in [
+            "root/link/file.txt",
+            "root/up/file.txt",
+            "root/hop3/file.txt",
+        ] {
+            assert_eq!(resolve_path(&mut reader, path).unwrap(), file);
+        }
+        // the last symlink is resolved to the symlink entry by default.
+        assert_ne!(resolve_path(&mut reader, "root/abs").unwrap(), file);
+        let follow = ResolveOptions::new().with_follow_last_symlink(true);
+        assert_eq!(
+            resolve_path_with(&mut reader, "root/abs", &follow).unwrap(),
+            file
+        );
+        assert!(matches!(
+            resolve_path(&mut reader, "root/loop/x"),
+            Err(CarError::SymlinkCycle { .. })
+        ));
+        let options = ResolveOptions::new().with_max_symlink_hops(2);
+        assert!(matches!(
+            resolve_path_with(&mut reader, "root/hop3/file.txt", &options),
+            Err(CarError::SymlinkHops { limit: 2, .. })
+        ));
+        assert!(resolve_path_with(&mut reader, "root/hop2/file.txt", &options).is_ok());
+    }
+}
@@ -0,0 +1,140 @@
+//! split the CAR into the shards bounded in size, e.g. by the sector size of the deals.
+use std::{collections::HashSet, io::Write};
+
+use integer_encoding::VarInt;
+
+use crate::{
+    error::CarError,
+    reader::CarReader,
+    writer::{CarWriter, StreamCarWriterV1},
+};
+
+/// the shard written by `split_car`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shard {
+    pub blocks: usize,
+    /// the bytes of the shard file, the header and the sections.
+    pub bytes: u64,
+}
+
+/// split the blocks of the car in the order into the CARv1 shards of at most `max_size`
+/// bytes, each shard carries the roots of the car in its header so it's a valid CAR on its
+/// own, and the shards together have every block once. the shard `i` is written to the
+/// output of `open(i)`, at least one shard is written.
+pub fn split_car<W, F>(
+    reader: &mut impl CarReader,
+    max_size: u64,
+    mut open: F,
+) -> Result<Vec<Shard>, CarError>
+where
+    W: Write,
+    F: FnMut(usize) -> Result<W, CarError>,
+{
+    let header = reader.header().clone();
+    let encoded = header.encode()?;
+    let header_size = (encoded.len().required_space() + encoded.len()) as u64;
+    let mut shards = Vec::new();
+    let mut writer: Option<StreamCarWriterV1<W>> = None;
+    let mut shard = Shard {
+        blocks: 0,
+        bytes: header_size,
+    };
+    let mut written = HashSet::new();
+    for block in reader.blocks() {
+        let (cid, data) = block?;
+        if !written.insert(cid) {
+            continue;
+        }
+        let len = cid.encoded_len() + data.len();
+        let section = (len.required_space() + len) as u64;
+        if header_size + section > max_size {
+            return Err(CarError::InvalidSection(format!(
+                "the section of {cid} of {section} bytes can't fit in the shard of {max_size} bytes"
+            )));
+        }
+        if shard.bytes + section > max_size {
+            if let Some(mut w) = writer.take() {
+                w.flush()?;
+                shards.push(shard);
+            }
+        }
+        let w = match writer.as_mut() {
+            Some(w) => w,
+            None => {
+                shard = Shard {
+                    blocks: 0,
+                    bytes: header_size,
+                };
+                writer.insert(StreamCarWriterV1::new(open(shards.len())?, header.clone()))
+            }
+        };
+        w.write_block(cid, &data)?;
+        shard.blocks += 1;
+        shard.bytes += section;
+    }
+    match writer {
+        Some(mut w) => w.flush()?,
+        // the car without the blocks is still written as one shard.
+        None => StreamCarWriterV1::new(open(0)?, header).flush()?,
+    }
+    shards.push(shard);
+    Ok(shards)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{header::CarHeader, reader, utils::raw_cid};
+    use cid::Cid;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_split_car() {
+        let datas: Vec<Vec<u8>> = (0..10u8).map(|i| vec![i; 100]).collect();
+        let cids: Vec<Cid> = datas
+            .iter()
+            .map(|d| raw_cid(d, multicodec::Codec::Sha2_256))
+            .collect();
+        let mut car = vec![];
+        let mut writer = StreamCarWriterV1::new(&mut car, CarHeader::new_v1(vec![cids[0]]));
+        for (cid, data) in cids.iter().zip(datas.iter()) {
+            writer.write_block(*cid, data).unwrap();
+        }
+        writer.flush().unwrap();
+        drop(writer);
+        let mut reader = reader::new_v1(Cursor::new(&car)).unwrap();
+
+        // the header of 59 bytes and two sections of 138 bytes per shard.
+        let mut outputs = vec![vec![]; 5];
+        let shards = {
+            let mut iter = outputs.iter_mut().enumerate();
+            split_car(&mut reader, 360, |i| {
+                let (n, output) = iter.next().unwrap();
+                assert_eq!(i, n);
+                Ok(output)
+            })
+            .unwrap()
+        };
+        assert_eq!(shards.len(), 5);
+        let mut split_cids = Vec::new();
+        for (shard, output) in shards.iter().zip(outputs.iter()) {
+            assert!(shard.bytes <= 360);
+            assert_eq!(shard.bytes, output.len() as u64);
+            let mut shard_reader = reader::new_v1(Cursor::new(output)).unwrap();
+            assert_eq!(shard_reader.header().roots(), vec![cids[0]]);
+            let blocks: Vec<Cid> = shard_reader.blocks().map(|b| b.unwrap().0).collect();
+            assert_eq!(blocks.len(), shard.blocks);
+            split_cids.extend(blocks);
+        }
+        assert_eq!(split_cids, cids);
+
+        // the whole car in one shard, and the shard too small for a section.
+        let mut output = vec![];
+        let mut once = Some(&mut output);
+        let shards =
+            split_car(&mut reader, car.len() as u64, |_| Ok(once.take().unwrap())).unwrap();
+        assert_eq!(shards.len(), 1);
+        assert_eq!(output, car);
+        assert!(split_car(&mut reader, 150, |_| Ok(vec![])).is_err());
+    }
+}
@@ -0,0 +1,49 @@
+//! the temp directories of the large operations, like the spill of the stream pack.
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::error::CarError;
+
+/// the temp directory removed on drop.
+pub(crate) struct TempDir(PathBuf);
+
+impl TempDir {
+    /// create the temp directory named by the `prefix` in the `parent`, the OS temp
+    /// directory if none. the name has the pid, the time and a counter, and the directory
+    /// is created exclusively, so the concurrent operations never share one.
+    pub(crate) fn new_in(parent: Option<&Path>, prefix: &str) -> Result<Self, CarError> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let parent = parent.map_or_else(std::env::temp_dir, Path::to_path_buf);
+        loop {
+            let nanos = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_nanos());
+            let name = format!(
+                "{prefix}-{}-{nanos}-{}",
+                std::process::id(),
+                COUNTER.fetch_add(1, Ordering::Relaxed)
+            );
+            let path = parent.join(name);
+            match fs::create_dir(&path) {
+                Ok(()) => return Ok(Self(path)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
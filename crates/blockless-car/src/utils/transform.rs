@@ -0,0 +1,340 @@
+//! the block transformation of the CAR to CAR rewrites, e.g. the migrations of the blocks
+//! without forking the rewrites. the parents of a changed block get their links fixed up.
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    io::Write,
+};
+
+use cid::{
+    multihash::{Code, MultihashDigest},
+    Cid, Version,
+};
+use ipld::{pb::DagPbCodec, raw::RawCodec};
+
+use crate::{
+    codec::encode_ipld,
+    error::CarError,
+    header::CarHeader,
+    reader::CarReader,
+    utils::complete::collect_links,
+    writer::{CarWriter, StreamCarWriterV1},
+    Ipld,
+};
+
+/// the decision of the `BlockTransform` on a block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockAction {
+    /// write the block as it is, or with the links fixed up.
+    Keep,
+    /// write the data instead, the cid is hashed again with the codec and the hash code.
+    Replace(Vec<u8>),
+    /// leave the block out, the links to it are kept.
+    Skip,
+    /// abort the rewrite with `CarError::Rejected` and the reason.
+    Reject(String),
+}
+
+/// the hook of the blocks of a rewrite, called once per block with the links already fixed
+/// up, the children before their parents. the closures `FnMut(&Cid, &[u8]) -> BlockAction`
+/// are transforms.
+pub trait BlockTransform {
+    /// decide the block of the original `cid` and the `data` to write.
+    fn transform(&mut self, cid: &Cid, data: &[u8]) -> BlockAction;
+}
+
+impl<F> BlockTransform for F
+where
+    F: FnMut(&Cid, &[u8]) -> BlockAction,
+{
+    fn transform(&mut self, cid: &Cid, data: &[u8]) -> BlockAction {
+        self(cid, data)
+    }
+}
+
+impl fmt::Debug for dyn BlockTransform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("BlockTransform")
+    }
+}
+
+/// the transform keeping every block, the rewrites without a transform.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KeepBlocks;
+
+impl BlockTransform for KeepBlocks {
+    fn transform(&mut self, _: &Cid, _: &[u8]) -> BlockAction {
+        BlockAction::Keep
+    }
+}
+
+/// the report of `transform_car`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TransformReport {
+    pub roots: Vec<Cid>,
+    /// the count of the blocks written.
+    pub blocks: usize,
+    /// the blocks written with a new cid, by the transform or the links fixed up.
+    pub rewritten: usize,
+    pub skipped: usize,
+}
+
+/// rewrite the blocks of the CAR by the `transform` to the CARv1 of `to`, in the file order.
+/// the changed cids are replaced in the links of the parents and the roots, the skipped roots
+/// are left out. the `Tsize` of the fixed up dag-pb links is kept.
+pub fn transform_car<W>(
+    reader: &mut impl CarReader,
+    transform: &mut impl BlockTransform,
+    to: W,
+) -> Result<TransformReport, CarError>
+where
+    W: Write,
+{
+    let mut rewrite = Rewrite::default();
+    rewrite.plan(reader, None, transform)?;
+    let roots = rewrite.roots(reader.header().roots());
+    let mut writer = StreamCarWriterV1::new(to, CarHeader::new_v1(roots.clone()));
+    let (blocks, _) = rewrite.write(reader, None, &mut writer, &mut HashSet::new())?;
+    writer.flush()?;
+    Ok(TransformReport {
+        roots,
+        blocks,
+        rewritten: rewrite.rewritten(),
+        skipped: rewrite.skipped(),
+    })
+}
+
+/// the new cids and the data of the blocks changed by a rewrite, none for the skipped.
+#[derive(Debug, Default)]
+pub(crate) struct Rewrite {
+    changed: HashMap<Cid, Option<(Cid, Vec<u8>)>>,
+    visited: HashSet<Cid>,
+}
+
+impl Rewrite {
+    /// transform the blocks of the CAR, or only of the `keep`, in the post order of the DAGs.
+    pub(crate) fn plan(
+        &mut self,
+        reader: &mut impl CarReader,
+        keep: Option<&HashSet<Cid>>,
+        transform: &mut impl BlockTransform,
+    ) -> Result<(), CarError> {
+        let raw_code: u64 = RawCodec.into();
+        let limits = reader.limits();
+        let cids: Vec<Cid> = reader.sections()?.iter().map(|s| s.cid()).collect();
+        for cid in cids {
+            if keep.is_some_and(|k| !k.contains(&cid)) || !self.visited.insert(cid) {
+                continue;
+            }
+            // the blocks with their links once expanded.
+            let mut stack: Vec<(Cid, Option<Vec<Cid>>)> = vec![(cid, None)];
+            while let Some((cid, links)) = stack.pop() {
+                if let Some(links) = links {
+                    self.finish(reader, cid, &links, transform)?;
+                    continue;
+                }
+                let mut links = Vec::new();
+                if cid.codec() != raw_code {
+                    collect_links(&reader.ipld(&cid)?, &mut links);
+                }
+                let children: Vec<Cid> = links
+                    .iter()
+                    .filter(|l| keep.is_none_or(|k| k.contains(l)))
+                    .copied()
+                    .collect();
+                stack.push((cid, Some(links)));
+                for child in children {
+                    if reader.block_len(&child)?.is_some() && self.visited.insert(child) {
+                        stack.push((child, None));
+                    }
+                }
+                limits.check_blocks(stack.len())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(
+        &mut self,
+        reader: &mut impl CarReader,
+        cid: Cid,
+        links: &[Cid],
+        transform: &mut impl BlockTransform,
+    ) -> Result<(), CarError> {
+        let fixed = links
+            .iter()
+            .any(|l| matches!(self.changed.get(l), Some(Some(_))));
+        let data = match fixed {
+            true => {
+                let mut ipld = reader.ipld(&cid)?;
+                self.fix_links(&mut ipld);
+                encode_ipld(cid.codec(), &ipld)?
+            }
+            false => reader.read_section_data(&cid)?,
+        };
+        let data = match transform.transform(&cid, &data) {
+            BlockAction::Keep if !fixed => return Ok(()),
+            BlockAction::Keep => data,
+            BlockAction::Replace(data) => data,
+            BlockAction::Skip => {
+                self.changed.insert(cid, None);
+                return Ok(());
+            }
+            BlockAction::Reject(reason) => {
+                return Err(CarError::Rejected(format!("the block {cid}: {reason}")))
+            }
+        };
+        let new_cid = rehash(&cid, &data)?;
+        if new_cid != cid {
+            self.changed.insert(cid, Some((new_cid, data)));
+        }
+        Ok(())
+    }
+
+    fn fix_links(&self, ipld: &mut Ipld) {
+        match ipld {
+            Ipld::Link(cid) => {
+                if let Some(Some((new_cid, _))) = self.changed.get(cid) {
+                    *cid = *new_cid;
+                }
+            }
+            Ipld::List(list) => list.iter_mut().for_each(|i| self.fix_links(i)),
+            Ipld::Map(map) => map.values_mut().for_each(|i| self.fix_links(i)),
+            _ => {}
+        }
+    }
+
+    /// the cid written for the `cid`, none if skipped.
+    pub(crate) fn cid(&self, cid: &Cid) -> Option<Cid> {
+        match self.changed.get(cid) {
+            Some(Some((new_cid, _))) => Some(*new_cid),
+            Some(None) => None,
+            None => Some(*cid),
+        }
+    }
+
+    pub(crate) fn roots(&self, roots: Vec<Cid>) -> Vec<Cid> {
+        let mut seen = HashSet::new();
+        roots
+            .iter()
+            .filter_map(|r| self.cid(r))
+            .filter(|r| seen.insert(*r))
+            .collect()
+    }
+
+    pub(crate) fn rewritten(&self) -> usize {
+        self.changed.values().filter(|c| c.is_some()).count()
+    }
+
+    pub(crate) fn skipped(&self) -> usize {
+        self.changed.values().filter(|c| c.is_none()).count()
+    }
+
+    /// write the blocks of the CAR, or only of the `keep`, in the file order, the cids in
+    /// the `written` are skipped. return the counts of the blocks written and skipped for
+    /// the cid written.
+    pub(crate) fn write<W>(
+        &self,
+        reader: &mut impl CarReader,
+        keep: Option<&HashSet<Cid>>,
+        writer: &mut StreamCarWriterV1<W>,
+        written: &mut HashSet<Cid>,
+    ) -> Result<(usize, usize), CarError>
+    where
+        W: Write,
+    {
+        let (mut blocks, mut duplicates) = (0, 0);
+        let cids: Vec<Cid> = reader.sections()?.iter().map(|s| s.cid()).collect();
+        for (idx, cid) in cids.iter().enumerate() {
+            if keep.is_some_and(|k| !k.contains(cid)) {
+                continue;
+            }
+            let Some(new_cid) = self.cid(cid) else {
+                continue;
+            };
+            if !written.insert(new_cid) {
+                duplicates += 1;
+                continue;
+            }
+            match self.changed.get(cid) {
+                Some(Some((new_cid, data))) => writer.write_block(*new_cid, data)?,
+                _ => match reader.block_at(idx)? {
+                    Some((cid, data)) => writer.write_block(cid, &data)?,
+                    None => continue,
+                },
+            }
+            blocks += 1;
+        }
+        Ok((blocks, duplicates))
+    }
+}
+
+/// the cid of the `data` with the codec, the hash code and the version of the `cid`.
+fn rehash(cid: &Cid, data: &[u8]) -> Result<Cid, CarError> {
+    let code = cid.hash().code();
+    let hash = Code::try_from(code)
+        .map_err(|_| CarError::NotImplemented(format!("the hash {code:#x}")))?
+        .digest(data);
+    match cid.version() {
+        Version::V0 if cid.codec() == u64::from(DagPbCodec) => {
+            Cid::new_v0(hash).map_err(|e| CarError::Parsing(e.to_string()))
+        }
+        _ => Ok(Cid::new_v1(cid.codec(), hash)),
+    }
+}
+
+#[cfg(all(test, feature = "unixfs-fs"))]
+mod test {
+    use super::*;
+    use crate::{
+        reader,
+        utils::{pack_files_with, resolve_path, PackOptions},
+    };
+    use std::{fs, io::Cursor};
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_transform_car() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        fs::create_dir_all(root_dir.join("sub")).unwrap();
+        fs::write(root_dir.join("sub/a.txt"), b"hello").unwrap();
+        fs::write(root_dir.join("b.txt"), b"secret").unwrap();
+        let mut car = Cursor::new(vec![]);
+        let root = pack_files_with(&root_dir, &mut car, &PackOptions::new()).unwrap();
+        let mut reader = reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+        let hello = resolve_path(&mut reader, "root/sub/a.txt").unwrap();
+        let secret = resolve_path(&mut reader, "root/b.txt").unwrap();
+
+        let mut out = vec![];
+        let report = transform_car(&mut reader, &mut KeepBlocks, &mut out).unwrap();
+        assert_eq!(report.roots, vec![root]);
+        assert_eq!(
+            (report.blocks, report.rewritten),
+            (reader.section_count().unwrap(), 0)
+        );
+
+        // the content of a.txt is replaced and the other is skipped, the parents up to the
+        // root are fixed up.
+        let mut out = vec![];
+        let mut transform = |cid: &Cid, _: &[u8]| match *cid {
+            c if c == hello => BlockAction::Replace(b"HELLO".to_vec()),
+            c if c == secret => BlockAction::Skip,
+            _ => BlockAction::Keep,
+        };
+        let report = transform_car(&mut reader, &mut transform, &mut out).unwrap();
+        // the file, the sub directory, the wrapping directory and the root.
+        assert_eq!((report.rewritten, report.skipped), (4, 1));
+        assert_ne!(report.roots, vec![root]);
+        let mut rewritten = reader::new_v1(Cursor::new(out)).unwrap();
+        let file = resolve_path(&mut rewritten, "root/sub/a.txt").unwrap();
+        assert_eq!(rewritten.get_block(&file).unwrap().unwrap(), b"HELLO");
+        assert!(rewritten.get_block(&secret).unwrap().is_none());
+
+        let mut reject = |_: &Cid, _: &[u8]| BlockAction::Reject("no".into());
+        assert!(matches!(
+            transform_car(&mut reader, &mut reject, vec![]),
+            Err(CarError::Rejected(_))
+        ));
+    }
+}
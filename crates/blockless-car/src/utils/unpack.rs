@@ -1,4 +1,4 @@
-use std::collections::{HashMap, VecDeque};
+use std::collections::VecDeque;
 use std::fs;
 use std::io::Write;
 use std::path::Path;
@@ -8,147 +8,998 @@ use cid::Cid;
 
 use crate::error::CarError;
 use crate::unixfs::{FileType, UnixFs};
-use crate::{reader::CarReader, Ipld};
+use crate::utils::{dir_entries, ProgressCounter};
+use crate::{store::BlockStore, Ipld};
+
+mod memory;
+mod meta;
+mod options;
+mod secure;
+mod symlink;
+pub use memory::*;
+pub use options::*;
+use symlink::{extract_symlinks, symlink_target};
 
 /// extract files to current path from CAR file.
 /// `cid` is the root cid
-pub fn extract_ipld_to_current_path(reader: &mut impl CarReader, cid: Cid) -> Result<(), CarError> {
-    extract_ipld(reader, cid, None::<PathBuf>)
+pub fn extract_ipld_to_current_path(
+    reader: &mut impl BlockStore,
+    cid: Cid,
+) -> Result<(), CarError> {
+    extract_ipld_with(reader, cid, None::<PathBuf>, &ExtractOptions::default())
 }
 
 /// extract files from CAR file.
 /// if the `parent` path is none, will use current path as root path.
 /// `cid` is the root cid
+#[deprecated(note = "use `unpack` with the `ExtractOptions`")]
 pub fn extract_ipld(
-    reader: &mut impl CarReader,
+    reader: &mut impl BlockStore,
     cid: Cid,
     parent: Option<impl AsRef<Path>>,
 ) -> Result<(), CarError> {
-    let parent = parent.map(|p| p.as_ref().into());
-    extract_ipld_inner(reader, cid, parent)
+    extract_ipld_with(reader, cid, parent, &ExtractOptions::default())
 }
 
-struct UnixfsCache {
-    inner: UnixFs,
-    path: PathBuf,
+/// extract files from CAR file with the options.
+/// the blocks are read from the `reader`, the CAR reader or any other `BlockStore`.
+/// if the `parent` path is none, will use current path as root path.
+/// `cid` is the root cid
+/// `options` is the extract options.
+pub fn extract_ipld_with(
+    reader: &mut impl BlockStore,
+    cid: Cid,
+    parent: Option<impl AsRef<Path>>,
+    options: &ExtractOptions,
+) -> Result<(), CarError> {
+    let parent = parent.map(|p| p.as_ref().into());
+    extract_ipld_inner(reader, cid, parent, options)
 }
 
-struct IndexRelation {
-    parent_cid: Cid,
-    index: usize,
+/// extract the subtree of the `cid` to the `target`, the `cid` can be any block of the
+/// CAR rather than a root. the directory `cid` is extracted as the `target` directory,
+/// the file `cid` is written to the `target` file.
+pub fn extract_ipld_subtree(
+    reader: &mut impl BlockStore,
+    cid: Cid,
+    target: impl AsRef<Path>,
+) -> Result<(), CarError> {
+    extract_ipld_subtree_with(reader, cid, target, &ExtractOptions::default())
 }
 
-impl IndexRelation {
-    fn full_path(
-        rel: Option<&IndexRelation>,
-        cache: &HashMap<Cid, UnixfsCache>,
-    ) -> Option<PathBuf> {
-        let filename = rel.and_then(|r| {
-            cache
-                .get(&r.parent_cid)
-                .map(|f| &f.inner.links[r.index].name)
-        });
-        let parent_path = rel.and_then(|r| cache.get(&r.parent_cid).map(|p| &p.path));
-        parent_path.zip(filename).map(|(p, n)| {
-            let path: PathBuf = p.into();
-            path.join(n)
-        })
+/// extract the subtree of the `cid` to the `target` with the options, see `extract_ipld_subtree`.
+pub fn extract_ipld_subtree_with(
+    reader: &mut impl BlockStore,
+    cid: Cid,
+    target: impl AsRef<Path>,
+    options: &ExtractOptions,
+) -> Result<(), CarError> {
+    if reader.get_len(&cid)?.is_none() {
+        return Err(CarError::NotFound(format!("the block {cid}")));
     }
+    extract_ipld_inner(reader, cid, Some(target.as_ref().into()), options)
+}
+
+/// the entry pending in the walk, the path is carried with the cid since the blocks of the
+/// identical files and directories are shared by their paths.
+struct Pending {
+    cid: Cid,
+    path: PathBuf,
+    parent: Option<Cid>,
+    depth: usize,
 }
 
 enum Type {
-    Directory,
+    Directory(Box<UnixFs>),
     File,
     FileLinks(Box<UnixFs>),
+    Symlink(String),
 }
 
 /// inner function, extract files from CAR file.
 /// if the `parent` path is none, will use current path as root path.
 /// `cid` is the file cid
 fn extract_ipld_inner(
-    reader: &mut impl CarReader,
+    reader: &mut impl BlockStore,
     cid: Cid,
     parent: Option<PathBuf>,
+    options: &ExtractOptions,
 ) -> Result<(), CarError> {
-    let mut queue = VecDeque::<Cid>::new();
-    let mut unixfs_cache: HashMap<Cid, UnixfsCache> = Default::default();
-    let mut relations: HashMap<Cid, IndexRelation> = Default::default();
-    queue.push_back(cid);
     let root_path = match parent {
         Some(p) => p,
         None => cid.to_string().into(),
     };
-    while let Some(cid) = queue.pop_front() {
-        let rel = relations.get(&cid);
-        let full_path = match IndexRelation::full_path(rel, &unixfs_cache) {
-            Some(f) => f,
-            None => root_path.clone(),
-        };
-        let file_ipld: Ipld = reader.ipld(&cid).unwrap();
+    let mut queue = VecDeque::from([Pending {
+        cid,
+        path: root_path.clone(),
+        parent: None,
+        depth: 0,
+    }]);
+    let mut entries = 0;
+    let limits = reader.block_limits();
+    // the mode and mtime of the directories, restored when their entries are extracted.
+    let mut dir_metas = Vec::new();
+    // the symlinks are created after the other entries, so the writes never go through them.
+    let mut symlinks = Vec::new();
+    let root = cid;
+    let progress = ProgressCounter::new(options.progress.as_ref());
+    let progress = progress.as_deref();
+    while let Some(Pending {
+        cid,
+        path: full_path,
+        parent,
+        depth,
+    }) = queue.pop_front()
+    {
+        options.cancel.check(entries)?;
+        let path = walk_path(&root, &root_path, &full_path);
+        let traversal = |e: CarError| e.traversal(&path, &cid, parent.as_ref());
+        let file_ipld: Ipld = reader.get_ipld(&cid).map_err(traversal)?;
+        report_block(reader, progress, &cid)?;
         let file_links = match file_ipld {
             Ipld::Bytes(b) => {
-                let mut file = fs::OpenOptions::new()
-                    .create(true)
-                    .write(true)
-                    .open(&full_path)
-                    .unwrap();
-                file.write_all(&b).unwrap();
+                if let Some(mut file) = create_file(&root_path, &full_path, options)? {
+                    write_content(&mut file, &b, progress)?;
+                    file_extracted(progress);
+                }
                 Type::File
             }
             m @ Ipld::Map(_) => {
-                let unixfs: UnixFs = (cid, m).try_into()?;
+                let mut unixfs: UnixFs = (cid, m).try_into().map_err(traversal)?;
                 match unixfs.file_type {
                     FileType::File => Type::FileLinks(Box::new(unixfs)),
+                    FileType::Symlink => Type::Symlink(symlink_target(cid, &unixfs)?),
                     _ => {
-                        for (idx, link) in unixfs.links().iter().enumerate() {
-                            let rel = IndexRelation {
-                                parent_cid: cid,
-                                index: idx,
-                            };
-                            queue.push_back(link.hash);
-                            relations.insert(link.hash, rel);
-                        }
-                        let rel = relations.get(&cid);
-                        let path = IndexRelation::full_path(rel, &unixfs_cache)
-                            .unwrap_or_else(|| root_path.clone());
-                        unixfs_cache.insert(
-                            cid,
-                            UnixfsCache {
-                                inner: unixfs,
+                        // the shards are flattened, the entries are queued with their paths.
+                        unixfs.links = dir_entries(reader, &unixfs).map_err(traversal)?;
+                        for link in unixfs.links() {
+                            secure::check_name(&full_path, &link.name)?;
+                            let path = full_path.join(&link.name);
+                            options.limits.check(&path, &link.name, depth + 1)?;
+                            queue.push_back(Pending {
+                                cid: link.hash,
                                 path,
-                            },
-                        );
-                        Type::Directory
+                                parent: Some(cid),
+                                depth: depth + 1,
+                            });
+                        }
+                        limits.check_blocks(queue.len())?;
+                        Type::Directory(Box::new(unixfs))
                     }
                 }
             }
-            _ => unimplemented!("not implement"),
+            _ => {
+                return Err(CarError::InvalidSection(format!(
+                    "the block {cid} is not unixfs node"
+                )))
+            }
         };
 
         match file_links {
             Type::FileLinks(f) => {
-                let mut file = fs::OpenOptions::new()
-                    .create(true)
-                    .write(true)
-                    .open(&full_path)
-                    .unwrap();
-                for ufs in f.links() {
-                    let file_ipld: Ipld = reader.ipld(&ufs.hash).unwrap();
-                    match file_ipld {
-                        Ipld::Bytes(b) => {
-                            file.write_all(&b).unwrap();
-                        }
-                        _ => unreachable!("should not happend."),
-                    }
+                if let Some(mut file) = create_file(&root_path, &full_path, options)? {
+                    write_file_dag(
+                        reader, &mut file, &path, cid, &f, options, entries, progress,
+                    )?;
+                    meta::restore_file(&file, &f, options)?;
+                    file_extracted(progress);
                 }
             }
-            Type::Directory => {
-                if !full_path.exists() {
-                    fs::create_dir(&full_path)?
+            Type::Directory(dir) => {
+                create_dir(&root_path, &full_path, options)?;
+                meta::restore_dir(&full_path, &dir, options)?;
+                if options.preserve_metadata {
+                    dir_metas.push((full_path, dir.mode, dir.mtime.clone()));
                 }
             }
+            Type::Symlink(target) => symlinks.push((full_path, target)),
             _ => {}
         }
+        entries += 1;
+    }
+    extract_symlinks(&root_path, &symlinks, options)?;
+    // the breadth first order, the children are restored before the parents.
+    for (path, mode, mtime) in dir_metas.iter().rev() {
+        meta::restore_dir_mode_mtime(path, *mode, mtime.as_ref())?;
+    }
+    Ok(())
+}
+
+/// the path of the entry in the errors of the walk, the `root` cid with the path
+/// relative to the extraction target `root_path`.
+fn walk_path(root: &Cid, root_path: &Path, full_path: &Path) -> String {
+    match full_path.strip_prefix(root_path) {
+        Ok(rel) if !rel.as_os_str().is_empty() => format!("{root}/{}", rel.display()),
+        _ => root.to_string(),
+    }
+}
+
+/// report the block of the `cid` read to the progress.
+fn report_block(
+    reader: &mut impl BlockStore,
+    progress: Option<&ProgressCounter>,
+    cid: &Cid,
+) -> Result<(), CarError> {
+    if let Some(progress) = progress {
+        let len = reader.get_len(cid)?.unwrap_or(0) as u64;
+        progress.update(|p| p.bytes_hashed += len);
+    }
+    Ok(())
+}
+
+/// write the file content, the bytes are reported to the progress.
+fn write_content(
+    file: &mut fs::File,
+    data: &[u8],
+    progress: Option<&ProgressCounter>,
+) -> Result<(), CarError> {
+    file.write_all(data)?;
+    if let Some(progress) = progress {
+        progress.update(|p| p.bytes_written += data.len() as u64);
+    }
+    Ok(())
+}
+
+fn file_extracted(progress: Option<&ProgressCounter>) {
+    if let Some(progress) = progress {
+        progress.update(|p| p.files += 1);
+    }
+}
+
+/// write the content of the file node `node_cid` at the `path`, the `entries` extracted
+/// are reported if cancelled.
+#[allow(clippy::too_many_arguments)]
+fn write_file_dag(
+    reader: &mut impl BlockStore,
+    file: &mut fs::File,
+    path: &str,
+    node_cid: Cid,
+    node: &UnixFs,
+    options: &ExtractOptions,
+    entries: usize,
+    progress: Option<&ProgressCounter>,
+) -> Result<(), CarError> {
+    let limits = reader.block_limits();
+    // the leaf node without raw leaves has the content inline.
+    if let Some(data) = node.data() {
+        write_content(file, data, progress)?;
+    }
+    // walk the file DAG depth first, the large file has the nested file nodes.
+    // the blocks with their parents and their depth in the file DAG.
+    let mut stack: Vec<(Cid, Cid, usize)> = node
+        .links()
+        .iter()
+        .rev()
+        .map(|l| (l.hash, node_cid, 1))
+        .collect();
+    while let Some((block_cid, parent, depth)) = stack.pop() {
+        options.cancel.check(entries)?;
+        limits.check_link_depth(depth)?;
+        let traversal = |e: CarError| e.traversal(path, &block_cid, Some(&parent));
+        let block = reader.get_ipld(&block_cid).map_err(traversal)?;
+        report_block(reader, progress, &block_cid)?;
+        match block {
+            Ipld::Bytes(b) => write_content(file, &b, progress)?,
+            m @ Ipld::Map(_) => {
+                let node: UnixFs = (block_cid, m).try_into().map_err(traversal)?;
+                if let Some(data) = node.data() {
+                    write_content(file, data, progress)?;
+                }
+                stack.extend(
+                    node.links()
+                        .iter()
+                        .rev()
+                        .map(|l| (l.hash, block_cid, depth + 1)),
+                );
+                limits.check_blocks(stack.len())?;
+            }
+            _ => {
+                return Err(CarError::InvalidSection(format!(
+                    "the block {block_cid} is not file content"
+                )))
+            }
+        }
     }
     Ok(())
 }
+
+fn target_error(err: std::io::Error, path: &Path) -> CarError {
+    if secure::is_symlink_error(&err) {
+        CarError::TargetSymlink(path.display().to_string())
+    } else {
+        CarError::IO(err)
+    }
+}
+
+/// the result of creating the entry existing in the target, none if the existing entry
+/// is kept by the `options.overwrite`.
+fn check_overwrite<T>(
+    result: std::io::Result<T>,
+    path: &Path,
+    options: &ExtractOptions,
+) -> Result<Option<T>, CarError> {
+    match result {
+        Ok(v) => Ok(Some(v)),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => match options.overwrite {
+            Overwrite::Never => Ok(None),
+            _ => Err(CarError::TargetExists(path.display().to_string())),
+        },
+        Err(e) => Err(target_error(e, path)),
+    }
+}
+
+/// create the file in the extraction target `root`, none if the existing file is kept,
+/// the symlinks in the target are not followed unless `follow_target_symlinks` is set.
+fn create_file(
+    root: &Path,
+    path: &Path,
+    options: &ExtractOptions,
+) -> Result<Option<fs::File>, CarError> {
+    let replace = options.overwrite == Overwrite::Always;
+    let result = if options.follow_target_symlinks {
+        fs::OpenOptions::new()
+            .create(replace)
+            .truncate(replace)
+            .create_new(!replace)
+            .write(true)
+            .open(path)
+    } else {
+        let rel = path.strip_prefix(root).unwrap_or(path);
+        secure::create_file(root, rel, replace)
+    };
+    check_overwrite(result, path, options)
+}
+
+/// create the directory in the extraction target `root`,
+/// the symlinks in the target are not followed unless `follow_target_symlinks` is set.
+fn create_dir(root: &Path, path: &Path, options: &ExtractOptions) -> Result<(), CarError> {
+    if options.follow_target_symlinks {
+        if !path.exists() {
+            fs::create_dir(path)?;
+        }
+        return Ok(());
+    }
+    let rel = path.strip_prefix(root).unwrap_or(path);
+    secure::create_dir(root, rel).map_err(|e| target_error(e, path))
+}
+
+#[cfg(test)]
+#[allow(deprecated)]
+mod test {
+    use super::*;
+    use crate::{
+        header::CarHeader,
+        reader::{self, CarReader},
+        store::MemoryBlockStore,
+        utils::{pack_files, PathLimits, Progress},
+        writer::{self, CarWriter},
+    };
+    use std::{
+        io::Cursor,
+        sync::{Arc, Mutex},
+    };
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_extract_ipld_path_limits() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        fs::create_dir_all(root_dir.join("a/b")).unwrap();
+        fs::write(root_dir.join("a/b/test.txt"), b"hello world").unwrap();
+        let mut car = Cursor::new(vec![]);
+        let root = pack_files(&root_dir, &mut car, multicodec::Codec::Sha2_256, false).unwrap();
+
+        let mut reader = reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+        let output = TempDir::new("blockless-car-temp-output-dir").unwrap();
+        let options = ExtractOptions::new().with_limits(PathLimits::new(3, 255));
+        let result = extract_ipld_with(&mut reader, root, Some(output.path()), &options);
+        assert!(matches!(
+            result,
+            Err(CarError::PathTooDeep { depth: 4, .. })
+        ));
+
+        let options = ExtractOptions::new().with_limits(PathLimits::new(4, 255));
+        extract_ipld_with(&mut reader, root, Some(output.path()), &options).unwrap();
+        let content = fs::read(output.path().join("root/a/b/test.txt")).unwrap();
+        assert_eq!(content, b"hello world");
+    }
+
+    #[test]
+    fn test_extract_ipld_overwrite() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        fs::create_dir_all(root_dir.join("a")).unwrap();
+        fs::write(root_dir.join("a/test.txt"), b"new").unwrap();
+        fs::write(root_dir.join("a/added.txt"), b"added").unwrap();
+        let mut car = Cursor::new(vec![]);
+        let root = pack_files(&root_dir, &mut car, multicodec::Codec::Sha2_256, false).unwrap();
+        let mut reader = reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+
+        let output = TempDir::new("blockless-car-temp-output-dir").unwrap();
+        let out_root = output.path().join("root");
+        let prepare = || {
+            fs::create_dir_all(out_root.join("a")).unwrap();
+            fs::write(out_root.join("a/test.txt"), b"old").unwrap();
+            fs::write(out_root.join("a/unrelated.txt"), b"kept").unwrap();
+            let _ = fs::remove_file(out_root.join("a/added.txt"));
+        };
+        let extract = |reader: &mut _, overwrite| {
+            let options = ExtractOptions::new().with_overwrite(overwrite);
+            extract_ipld_with(reader, root, Some(output.path()), &options)
+        };
+
+        prepare();
+        let result = extract(&mut reader, Overwrite::Error);
+        assert!(matches!(result, Err(CarError::TargetExists(_))));
+
+        prepare();
+        extract(&mut reader, Overwrite::Never).unwrap();
+        assert_eq!(fs::read(out_root.join("a/test.txt")).unwrap(), b"old");
+        assert_eq!(fs::read(out_root.join("a/added.txt")).unwrap(), b"added");
+        assert_eq!(fs::read(out_root.join("a/unrelated.txt")).unwrap(), b"kept");
+
+        prepare();
+        extract(&mut reader, Overwrite::Always).unwrap();
+        assert_eq!(fs::read(out_root.join("a/test.txt")).unwrap(), b"new");
+        assert_eq!(fs::read(out_root.join("a/added.txt")).unwrap(), b"added");
+        assert_eq!(fs::read(out_root.join("a/unrelated.txt")).unwrap(), b"kept");
+    }
+
+    #[test]
+    fn test_extract_ipld_duplicate_content() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        fs::create_dir_all(root_dir.join("src")).unwrap();
+        // the files share the block, the default options fail on any path written twice.
+        fs::write(root_dir.join("src/a.txt"), b"same").unwrap();
+        fs::write(root_dir.join("src/b.txt"), b"same").unwrap();
+        let mut car = Cursor::new(vec![]);
+        let root = pack_files(&root_dir, &mut car, multicodec::Codec::Sha2_256, false).unwrap();
+        let mut reader = reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+
+        let output = TempDir::new("blockless-car-temp-output-dir").unwrap();
+        extract_ipld_with(
+            &mut reader,
+            root,
+            Some(output.path()),
+            &ExtractOptions::new(),
+        )
+        .unwrap();
+        for name in ["a.txt", "b.txt"] {
+            let content = fs::read(output.path().join("root/src").join(name)).unwrap();
+            assert_eq!(content, b"same");
+        }
+    }
+
+    #[test]
+    fn test_extract_ipld_subtree() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        fs::create_dir_all(root_dir.join("a/b")).unwrap();
+        fs::write(root_dir.join("a/b/test.txt"), b"hello world").unwrap();
+        fs::write(root_dir.join("a/other.txt"), b"other").unwrap();
+        let mut car = Cursor::new(vec![]);
+        let root = pack_files(&root_dir, &mut car, multicodec::Codec::Sha2_256, false).unwrap();
+        let mut reader = reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+
+        let output = TempDir::new("blockless-car-temp-output-dir").unwrap();
+        let b = reader.search_file_cid("b").unwrap();
+        assert_ne!(b, root);
+        extract_ipld_subtree(&mut reader, b, output.path().join("b")).unwrap();
+        let content = fs::read(output.path().join("b/test.txt")).unwrap();
+        assert_eq!(content, b"hello world");
+        assert!(!output.path().join("root").exists());
+        let file = reader.search_file_cid("other.txt").unwrap();
+        extract_ipld_subtree(&mut reader, file, output.path().join("other")).unwrap();
+        assert_eq!(fs::read(output.path().join("other")).unwrap(), b"other");
+
+        let missing = crate::utils::raw_cid(b"missing", multicodec::Codec::Sha2_256);
+        let result = extract_ipld_subtree(&mut reader, missing, output.path().join("m"));
+        assert!(matches!(result, Err(CarError::NotFound(_))));
+
+        // the blocks in the memory store are extracted the same.
+        let mut store = MemoryBlockStore::from_reader(&mut reader).unwrap();
+        extract_ipld_subtree(&mut store, b, output.path().join("b2")).unwrap();
+        let content = fs::read(output.path().join("b2/test.txt")).unwrap();
+        assert_eq!(content, b"hello world");
+        let result = extract_ipld_subtree(&mut store, missing, output.path().join("m"));
+        assert!(matches!(result, Err(CarError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_extract_ipld_on_demand() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        fs::create_dir_all(root_dir.join("a")).unwrap();
+        fs::write(root_dir.join("a/test.txt"), b"hello world").unwrap();
+        let mut car = Cursor::new(vec![]);
+        let root = pack_files(&root_dir, &mut car, multicodec::Codec::Sha2_256, false).unwrap();
+        // the garbage after the last block is never read by the lookups of the extraction.
+        let mut car = car.into_inner();
+        car.extend_from_slice(&[0, 0xff]);
+        let mut reader = reader::new_v1(Cursor::new(car)).unwrap();
+        let output = TempDir::new("blockless-car-temp-output-dir").unwrap();
+        extract_ipld_with(
+            &mut reader,
+            root,
+            Some(output.path()),
+            &ExtractOptions::new(),
+        )
+        .unwrap();
+        let content = fs::read(output.path().join("root/a/test.txt")).unwrap();
+        assert_eq!(content, b"hello world");
+        assert!(reader.section_count().is_err());
+    }
+
+    #[test]
+    fn test_extract_ipld_traversal_error() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        fs::create_dir_all(root_dir.join("a")).unwrap();
+        fs::write(root_dir.join("a/other.txt"), b"other").unwrap();
+        let mut car = Cursor::new(vec![]);
+        let root = pack_files(&root_dir, &mut car, multicodec::Codec::Sha2_256, false).unwrap();
+        let mut reader = reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+        let a = reader.search_file_cid("a").unwrap();
+
+        // the CAR without the file block.
+        let file = crate::utils::raw_cid(b"other", multicodec::Codec::Sha2_256);
+        let mut partial = Cursor::new(vec![]);
+        {
+            let mut writer = writer::new_v1(&mut partial, CarHeader::new_v1(vec![root])).unwrap();
+            for block in reader.blocks() {
+                let (cid, data) = block.unwrap();
+                if cid != file {
+                    writer.write_block(cid, data).unwrap();
+                }
+            }
+            writer.flush().unwrap();
+        }
+        let mut reader = reader::new_v1(Cursor::new(partial.into_inner())).unwrap();
+        let output = TempDir::new("blockless-car-temp-output-dir").unwrap();
+        let result = extract_ipld(&mut reader, root, Some(output.path()));
+        match result {
+            Err(CarError::Traversal {
+                path, cid, parent, ..
+            }) => {
+                assert_eq!(path, format!("{root}/root/a/other.txt"));
+                assert_eq!(cid, file.to_string());
+                assert_eq!(parent, Some(a.to_string()));
+            }
+            rs => panic!("unexpected result {rs:?}"),
+        }
+    }
+
+    #[test]
+    fn test_extract_ipld_unsafe_names() {
+        use crate::{codec::Encoder, unixfs::Link};
+        use ipld::{pb::DagPbCodec, prelude::Codec};
+
+        let file = crate::utils::raw_cid(b"evil", multicodec::Codec::Sha2_256);
+        for name in ["../evil", "..", "a/evil", "/tmp/evil", "", "."] {
+            let dir = UnixFs {
+                file_type: FileType::Directory,
+                links: vec![Link {
+                    hash: file,
+                    file_type: FileType::File,
+                    name: name.to_string(),
+                    tsize: 4,
+                }],
+                ..Default::default()
+            };
+            let bs = DagPbCodec.encode(&dir.encode().unwrap()).unwrap();
+            let root = crate::utils::pb_cid(&bs, multicodec::Codec::Sha2_256);
+            let mut car = Cursor::new(vec![]);
+            {
+                let mut writer = writer::new_v1(&mut car, CarHeader::new_v1(vec![root])).unwrap();
+                writer.write_block(root, bs).unwrap();
+                writer.write_block(file, b"evil").unwrap();
+                writer.flush().unwrap();
+            }
+            let mut reader = reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+            let output = TempDir::new("blockless-car-temp-output-dir").unwrap();
+            let target = output.path().join("target");
+            let result = extract_ipld(&mut reader, root, Some(&target));
+            assert!(
+                matches!(result, Err(CarError::UnsafePath(_))),
+                "{name:?}: {result:?}"
+            );
+            assert!(!output.path().join("evil").exists());
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_extract_ipld_target_symlinks() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        fs::create_dir_all(root_dir.join("a")).unwrap();
+        fs::write(root_dir.join("a/test.txt"), b"hello world").unwrap();
+        let mut car = Cursor::new(vec![]);
+        let root = pack_files(&root_dir, &mut car, multicodec::Codec::Sha2_256, false).unwrap();
+        let mut reader = reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+
+        // the previous extracted "root/a" is a symlink to the outside directory.
+        let output = TempDir::new("blockless-car-temp-output-dir").unwrap();
+        let outside = TempDir::new("blockless-car-temp-outside-dir").unwrap();
+        fs::create_dir(output.path().join("root")).unwrap();
+        std::os::unix::fs::symlink(outside.path(), output.path().join("root/a")).unwrap();
+
+        let options = ExtractOptions::new();
+        let result = extract_ipld_with(&mut reader, root, Some(output.path()), &options);
+        assert!(
+            matches!(result, Err(CarError::TargetSymlink(_))),
+            "{result:?}"
+        );
+        assert!(!outside.path().join("test.txt").exists());
+
+        let options = ExtractOptions::new().with_follow_target_symlinks(true);
+        extract_ipld_with(&mut reader, root, Some(output.path()), &options).unwrap();
+        assert!(outside.path().join("test.txt").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_extract_ipld_preserve_owner() {
+        use crate::utils::{pack_files_with, PackOptions};
+        use std::os::unix::fs::MetadataExt;
+
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        fs::create_dir_all(root_dir.join("a")).unwrap();
+        fs::write(root_dir.join("a/test.txt"), b"hello world").unwrap();
+        let owner = fs::metadata(root_dir.join("a/test.txt")).unwrap();
+
+        let mut car = Cursor::new(vec![]);
+        let options = PackOptions::new().with_preserve_owner(true);
+        let root = pack_files_with(&root_dir, &mut car, &options).unwrap();
+        let mut reader = reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+
+        // the small file is wrapped into a file node to carry the owner.
+        let dir: UnixFs = (root, reader.ipld(&root).unwrap()).try_into().unwrap();
+        let dir_cid = dir.links()[0].hash;
+        let dir: UnixFs = (dir_cid, reader.ipld(&dir_cid).unwrap())
+            .try_into()
+            .unwrap();
+        let a_cid = dir.links()[0].hash;
+        let a: UnixFs = (a_cid, reader.ipld(&a_cid).unwrap()).try_into().unwrap();
+        assert_eq!(a.uid(), Some(owner.uid()));
+        let file_cid = a.links()[0].hash;
+        let file: UnixFs = (file_cid, reader.ipld(&file_cid).unwrap())
+            .try_into()
+            .unwrap();
+        assert_eq!(file.file_type, FileType::File);
+        assert_eq!(file.uid(), Some(owner.uid()));
+        assert_eq!(file.gid(), Some(owner.gid()));
+
+        let output = TempDir::new("blockless-car-temp-output-dir").unwrap();
+        let options = ExtractOptions::new().with_preserve_owner(true);
+        extract_ipld_with(&mut reader, root, Some(output.path()), &options).unwrap();
+        let extracted = output.path().join("root/a/test.txt");
+        assert_eq!(fs::read(&extracted).unwrap(), b"hello world");
+        assert_eq!(fs::metadata(&extracted).unwrap().uid(), owner.uid());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_extract_ipld_symlinks() {
+        use std::os::unix::fs::symlink;
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        fs::create_dir_all(root_dir.join("lib")).unwrap();
+        fs::write(root_dir.join("lib/libfoo.so.1"), b"elf").unwrap();
+        symlink("libfoo.so.1", root_dir.join("lib/libfoo.so")).unwrap();
+        symlink("lib/libfoo.so", root_dir.join("current")).unwrap();
+        symlink("lib", root_dir.join("lib64")).unwrap();
+        symlink("/etc/passwd", root_dir.join("passwd")).unwrap();
+        let mut car = Cursor::new(vec![]);
+        let root = pack_files(&root_dir, &mut car, multicodec::Codec::Sha2_256, false).unwrap();
+        let mut reader = reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+
+        let tree = extract_to_memory(&mut reader, root, MemoryLimits::default()).unwrap();
+        assert_eq!(
+            tree.get("root/current"),
+            Some(&VirtualEntry::Symlink("lib/libfoo.so".into()))
+        );
+
+        let output = TempDir::new("blockless-car-temp-output-dir").unwrap();
+        extract_ipld(&mut reader, root, Some(output.path())).unwrap();
+        let out_root = output.path().join("root");
+        assert_eq!(
+            fs::read_link(out_root.join("lib/libfoo.so")).unwrap(),
+            Path::new("libfoo.so.1")
+        );
+        assert_eq!(
+            fs::read_link(out_root.join("passwd")).unwrap(),
+            Path::new("/etc/passwd")
+        );
+        assert_eq!(fs::read(out_root.join("current")).unwrap(), b"elf");
+        // the symlinks extracted before are replaced.
+        let options = ExtractOptions::new().with_overwrite(Overwrite::Always);
+        extract_ipld_with(&mut reader, root, Some(output.path()), &options).unwrap();
+
+        // the absolute target is out of the extraction.
+        let output = TempDir::new("blockless-car-temp-output-dir").unwrap();
+        let options = ExtractOptions::new()
+            .with_dereference(true)
+            .with_overwrite(Overwrite::Always);
+        let result = extract_ipld_with(&mut reader, root, Some(output.path()), &options);
+        assert!(matches!(result, Err(CarError::InvalidFile(_))));
+
+        fs::remove_file(root_dir.join("passwd")).unwrap();
+        let mut car = Cursor::new(vec![]);
+        let root = pack_files(&root_dir, &mut car, multicodec::Codec::Sha2_256, false).unwrap();
+        let mut reader = reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+        extract_ipld_with(&mut reader, root, Some(output.path()), &options).unwrap();
+        let out_root = output.path().join("root");
+        for path in ["current", "lib/libfoo.so", "lib64/libfoo.so.1"] {
+            let metadata = fs::symlink_metadata(out_root.join(path)).unwrap();
+            assert!(metadata.is_file(), "{path}");
+            assert_eq!(fs::read(out_root.join(path)).unwrap(), b"elf");
+        }
+        // the symlink inside the copied directory is left out.
+        assert!(!out_root.join("lib64/libfoo.so").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_extract_ipld_symlink_prefixes() {
+        use std::os::unix::fs::symlink;
+        let prefix: SymlinkPrefix = "/home/alice/project=/srv/project".parse().unwrap();
+        assert_eq!(
+            prefix.rewrite("/home/alice/project/lib").as_deref(),
+            Some("/srv/project/lib")
+        );
+        assert_eq!(prefix.rewrite("/home/alice/projects"), None);
+        assert!("relative=/srv".parse::<SymlinkPrefix>().is_err());
+
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        fs::create_dir_all(root_dir.join("lib")).unwrap();
+        fs::write(root_dir.join("lib/libfoo.so.1"), b"elf").unwrap();
+        symlink(
+            "/home/alice/project/lib/libfoo.so.1",
+            root_dir.join("lib/libfoo.so"),
+        )
+        .unwrap();
+        symlink("/home/alice/projects", root_dir.join("other")).unwrap();
+        let mut car = Cursor::new(vec![]);
+        let root = pack_files(&root_dir, &mut car, multicodec::Codec::Sha2_256, false).unwrap();
+        let mut reader = reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+
+        let output = TempDir::new("blockless-car-temp-output-dir").unwrap();
+        let options = ExtractOptions::new().with_symlink_prefix(prefix);
+        extract_ipld_with(&mut reader, root, Some(output.path()), &options).unwrap();
+        let out_root = output.path().join("root");
+        assert_eq!(
+            fs::read_link(out_root.join("lib/libfoo.so")).unwrap(),
+            Path::new("/srv/project/lib/libfoo.so.1")
+        );
+        assert_eq!(
+            fs::read_link(out_root.join("other")).unwrap(),
+            Path::new("/home/alice/projects")
+        );
+
+        // the targets rewritten into the extraction can be dereferenced.
+        fs::remove_file(root_dir.join("other")).unwrap();
+        let mut car = Cursor::new(vec![]);
+        let root = pack_files(&root_dir, &mut car, multicodec::Codec::Sha2_256, false).unwrap();
+        let mut reader = reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+        let output = TempDir::new("blockless-car-temp-output-dir").unwrap();
+        let options = ExtractOptions::new()
+            .with_dereference(true)
+            .with_symlink_prefix(SymlinkPrefix::new("/home/alice/project/lib", "."));
+        extract_ipld_with(&mut reader, root, Some(output.path()), &options).unwrap();
+        let libfoo = output.path().join("root/lib/libfoo.so");
+        assert!(fs::symlink_metadata(&libfoo).unwrap().is_file());
+        assert_eq!(fs::read(libfoo).unwrap(), b"elf");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_extract_ipld_preserve_metadata() {
+        use crate::{
+            unixfs::UnixTime,
+            utils::{pack_files_with, PackOptions},
+        };
+        use std::{
+            os::unix::fs::PermissionsExt,
+            time::{Duration, UNIX_EPOCH},
+        };
+
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        fs::create_dir_all(root_dir.join("bin")).unwrap();
+        let script = root_dir.join("bin/install.sh");
+        fs::write(&script, b"#!/bin/sh").unwrap();
+        let mtime = UNIX_EPOCH + Duration::new(1_700_000_000, 500);
+        let set = |path: &Path, mode: u32| {
+            let file = fs::File::open(path).unwrap();
+            file.set_modified(mtime).unwrap();
+            fs::set_permissions(path, fs::Permissions::from_mode(mode)).unwrap();
+        };
+        set(&script, 0o755);
+        // the read only directory is restored after its entries.
+        set(&root_dir.join("bin"), 0o555);
+
+        let mut car = Cursor::new(vec![]);
+        let options = PackOptions::new().with_preserve_metadata(true);
+        let root = pack_files_with(&root_dir, &mut car, &options).unwrap();
+        fs::set_permissions(root_dir.join("bin"), fs::Permissions::from_mode(0o755)).unwrap();
+        let mut reader = reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+        let file = reader.search_file_cid("install.sh").unwrap();
+        let file = reader.unixfs(&file).unwrap();
+        assert_eq!(file.mode(), Some(0o755));
+        assert_eq!(file.mtime(), Some(&UnixTime::from_system_time(mtime)));
+
+        let output = TempDir::new("blockless-car-temp-output-dir").unwrap();
+        let options = ExtractOptions::new().with_preserve_metadata(true);
+        extract_ipld_with(&mut reader, root, Some(output.path()), &options).unwrap();
+        for (path, mode) in [("root/bin/install.sh", 0o755), ("root/bin", 0o555)] {
+            let metadata = fs::metadata(output.path().join(path)).unwrap();
+            assert_eq!(metadata.permissions().mode() & 0o7777, mode, "{path}");
+            assert_eq!(metadata.modified().unwrap(), mtime, "{path}");
+        }
+        assert_eq!(
+            fs::read(output.path().join("root/bin/install.sh")).unwrap(),
+            b"#!/bin/sh"
+        );
+        fs::set_permissions(
+            output.path().join("root/bin"),
+            fs::Permissions::from_mode(0o755),
+        )
+        .unwrap();
+
+        let before_epoch = UNIX_EPOCH - Duration::new(1, 500);
+        let time = UnixTime::from_system_time(before_epoch);
+        assert_eq!(
+            (time.seconds, time.fractional_nanoseconds),
+            (-2, Some(999_999_500))
+        );
+        assert_eq!(time.to_system_time(), before_epoch);
+    }
+
+    #[cfg(all(unix, feature = "xattr"))]
+    #[test]
+    fn test_extract_ipld_preserve_xattrs() {
+        use crate::utils::{pack_files_with, PackOptions};
+
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        fs::create_dir_all(&root_dir).unwrap();
+        let file = root_dir.join("test.txt");
+        fs::write(&file, b"hello world").unwrap();
+        if xattr::set(&file, "user.car-test", b"value").is_err() {
+            // the file system doesn't support the user xattrs.
+            return;
+        }
+        xattr::set(&root_dir, "user.car-dir", b"dir").unwrap();
+
+        let mut car = Cursor::new(vec![]);
+        let options = PackOptions::new().with_preserve_xattrs(true);
+        let root = pack_files_with(&root_dir, &mut car, &options).unwrap();
+        let mut reader = reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+
+        let output = TempDir::new("blockless-car-temp-output-dir").unwrap();
+        let options = ExtractOptions::new().with_preserve_xattrs(true);
+        extract_ipld_with(&mut reader, root, Some(output.path()), &options).unwrap();
+        let extracted = output.path().join("root/test.txt");
+        assert_eq!(fs::read(&extracted).unwrap(), b"hello world");
+        let value = xattr::get(&extracted, "user.car-test").unwrap();
+        assert_eq!(value.as_deref(), Some(&b"value"[..]));
+        let value = xattr::get(output.path().join("root"), "user.car-dir").unwrap();
+        assert_eq!(value.as_deref(), Some(&b"dir"[..]));
+
+        // the xattrs are not restored by default.
+        let output = TempDir::new("blockless-car-temp-output-dir").unwrap();
+        extract_ipld(&mut reader, root, Some(output.path())).unwrap();
+        let value = xattr::get(output.path().join("root/test.txt"), "user.car-test").unwrap();
+        assert_eq!(value, None);
+    }
+
+    #[test]
+    fn test_extract_ipld_hamt() {
+        use crate::utils::{pack_files_with, PackOptions};
+
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        fs::create_dir_all(root_dir.join("sub")).unwrap();
+        for i in 0..100 {
+            fs::write(root_dir.join(format!("file-{i}.txt")), i.to_string()).unwrap();
+        }
+        fs::write(root_dir.join("sub/test.txt"), b"hello world").unwrap();
+        let mut car = Cursor::new(vec![]);
+        let options = PackOptions::new().with_hamt_threshold(Some(256));
+        let root = pack_files_with(&root_dir, &mut car, &options).unwrap();
+        let mut reader = reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+
+        let output = TempDir::new("blockless-car-temp-output-dir").unwrap();
+        extract_ipld(&mut reader, root, Some(output.path())).unwrap();
+        let extracted = output.path().join("root");
+        assert_eq!(fs::read_dir(&extracted).unwrap().count(), 101);
+        assert_eq!(fs::read(extracted.join("file-42.txt")).unwrap(), b"42");
+        assert_eq!(
+            fs::read(extracted.join("sub/test.txt")).unwrap(),
+            b"hello world"
+        );
+    }
+
+    #[test]
+    fn test_extract_ipld_cancel() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        fs::create_dir_all(&root_dir).unwrap();
+        fs::write(root_dir.join("test.txt"), b"hello world").unwrap();
+        let mut car = Cursor::new(vec![]);
+        let root = pack_files(&root_dir, &mut car, multicodec::Codec::Sha2_256, false).unwrap();
+        let mut reader = reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+
+        let cancel = crate::utils::CancelToken::new();
+        cancel.cancel();
+        let output = TempDir::new("blockless-car-temp-output-dir").unwrap();
+        let options = ExtractOptions::new().with_cancel(cancel);
+        let result = extract_ipld_with(&mut reader, root, Some(output.path()), &options);
+        assert!(matches!(result, Err(CarError::Cancelled { entries: 0 })));
+        assert!(!output.path().join("root").exists());
+    }
+
+    #[test]
+    fn test_extract_ipld_progress() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        fs::create_dir_all(root_dir.join("sub")).unwrap();
+        fs::write(root_dir.join("a.txt"), b"hello world").unwrap();
+        // the chunks differ, each block is read once.
+        let content: Vec<u8> = (0..600_000u32).map(|i| (i % 251) as u8).collect();
+        fs::write(root_dir.join("sub/b.bin"), content).unwrap();
+        let mut car = Cursor::new(vec![]);
+        let root = pack_files(&root_dir, &mut car, multicodec::Codec::Sha2_256, false).unwrap();
+        let mut reader = reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+        let blocks: u64 = reader
+            .sections()
+            .unwrap()
+            .iter()
+            .map(|s| s.len() as u64)
+            .sum();
+
+        let last = Arc::new(Mutex::new(Progress::default()));
+        let hook = {
+            let last = last.clone();
+            move |p: &Progress| *last.lock().unwrap() = *p
+        };
+        let output = TempDir::new("blockless-car-temp-output-dir").unwrap();
+        let options = ExtractOptions::new().with_progress(Arc::new(hook));
+        extract_ipld_with(&mut reader, root, Some(output.path()), &options).unwrap();
+        let progress = *last.lock().unwrap();
+        assert_eq!(progress.files, 2);
+        assert_eq!(progress.bytes_written, 600_011);
+        assert_eq!(progress.bytes_hashed, blocks);
+    }
+
+    #[test]
+    fn test_extract_ipld_nested_file() {
+        use crate::utils::{pack_files_with, PackOptions};
+
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        fs::create_dir_all(&root_dir).unwrap();
+        // more chunks than two levels of the file nodes link, the file DAG has three levels.
+        let data: Vec<u8> = (0..174 * 174 * 16 + 1)
+            .map(|i: u32| (i % 251) as u8)
+            .collect();
+        fs::write(root_dir.join("data.bin"), &data).unwrap();
+        for raw_leaves in [true, false] {
+            let options = PackOptions::new()
+                .with_chunk_size(16)
+                .with_raw_leaves(raw_leaves);
+            let mut car = Cursor::new(vec![]);
+            let root = pack_files_with(&root_dir, &mut car, &options).unwrap();
+            let mut reader = reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+
+            let output = TempDir::new("blockless-car-temp-output-dir").unwrap();
+            extract_ipld(&mut reader, root, Some(output.path())).unwrap();
+            let extracted = fs::read(output.path().join("root/data.bin")).unwrap();
+            assert!(extracted == data);
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use std::{collections::BTreeMap, path::Path};
+
+use cid::Cid;
+
+use crate::{
+    error::CarError,
+    store::BlockStore,
+    unixfs::{FileType, UnixFs},
+    utils::{dir_entries, PathLimits},
+    Ipld,
+};
+
+use super::{secure, symlink_target};
+
+/// the default maximum bytes of the file contents extracted to the memory.
+pub const DEFAULT_MEMORY_MAX_BYTES: u64 = 64 << 20;
+
+/// the default maximum count of the entries extracted to the memory.
+pub const DEFAULT_MEMORY_MAX_ENTRIES: usize = 10000;
+
+/// the budget of extracting to the memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryLimits {
+    /// the maximum bytes of all the file contents.
+    pub max_bytes: u64,
+    /// the maximum count of the files and directories.
+    pub max_entries: usize,
+    /// the limits of the path depth and name length.
+    pub path: PathLimits,
+}
+
+impl Default for MemoryLimits {
+    fn default() -> Self {
+        Self {
+            max_bytes: DEFAULT_MEMORY_MAX_BYTES,
+            max_entries: DEFAULT_MEMORY_MAX_ENTRIES,
+            path: PathLimits::default(),
+        }
+    }
+}
+
+impl MemoryLimits {
+    pub fn new(max_bytes: u64, max_entries: usize) -> Self {
+        Self {
+            max_bytes,
+            max_entries,
+            path: PathLimits::default(),
+        }
+    }
+
+    pub fn with_path_limits(mut self, path: PathLimits) -> Self {
+        self.path = path;
+        self
+    }
+}
+
+/// the entry of the in-memory tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VirtualEntry {
+    File(Vec<u8>),
+    Dir(VirtualDir),
+    /// the symlink with its target, the target is not resolved.
+    Symlink(String),
+}
+
+/// the in-memory directory, the entries are sorted by the name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VirtualDir {
+    pub entries: BTreeMap<String, VirtualEntry>,
+}
+
+impl VirtualDir {
+    /// get the entry by the `/` separated path relative to the directory.
+    pub fn get(&self, path: &str) -> Option<&VirtualEntry> {
+        let mut names = path.split('/').filter(|n| !n.is_empty());
+        let mut entry = self.entries.get(names.next()?)?;
+        for name in names {
+            match entry {
+                VirtualEntry::Dir(dir) => entry = dir.entries.get(name)?,
+                VirtualEntry::File(_) | VirtualEntry::Symlink(_) => return None,
+            }
+        }
+        Some(entry)
+    }
+
+    /// get the file content by the `/` separated path.
+    pub fn file(&self, path: &str) -> Option<&[u8]> {
+        match self.get(path)? {
+            VirtualEntry::File(data) => Some(data),
+            VirtualEntry::Dir(_) | VirtualEntry::Symlink(_) => None,
+        }
+    }
+
+    /// all the files with their paths in the depth first order, the symlinks are not listed.
+    pub fn files(&self) -> Vec<(String, &[u8])> {
+        let mut files = Vec::new();
+        let mut stack = vec![(String::new(), self)];
+        while let Some((prefix, dir)) = stack.pop() {
+            for (name, entry) in dir.entries.iter().rev() {
+                let path = if prefix.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{prefix}/{name}")
+                };
+                match entry {
+                    VirtualEntry::File(data) => files.push((path, &data[..])),
+                    VirtualEntry::Dir(dir) => stack.push((path, dir)),
+                    VirtualEntry::Symlink(_) => {}
+                }
+            }
+        }
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+        files
+    }
+}
+
+struct Budget {
+    limits: MemoryLimits,
+    bytes: u64,
+    entries: usize,
+}
+
+impl Budget {
+    fn add_entry(&mut self, path: &str) -> Result<(), CarError> {
+        self.entries += 1;
+        if self.entries > self.limits.max_entries {
+            return Err(CarError::BudgetExceeded(format!(
+                "{path}: more than {} entries",
+                self.limits.max_entries
+            )));
+        }
+        Ok(())
+    }
+
+    fn add_bytes(&mut self, path: &str, len: usize) -> Result<(), CarError> {
+        self.bytes += len as u64;
+        if self.bytes > self.limits.max_bytes {
+            return Err(CarError::BudgetExceeded(format!(
+                "{path}: more than {} bytes",
+                self.limits.max_bytes
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// extract the tree of the `root` to the memory, the files and directories under the `root`
+/// directory are the entries of the returned directory, the file or symlink `root` is named by its cid.
+/// the extract fails with `CarError::BudgetExceeded` if the tree exceeds the `limits`.
+pub fn extract_to_memory(
+    reader: &mut impl BlockStore,
+    root: Cid,
+    limits: MemoryLimits,
+) -> Result<VirtualDir, CarError> {
+    let mut budget = Budget {
+        limits,
+        bytes: 0,
+        entries: 0,
+    };
+    match read_entry(reader, root, "", 0, &mut budget)? {
+        VirtualEntry::Dir(dir) => Ok(dir),
+        entry => {
+            let mut dir = VirtualDir::default();
+            dir.entries.insert(root.to_string(), entry);
+            Ok(dir)
+        }
+    }
+}
+
+fn read_entry(
+    reader: &mut impl BlockStore,
+    cid: Cid,
+    path: &str,
+    depth: usize,
+    budget: &mut Budget,
+) -> Result<VirtualEntry, CarError> {
+    let node = match reader.get_ipld(&cid)? {
+        Ipld::Bytes(b) => {
+            budget.add_bytes(path, b.len())?;
+            return Ok(VirtualEntry::File(b));
+        }
+        m @ Ipld::Map(_) => UnixFs::try_from((cid, m))?,
+        _ => {
+            return Err(CarError::InvalidSection(format!(
+                "the block {cid} is not unixfs node"
+            )))
+        }
+    };
+    match node.file_type {
+        FileType::File => return read_file(reader, &node, path, budget).map(VirtualEntry::File),
+        FileType::Symlink => return symlink_target(cid, &node).map(VirtualEntry::Symlink),
+        _ => {}
+    }
+    let mut dir = VirtualDir::default();
+    for link in dir_entries(reader, &node)?.iter() {
+        secure::check_name(Path::new(path), &link.name)?;
+        let child = match path {
+            "" => link.name.clone(),
+            _ => format!("{path}/{}", link.name),
+        };
+        budget.limits.path.check(&child, &link.name, depth + 1)?;
+        budget.add_entry(&child)?;
+        let entry = read_entry(reader, link.hash, &child, depth + 1, budget)?;
+        dir.entries.insert(link.name.clone(), entry);
+    }
+    Ok(VirtualEntry::Dir(dir))
+}
+
+/// read the file DAG depth first, the large file has the nested file nodes.
+fn read_file(
+    reader: &mut impl BlockStore,
+    node: &UnixFs,
+    path: &str,
+    budget: &mut Budget,
+) -> Result<Vec<u8>, CarError> {
+    let mut data = Vec::new();
+    if let Some(inline) = node.data() {
+        budget.add_bytes(path, inline.len())?;
+        data.extend_from_slice(inline);
+    }
+    let limits = reader.block_limits();
+    // the blocks with their depth in the file DAG.
+    let mut stack: Vec<(Cid, usize)> = node.links().iter().rev().map(|l| (l.hash, 1)).collect();
+    while let Some((block_cid, depth)) = stack.pop() {
+        limits.check_link_depth(depth)?;
+        match reader.get_ipld(&block_cid)? {
+            Ipld::Bytes(b) => {
+                budget.add_bytes(path, b.len())?;
+                data.extend_from_slice(&b);
+            }
+            m @ Ipld::Map(_) => {
+                let node: UnixFs = (block_cid, m).try_into()?;
+                if let Some(inline) = node.data() {
+                    budget.add_bytes(path, inline.len())?;
+                    data.extend_from_slice(inline);
+                }
+                stack.extend(node.links().iter().rev().map(|l| (l.hash, depth + 1)));
+                limits.check_blocks(stack.len())?;
+            }
+            _ => {
+                return Err(CarError::InvalidSection(format!(
+                    "the block {block_cid} is not file content"
+                )))
+            }
+        }
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{pack_dir, reader, utils::PackOptions};
+    use std::{fs, io::Cursor};
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_extract_to_memory() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        fs::create_dir_all(root_dir.join("a/b")).unwrap();
+        fs::create_dir_all(root_dir.join("empty")).unwrap();
+        fs::write(root_dir.join("a/b/test.txt"), b"hello world").unwrap();
+        fs::write(root_dir.join("top.txt"), b"top").unwrap();
+        let mut car = Cursor::new(vec![]);
+        let root = pack_dir(&root_dir, &mut car, &PackOptions::new()).unwrap();
+        let mut reader = reader::new_v1(Cursor::new(car.into_inner())).unwrap();
+
+        let tree = extract_to_memory(&mut reader, root, MemoryLimits::default()).unwrap();
+        assert_eq!(tree.file("root/a/b/test.txt"), Some(&b"hello world"[..]));
+        assert_eq!(tree.file("root/top.txt"), Some(&b"top"[..]));
+        assert_eq!(
+            tree.get("root/empty"),
+            Some(&VirtualEntry::Dir(VirtualDir::default()))
+        );
+        let files: Vec<_> = tree.files().into_iter().map(|(p, _)| p).collect();
+        assert_eq!(files, ["root/a/b/test.txt", "root/top.txt"]);
+
+        let result = extract_to_memory(&mut reader, root, MemoryLimits::new(13, 100));
+        assert!(matches!(result, Err(CarError::BudgetExceeded(_))));
+        let result = extract_to_memory(&mut reader, root, MemoryLimits::new(100, 5));
+        assert!(matches!(result, Err(CarError::BudgetExceeded(_))));
+        assert!(extract_to_memory(&mut reader, root, MemoryLimits::new(14, 6)).is_ok());
+    }
+}
@@ -0,0 +1,126 @@
+//! restore the metadata stored in the unixfs nodes to the extracted entries.
+//! the metadata the user has no privilege to restore or the file system
+//! doesn't support is skipped.
+use std::{fs::File, path::Path};
+
+use crate::{
+    error::CarError,
+    unixfs::{UnixFs, UnixTime},
+};
+
+use super::ExtractOptions;
+
+#[cfg(unix)]
+fn ignore_unpermitted(result: std::io::Result<()>) -> Result<(), CarError> {
+    use std::io::ErrorKind;
+    match result {
+        Err(e)
+            if matches!(
+                e.kind(),
+                ErrorKind::PermissionDenied | ErrorKind::Unsupported
+            ) =>
+        {
+            Ok(())
+        }
+        r => Ok(r?),
+    }
+}
+
+#[cfg(unix)]
+#[inline]
+fn has_owner(unixfs: &UnixFs, options: &ExtractOptions) -> bool {
+    options.preserve_owner && (unixfs.uid.is_some() || unixfs.gid.is_some())
+}
+
+/// restore the metadata of the extracted file.
+#[cfg(unix)]
+pub(super) fn restore_file(
+    file: &File,
+    unixfs: &UnixFs,
+    options: &ExtractOptions,
+) -> Result<(), CarError> {
+    if has_owner(unixfs, options) {
+        ignore_unpermitted(std::os::unix::fs::fchown(file, unixfs.uid, unixfs.gid))?;
+    }
+    #[cfg(feature = "xattr")]
+    if options.preserve_xattrs {
+        use xattr::FileExt;
+        for x in unixfs.xattrs() {
+            ignore_unpermitted(file.set_xattr(&x.name, &x.value))?;
+        }
+    }
+    if options.preserve_metadata {
+        restore_mode_mtime(file, unixfs.mode, unixfs.mtime.as_ref())?;
+    }
+    Ok(())
+}
+
+/// restore the mode and the mtime, the mode is set last so the read only
+/// mode doesn't block the other metadata.
+#[cfg(unix)]
+pub(super) fn restore_mode_mtime(
+    file: &File,
+    mode: Option<u32>,
+    mtime: Option<&UnixTime>,
+) -> Result<(), CarError> {
+    use std::{fs::FileTimes, os::unix::fs::PermissionsExt};
+    if let Some(mtime) = mtime {
+        let times = FileTimes::new().set_modified(mtime.to_system_time());
+        ignore_unpermitted(file.set_times(times))?;
+    }
+    if let Some(mode) = mode {
+        let permissions = std::fs::Permissions::from_mode(mode & 0o7777);
+        ignore_unpermitted(file.set_permissions(permissions))?;
+    }
+    Ok(())
+}
+
+/// the mode and mtime of the directory, restored after its entries are extracted.
+#[cfg(unix)]
+pub(super) fn restore_dir_mode_mtime(
+    path: &Path,
+    mode: Option<u32>,
+    mtime: Option<&UnixTime>,
+) -> Result<(), CarError> {
+    let dir = File::open(path)?;
+    restore_mode_mtime(&dir, mode, mtime)
+}
+
+/// restore the metadata of the extracted directory.
+#[cfg(unix)]
+pub(super) fn restore_dir(
+    path: &Path,
+    unixfs: &UnixFs,
+    options: &ExtractOptions,
+) -> Result<(), CarError> {
+    if has_owner(unixfs, options) {
+        ignore_unpermitted(std::os::unix::fs::lchown(path, unixfs.uid, unixfs.gid))?;
+    }
+    #[cfg(feature = "xattr")]
+    if options.preserve_xattrs {
+        for x in unixfs.xattrs() {
+            ignore_unpermitted(xattr::set(path, &x.name, &x.value))?;
+        }
+    }
+    Ok(())
+}
+
+/// the owner and extended attributes are not available on the platform.
+#[cfg(not(unix))]
+pub(super) fn restore_file(_: &File, _: &UnixFs, _: &ExtractOptions) -> Result<(), CarError> {
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub(super) fn restore_dir(_: &Path, _: &UnixFs, _: &ExtractOptions) -> Result<(), CarError> {
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub(super) fn restore_dir_mode_mtime(
+    _: &Path,
+    _: Option<u32>,
+    _: Option<&UnixTime>,
+) -> Result<(), CarError> {
+    Ok(())
+}
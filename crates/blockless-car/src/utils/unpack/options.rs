@@ -0,0 +1,169 @@
+use std::{borrow::Cow, str::FromStr, sync::Arc};
+
+use crate::{
+    error::CarError,
+    utils::{CancelToken, PathLimits, ProgressHook},
+};
+
+/// the policy of the extracted files and symlinks whose paths exist in the target,
+/// the existing directories are merged and the unrelated entries are kept.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Overwrite {
+    /// replace the existing entry.
+    Always,
+    /// keep the existing entry, the extracted one is skipped.
+    Never,
+    /// fail the extract with `CarError::TargetExists`, the default so the existing
+    /// files are never lost silently.
+    #[default]
+    Error,
+}
+
+/// the rewrite of the absolute symlink targets, the targets recorded on the packing
+/// machine are mapped to the paths valid on the extracting machine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymlinkPrefix {
+    /// the prefix of the recorded target, matched by the whole path components.
+    pub from: String,
+    pub to: String,
+}
+
+impl SymlinkPrefix {
+    pub fn new(from: impl Into<String>, to: impl Into<String>) -> Self {
+        Self {
+            from: from.into(),
+            to: to.into(),
+        }
+    }
+
+    /// the `target` with the prefix replaced, none if the prefix doesn't match.
+    pub fn rewrite(&self, target: &str) -> Option<String> {
+        let from = match self.from.trim_end_matches('/') {
+            "" => "/",
+            from => from,
+        };
+        let rest = target.strip_prefix(from)?;
+        if !(rest.is_empty() || rest.starts_with('/') || from.ends_with('/')) {
+            return None;
+        }
+        let to = self.to.trim_end_matches('/');
+        Some(match rest.trim_start_matches('/') {
+            "" if to.is_empty() => ".".to_string(),
+            "" => to.to_string(),
+            rest if to.is_empty() => rest.to_string(),
+            rest => format!("{to}/{rest}"),
+        })
+    }
+}
+
+/// parse the rewrite `<old>=<new>`, e.g. `/home/alice/project=/srv/project`.
+impl FromStr for SymlinkPrefix {
+    type Err = CarError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((from, to)) if from.starts_with('/') => Ok(Self::new(from, to)),
+            _ => Err(CarError::InvalidSymlinkPrefix(format!(
+                "{s}: expect <absolute old>=<new>"
+            ))),
+        }
+    }
+}
+
+/// the options of extracting files from the CAR file.
+#[derive(Debug, Clone, Default)]
+pub struct ExtractOptions {
+    /// the limits of the path depth and name length.
+    pub limits: PathLimits,
+    /// follow the symlinks in the extraction target, by default the symlinks
+    /// are not followed so the writes can't escape the target.
+    pub follow_target_symlinks: bool,
+    /// the policy of the files and symlinks existing in the target, `Overwrite::Error`
+    /// by default.
+    pub overwrite: Overwrite,
+    /// restore the uid and gid stored in the unixfs nodes, the entries keep the
+    /// current user as owner if the user has no privilege to change the owner.
+    pub preserve_owner: bool,
+    /// restore the mode and mtime stored in the unixfs nodes, the directories are
+    /// restored after their entries are extracted.
+    pub preserve_metadata: bool,
+    /// copy the targets in place of the symlinks instead of recreating the symlinks,
+    /// the targets must be extracted inside the extraction target.
+    pub dereference: bool,
+    /// the rewrites of the absolute symlink targets, the first matched one applies.
+    pub symlink_prefixes: Vec<SymlinkPrefix>,
+    /// restore the extended attributes stored in the unixfs nodes, the attributes
+    /// not permitted or not supported by the target file system are skipped.
+    #[cfg(feature = "xattr")]
+    pub preserve_xattrs: bool,
+    /// the token for cancelling the extract, checked between the entries and the blocks.
+    pub cancel: CancelToken,
+    /// the hook of the files extracted, the bytes of the blocks read and the bytes of the
+    /// file content written.
+    pub progress: Option<Arc<dyn ProgressHook>>,
+}
+
+impl ExtractOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_limits(mut self, limits: PathLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn with_follow_target_symlinks(mut self, follow: bool) -> Self {
+        self.follow_target_symlinks = follow;
+        self
+    }
+
+    pub fn with_overwrite(mut self, overwrite: Overwrite) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    pub fn with_preserve_owner(mut self, preserve_owner: bool) -> Self {
+        self.preserve_owner = preserve_owner;
+        self
+    }
+
+    pub fn with_preserve_metadata(mut self, preserve_metadata: bool) -> Self {
+        self.preserve_metadata = preserve_metadata;
+        self
+    }
+
+    pub fn with_dereference(mut self, dereference: bool) -> Self {
+        self.dereference = dereference;
+        self
+    }
+
+    pub fn with_symlink_prefix(mut self, prefix: SymlinkPrefix) -> Self {
+        self.symlink_prefixes.push(prefix);
+        self
+    }
+
+    /// the `target` rewritten by the first matched symlink prefix.
+    pub(crate) fn rewrite_symlink<'a>(&self, target: &'a str) -> Cow<'a, str> {
+        self.symlink_prefixes
+            .iter()
+            .find_map(|p| p.rewrite(target))
+            .map_or(Cow::Borrowed(target), Cow::Owned)
+    }
+
+    #[cfg(feature = "xattr")]
+    pub fn with_preserve_xattrs(mut self, preserve_xattrs: bool) -> Self {
+        self.preserve_xattrs = preserve_xattrs;
+        self
+    }
+
+    pub fn with_cancel(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    pub fn with_progress(mut self, progress: Arc<dyn ProgressHook>) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+}
@@ -0,0 +1,281 @@
+//! create the files and directories in the extraction target without following symlinks.
+//! `root` is the extraction target, `rel` is the path relative to the target.
+use std::{
+    fs::File,
+    io,
+    path::{Component, Path},
+};
+
+use crate::error::CarError;
+
+#[cfg(unix)]
+mod imp {
+    use std::{
+        ffi::{CStr, CString, OsStr},
+        fs::{self, File},
+        io,
+        os::unix::{
+            ffi::OsStrExt,
+            fs::OpenOptionsExt,
+            io::{AsRawFd, FromRawFd, OwnedFd},
+        },
+        path::{Component, Path},
+    };
+
+    fn cstring(name: &OsStr) -> io::Result<CString> {
+        CString::new(name.as_bytes()).map_err(|_| io::ErrorKind::InvalidInput.into())
+    }
+
+    fn symlink_error() -> io::Error {
+        io::Error::from_raw_os_error(libc::ELOOP)
+    }
+
+    fn is_symlink_at(dir: &OwnedFd, name: &CStr) -> bool {
+        let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+        let rs = unsafe {
+            libc::fstatat(
+                dir.as_raw_fd(),
+                name.as_ptr(),
+                &mut stat,
+                libc::AT_SYMLINK_NOFOLLOW,
+            )
+        };
+        rs == 0 && (stat.st_mode & libc::S_IFMT) == libc::S_IFLNK
+    }
+
+    fn open_dir_at(dir: &OwnedFd, name: &CStr) -> io::Result<OwnedFd> {
+        let flags = libc::O_RDONLY | libc::O_DIRECTORY | libc::O_NOFOLLOW | libc::O_CLOEXEC;
+        let fd = unsafe { libc::openat(dir.as_raw_fd(), name.as_ptr(), flags) };
+        if fd < 0 {
+            let err = io::Error::last_os_error();
+            // some platforms report ENOTDIR instead of ELOOP for the symlink to directory.
+            if err.raw_os_error() == Some(libc::ENOTDIR) && is_symlink_at(dir, name) {
+                return Err(symlink_error());
+            }
+            return Err(err);
+        }
+        Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+    }
+
+    fn mkdir_at(dir: &OwnedFd, name: &CStr) -> io::Result<()> {
+        if unsafe { libc::mkdirat(dir.as_raw_fd(), name.as_ptr(), 0o777) } < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::AlreadyExists {
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    fn open_root(root: &Path) -> io::Result<OwnedFd> {
+        if fs::symlink_metadata(root)?.is_symlink() {
+            return Err(symlink_error());
+        }
+        let file = fs::OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_DIRECTORY | libc::O_NOFOLLOW)
+            .open(root)?;
+        Ok(file.into())
+    }
+
+    /// open the parent directory of `rel` component by component, return the parent and the file name.
+    fn open_parent(root: &Path, rel: &Path) -> io::Result<(OwnedFd, CString)> {
+        let mut names = Vec::new();
+        for component in rel.components() {
+            match component {
+                Component::Normal(name) => names.push(cstring(name)?),
+                Component::CurDir => {}
+                _ => return Err(io::ErrorKind::InvalidInput.into()),
+            }
+        }
+        let name = names.pop().ok_or(io::ErrorKind::InvalidInput)?;
+        let mut dir = open_root(root)?;
+        for n in names.iter() {
+            dir = open_dir_at(&dir, n)?;
+        }
+        Ok((dir, name))
+    }
+
+    pub(super) fn create_dir(root: &Path, rel: &Path) -> io::Result<()> {
+        if rel.as_os_str().is_empty() {
+            match fs::symlink_metadata(root) {
+                Ok(m) if m.is_symlink() => return Err(symlink_error()),
+                Ok(_) => return Ok(()),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => return fs::create_dir(root),
+                Err(e) => return Err(e),
+            }
+        }
+        let (dir, name) = open_parent(root, rel)?;
+        mkdir_at(&dir, &name)?;
+        // make sure the existing entry is a directory rather than a symlink.
+        open_dir_at(&dir, &name).map(|_| ())
+    }
+
+    pub(super) fn create_file(root: &Path, rel: &Path, replace: bool) -> io::Result<File> {
+        if rel.as_os_str().is_empty() {
+            return fs::OpenOptions::new()
+                .create(replace)
+                .truncate(replace)
+                .create_new(!replace)
+                .write(true)
+                .custom_flags(libc::O_NOFOLLOW)
+                .open(root);
+        }
+        let (dir, name) = open_parent(root, rel)?;
+        let replace_flag = if replace { libc::O_TRUNC } else { libc::O_EXCL };
+        let flags =
+            libc::O_WRONLY | libc::O_CREAT | replace_flag | libc::O_NOFOLLOW | libc::O_CLOEXEC;
+        let fd = unsafe { libc::openat(dir.as_raw_fd(), name.as_ptr(), flags, 0o666) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(unsafe { File::from_raw_fd(fd) })
+    }
+
+    pub(super) fn create_symlink(
+        root: &Path,
+        rel: &Path,
+        target: &str,
+        replace: bool,
+    ) -> io::Result<()> {
+        if rel.as_os_str().is_empty() {
+            match fs::symlink_metadata(root) {
+                Ok(m) if replace && !m.is_dir() => fs::remove_file(root)?,
+                _ => {}
+            }
+            return std::os::unix::fs::symlink(target, root);
+        }
+        let target = cstring(OsStr::new(target))?;
+        let (dir, name) = open_parent(root, rel)?;
+        let symlink_at =
+            || unsafe { libc::symlinkat(target.as_ptr(), dir.as_raw_fd(), name.as_ptr()) };
+        if symlink_at() < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::AlreadyExists || !replace {
+                return Err(err);
+            }
+            // replace the existing entry, like the files are truncated.
+            if unsafe { libc::unlinkat(dir.as_raw_fd(), name.as_ptr(), 0) } < 0 || symlink_at() < 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) fn is_symlink_error(err: &io::Error) -> bool {
+        err.raw_os_error() == Some(libc::ELOOP)
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use std::{
+        fs::{self, File},
+        io,
+        path::Path,
+    };
+
+    /// check there is no symlink in the existing components of the path.
+    fn check_no_symlink(root: &Path, rel: &Path) -> io::Result<()> {
+        let mut path = root.to_path_buf();
+        let mut components = rel.components();
+        loop {
+            match fs::symlink_metadata(&path) {
+                Ok(m) if m.is_symlink() => {
+                    return Err(io::Error::new(io::ErrorKind::Other, SYMLINK_ERROR))
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+                Err(e) => return Err(e),
+                Ok(_) => {}
+            }
+            match components.next() {
+                Some(c) => path.push(c),
+                None => return Ok(()),
+            }
+        }
+    }
+
+    const SYMLINK_ERROR: &str = "symlink in the extraction target";
+
+    pub(super) fn create_dir(root: &Path, rel: &Path) -> io::Result<()> {
+        check_no_symlink(root, rel)?;
+        let path = root.join(rel);
+        if !path.exists() {
+            fs::create_dir(&path)?;
+        }
+        Ok(())
+    }
+
+    pub(super) fn create_file(root: &Path, rel: &Path, replace: bool) -> io::Result<File> {
+        check_no_symlink(root, rel)?;
+        fs::OpenOptions::new()
+            .create(replace)
+            .truncate(replace)
+            .create_new(!replace)
+            .write(true)
+            .open(root.join(rel))
+    }
+
+    /// the symlinks need the privilege on windows, they are not recreated.
+    pub(super) fn create_symlink(
+        _root: &Path,
+        _rel: &Path,
+        _target: &str,
+        _replace: bool,
+    ) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    pub(super) fn is_symlink_error(err: &io::Error) -> bool {
+        err.get_ref().map(|e| e.to_string()) == Some(SYMLINK_ERROR.to_string())
+    }
+}
+
+/// check the link `name` of the directory `dir` is a single normal path component,
+/// the names come from the CAR, `..`, the separators and the absolute paths would escape the target.
+pub(crate) fn check_name(dir: &Path, name: &str) -> Result<(), CarError> {
+    let mut components = Path::new(name).components();
+    let normal = match (components.next(), components.next()) {
+        (Some(Component::Normal(n)), None) => n == name,
+        _ => false,
+    };
+    if !normal || name.contains(['/', '\\', '\0']) {
+        return Err(CarError::UnsafePath(format!(
+            "{name:?} in {}",
+            dir.display()
+        )));
+    }
+    Ok(())
+}
+
+/// create the directory `rel` in the `root` without following symlinks.
+#[inline]
+pub(crate) fn create_dir(root: &Path, rel: &Path) -> io::Result<()> {
+    imp::create_dir(root, rel)
+}
+
+/// create the file `rel` in the `root` without following symlinks, the existing file
+/// is truncated if `replace`, otherwise the `AlreadyExists` error is returned.
+#[inline]
+pub(crate) fn create_file(root: &Path, rel: &Path, replace: bool) -> io::Result<File> {
+    imp::create_file(root, rel, replace)
+}
+
+/// create the symlink `rel` to the `target` in the `root` without following symlinks,
+/// the existing entry is replaced if `replace`, otherwise the `AlreadyExists` error is returned.
+#[inline]
+pub(crate) fn create_symlink(
+    root: &Path,
+    rel: &Path,
+    target: &str,
+    replace: bool,
+) -> io::Result<()> {
+    imp::create_symlink(root, rel, target, replace)
+}
+
+/// check the error is caused by a symlink in the path.
+#[inline]
+pub(crate) fn is_symlink_error(err: &io::Error) -> bool {
+    imp::is_symlink_error(err)
+}
@@ -0,0 +1,161 @@
+//! recreate the symlinks of the extraction, or copy their targets with the `dereference`.
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+use cid::Cid;
+
+use crate::{error::CarError, unixfs::UnixFs};
+
+use super::{check_overwrite, create_dir, create_file, secure, ExtractOptions, Overwrite};
+
+/// the bound of the chained symlinks, same as the linux.
+const MAX_SYMLINK_HOPS: usize = 40;
+
+/// the target of the symlink node.
+pub(super) fn symlink_target(cid: Cid, node: &UnixFs) -> Result<String, CarError> {
+    node.symlink_target()
+        .map(String::from)
+        .ok_or_else(|| CarError::InvalidSection(format!("the symlink {cid} has no utf8 target")))
+}
+
+/// create the symlinks extracted to the paths in the extraction target `root`,
+/// or copy their targets in place with the `options.dereference`.
+/// the targets are rewritten by the `options.symlink_prefixes` first.
+pub(super) fn extract_symlinks(
+    root: &Path,
+    symlinks: &[(PathBuf, String)],
+    options: &ExtractOptions,
+) -> Result<(), CarError> {
+    let symlinks: Vec<(&Path, Cow<str>)> = symlinks
+        .iter()
+        .map(|(path, target)| (path.as_path(), options.rewrite_symlink(target)))
+        .collect();
+    if options.dereference {
+        let links: HashMap<&Path, &str> = symlinks
+            .iter()
+            .map(|(path, target)| (*path, target.as_ref()))
+            .collect();
+        for (path, target) in symlinks.iter() {
+            let source = resolve(root, path, target, &links).ok_or_else(|| {
+                CarError::InvalidFile(format!(
+                    "the symlink {} -> {target} is out of the extraction target",
+                    path.display()
+                ))
+            })?;
+            copy_entry(root, &source, path, options)?;
+        }
+        return Ok(());
+    }
+    for (path, target) in symlinks.iter() {
+        create_symlink(root, path, target, options)?;
+    }
+    Ok(())
+}
+
+/// create the symlink in the extraction target `root`, the existing entry is kept or
+/// replaced by the `options.overwrite`.
+/// the symlinks in the target are not followed unless `follow_target_symlinks` is set.
+fn create_symlink(
+    root: &Path,
+    path: &Path,
+    target: &str,
+    options: &ExtractOptions,
+) -> Result<(), CarError> {
+    let replace = options.overwrite == Overwrite::Always;
+    #[cfg(unix)]
+    if options.follow_target_symlinks {
+        if replace && fs::symlink_metadata(path).is_ok_and(|m| !m.is_dir()) {
+            fs::remove_file(path)?;
+        }
+        check_overwrite(std::os::unix::fs::symlink(target, path), path, options)?;
+        return Ok(());
+    }
+    let rel = path.strip_prefix(root).unwrap_or(path);
+    check_overwrite(
+        secure::create_symlink(root, rel, target, replace),
+        path,
+        options,
+    )?;
+    Ok(())
+}
+
+/// resolve the symlink at the `path` lexically in the `root`, the chained symlinks
+/// of the extraction are followed. return none if the target escapes the `root`.
+fn resolve(
+    root: &Path,
+    path: &Path,
+    target: &str,
+    links: &HashMap<&Path, &str>,
+) -> Option<PathBuf> {
+    let (mut path, mut target) = (path.to_path_buf(), target);
+    for _ in 0..MAX_SYMLINK_HOPS {
+        let mut resolved = path.strip_prefix(root).ok()?.parent()?.to_path_buf();
+        for component in Path::new(target).components() {
+            match component {
+                Component::Normal(name) => resolved.push(name),
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    if !resolved.pop() {
+                        return None;
+                    }
+                }
+                // the absolute targets are out of the extraction.
+                Component::RootDir | Component::Prefix(_) => return None,
+            }
+        }
+        path = root.join(resolved);
+        match links.get(path.as_path()) {
+            Some(next) => target = next,
+            None => return Some(path),
+        }
+    }
+    None
+}
+
+/// copy the extracted file or directory `source` to the `dest`, the symlinks
+/// inside the copied directories are left out.
+fn copy_entry(
+    root: &Path,
+    source: &Path,
+    dest: &Path,
+    options: &ExtractOptions,
+) -> Result<(), CarError> {
+    let metadata = match fs::symlink_metadata(source) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(CarError::InvalidFile(format!(
+                "the symlink {} is dangling",
+                dest.display()
+            )))
+        }
+        Err(e) => return Err(e.into()),
+    };
+    if metadata.is_symlink() {
+        return Err(CarError::TargetSymlink(source.display().to_string()));
+    }
+    if metadata.is_file() {
+        if let Some(mut file) = create_file(root, dest, options)? {
+            io::copy(&mut fs::File::open(source)?, &mut file)?;
+        }
+        return Ok(());
+    }
+    if dest.starts_with(source) {
+        return Err(CarError::InvalidFile(format!(
+            "the symlink {} links its ancestor",
+            dest.display()
+        )));
+    }
+    create_dir(root, dest, options)?;
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        if entry.file_type()?.is_symlink() {
+            continue;
+        }
+        copy_entry(root, &entry.path(), &dest.join(entry.file_name()), options)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,238 @@
+use std::{
+    num::NonZeroUsize,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+};
+
+use cid::{
+    multihash::{Code, MultihashDigest},
+    Cid,
+};
+
+use crate::{
+    error::CarError,
+    reader::CarReader,
+    utils::{ordered::OrderedReceiver, IDENTITY_CODE},
+};
+
+/// the blocks read ahead for every hashing worker.
+const READ_AHEAD_PER_JOB: usize = 4;
+
+/// the report of the blocks verification.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// the count of the blocks verified.
+    pub blocks: usize,
+    /// the blocks whose content don't match the cid.
+    pub failures: Vec<Cid>,
+    /// the offsets of the block data of the `failures` in the car file.
+    pub failure_offsets: Vec<u64>,
+    /// the last section ends after the end of the file, its block is not verified.
+    pub truncated: bool,
+}
+
+impl VerifyReport {
+    #[inline(always)]
+    pub fn is_ok(&self) -> bool {
+        self.failures.is_empty() && !self.truncated
+    }
+}
+
+/// the block can't be read because the file is cut off in its section, the section cut off
+/// is the last one.
+fn is_cut_off(err: &CarError) -> bool {
+    matches!(err, CarError::IO(e) if e.kind() == std::io::ErrorKind::UnexpectedEof)
+}
+
+/// check the digest of the block `data` matches the `cid`.
+pub fn verify_block(cid: &Cid, data: &[u8]) -> Result<bool, CarError> {
+    let hash = cid.hash();
+    if hash.code() == IDENTITY_CODE {
+        return Ok(hash.digest() == data);
+    }
+    let code = Code::try_from(hash.code())
+        .map_err(|_| CarError::NotImplemented(format!("hash code {:#x}", hash.code())))?;
+    Ok(code.digest(data) == *hash)
+}
+
+/// the offsets of the block data of the sections at the indexes in the file order,
+/// the sections are indexed only if there are the indexes.
+fn section_offsets(reader: &mut impl CarReader, indexes: &[usize]) -> Result<Vec<u64>, CarError> {
+    if indexes.is_empty() {
+        return Ok(Vec::new());
+    }
+    let sections = reader.sections()?;
+    Ok(indexes.iter().map(|i| sections[*i].pos()).collect())
+}
+
+/// re-hash all blocks in the car file and check the digests match their cids.
+pub fn verify_blocks(reader: &mut impl CarReader) -> Result<VerifyReport, CarError> {
+    let mut report = VerifyReport::default();
+    let mut failed = Vec::new();
+    for block in reader.blocks() {
+        let (cid, data) = match block {
+            Ok(block) => block,
+            Err(e) if is_cut_off(&e) => {
+                report.truncated = true;
+                break;
+            }
+            Err(e) => return Err(e),
+        };
+        if !verify_block(&cid, &data)? {
+            report.failures.push(cid);
+            failed.push(report.blocks);
+        }
+        report.blocks += 1;
+    }
+    report.failure_offsets = section_offsets(reader, &failed)?;
+    Ok(report)
+}
+
+/// re-hash all blocks with `jobs` hashing workers, the blocks are read ahead by the
+/// current thread and the results are collected in the file order.
+/// `jobs` is the count of the workers, 0 means the available parallelism. the report is the
+/// same for any `jobs`.
+pub fn verify_blocks_parallel(
+    reader: &mut impl CarReader,
+    jobs: usize,
+) -> Result<VerifyReport, CarError> {
+    let jobs = match jobs {
+        0 => thread::available_parallelism().map_or(1, NonZeroUsize::get),
+        n => n,
+    };
+    if jobs == 1 {
+        return verify_blocks(reader);
+    }
+    let (block_tx, block_rx) =
+        mpsc::sync_channel::<(usize, Cid, Vec<u8>)>(jobs * READ_AHEAD_PER_JOB);
+    let block_rx = Arc::new(Mutex::new(block_rx));
+    let (result_tx, result_rx) = mpsc::channel();
+    let read_result = thread::scope(|scope| {
+        for _ in 0..jobs {
+            let block_rx = block_rx.clone();
+            let result_tx = result_tx.clone();
+            scope.spawn(move || loop {
+                // the lock is released before hashing.
+                let received = block_rx.lock().unwrap().recv();
+                let Ok((idx, cid, data)) = received else {
+                    break;
+                };
+                if result_tx
+                    .send((idx, (cid, verify_block(&cid, &data))))
+                    .is_err()
+                {
+                    break;
+                }
+            });
+        }
+        drop(result_tx);
+        let (mut blocks, mut truncated) = (0, false);
+        for block in reader.blocks() {
+            let (cid, data) = match block {
+                Ok(block) => block,
+                Err(e) if is_cut_off(&e) => {
+                    truncated = true;
+                    break;
+                }
+                Err(e) => return Err(e),
+            };
+            // the workers only stop when the channel is closed.
+            let _ = block_tx.send((blocks, cid, data));
+            blocks += 1;
+        }
+        drop(block_tx);
+        Ok::<_, CarError>((blocks, truncated))
+    });
+    let (blocks, truncated) = read_result?;
+    let mut report = VerifyReport {
+        blocks,
+        truncated,
+        ..Default::default()
+    };
+    let mut failed = Vec::new();
+    for (idx, (cid, rs)) in OrderedReceiver::new(result_rx) {
+        if !rs? {
+            report.failures.push(cid);
+            failed.push(idx);
+        }
+    }
+    report.failure_offsets = section_offsets(reader, &failed)?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        header::CarHeader,
+        reader,
+        utils::raw_cid,
+        writer::{self, CarWriter},
+    };
+    use std::io::Cursor;
+
+    #[test]
+    fn test_verify_blocks() {
+        let file = std::path::Path::new("test").join("carv1-basic.car");
+        let file = std::fs::File::open(file).unwrap();
+        let mut reader = reader::new_v1(file).unwrap();
+        let report = verify_blocks(&mut reader).unwrap();
+        assert_eq!(report.blocks, 6);
+        assert!(report.is_ok());
+
+        let good = raw_cid(b"good", multicodec::Codec::Sha2_256);
+        let bad = raw_cid(b"bad", multicodec::Codec::Sha2_256);
+        let mut buf = Cursor::new(vec![]);
+        {
+            let mut writer = writer::new_v1(&mut buf, CarHeader::new_v1(vec![good])).unwrap();
+            writer.write_block(good, b"good").unwrap();
+            writer.write_block(bad, b"corrupted").unwrap();
+            writer.flush().unwrap();
+        }
+        let mut data = buf.into_inner();
+        let mut reader = reader::new_v1(Cursor::new(data.clone())).unwrap();
+        let report = verify_blocks(&mut reader).unwrap();
+        assert_eq!(report.blocks, 2);
+        assert_eq!(report.failures, vec![bad]);
+        let offset = reader.sections().unwrap()[1].pos();
+        assert_eq!(report.failure_offsets, vec![offset]);
+
+        // the cut off section is reported instead of failing the verification.
+        data.truncate(data.len() - 2);
+        let mut reader = reader::new_v1(Cursor::new(data)).unwrap();
+        for jobs in [1, 4] {
+            let report = verify_blocks_parallel(&mut reader, jobs).unwrap();
+            assert_eq!(report.blocks, 1);
+            assert!(report.truncated);
+            assert!(!report.is_ok());
+        }
+    }
+
+    #[test]
+    fn test_verify_blocks_parallel() {
+        let mut buf = Cursor::new(vec![]);
+        let mut expected = vec![];
+        {
+            let root = raw_cid(b"root", multicodec::Codec::Sha2_256);
+            let mut writer = writer::new_v1(&mut buf, CarHeader::new_v1(vec![root])).unwrap();
+            for i in 0..100u32 {
+                let data = i.to_be_bytes();
+                let cid = raw_cid(&data, multicodec::Codec::Sha2_256);
+                if i % 7 == 0 {
+                    writer.write_block(cid, b"corrupted").unwrap();
+                    expected.push(cid);
+                } else {
+                    writer.write_block(cid, data).unwrap();
+                }
+            }
+            writer.flush().unwrap();
+        }
+        let mut reader = reader::new_v1(Cursor::new(buf.into_inner())).unwrap();
+        for jobs in [0, 1, 4] {
+            let report = verify_blocks_parallel(&mut reader, jobs).unwrap();
+            assert_eq!(report.blocks, 100);
+            assert_eq!(report.failures, expected);
+            assert_eq!(report.failure_offsets.len(), expected.len());
+        }
+    }
+}
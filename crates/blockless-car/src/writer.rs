@@ -7,8 +7,20 @@ use crate::{
     CarHeader, Ipld,
 };
 
+#[cfg(feature = "tokio")]
+mod async_writer_v1;
+mod dedup;
+mod stream_writer_v1;
+mod tee_writer;
 mod writer_v1;
+#[cfg(feature = "tokio")]
+pub use async_writer_v1::AsyncCarWriterV1;
+pub use dedup::BlockDedup;
+pub(crate) use dedup::WrittenBlocks;
+pub use stream_writer_v1::StreamCarWriterV1;
+pub use tee_writer::TeeCarWriter;
 pub(crate) use writer_v1::CarWriterV1;
+pub use writer_v1::DEFAULT_WRITE_BUFFER_SIZE;
 
 pub enum WriteStream<'bs> {
     Bytes(&'bs [u8]),
@@ -49,9 +61,24 @@ pub trait CarWriter {
         }
     }
 
+    /// rewrite the header of the same encoded length, e.g. the roots known after the blocks.
+    /// the header of another length, like the different roots count, is replaced in the
+    /// written file by `utils::replace_roots`.
     fn rewrite_header(&mut self, header: CarHeader) -> Result<(), CarError>;
 
     fn flush(&mut self) -> Result<(), CarError>;
+
+    /// the bytes of the header and the blocks written, e.g. for the progress of the pack.
+    /// the writers not counting them return 0.
+    fn bytes_written(&self) -> u64 {
+        0
+    }
+
+    /// the blocks written, the duplicates skipped are not counted.
+    /// the writers not counting them return 0.
+    fn blocks_written(&self) -> u64 {
+        0
+    }
 }
 
 pub fn new_v1<W>(inner: W, header: CarHeader) -> Result<impl CarWriter, CarError>
@@ -61,6 +88,29 @@ where
     Ok(CarWriterV1::new(inner, header))
 }
 
+/// create the writer buffering the `buffer_size` bytes before writing to the output, e.g.
+/// `DEFAULT_WRITE_BUFFER_SIZE`, the blocks buffered are written by the `flush` and the
+/// `rewrite_header`, or by the drop ignoring the errors like the `BufWriter`. the writer of
+/// `new_v1` writes every call through.
+pub fn new_v1_with_buffer_size<W>(
+    inner: W,
+    header: CarHeader,
+    buffer_size: usize,
+) -> Result<impl CarWriter, CarError>
+where
+    W: std::io::Write + std::io::Seek,
+{
+    Ok(CarWriterV1::new(inner, header).with_buffer_size(buffer_size))
+}
+
+/// create the writer over the non-seekable output, the `header` must contain the final roots.
+pub fn new_stream_v1<W>(inner: W, header: CarHeader) -> Result<impl CarWriter, CarError>
+where
+    W: std::io::Write,
+{
+    Ok(StreamCarWriterV1::new(inner, header))
+}
+
 pub fn new_v1_default_roots<W>(
     inner: W,
     hasher_codec: multicodec::Codec,
@@ -0,0 +1,164 @@
+use cid::Cid;
+use integer_encoding::VarInt;
+use ipld::{pb::DagPbCodec, prelude::Codec};
+use tokio::io::{AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
+
+use super::{BlockDedup, WrittenBlocks};
+use crate::{
+    error::CarError,
+    header::CarHeader,
+    utils::{pb_cid, raw_cid},
+    Ipld,
+};
+
+/// the CARv1 writer over the `tokio::io::AsyncWrite`.
+/// the output is not required to be seekable, unless the header is rewritten.
+pub struct AsyncCarWriterV1<W> {
+    inner: W,
+    header: CarHeader,
+    is_header_written: bool,
+    hashes_written: WrittenBlocks,
+    // the sections written.
+    blocks: u64,
+}
+
+impl<W> AsyncCarWriterV1<W>
+where
+    W: AsyncWrite + Unpin,
+{
+    pub fn new(inner: W, header: CarHeader) -> Self {
+        Self {
+            inner,
+            header,
+            is_header_written: false,
+            hashes_written: WrittenBlocks::default(),
+            blocks: 0,
+        }
+    }
+
+    /// the blocks written, the duplicates skipped are not counted.
+    #[inline(always)]
+    pub fn blocks_written(&self) -> u64 {
+        self.blocks
+    }
+
+    /// the blocks remembered to skip the repeated ones, set before the first block.
+    pub fn with_dedup(mut self, dedup: BlockDedup) -> Self {
+        self.hashes_written = WrittenBlocks::new(dedup);
+        self
+    }
+
+    async fn write_head(&mut self) -> Result<(), CarError> {
+        let head = self.header.encode()?;
+        self.inner.write_all(&head.len().encode_var_vec()).await?;
+        self.inner.write_all(&head).await?;
+        self.is_header_written = true;
+        Ok(())
+    }
+
+    pub async fn write_block<T>(&mut self, cid: Cid, data: T) -> Result<(), CarError>
+    where
+        T: AsRef<[u8]>,
+    {
+        if !self.is_header_written {
+            self.write_head().await?;
+        }
+        if !self.hashes_written.contains(&cid) {
+            let cid_buf = cid.to_bytes();
+            let data = data.as_ref();
+            let sec_len = data.len() + cid_buf.len();
+            self.inner.write_all(&sec_len.encode_var_vec()).await?;
+            self.inner.write_all(&cid_buf).await?;
+            self.inner.write_all(data).await?;
+            self.hashes_written.insert(cid);
+            self.blocks += 1;
+        }
+        Ok(())
+    }
+
+    pub async fn write_ipld(
+        &mut self,
+        ipld: Ipld,
+        hasher_codec: multicodec::Codec,
+    ) -> Result<Cid, CarError> {
+        match ipld {
+            Ipld::Bytes(buf) => {
+                let file_cid = raw_cid(&buf, hasher_codec);
+                self.write_block(file_cid, &buf).await?;
+                Ok(file_cid)
+            }
+            fs_ipld @ Ipld::Map(_) => {
+                let bs: Vec<u8> = DagPbCodec
+                    .encode(&fs_ipld)
+                    .map_err(|e| CarError::Parsing(e.to_string()))?;
+                let cid = pb_cid(&bs, hasher_codec);
+                self.write_block(cid, &bs).await?;
+                Ok(cid)
+            }
+            _ => Err(CarError::Parsing("Not support write ipld.".to_lowercase())),
+        }
+    }
+
+    pub async fn flush(&mut self) -> Result<(), CarError> {
+        if !self.is_header_written {
+            self.write_head().await?;
+        }
+        self.inner.flush().await?;
+        Ok(())
+    }
+
+    /// return the inner writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W> AsyncCarWriterV1<W>
+where
+    W: AsyncWrite + AsyncSeek + Unpin,
+{
+    /// the new header must have the same encoded length, the blocks follow the header.
+    pub async fn rewrite_header(&mut self, header: CarHeader) -> Result<(), CarError> {
+        if header.encode()?.len() != self.header.encode()?.len() {
+            return Err(CarError::InvalidSection(
+                "the length of the header is not match.".to_string(),
+            ));
+        }
+        self.header = header;
+        self.inner.rewind().await?;
+        self.write_head().await
+    }
+}
+
+#[cfg(all(test, feature = "reader"))]
+mod test {
+    use cid::multihash::{Code::Blake2b256, MultihashDigest};
+    use ipld_cbor::DagCborCodec;
+
+    use super::*;
+    use crate::reader::{self, AsyncCarReaderV1, CarReader};
+
+    #[tokio::test]
+    async fn test_async_writer_reader_v1() {
+        let cid_test1 = Cid::new_v1(DagCborCodec.into(), Blake2b256.digest(b"test"));
+        let cid_test2 = Cid::new_v1(DagCborCodec.into(), Blake2b256.digest(b"test2"));
+        let mut writer = AsyncCarWriterV1::new(Vec::new(), CarHeader::new_v1(vec![cid_test2]));
+        writer.write_block(cid_test1, b"test1").await.unwrap();
+        writer.write_block(cid_test2, b"test2").await.unwrap();
+        writer.flush().await.unwrap();
+        let buffer = writer.into_inner();
+
+        // the sync reader reads the same format.
+        let mut car_reader = reader::new_v1(std::io::Cursor::new(&buffer)).unwrap();
+        assert_eq!(car_reader.header().roots(), vec![cid_test2]);
+        assert_eq!(car_reader.sections().unwrap().len(), 2);
+
+        let mut reader = AsyncCarReaderV1::new(&buffer[..]).await.unwrap();
+        assert_eq!(reader.header().roots(), vec![cid_test2]);
+        let block = reader.next_block().await.unwrap();
+        assert_eq!(block, Some((cid_test1, b"test1".to_vec())));
+        let block = reader.next_block().await.unwrap();
+        assert_eq!(block, Some((cid_test2, b"test2".to_vec())));
+        assert_eq!(reader.next_block().await.unwrap(), None);
+    }
+}
@@ -0,0 +1,112 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    str::FromStr,
+};
+
+use cid::Cid;
+
+use crate::error::CarError;
+
+/// which blocks the writer remembers to skip the blocks written before.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BlockDedup {
+    /// every cid written is kept, each block is written once.
+    #[default]
+    All,
+    /// no cid is kept, the repeated blocks are written again, for the inputs known unique.
+    Off,
+    /// only the last cids written are kept, the memory is bounded and the repeated blocks
+    /// close together are still written once.
+    Recent(usize),
+}
+
+/// parse `all`, `off` or the count of the recent cids.
+impl FromStr for BlockDedup {
+    type Err = CarError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(BlockDedup::All),
+            "off" => Ok(BlockDedup::Off),
+            n => n.parse().map(BlockDedup::Recent).map_err(|_| {
+                CarError::Parsing(format!("{s}: expect all, off or the count of the cids"))
+            }),
+        }
+    }
+}
+
+/// the cids written by the writer of the `BlockDedup`.
+#[derive(Debug, Default)]
+pub(crate) struct WrittenBlocks {
+    dedup: BlockDedup,
+    cids: HashSet<Cid>,
+    // the order of the cids written, only for the `Recent`.
+    order: VecDeque<Cid>,
+}
+
+impl WrittenBlocks {
+    pub(crate) fn new(dedup: BlockDedup) -> Self {
+        Self {
+            dedup,
+            ..Default::default()
+        }
+    }
+
+    #[inline]
+    pub(crate) fn contains(&self, cid: &Cid) -> bool {
+        self.cids.contains(cid)
+    }
+
+    pub(crate) fn insert(&mut self, cid: Cid) {
+        match self.dedup {
+            BlockDedup::All => {
+                self.cids.insert(cid);
+            }
+            BlockDedup::Off | BlockDedup::Recent(0) => {}
+            BlockDedup::Recent(capacity) => {
+                if !self.cids.insert(cid) {
+                    return;
+                }
+                self.order.push_back(cid);
+                if self.order.len() > capacity {
+                    if let Some(old) = self.order.pop_front() {
+                        self.cids.remove(&old);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cid::multihash::{Code, MultihashDigest};
+
+    #[test]
+    fn test_written_blocks() {
+        let cids: Vec<Cid> = (0..3u8)
+            .map(|i| Cid::new_v1(0x55, Code::Sha2_256.digest(&[i])))
+            .collect();
+        let mut all = WrittenBlocks::new(BlockDedup::All);
+        let mut off = WrittenBlocks::new(BlockDedup::Off);
+        let mut recent = WrittenBlocks::new(BlockDedup::Recent(2));
+        for cid in cids.iter() {
+            all.insert(*cid);
+            off.insert(*cid);
+            recent.insert(*cid);
+        }
+        assert!(cids.iter().all(|c| all.contains(c)));
+        assert!(!cids.iter().any(|c| off.contains(c)));
+        // the oldest is forgotten.
+        assert!(!recent.contains(&cids[0]));
+        assert!(recent.contains(&cids[1]) && recent.contains(&cids[2]));
+
+        assert_eq!("off".parse::<BlockDedup>().unwrap(), BlockDedup::Off);
+        assert_eq!(
+            "100".parse::<BlockDedup>().unwrap(),
+            BlockDedup::Recent(100)
+        );
+        assert!("some".parse::<BlockDedup>().is_err());
+    }
+}
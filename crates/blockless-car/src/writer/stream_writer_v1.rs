@@ -0,0 +1,128 @@
+use cid::Cid;
+
+use super::{BlockDedup, CarWriter, CarWriterV1, WriteStream};
+use crate::{error::CarError, header::CarHeader};
+
+/// the CARv1 writer over the plain `Write`, e.g. the stdout or the socket.
+/// the output is not required to be seekable, so the final roots must be given up front,
+/// the header is written with the first block and can't be rewritten after that.
+pub struct StreamCarWriterV1<W: std::io::Write> {
+    inner: CarWriterV1<W>,
+}
+
+impl<W> StreamCarWriterV1<W>
+where
+    W: std::io::Write,
+{
+    pub fn new(inner: W, header: CarHeader) -> Self {
+        Self {
+            inner: CarWriterV1::new(inner, header),
+        }
+    }
+
+    /// the blocks remembered to skip the repeated ones, `BlockDedup::All` by default.
+    pub fn with_dedup(mut self, dedup: BlockDedup) -> Self {
+        self.inner = self.inner.with_dedup(dedup);
+        self
+    }
+
+    /// the bytes buffered before writing to the output, none by default. the buffered
+    /// blocks are written by the `flush`, or by the drop ignoring the errors.
+    pub fn with_buffer_size(mut self, size: usize) -> Self {
+        self.inner = self.inner.with_buffer_size(size);
+        self
+    }
+}
+
+impl<W> CarWriter for StreamCarWriterV1<W>
+where
+    W: std::io::Write,
+{
+    #[inline(always)]
+    fn write_block<T>(&mut self, cid: Cid, data: T) -> Result<(), CarError>
+    where
+        T: AsRef<[u8]>,
+    {
+        self.inner.write_section(cid, data)
+    }
+
+    #[inline(always)]
+    fn stream_block<F, R>(
+        &mut self,
+        cid_f: F,
+        stream_len: usize,
+        r: &mut R,
+    ) -> Result<Cid, CarError>
+    where
+        R: std::io::Read + std::io::Seek,
+        F: FnMut(WriteStream) -> Option<Result<Cid, CarError>>,
+    {
+        self.inner.stream_section(cid_f, stream_len, r)
+    }
+
+    /// the header can be replaced only before the first block written.
+    fn rewrite_header(&mut self, header: CarHeader) -> Result<(), CarError> {
+        if self.inner.is_header_written() {
+            return Err(CarError::InvalidSection(
+                "the header is written to the stream.".to_string(),
+            ));
+        }
+        self.inner.replace_header(header);
+        Ok(())
+    }
+
+    /// write the header if no block written and flush the output.
+    fn flush(&mut self) -> Result<(), CarError> {
+        if !self.inner.is_header_written() {
+            self.inner.write_head()?;
+        }
+        self.inner.flush_inner()
+    }
+
+    #[inline(always)]
+    fn bytes_written(&self) -> u64 {
+        self.inner.written()
+    }
+
+    #[inline(always)]
+    fn blocks_written(&self) -> u64 {
+        self.inner.blocks()
+    }
+}
+
+#[cfg(all(test, feature = "reader"))]
+mod test {
+    use super::*;
+    use crate::{
+        reader::{self, CarReader},
+        utils::raw_cid,
+    };
+
+    /// the writer only implements `Write` like the pipe.
+    struct Pipe(Vec<u8>);
+
+    impl std::io::Write for Pipe {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_stream_write() {
+        let cid = raw_cid(b"test", multicodec::Codec::Sha2_256);
+        let mut pipe = Pipe(vec![]);
+        {
+            let mut writer = StreamCarWriterV1::new(&mut pipe, CarHeader::new_v1(vec![cid]));
+            writer.write_block(cid, b"test").unwrap();
+            assert!(writer.rewrite_header(CarHeader::new_v1(vec![cid])).is_err());
+            writer.flush().unwrap();
+        }
+        let mut reader = reader::new_v1(std::io::Cursor::new(pipe.0)).unwrap();
+        assert_eq!(reader.header().roots(), vec![cid]);
+        assert_eq!(reader.read_section_data(&cid).unwrap(), b"test");
+    }
+}
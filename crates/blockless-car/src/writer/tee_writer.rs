@@ -0,0 +1,144 @@
+use std::{
+    collections::HashSet,
+    io::{Read, Seek},
+};
+
+use cid::Cid;
+
+use super::{CarWriter, WriteStream};
+use crate::{error::CarError, header::CarHeader};
+
+/// the writer of each block to all the inner writers in one pass, e.g. the archive file
+/// and the upload stream. the dedup set is shared, the block written once is skipped
+/// by all the writers.
+pub struct TeeCarWriter<W> {
+    writers: Vec<W>,
+    written: HashSet<Cid>,
+}
+
+impl<W> TeeCarWriter<W>
+where
+    W: CarWriter,
+{
+    pub fn new(writers: Vec<W>) -> Self {
+        Self {
+            writers,
+            written: HashSet::new(),
+        }
+    }
+
+    /// the inner writers in the order given to `new`.
+    pub fn into_inner(self) -> Vec<W> {
+        self.writers
+    }
+}
+
+impl<W> CarWriter for TeeCarWriter<W>
+where
+    W: CarWriter,
+{
+    fn write_block<T>(&mut self, cid: Cid, data: T) -> Result<(), CarError>
+    where
+        T: AsRef<[u8]>,
+    {
+        if self.written.insert(cid) {
+            for writer in self.writers.iter_mut() {
+                writer.write_block(cid, data.as_ref())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// the block is read once into the memory and written to all the writers,
+    /// the streamed blocks are the file chunks.
+    fn stream_block<F, R>(
+        &mut self,
+        mut cid_f: F,
+        stream_len: usize,
+        r: &mut R,
+    ) -> Result<Cid, CarError>
+    where
+        R: Read + Seek,
+        F: FnMut(WriteStream) -> Option<Result<Cid, CarError>>,
+    {
+        let mut data = Vec::with_capacity(stream_len);
+        r.take(stream_len as u64).read_to_end(&mut data)?;
+        if let Some(Err(e)) = cid_f(WriteStream::Bytes(&data)) {
+            return Err(e);
+        }
+        let cid = match cid_f(WriteStream::End) {
+            Some(Ok(cid)) => cid,
+            Some(Err(e)) => return Err(e),
+            None => unreachable!("cid function cannot return None here"),
+        };
+        self.write_block(cid, &data)?;
+        Ok(cid)
+    }
+
+    /// rewrite the header of all the writers, the stream writers fail after the first block.
+    fn rewrite_header(&mut self, header: CarHeader) -> Result<(), CarError> {
+        for writer in self.writers.iter_mut() {
+            writer.rewrite_header(header.clone())?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), CarError> {
+        for writer in self.writers.iter_mut() {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    /// the bytes written by the first writer, the writers get the same blocks.
+    fn bytes_written(&self) -> u64 {
+        self.writers.first().map_or(0, |w| w.bytes_written())
+    }
+
+    /// the blocks written by the first writer.
+    fn blocks_written(&self) -> u64 {
+        self.writers.first().map_or(0, |w| w.blocks_written())
+    }
+}
+
+#[cfg(all(test, feature = "unixfs-fs"))]
+mod test {
+    use super::*;
+    use crate::{
+        utils::{pack_files_into, pack_files_with, raw_cid, PackOptions},
+        writer::StreamCarWriterV1,
+    };
+    use std::{fs, io::Cursor};
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_tee_write() {
+        let temp_dir = TempDir::new("blockless-car-temp-dir").unwrap();
+        let root_dir = temp_dir.path().join("root");
+        fs::create_dir_all(root_dir.join("sub")).unwrap();
+        fs::write(root_dir.join("a.txt"), b"hello").unwrap();
+        // the duplicate content is written once.
+        fs::write(root_dir.join("sub/b.txt"), b"hello").unwrap();
+        fs::write(root_dir.join("big.bin"), vec![3u8; 600_000]).unwrap();
+        let options = PackOptions::new();
+        let mut car = Cursor::new(vec![]);
+        let root = pack_files_with(&root_dir, &mut car, &options).unwrap();
+
+        // the stream writers are given the final roots up front.
+        let (mut archive, mut upload) = (vec![], vec![]);
+        let header = CarHeader::new_v1(vec![root]);
+        let mut tee = TeeCarWriter::new(vec![
+            StreamCarWriterV1::new(&mut archive, header.clone()),
+            StreamCarWriterV1::new(&mut upload, header),
+        ]);
+        let (tee_root, _) = pack_files_into(&root_dir, &mut tee, &options).unwrap();
+        let cid = raw_cid(b"hello", multicodec::Codec::Sha2_256);
+        tee.write_block(cid, b"hello").unwrap();
+        tee.flush().unwrap();
+        drop(tee);
+        assert_eq!(tee_root, root);
+        let car = car.into_inner();
+        assert_eq!(archive, car);
+        assert_eq!(upload, car);
+    }
+}
@@ -1,45 +1,148 @@
-use super::{CarWriter, WriteStream};
-use crate::{error::CarError, header::CarHeader};
+use super::{BlockDedup, CarWriter, WriteStream, WrittenBlocks};
+use crate::{error::CarError, header::CarHeader, section::write_section_header};
 use cid::Cid;
 use integer_encoding::VarIntWriter;
+use std::io::{self, Seek, SeekFrom, Write};
 
 // how many bytes to read at once from stream
 const BUFFER_SIZE: usize = 10240;
 
-pub(crate) struct CarWriterV1<W> {
+/// the bytes buffered by the pack writers before writing to the target, the framings
+/// and the small blocks are written in one call instead of three per block.
+pub const DEFAULT_WRITE_BUFFER_SIZE: usize = 64 << 10;
+
+/// the output buffering the writes up to the `capacity`, the buffer is written by the
+/// flush and before the seeks. like the `BufWriter` the buffer is written on drop too,
+/// the errors of the drop are ignored, so the `flush` is required to see them.
+struct Buffered<W: Write> {
     inner: W,
+    buf: Vec<u8>,
+    capacity: usize,
+}
+
+impl<W: Write> Buffered<W> {
+    fn flush_buf(&mut self) -> io::Result<()> {
+        self.inner.write_all(&self.buf)?;
+        self.buf.clear();
+        Ok(())
+    }
+}
+
+impl<W: Write> Drop for Buffered<W> {
+    fn drop(&mut self) {
+        if !self.buf.is_empty() {
+            let _ = self.flush_buf();
+        }
+    }
+}
+
+impl<W: Write> Write for Buffered<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if self.buf.len() + data.len() > self.capacity {
+            self.flush_buf()?;
+        }
+        if data.len() >= self.capacity {
+            return self.inner.write(data);
+        }
+        self.buf.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flush_buf()?;
+        self.inner.flush()
+    }
+}
+
+impl<W: Write + Seek> Seek for Buffered<W> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.flush_buf()?;
+        self.inner.seek(pos)
+    }
+}
+
+pub(crate) struct CarWriterV1<W: Write> {
+    inner: Buffered<W>,
     header: CarHeader,
     is_header_written: bool,
-    hashes_written: Vec<Cid>,
+    hashes_written: WrittenBlocks,
+    // the bytes of the header and the sections written, the header rewrites not counted.
+    written: u64,
+    // the sections written.
+    blocks: u64,
 }
 
 impl<W> CarWriterV1<W>
 where
-    W: std::io::Write + std::io::Seek,
+    W: std::io::Write,
 {
-    fn write_head(&mut self) -> Result<(), CarError> {
+    pub(crate) fn write_head(&mut self) -> Result<(), CarError> {
         let head = self.header.encode()?;
-        self.inner.write_varint(head.len())?;
+        let varint_len = self.inner.write_varint(head.len())?;
         self.inner.write_all(&head)?;
+        if !self.is_header_written {
+            self.written += (varint_len + head.len()) as u64;
+        }
         self.is_header_written = true;
         Ok(())
     }
 
     pub fn new(inner: W, header: CarHeader) -> Self {
         Self {
-            inner,
+            inner: Buffered {
+                inner,
+                buf: Vec::new(),
+                capacity: 0,
+            },
             header,
             is_header_written: false,
-            hashes_written: vec![],
+            hashes_written: WrittenBlocks::default(),
+            written: 0,
+            blocks: 0,
         }
     }
-}
 
-impl<W> CarWriter for CarWriterV1<W>
-where
-    W: std::io::Write + std::io::Seek,
-{
-    fn write_block<T>(&mut self, cid: cid::Cid, data: T) -> Result<(), CarError>
+    /// the blocks remembered to skip the repeated ones, set before the first block.
+    pub(crate) fn with_dedup(mut self, dedup: BlockDedup) -> Self {
+        self.hashes_written = WrittenBlocks::new(dedup);
+        self
+    }
+
+    /// the bytes buffered before writing to the output, none by default. the buffered
+    /// blocks are written by the `flush` and the `rewrite_header`.
+    pub(crate) fn with_buffer_size(mut self, size: usize) -> Self {
+        self.inner.capacity = size;
+        self.inner.buf = Vec::with_capacity(size);
+        self
+    }
+
+    #[inline(always)]
+    pub(crate) fn is_header_written(&self) -> bool {
+        self.is_header_written
+    }
+
+    /// replace the header not written yet, the output is not required to be seekable.
+    pub(crate) fn replace_header(&mut self, header: CarHeader) {
+        debug_assert!(!self.is_header_written);
+        self.header = header;
+    }
+
+    #[inline(always)]
+    pub(crate) fn written(&self) -> u64 {
+        self.written
+    }
+
+    #[inline(always)]
+    pub(crate) fn blocks(&self) -> u64 {
+        self.blocks
+    }
+
+    pub(crate) fn flush_inner(&mut self) -> Result<(), CarError> {
+        self.inner.flush()?;
+        Ok(())
+    }
+
+    pub(crate) fn write_section<T>(&mut self, cid: Cid, data: T) -> Result<(), CarError>
     where
         T: AsRef<[u8]>,
     {
@@ -47,36 +150,17 @@ where
             self.write_head()?;
         }
         if !self.hashes_written.contains(&cid) {
-            let mut cid_buff: Vec<u8> = Vec::new();
-            cid.write_bytes(&mut cid_buff)
-                .map_err(|e| CarError::Parsing(e.to_string()))?;
             let data = data.as_ref();
-            let sec_len = data.len() + cid_buff.len();
-            self.inner.write_varint(sec_len)?;
-            self.inner.write_all(&cid_buff[..])?;
+            let header_len = write_section_header(&mut self.inner, &cid, data.len())?;
             self.inner.write_all(data)?;
-            self.hashes_written.push(cid);
+            self.written += (header_len + data.len()) as u64;
+            self.hashes_written.insert(cid);
+            self.blocks += 1;
         }
         Ok(())
     }
 
-    fn flush(&mut self) -> Result<(), CarError> {
-        self.inner.flush()?;
-        Ok(())
-    }
-
-    fn rewrite_header(&mut self, header: CarHeader) -> Result<(), CarError> {
-        if header.roots().len() != self.header.roots().len() {
-            return Err(CarError::InvalidSection(
-                "the root cid is not match.".to_string(),
-            ));
-        }
-        self.header = header;
-        self.inner.rewind()?;
-        self.write_head()
-    }
-
-    fn stream_block<F, R>(
+    pub(crate) fn stream_section<F, R>(
         &mut self,
         mut cid_f: F,
         stream_size: usize,
@@ -115,12 +199,8 @@ where
 
         if !self.hashes_written.contains(&cid) {
             // write length and CID to stream
-            let mut cid_buf: Vec<u8> = Vec::new();
-            cid.write_bytes(&mut cid_buf)
-                .map_err(|e| CarError::Parsing(e.to_string()))?;
-            let sec_len = stream_size + cid_buf.len();
-            self.inner.write_varint(sec_len)?;
-            self.inner.write_all(cid_buf.as_slice())?;
+            let header_len = write_section_header(&mut self.inner, &cid, stream_size)?;
+            self.written += (header_len + stream_size) as u64;
 
             // stream r a second time to write into output stream
             let mut read_size = 0;
@@ -134,13 +214,70 @@ where
                 read_size += n;
                 self.inner.write_all(&buffer[0..n])?;
             }
-            self.hashes_written.push(cid);
+            self.hashes_written.insert(cid);
+            self.blocks += 1;
         }
         Ok(cid)
     }
 }
 
-#[cfg(test)]
+impl<W> CarWriter for CarWriterV1<W>
+where
+    W: Write + Seek,
+{
+    #[inline(always)]
+    fn write_block<T>(&mut self, cid: Cid, data: T) -> Result<(), CarError>
+    where
+        T: AsRef<[u8]>,
+    {
+        self.write_section(cid, data)
+    }
+
+    #[inline(always)]
+    fn flush(&mut self) -> Result<(), CarError> {
+        self.flush_inner()
+    }
+
+    #[inline(always)]
+    fn bytes_written(&self) -> u64 {
+        self.written
+    }
+
+    #[inline(always)]
+    fn blocks_written(&self) -> u64 {
+        self.blocks()
+    }
+
+    /// the new header must have the same encoded length, the blocks follow the header.
+    /// the blocks buffered are written before the rewind, the header is flushed.
+    fn rewrite_header(&mut self, header: CarHeader) -> Result<(), CarError> {
+        if header.encode()?.len() != self.header.encode()?.len() {
+            return Err(CarError::InvalidSection(
+                "the length of the header is not match.".to_string(),
+            ));
+        }
+        self.header = header;
+        self.inner.rewind()?;
+        self.write_head()?;
+        self.flush_inner()
+    }
+
+    #[inline(always)]
+    fn stream_block<F, R>(
+        &mut self,
+        cid_f: F,
+        stream_size: usize,
+        r: &mut R,
+    ) -> Result<Cid, CarError>
+    where
+        R: std::io::Read + std::io::Seek,
+        F: FnMut(WriteStream) -> Option<Result<Cid, CarError>>,
+    {
+        self.stream_section(cid_f, stream_size, r)
+    }
+}
+
+#[cfg(all(test, feature = "reader"))]
 mod test {
     use std::io::Cursor;
 
@@ -166,9 +303,101 @@ mod test {
         writer.write_block(cid_test1, b"test1").unwrap();
         writer.write_block(cid_test2, b"test2").unwrap();
         writer.flush().unwrap();
+        drop(writer);
         let mut reader = Cursor::new(&buffer);
-        let car_reader = CarReaderV1::new(&mut reader).unwrap();
+        let mut car_reader = CarReaderV1::new(&mut reader).unwrap();
         assert_eq!(vec![cid_test2], car_reader.header().roots());
-        assert_eq!(car_reader.sections().len(), 2);
+        assert_eq!(car_reader.sections().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_writer_dedup_blocks() {
+        let cids: Vec<Cid> = (0..1000u32)
+            .map(|i| Cid::new_v1(DagCborCodec.into(), Blake2b256.digest(&i.to_be_bytes())))
+            .collect();
+        let header = CarHeader::V1(CarHeaderV1::new(vec![cids[0]]));
+        let mut buffer = Cursor::new(Vec::new());
+        let mut writer = CarWriterV1::new(&mut buffer, header);
+        for cid in cids.iter().chain(cids.iter().rev()) {
+            writer.write_block(*cid, cid.to_bytes()).unwrap();
+        }
+        writer.flush().unwrap();
+        // the duplicates skipped are not counted.
+        assert_eq!(writer.blocks_written(), cids.len() as u64);
+        drop(writer);
+        let mut car_reader = CarReaderV1::new(Cursor::new(buffer.into_inner())).unwrap();
+        assert_eq!(car_reader.sections().unwrap().len(), cids.len());
+    }
+
+    #[test]
+    fn test_writer_flush_on_drop() {
+        let cid = Cid::new_v1(DagCborCodec.into(), Blake2b256.digest(b"test"));
+        let mut buffer = Cursor::new(Vec::new());
+        let mut writer = CarWriterV1::new(&mut buffer, CarHeader::new_v1(vec![cid]))
+            .with_buffer_size(DEFAULT_WRITE_BUFFER_SIZE);
+        writer.write_block(cid, b"test").unwrap();
+        // the buffered block is written by the drop without the flush.
+        assert!(!writer.inner.buf.is_empty());
+        drop(writer);
+        let mut car_reader = CarReaderV1::new(Cursor::new(buffer.into_inner())).unwrap();
+        assert_eq!(car_reader.get_block(&cid).unwrap(), Some(b"test".to_vec()));
+    }
+
+    /// the output counting the write calls.
+    struct CountingWriter {
+        inner: Cursor<Vec<u8>>,
+        writes: usize,
+    }
+
+    impl Write for CountingWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.writes += 1;
+            self.inner.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Seek for CountingWriter {
+        fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    #[test]
+    fn test_writer_buffer_size() {
+        let cids: Vec<Cid> = (0..100u32)
+            .map(|i| Cid::new_v1(DagCborCodec.into(), Blake2b256.digest(&i.to_be_bytes())))
+            .collect();
+        let write = |size: usize| {
+            let mut out = CountingWriter {
+                inner: Cursor::new(Vec::new()),
+                writes: 0,
+            };
+            let header = CarHeader::new_v1(vec![cids[0]]);
+            let mut writer = CarWriterV1::new(&mut out, header).with_buffer_size(size);
+            for cid in cids.iter() {
+                writer.write_block(*cid, cid.to_bytes()).unwrap();
+            }
+            // the header of the same length is written over the buffered blocks.
+            writer
+                .rewrite_header(CarHeader::new_v1(vec![cids[1]]))
+                .unwrap();
+            let written = writer.bytes_written();
+            drop(writer);
+            assert_eq!(written, out.inner.get_ref().len() as u64);
+            (out.inner.into_inner(), out.writes)
+        };
+        let (direct, direct_writes) = write(0);
+        let (buffered, buffered_writes) = write(DEFAULT_WRITE_BUFFER_SIZE);
+        assert_eq!(direct, buffered);
+        assert_eq!(write(100).0, direct);
+        assert!(direct_writes > 3 * cids.len());
+        assert_eq!(buffered_writes, 2);
+        let mut reader = CarReaderV1::new(Cursor::new(buffered)).unwrap();
+        assert_eq!(reader.header().roots(), vec![cids[1]]);
+        assert_eq!(reader.section_count().unwrap(), cids.len());
     }
 }